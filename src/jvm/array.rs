@@ -20,6 +20,9 @@
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use jvm::heap::allocate_identity;
+use jvm::heap::Allocation;
+use jvm::monitor::Monitor;
 use jvm::typevalues::JvmValue;
 use std::fmt;
 
@@ -31,25 +34,68 @@ enum_from_primitive! {
 		Double = 0x7,
 		Byte = 0x8,
 		Short = 0x9,
-		Integer = 0x10,
-		Long = 0x11,
+		Integer = 0xa,
+		Long = 0xb,
 	}
 }
 pub struct JvmArray {
+	/*
+	 * What tells this array apart from every other object and array
+	 * (see heap::allocate_identity).
+	 */
+	identity: u64,
 	dimension: usize,
 	values: Vec<Option<JvmValue>>,
+	monitor: Monitor,
+	/*
+	 * The part of the heap that the array takes up, when it counts
+	 * against the heap limit.
+	 */
+	allocation: Option<Allocation>,
 }
 
 impl JvmArray {
 	pub fn new(dimension: usize) -> Self {
 		let mut res = JvmArray {
+			identity: allocate_identity(),
 			dimension,
 			values: vec![],
+			monitor: Monitor::new(),
+			allocation: None,
 		};
 		res.values.resize(dimension as usize, None);
 		res
 	}
 
+	/*
+	 * Make an array whose every element starts out as _value_.
+	 */
+	pub fn new_with_value(dimension: usize, value: JvmValue) -> Self {
+		JvmArray {
+			identity: allocate_identity(),
+			dimension,
+			values: vec![Some(value); dimension],
+			monitor: Monitor::new(),
+			allocation: None,
+		}
+	}
+
+	pub fn identity(&self) -> u64 {
+		self.identity
+	}
+
+	pub fn set_allocation(&mut self, allocation: Allocation) {
+		self.allocation = Some(allocation);
+	}
+
+	pub fn monitor(&self) -> &Monitor {
+		&self.monitor
+	}
+
+	pub fn monitor_mut(&mut self) -> &mut Monitor {
+		&mut self.monitor
+	}
+
 	pub fn inbounds(&self, index: usize) -> bool {
 		index < self.dimension
 	}
@@ -66,7 +112,14 @@ impl JvmArray {
 		&self.values[index]
 	}
 
-	pub fn dimension(&self) -> usize {
+	/*
+	 * A copy of the element at _index_.
+	 */
+	pub fn get(&self, index: usize) -> Option<JvmValue> {
+		self.values[index].clone()
+	}
+
+	pub fn len(&self) -> usize {
 		self.dimension
 	}
 }
@@ -84,3 +137,23 @@ impl fmt::Display for JvmArray {
 		write!(f, ".")
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::typevalues::JvmPrimitiveType;
+
+	#[test]
+	fn len_is_the_dimension() {
+		let array = JvmArray::new(4);
+		assert_eq!(array.len(), 4);
+		assert!(array.inbounds(3));
+		assert!(!array.inbounds(4));
+		assert!(array.get(3).is_none());
+
+		let array =
+			JvmArray::new_with_value(2, JvmValue::Primitive(JvmPrimitiveType::Integer, 0, 7, 0));
+		assert_eq!(array.len(), 2);
+		assert_eq!(JvmArray::new(0).len(), 0);
+	}
+}
@@ -0,0 +1,171 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+
+/*
+ * An entry in a BootstrapMethods attribute: the MethodHandle
+ * constant of the bootstrap method and the constants that are
+ * passed to it as static arguments.
+ */
+#[derive(Default, Clone)]
+pub struct BootstrapMethod {
+	bootstrap_method_ref: u16,
+	bootstrap_arguments: Vec<u16>,
+}
+
+impl BootstrapMethod {
+	pub fn byte_len(&self) -> usize {
+		4 + 2 * self.bootstrap_arguments.len()
+	}
+
+	pub fn bootstrap_method_ref(&self) -> u16 {
+		self.bootstrap_method_ref
+	}
+
+	pub fn bootstrap_arguments(&self) -> &Vec<u16> {
+		&self.bootstrap_arguments
+	}
+}
+
+impl<'l> From<&'l [u8]> for BootstrapMethod {
+	fn from(bytes: &'l [u8]) -> Self {
+		let bootstrap_method_ref = (bytes[0] as u16) << 8 | (bytes[1] as u16) << 0;
+		let argument_count = (bytes[2] as u16) << 8 | (bytes[3] as u16) << 0;
+		let bootstrap_arguments = (0..argument_count as usize)
+			.map(|i| (bytes[4 + 2 * i] as u16) << 8 | (bytes[4 + 2 * i + 1] as u16) << 0)
+			.collect();
+		BootstrapMethod {
+			bootstrap_method_ref,
+			bootstrap_arguments,
+		}
+	}
+}
+
+#[derive(Default, Clone)]
+pub struct BootstrapMethods {
+	methods: Vec<BootstrapMethod>,
+}
+
+impl BootstrapMethods {
+	pub fn methods(&self) -> &Vec<BootstrapMethod> {
+		&self.methods
+	}
+
+	pub fn get(&self, index: usize) -> Option<&BootstrapMethod> {
+		self.methods.get(index)
+	}
+
+	pub fn len(&self) -> usize {
+		self.methods.len()
+	}
+}
+
+impl From<Vec<u8>> for BootstrapMethods {
+	fn from(bytes: Vec<u8>) -> Self {
+		let mut offset: usize = 0;
+		let mut methods: Vec<BootstrapMethod> = Vec::new();
+		let methods_count = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
+		offset += 2;
+
+		for _ in 0..methods_count as usize {
+			let method = BootstrapMethod::from(&bytes[offset..]);
+			offset += method.byte_len();
+			methods.push(method);
+		}
+		BootstrapMethods { methods }
+	}
+}
+
+impl fmt::Display for BootstrapMethods {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut result = Ok(());
+		for (i, method) in self.methods.iter().enumerate() {
+			result = write!(
+				f,
+				"{}: bootstrap_method_ref: {}, bootstrap_arguments: {:?}\n",
+				i, method.bootstrap_method_ref, method.bootstrap_arguments
+			);
+		}
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::lambda_class;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::constant::Constant;
+
+	#[test]
+	fn lambdas_have_bootstrap_methods() {
+		let mut builder = lambda_class("Lambdas");
+		let handle = builder.method_handle(5, 1);
+		let second = builder.bootstrap_method(handle, &[]);
+		let class = builder.build();
+		let bootstrap_methods = class.get_bootstrap_methods().unwrap();
+
+		assert_eq!(bootstrap_methods.len(), 2);
+		assert_eq!(second, 1);
+		let first = bootstrap_methods.get(0).unwrap();
+		assert!(matches!(
+			class
+				.get_constant_pool_ref()
+				.get_constant_ref(first.bootstrap_method_ref() as usize),
+			Constant::MethodHandle(_, 6, _)
+		));
+		assert_eq!(first.bootstrap_arguments().len(), 3);
+		assert_eq!(
+			bootstrap_methods.get(1).unwrap().bootstrap_method_ref(),
+			handle
+		);
+		assert!(bootstrap_methods
+			.get(1)
+			.unwrap()
+			.bootstrap_arguments()
+			.is_empty());
+		assert!(bootstrap_methods.get(2).is_none());
+
+		assert!(ClassFileBuilder::new("Plain", Some("java/lang/Object"))
+			.build()
+			.get_bootstrap_methods()
+			.is_none());
+	}
+
+	#[test]
+	fn decodes_the_attribute_bytes() {
+		let bootstrap_methods =
+			BootstrapMethods::from(vec![0, 2, 0, 7, 0, 2, 0, 8, 0, 9, 0, 10, 0, 0]);
+		assert_eq!(bootstrap_methods.len(), 2);
+		assert_eq!(bootstrap_methods.get(0).unwrap().bootstrap_method_ref(), 7);
+		assert_eq!(
+			bootstrap_methods.get(0).unwrap().bootstrap_arguments(),
+			&vec![8, 9]
+		);
+		assert_eq!(bootstrap_methods.get(1).unwrap().bootstrap_method_ref(), 10);
+		assert!(bootstrap_methods
+			.get(1)
+			.unwrap()
+			.bootstrap_arguments()
+			.is_empty());
+	}
+}
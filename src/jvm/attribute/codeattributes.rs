@@ -19,9 +19,16 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
+use jvm::attribute::linenumbertable::LineNumberTable;
+use jvm::attribute::Attributes;
+use jvm::class::ClassFileReader;
+use jvm::constantpool::ConstantPool;
 use jvm::exceptions::ExceptionTable;
+use jvm::exceptions::ExceptionTableEntry;
+use std::convert::TryFrom;
 use std::fmt;
 
+#[derive(Default, Clone)]
 pub struct CodeAttribute {
 	pub bytes: Vec<u8>,
 	pub code_offset: usize,
@@ -30,6 +37,7 @@ pub struct CodeAttribute {
 	code_length: u32,
 	exceptions_table_count: u16,
 	exceptions: ExceptionTable,
+	attributes: Attributes,
 }
 
 impl From<Vec<u8>> for CodeAttribute {
@@ -51,8 +59,14 @@ impl From<Vec<u8>> for CodeAttribute {
 		offset += (code_length as usize) * 1;
 
 		let exceptions = ExceptionTable::from(&bytes[offset..].to_vec());
+		offset += exceptions.byte_len();
 
-		exceptions.byte_len();
+		/*
+		 * A Code attribute whose own attributes are cut short is
+		 * treated as having none.
+		 */
+		let attributes =
+			Attributes::try_from(&mut ClassFileReader::new(&bytes[offset..])).unwrap_or_default();
 
 		CodeAttribute {
 			bytes: bytes,
@@ -62,10 +76,37 @@ impl From<Vec<u8>> for CodeAttribute {
 			code_offset: code_offset,
 			exceptions_table_count: exceptions.exceptions_table_count(),
 			exceptions: exceptions,
+			attributes: attributes,
 		}
 	}
 }
 
+impl CodeAttribute {
+	pub fn max_stack(&self) -> u16 {
+		self.max_stack
+	}
+
+	pub fn max_locals(&self) -> u16 {
+		self.max_locals
+	}
+
+	pub fn code(&self) -> &[u8] {
+		&self.bytes[self.code_offset..self.code_offset + self.code_length as usize]
+	}
+
+	pub fn attributes(&self) -> &Attributes {
+		&self.attributes
+	}
+
+	pub fn exception_table(&self) -> &Vec<ExceptionTableEntry> {
+		self.exceptions.entries()
+	}
+
+	pub fn line_number_table(&self, cp: &ConstantPool) -> Option<LineNumberTable> {
+		self.attributes.get_line_number_table(cp)
+	}
+}
+
 impl fmt::Display for CodeAttribute {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		let mut result: fmt::Result;
@@ -80,3 +121,107 @@ impl fmt::Display for CodeAttribute {
 		result
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::class::Class;
+	use jvm::method::MethodAccessFlags;
+
+	/*
+	 * The Code attribute of _class_'s method run()V.
+	 */
+	fn code_of(class: &Class) -> CodeAttribute {
+		class
+			.get_method_rc_by_name_and_type(&format!("run"), &format!("()V"))
+			.unwrap()
+			.attributes
+			.get_code(class.get_constant_pool_ref())
+			.unwrap()
+	}
+
+	#[test]
+	fn parses_the_exception_table() {
+		let mut builder = ClassFileBuilder::new("Guarded", Some("java/lang/Object"));
+		let exception = builder.class("java/lang/Exception");
+		/*
+		 * A try block from 0 to 2 with a catch at 3 and a finally at 5:
+		 * nop; nop; return; astore_0; return; athrow
+		 */
+		let class = builder
+			.method_with_tables(
+				MethodAccessFlags::Public as u16,
+				"run",
+				"()V",
+				1,
+				1,
+				&[0x00, 0x00, 0xb1, 0x4b, 0xb1, 0xbf],
+				&[(0, 2, 3, exception), (0, 5, 5, 0)],
+				&[],
+			)
+			.build();
+		let code = code_of(&class);
+		let entries = code.exception_table();
+
+		assert_eq!(entries.len(), 2);
+		assert_eq!(
+			(
+				entries[0].start_pc(),
+				entries[0].end_pc(),
+				entries[0].handler_pc(),
+				entries[0].catch_type()
+			),
+			(0, 2, 3, exception)
+		);
+		assert_eq!(
+			(
+				entries[1].start_pc(),
+				entries[1].end_pc(),
+				entries[1].handler_pc(),
+				entries[1].catch_type()
+			),
+			(0, 5, 5, 0)
+		);
+		assert_eq!(
+			class
+				.get_constant_pool_ref()
+				.get_class_name(exception as usize)
+				.ok(),
+			Some(format!("java/lang/Exception"))
+		);
+	}
+
+	#[test]
+	fn parses_max_stack_max_locals_and_code() {
+		let mut builder = ClassFileBuilder::new("Sized", Some("java/lang/Object"));
+		let class = builder
+			.method_with_tables(
+				MethodAccessFlags::Public as u16,
+				"run",
+				"()V",
+				3,
+				7,
+				&[0x04, 0x3c, 0xb1],
+				&[],
+				&[(0, 10)],
+			)
+			.build();
+		let code = code_of(&class);
+
+		assert_eq!(code.max_stack(), 3);
+		assert_eq!(code.max_locals(), 7);
+		assert_eq!(code.code(), &[0x04, 0x3c, 0xb1]);
+		assert!(code.exception_table().is_empty());
+		assert_eq!(code.attributes().len(), 1);
+
+		/*
+		 * Methods size their frames from it.
+		 */
+		let method = class
+			.get_method_rc_by_name_and_type(&format!("run"), &format!("()V"))
+			.unwrap();
+		assert_eq!(method.max_locals, 7);
+		assert_eq!(method.max_stack, 3);
+	}
+}
@@ -0,0 +1,174 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+use std::iter::repeat;
+
+/*
+ * An entry in a LineNumberTable attribute: the code starting at
+ * start_pc came from source line line_number.
+ */
+#[derive(Default, Clone)]
+pub struct LineNumberTableEntry {
+	start_pc: u16,
+	line_number: u16,
+}
+
+impl LineNumberTableEntry {
+	pub fn byte_len(&self) -> usize {
+		4 as usize
+	}
+
+	pub fn start_pc(&self) -> u16 {
+		self.start_pc
+	}
+
+	pub fn line_number(&self) -> u16 {
+		self.line_number
+	}
+}
+
+impl<'l> From<&'l [u8]> for LineNumberTableEntry {
+	fn from(bytes: &'l [u8]) -> Self {
+		let start_pc = (bytes[0] as u16) << 8 | (bytes[1] as u16) << 0;
+		let line_number = (bytes[2] as u16) << 8 | (bytes[3] as u16) << 0;
+		LineNumberTableEntry {
+			start_pc,
+			line_number,
+		}
+	}
+}
+
+#[derive(Default, Clone)]
+pub struct LineNumberTable {
+	entries: Vec<LineNumberTableEntry>,
+}
+
+impl LineNumberTable {
+	pub fn entries(&self) -> &Vec<LineNumberTableEntry> {
+		&self.entries
+	}
+
+	/*
+	 * A method may have more than one LineNumberTable attribute
+	 * (jvms 4.7.12); together they describe the whole method.
+	 */
+	pub fn extend(&mut self, other: LineNumberTable) {
+		self.entries.extend(other.entries);
+	}
+
+	/*
+	 * The entries are not required to be in any order; the line
+	 * for _pc_ is the one whose range starts closest before it.
+	 */
+	pub fn line_for_pc(&self, pc: u16) -> Option<u16> {
+		self.entries
+			.iter()
+			.filter(|entry| entry.start_pc <= pc)
+			.max_by_key(|entry| entry.start_pc)
+			.map(|entry| entry.line_number)
+	}
+}
+
+impl From<Vec<u8>> for LineNumberTable {
+	fn from(bytes: Vec<u8>) -> Self {
+		let mut offset: usize = 0;
+		let mut entries: Vec<LineNumberTableEntry>;
+		let entries_count = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
+		offset += 2;
+
+		entries = repeat(LineNumberTableEntry {
+			..Default::default()
+		})
+		.take(entries_count as usize)
+		.collect();
+
+		for i in 0..entries_count as usize {
+			entries[i] = LineNumberTableEntry::from(&bytes[offset..]);
+			offset += entries[i].byte_len();
+		}
+		LineNumberTable { entries }
+	}
+}
+
+impl fmt::Display for LineNumberTable {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut result = Ok(());
+		for entry in &self.entries {
+			result = write!(
+				f,
+				"start_pc: {}, line_number: {}\n",
+				entry.start_pc, entry.line_number
+			);
+		}
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::method::MethodAccessFlags;
+
+	#[test]
+	fn maps_pcs_to_lines() {
+		/*
+		 * Out of order: pc 4 is line 12 and pc 0 is line 10.
+		 */
+		let table = LineNumberTable::from(vec![0, 2, 0, 4, 0, 12, 0, 0, 0, 10]);
+		assert_eq!(table.entries().len(), 2);
+		assert_eq!(table.line_for_pc(0), Some(10));
+		assert_eq!(table.line_for_pc(3), Some(10));
+		assert_eq!(table.line_for_pc(4), Some(12));
+		assert_eq!(table.line_for_pc(100), Some(12));
+
+		let table = LineNumberTable::from(vec![0, 1, 0, 2, 0, 7]);
+		assert_eq!(table.line_for_pc(1), None);
+	}
+
+	#[test]
+	fn methods_have_their_line_numbers() {
+		let mut builder = ClassFileBuilder::new("Lines", Some("java/lang/Object"));
+		let class = builder
+			.method_with_tables(
+				MethodAccessFlags::Public as u16,
+				"run",
+				"()V",
+				1,
+				1,
+				&[0x04, 0x3b, 0x00, 0xb1],
+				&[],
+				&[(0, 3), (2, 4), (3, 6)],
+			)
+			.build();
+		let method = class
+			.get_method_rc_by_name_and_type(&format!("run"), &format!("()V"))
+			.unwrap();
+		let table = method
+			.get_line_number_table(class.get_constant_pool_ref())
+			.unwrap();
+
+		assert_eq!(table.line_for_pc(1), Some(3));
+		assert_eq!(table.line_for_pc(2), Some(4));
+		assert_eq!(table.line_for_pc(3), Some(6));
+	}
+}
@@ -19,14 +19,24 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
+use jvm::attribute::bootstrapmethods::BootstrapMethods;
+use jvm::attribute::codeattributes::CodeAttribute;
+use jvm::attribute::linenumbertable::LineNumberTable;
+use jvm::class::ClassFileError;
+use jvm::class::ClassFileReader;
+use jvm::constant::Constant;
+use jvm::constant::Utf8Reserved;
+use jvm::constantpool::ConstantPool;
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::repeat;
 
+pub mod bootstrapmethods;
 pub mod codeattributes;
+pub mod linenumbertable;
 
 #[derive(Default, Clone)]
 pub struct Attribute {
-	pub byte_len: usize,
 	pub attribute_name_index: u16,
 	pub attribute_length: u32,
 	pub info: Vec<u8>,
@@ -39,10 +49,6 @@ impl Attribute {
 			..Default::default()
 		}
 	}
-
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
-	}
 }
 
 impl fmt::Display for Attribute {
@@ -60,40 +66,23 @@ impl fmt::Display for Attribute {
 	}
 }
 
-impl<'l> From<&'l Vec<u8>> for Attribute {
-	fn from(bytes: &'l Vec<u8>) -> Self {
-		let mut offset = 0;
-		let attribute_name_index: u16;
-		let attribute_length: u32;
-		let mut info: Vec<u8>;
-
-		attribute_name_index = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16);
-		offset += 2;
-		attribute_length = (bytes[offset + 0] as u32) << 24
-			| (bytes[offset + 1] as u32) << 16
-			| (bytes[offset + 2] as u32) << 8
-			| (bytes[offset + 3] as u32);
-		offset += 4;
-		info = repeat(0).take(attribute_length as usize).collect();
-		/*
-		 * Parse the attributes
-		 */
-		for ii in 0..attribute_length {
-			info[ii as usize] = bytes[offset];
-			offset += 1;
-		}
-		Attribute {
-			byte_len: offset,
+impl<'l, 'a> TryFrom<&'l mut ClassFileReader<'a>> for Attribute {
+	type Error = ClassFileError;
+
+	fn try_from(reader: &'l mut ClassFileReader<'a>) -> Result<Self, ClassFileError> {
+		let attribute_name_index = reader.read_u16()?;
+		let attribute_length = reader.read_u32()?;
+		let info = reader.read_bytes(attribute_length as usize)?.to_vec();
+		Ok(Attribute {
 			attribute_name_index,
 			attribute_length,
 			info,
-		}
+		})
 	}
 }
 
 #[derive(Default, Clone)]
 pub struct Attributes {
-	byte_len: usize,
 	attributes: Vec<Attribute>,
 }
 
@@ -114,39 +103,68 @@ impl Attributes {
 		self.attributes.len()
 	}
 
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
-	}
-
 	pub fn attributes_count(&self) -> u16 {
 		self.attributes.len() as u16
 	}
-}
 
-impl<'l> From<&'l Vec<u8>> for Attributes {
-	fn from(bytes: &'l Vec<u8>) -> Self {
-		let mut offset = 0;
-		let mut attributes: Vec<Attribute>;
-		let attributes_count: u16 =
-			(bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
-
-		offset += 2;
+	/*
+	 * Decode the Code attribute, if there is one.
+	 */
+	pub fn get_code(&self, cp: &ConstantPool) -> Option<CodeAttribute> {
+		for attribute in &self.attributes {
+			if let Constant::Utf8(_, Utf8Reserved::Code, _, _) =
+				cp.get_constant_ref(attribute.attribute_name_index as usize)
+			{
+				return Some(CodeAttribute::from(attribute.info.clone()));
+			}
+		}
+		None
+	}
 
-		attributes = repeat(Attribute {
-			..Default::default()
-		})
-		.take(attributes_count as usize)
-		.collect();
+	/*
+	 * Decode and merge the LineNumberTable attributes, if there are any.
+	 */
+	pub fn get_line_number_table(&self, cp: &ConstantPool) -> Option<LineNumberTable> {
+		let mut line_number_table: Option<LineNumberTable> = None;
+		for attribute in &self.attributes {
+			if let Constant::Utf8(_, Utf8Reserved::LineNumberTable, _, _) =
+				cp.get_constant_ref(attribute.attribute_name_index as usize)
+			{
+				let table = LineNumberTable::from(attribute.info.clone());
+				match line_number_table {
+					Some(ref mut existing) => existing.extend(table),
+					None => line_number_table = Some(table),
+				}
+			}
+		}
+		line_number_table
+	}
 
-		for attribute_index in 0..attributes_count as usize {
-			attributes[attribute_index as usize] = Attribute::from(&bytes[offset..].to_vec());
-			offset += attributes[attribute_index as usize].byte_len();
+	/*
+	 * Decode the (class-level) BootstrapMethods attribute, if there is one.
+	 */
+	pub fn get_bootstrap_methods(&self, cp: &ConstantPool) -> Option<BootstrapMethods> {
+		for attribute in &self.attributes {
+			if let Constant::Utf8(_, Utf8Reserved::BootstrapMethods, _, _) =
+				cp.get_constant_ref(attribute.attribute_name_index as usize)
+			{
+				return Some(BootstrapMethods::from(attribute.info.clone()));
+			}
 		}
+		None
+	}
+}
+
+impl<'l, 'a> TryFrom<&'l mut ClassFileReader<'a>> for Attributes {
+	type Error = ClassFileError;
 
-		Attributes {
-			byte_len: offset,
-			attributes,
+	fn try_from(reader: &'l mut ClassFileReader<'a>) -> Result<Self, ClassFileError> {
+		let attributes_count = reader.read_u16()?;
+		let mut attributes: Vec<Attribute> = Vec::with_capacity(attributes_count as usize);
+		for _ in 0..attributes_count {
+			attributes.push(Attribute::try_from(&mut *reader)?);
 		}
+		Ok(Attributes { attributes })
 	}
 }
 
@@ -19,19 +19,122 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
+use jvm::attribute::bootstrapmethods::BootstrapMethods;
 use jvm::attribute::Attributes;
 use jvm::constant::Constant;
 use jvm::constantpool::ConstantPool;
+use jvm::constantpool::ConstantTag;
 use jvm::field::Fields;
 use jvm::method::Method;
+use jvm::method::MethodAccessFlags;
 use jvm::method::Methods;
 use jvm::methodarea::MethodArea;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fs;
 use std::io::Read;
-use std::iter;
 use std::rc::Rc;
 
+const CLASS_FILE_MAGIC: u32 = 0xCAFEBABE;
+
+/*
+ * The class file versions that we know how to load: those from JDK
+ * 1.0.2 through Java SE 17.
+ */
+const MIN_SUPPORTED_MAJOR_VERSION: u16 = 45;
+const MAX_SUPPORTED_MAJOR_VERSION: u16 = 61;
+
+/*
+ * Why some bytes are not a class file that we can load.
+ */
+#[derive(Clone, Debug)]
+pub enum ClassFileError {
+	Truncated(usize),
+	BadMagic(u32),
+	UnsupportedVersion(u16, u16),
+	BadConstantTag(u8, usize),
+}
+
+impl fmt::Display for ClassFileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ClassFileError::Truncated(offset) => {
+				write!(f, "class file ends before byte {}", offset)
+			}
+			ClassFileError::BadMagic(magic) => {
+				write!(f, "class file has the wrong magic number {:x}", magic)
+			}
+			ClassFileError::UnsupportedVersion(major, minor) => {
+				write!(f, "class file has unsupported version {}.{}", major, minor)
+			}
+			ClassFileError::BadConstantTag(tag, offset) => write!(
+				f,
+				"class file has unknown constant pool tag {} at byte {}",
+				tag, offset
+			),
+		}
+	}
+}
+
+/*
+ * Reads the big-endian values in a class file one after the other,
+ * making sure that each one is there before reading it.
+ */
+pub struct ClassFileReader<'a> {
+	bytes: &'a [u8],
+	offset: usize,
+}
+
+impl<'a> ClassFileReader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		ClassFileReader {
+			bytes: bytes,
+			offset: 0,
+		}
+	}
+
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/*
+	 * The bytes that have not been read yet.
+	 */
+	pub fn remaining(&self) -> &'a [u8] {
+		&self.bytes[self.offset..]
+	}
+
+	pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], ClassFileError> {
+		if count > self.bytes.len() - self.offset {
+			return Err(ClassFileError::Truncated(self.offset.saturating_add(count)));
+		}
+		let bytes = &self.bytes[self.offset..self.offset + count];
+		self.offset += count;
+		Ok(bytes)
+	}
+
+	pub fn skip(&mut self, count: usize) -> Result<(), ClassFileError> {
+		self.read_bytes(count).map(|_| ())
+	}
+
+	pub fn read_u8(&mut self) -> Result<u8, ClassFileError> {
+		Ok(self.read_bytes(1)?[0])
+	}
+
+	pub fn read_u16(&mut self) -> Result<u16, ClassFileError> {
+		let bytes = self.read_bytes(2)?;
+		Ok((bytes[0] as u16) << 8 | (bytes[1] as u16))
+	}
+
+	pub fn read_u32(&mut self) -> Result<u32, ClassFileError> {
+		let bytes = self.read_bytes(4)?;
+		Ok((bytes[0] as u32) << 24
+			| (bytes[1] as u32) << 16
+			| (bytes[2] as u32) << 8
+			| (bytes[3] as u32))
+	}
+}
+
 #[repr(u16)]
 pub enum ClassAccessFlags {
 	Public = 0x0001,
@@ -77,6 +180,14 @@ impl Class {
 		&self.constant_pool
 	}
 
+	pub fn is_interface(&self) -> bool {
+		(self.access_flags & (ClassAccessFlags::Interface as u16)) != 0
+	}
+
+	pub fn is_abstract(&self) -> bool {
+		(self.access_flags & (ClassAccessFlags::Abstract as u16)) != 0
+	}
+
 	pub fn resolve_superclass(&self) -> Option<String> {
 		let mut superclass_name: Option<String> = None;
 		let cp = &self.constant_pool;
@@ -97,6 +208,28 @@ impl Class {
 		self.resolve_superclass()
 	}
 
+	/*
+	 * The names of the interfaces that this class directly implements
+	 * (or, for an interface, directly extends).
+	 */
+	pub fn get_interface_names(&self) -> Vec<String> {
+		let cp = &self.constant_pool;
+		let mut interface_names: Vec<String> = vec![];
+
+		for interface in &self.interfaces {
+			if let Constant::Class(_, interface_name_index) =
+				cp.get_constant_ref(*interface as usize)
+			{
+				if let Constant::Utf8(_, _, _, interface_name) =
+					cp.get_constant_ref(*interface_name_index as usize)
+				{
+					interface_names.push(interface_name.to_string());
+				}
+			}
+		}
+		interface_names
+	}
+
 	/**
 	 * is_type_of
 	 *
@@ -120,61 +253,23 @@ impl Class {
 		}
 	}
 
-	pub fn resolve_field_ref(&self, field_ref_index: usize) -> Option<(String, String, String)> {
-		let mut result: Option<(String, String, String)> = None;
-		let cp = &self.constant_pool;
+	/*
+	 * The name of the class (or interface or array type) named by
+	 * the Class constant at _class_ref_index_.
+	 */
+	pub fn resolve_class_ref(&self, class_ref_index: usize) -> Option<String> {
+		self.constant_pool.resolve_class(class_ref_index)
+	}
 
-		if let Constant::Fieldref(_, class_idx, name_type_idx) =
-			cp.get_constant_clone(field_ref_index as usize)
-		{
-			if let Constant::NameAndType(_, field_name_idx, field_type_idx) =
-				cp.get_constant_clone(name_type_idx as usize)
-			{
-				if let Constant::Utf8(_, _, _, name) =
-					cp.get_constant_clone(field_name_idx as usize)
-				{
-					if let Constant::Utf8(_, _, _, r#type) =
-						cp.get_constant_clone(field_type_idx as usize)
-					{
-						if let Constant::Class(_, field_class_idx) =
-							cp.get_constant_clone(class_idx as usize)
-						{
-							if let Constant::Utf8(_, _, _, class) =
-								cp.get_constant_clone(field_class_idx as usize)
-							{
-								result = Some((class, name, r#type));
-							}
-						}
-					}
-				}
-			}
-		}
-		/*else {
-					FatalError::new(FatalErrorType::InvalidConstantReference(
-						class.get_class_name().unwrap(),
-						"Utf8".to_string(),
-						field_name_idx as u16,
-					))
-					.call();
-				}
-			} else {
-				FatalError::new(FatalErrorType::InvalidConstantReference(
-					class.get_class_name().unwrap(),
-					"NameAndType".to_string(),
-					field_name_type_idx as u16,
-				))
-				.call();
-			}
-		} else {
-			FatalError::new(FatalErrorType::InvalidConstantReference(
-				class.get_class_name().unwrap(),
-				"Fieldref".to_string(),
-				field_index as u16,
-			))
-			.call();
-		}
-		*/
-		result
+	/// Resolve a field reference into the class of the field, the name
+	/// of the field and the type of the field.
+	///
+	/// # Arguments
+	///
+	/// `field_ref_index` - The index into this class' constant pool
+	/// that points to a field reference.
+	pub fn resolve_field_ref(&self, field_ref_index: usize) -> Option<(String, String, String)> {
+		self.constant_pool.resolve_fieldref(field_ref_index)
 	}
 
 	/// Resolve a method reference into the name of method, the type of
@@ -185,38 +280,7 @@ impl Class {
 	/// `method_ref_index` - The index into this class' constant pool
 	/// that points to a method reference.
 	pub fn resolve_method_ref(&self, method_ref_index: usize) -> Option<(String, String, String)> {
-		let mut result: Option<(String, String, String)> = None;
-		let cp = &self.constant_pool;
-
-		if let Constant::Methodref(_, class_index, method_index) =
-			cp.get_constant_ref(method_ref_index)
-		{
-			if let Constant::Class(_, class_name_index) = cp.get_constant_ref(*class_index as usize)
-			{
-				if let Constant::NameAndType(_, method_name_index, method_type_index) =
-					cp.get_constant_ref(*method_index as usize)
-				{
-					if let Constant::Utf8(_, _, _, class_name) =
-						cp.get_constant_ref(*class_name_index as usize)
-					{
-						if let Constant::Utf8(_, _, _, method_name) =
-							cp.get_constant_ref(*method_name_index as usize)
-						{
-							if let Constant::Utf8(_, _, _, method_type) =
-								cp.get_constant_ref(*method_type_index as usize)
-							{
-								result = Some((
-									method_name.to_string(),
-									method_type.to_string(),
-									class_name.to_string(),
-								));
-							}
-						}
-					}
-				}
-			}
-		}
-		result
+		self.constant_pool.resolve_methodref(method_ref_index)
 	}
 
 	pub fn get_method_rc_by_name_and_type(
@@ -240,6 +304,33 @@ impl Class {
 		&self.fields
 	}
 
+	/*
+	 * The name, descriptor and access flags of each of the class'
+	 * methods, in the order that they are declared.
+	 */
+	pub fn get_method_signatures(&self) -> Vec<(String, String, u16)> {
+		let mut signatures: Vec<(String, String, u16)> = Vec::new();
+		for i in 0..self.methods.methods_count() {
+			let method = self.methods.get(i as usize);
+			if let (Ok(name), Ok(descriptor)) = (
+				self.constant_pool.get_utf8(method.name_index as usize),
+				self.constant_pool
+					.get_utf8(method.descriptor_index as usize),
+			) {
+				signatures.push((
+					name.to_string(),
+					descriptor.to_string(),
+					method.access_flags,
+				));
+			}
+		}
+		signatures
+	}
+
+	pub fn get_bootstrap_methods(&self) -> Option<BootstrapMethods> {
+		self.attributes.get_bootstrap_methods(&self.constant_pool)
+	}
+
 	pub fn get_class_name(&self) -> Option<String> {
 		match self
 			.constant_pool
@@ -257,98 +348,117 @@ impl Class {
 		}
 	}
 
-	fn load_constant_pool(c: &mut Class, offset: usize) -> usize {
-		c.constant_pool = ConstantPool::from(&c.bytes[offset..].to_vec());
+	/*
+	 * The name of the class' runtime package: everything up to the
+	 * last / in the class name. There is only one class loader, so
+	 * this is all that is needed to tell whether two classes share a
+	 * runtime package.
+	 */
+	pub fn get_package_name(&self) -> Option<String> {
+		self.get_class_name()
+			.map(|class_name| match class_name.rfind('/') {
+				Some(index) => class_name[..index].to_string(),
+				None => String::new(),
+			})
+	}
+
+	fn load_constant_pool(
+		c: &mut Class,
+		reader: &mut ClassFileReader,
+	) -> Result<(), ClassFileError> {
+		c.constant_pool = ConstantPool::try_from(reader)?;
 		c.constant_pool_count = c.constant_pool.constant_pool_count();
-		offset + c.constant_pool.byte_len()
+		Ok(())
 	}
 
-	fn load_attributes(c: &mut Class, offset: usize) -> usize {
-		c.attributes = Attributes::from(&c.bytes[offset..].to_vec());
+	fn load_attributes(c: &mut Class, reader: &mut ClassFileReader) -> Result<(), ClassFileError> {
+		c.attributes = Attributes::try_from(reader)?;
 		c.attributes_count = c.attributes.attributes_count();
-		offset + c.attributes.byte_len()
+		Ok(())
 	}
 
-	fn load_fields(c: &mut Class, offset: usize) -> usize {
-		c.fields = Fields::from(&c.bytes[offset..].to_vec());
+	fn load_fields(c: &mut Class, reader: &mut ClassFileReader) -> Result<(), ClassFileError> {
+		c.fields = Fields::try_from(reader)?;
 		c.fields_count = c.fields.fields_count();
-		offset + c.fields.byte_len()
+		Ok(())
 	}
 
-	fn load_methods(c: &mut Class, offset: usize) -> usize {
-		c.methods = Methods::from((&c.bytes[offset..].to_vec(), &c.constant_pool));
+	fn load_methods(c: &mut Class, reader: &mut ClassFileReader) -> Result<(), ClassFileError> {
+		c.methods = Methods::try_from((reader, &c.constant_pool))?;
 		c.methods_count = c.methods.methods_count();
-		offset + c.methods.byte_len()
+		Ok(())
 	}
 
+	/*
+	 * Parse a class from the contents of a class file, wherever they
+	 * came from (a file, a JAR file, ...).
+	 */
 	pub fn load_from_bytes(bytes: Vec<u8>) -> Option<Class> {
 		let mut c = Class::default();
-		let mut offset: usize = 0;
-
+		if let Err(error) = Class::parse(&mut c, &mut ClassFileReader::new(&bytes)) {
+			print!("oops: {}\n", error);
+			return None;
+		}
 		c.bytes = bytes;
+		Some(c)
+	}
 
-		c.magic = (c.bytes[offset + 0] as u32) << 24
-			| (c.bytes[offset + 1] as u32) << 16
-			| (c.bytes[offset + 2] as u32) << 8
-			| (c.bytes[offset + 3] as u32) << 0;
-		offset += 4;
-
-		c.minor_version = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16) << 0;
-		offset += 2;
-
-		c.major_version = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16) << 0;
-		offset += 2;
+	fn parse(c: &mut Class, reader: &mut ClassFileReader) -> Result<(), ClassFileError> {
+		c.magic = reader.read_u32()?;
+		if c.magic != CLASS_FILE_MAGIC {
+			return Err(ClassFileError::BadMagic(c.magic));
+		}
+		c.minor_version = reader.read_u16()?;
+		c.major_version = reader.read_u16()?;
+		if c.major_version < MIN_SUPPORTED_MAJOR_VERSION
+			|| c.major_version > MAX_SUPPORTED_MAJOR_VERSION
+		{
+			return Err(ClassFileError::UnsupportedVersion(
+				c.major_version,
+				c.minor_version,
+			));
+		}
 
 		/*
 		 * Load the constants pool.
 		 */
-		offset = Class::load_constant_pool(&mut c, offset);
+		Class::load_constant_pool(c, reader)?;
 
-		c.access_flags = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16);
-		offset += 2;
-
-		c.this_class = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16);
-		offset += 2;
-
-		c.super_class = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16);
-		offset += 2;
-
-		c.interfaces_count = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16);
-		offset += 2;
+		c.access_flags = reader.read_u16()?;
+		c.this_class = reader.read_u16()?;
+		if let Some(class_name) = c.constant_pool.resolve_class(c.this_class as usize) {
+			c.constant_pool.set_class_name(class_name);
+		}
+		c.super_class = reader.read_u16()?;
+		c.interfaces_count = reader.read_u16()?;
 
 		/*
 		 * Handle the interfaces.
 		 */
-		c.interfaces = iter::repeat(0 as u16)
-			.take(c.interfaces_count as usize)
-			.collect();
-		for i in 0..c.interfaces_count as usize {
-			c.interfaces[i] = (c.bytes[offset + 0] as u16) << 8 | (c.bytes[offset + 1] as u16);
-			offset += 2;
+		c.interfaces = Vec::with_capacity(c.interfaces_count as usize);
+		for _ in 0..c.interfaces_count {
+			c.interfaces.push(reader.read_u16()?);
 		}
 
 		/*
 		 * Now parse the fields.
 		 */
-
-		offset = Class::load_fields(&mut c, offset);
+		Class::load_fields(c, reader)?;
 
 		/*
 		 * Now parse the methods.
 		 */
-		offset = Class::load_methods(&mut c, offset);
+		Class::load_methods(c, reader)?;
 
-		Class::load_attributes(&mut c, offset);
-		Some(c)
+		Class::load_attributes(c, reader)
 	}
 
 	/*
-	 * TODO: This is going to be have to be much more robust!
+	 * Read the class file _class_with_path_ and parse it with
+	 * load_from_bytes.
 	 */
 	pub fn load_from_file(class_with_path: &str) -> Option<Class> {
 		let mut bytes: Vec<u8> = Vec::new();
-		let mut c = Class::default();
-		let mut offset: usize = 0;
 
 		match fs::File::open(class_with_path) {
 			Ok(mut fd) => {
@@ -370,6 +480,140 @@ impl Class {
 		}
 		Class::load_from_bytes(bytes)
 	}
+
+	/*
+	 * A java/lang/Object for when there is no class file for it: an
+	 * <init> that just returns and native hashCode, equals, getClass
+	 * and toString (see NativeRegistry). It is put together as a class
+	 * file so that it is parsed like any other class.
+	 */
+	pub fn synthetic_object() -> Option<Class> {
+		let utf8s = [
+			"java/lang/Object",
+			"Code",
+			"<init>",
+			"()V",
+			"hashCode",
+			"()I",
+			"equals",
+			"(Ljava/lang/Object;)Z",
+			"getClass",
+			"()Ljava/lang/Class;",
+			"toString",
+			"()Ljava/lang/String;",
+		];
+		/*
+		 * Constant #1 is the Class; the Utf8s are #2 and up.
+		 */
+		let utf8_index = |utf8: &str| 2 + utf8s.iter().position(|u| *u == utf8).unwrap() as u16;
+
+		let mut bytes: Vec<u8> = Vec::new();
+		let write_u16 = |bytes: &mut Vec<u8>, value: u16| {
+			bytes.extend_from_slice(&[(value >> 8) as u8, value as u8])
+		};
+		bytes.extend_from_slice(&[0xCA, 0xFE, 0xBA, 0xBE]);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 52);
+
+		write_u16(&mut bytes, 2 + utf8s.len() as u16);
+		bytes.push(ConstantTag::Class as u8);
+		write_u16(&mut bytes, utf8_index("java/lang/Object"));
+		for utf8 in utf8s.iter() {
+			bytes.push(ConstantTag::Utf8 as u8);
+			write_u16(&mut bytes, utf8.len() as u16);
+			bytes.extend_from_slice(utf8.as_bytes());
+		}
+
+		write_u16(
+			&mut bytes,
+			ClassAccessFlags::Public as u16 | ClassAccessFlags::Super as u16,
+		);
+		write_u16(&mut bytes, 1);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 0);
+
+		let natives = [
+			("hashCode", "()I"),
+			("equals", "(Ljava/lang/Object;)Z"),
+			("getClass", "()Ljava/lang/Class;"),
+			("toString", "()Ljava/lang/String;"),
+		];
+		write_u16(&mut bytes, 1 + natives.len() as u16);
+
+		/*
+		 * <init>: max_stack 0, max_locals 1 and a lone return.
+		 */
+		write_u16(&mut bytes, MethodAccessFlags::Public as u16);
+		write_u16(&mut bytes, utf8_index("<init>"));
+		write_u16(&mut bytes, utf8_index("()V"));
+		write_u16(&mut bytes, 1);
+		write_u16(&mut bytes, utf8_index("Code"));
+		bytes.extend_from_slice(&[0, 0, 0, 13]);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 1);
+		bytes.extend_from_slice(&[0, 0, 0, 1, 0xb1]);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 0);
+
+		for (name, descriptor) in natives.iter() {
+			write_u16(
+				&mut bytes,
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Native as u16,
+			);
+			write_u16(&mut bytes, utf8_index(name));
+			write_u16(&mut bytes, utf8_index(descriptor));
+			write_u16(&mut bytes, 0);
+		}
+
+		write_u16(&mut bytes, 0);
+		Class::load_from_bytes(bytes)
+	}
+
+	/*
+	 * The class of arrays of type _class_name_ ([I or
+	 * [Ljava/lang/String;, say): a final subclass of java/lang/Object
+	 * without fields or methods of its own. Arrays have no class files,
+	 * so this is the only way to get one.
+	 */
+	pub fn synthetic_array(class_name: &str) -> Option<Class> {
+		if !class_name.starts_with("[") {
+			return None;
+		}
+		let utf8s = [class_name, "java/lang/Object"];
+
+		let mut bytes: Vec<u8> = Vec::new();
+		let write_u16 = |bytes: &mut Vec<u8>, value: u16| {
+			bytes.extend_from_slice(&[(value >> 8) as u8, value as u8])
+		};
+		bytes.extend_from_slice(&[0xCA, 0xFE, 0xBA, 0xBE]);
+		write_u16(&mut bytes, 0);
+		write_u16(&mut bytes, 52);
+
+		/*
+		 * Constants #1 and #3 are the Classes; #2 and #4 their names.
+		 */
+		write_u16(&mut bytes, 1 + 2 * utf8s.len() as u16);
+		for (index, utf8) in utf8s.iter().enumerate() {
+			bytes.push(ConstantTag::Class as u8);
+			write_u16(&mut bytes, 2 * index as u16 + 2);
+			bytes.push(ConstantTag::Utf8 as u8);
+			write_u16(&mut bytes, utf8.len() as u16);
+			bytes.extend_from_slice(utf8.as_bytes());
+		}
+
+		write_u16(
+			&mut bytes,
+			ClassAccessFlags::Public as u16 | ClassAccessFlags::Final as u16,
+		);
+		write_u16(&mut bytes, 1);
+		write_u16(&mut bytes, 3);
+		/*
+		 * No interfaces, fields, methods or attributes.
+		 */
+		bytes.extend_from_slice(&[0; 8]);
+		Class::load_from_bytes(bytes)
+	}
 }
 
 impl fmt::Display for Class {
@@ -391,8 +635,8 @@ impl fmt::Display for Class {
 		write!(f, "this_class: {}\n", self.this_class);
 		write!(f, "super_class: {}\n", self.super_class);
 		write!(f, "interfaces_count: {}\n", self.interfaces_count);
-		for i in 1..self.interfaces_count {
-			write!(f, "#{}: {}\n", i, self.interfaces[i as usize - 1]);
+		for i in 0..self.interfaces_count as usize {
+			write!(f, "#{}: {}\n", i, self.interfaces[i]);
 		}
 		write!(f, "fields_count: {}\n", self.fields_count);
 		write!(f, "fields: {}\n", self.fields);
@@ -402,3 +646,722 @@ impl fmt::Display for Class {
 		write!(f, "attributes: {}\n", self.attributes)
 	}
 }
+
+#[cfg(test)]
+pub mod tests {
+	use super::*;
+	use jvm::field::FieldAccessFlags;
+
+	fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+		bytes.extend_from_slice(&value.to_be_bytes());
+	}
+
+	fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+		bytes.extend_from_slice(&value.to_be_bytes());
+	}
+
+	/*
+	 * Puts together a class file, constant by constant and member by
+	 * member, for the tests of the parts of the JVM that need
+	 * classes. Adding a constant that is already there gives the
+	 * index of the one that is.
+	 */
+	pub struct ClassFileBuilder {
+		constants: Vec<(u16, Vec<u8>)>,
+		next_index: u16,
+		access_flags: u16,
+		this_class: u16,
+		super_class: u16,
+		interfaces: Vec<u16>,
+		fields: Vec<Vec<u8>>,
+		methods: Vec<Vec<u8>>,
+		bootstrap_methods: Vec<Vec<u8>>,
+	}
+
+	impl ClassFileBuilder {
+		/*
+		 * A public class _class_name_ that extends _superclass_name_
+		 * (or nothing, when that is None).
+		 */
+		pub fn new(class_name: &str, superclass_name: Option<&str>) -> Self {
+			let mut builder = ClassFileBuilder {
+				constants: Vec::new(),
+				next_index: 1,
+				access_flags: ClassAccessFlags::Public as u16 | ClassAccessFlags::Super as u16,
+				this_class: 0,
+				super_class: 0,
+				interfaces: Vec::new(),
+				fields: Vec::new(),
+				methods: Vec::new(),
+				bootstrap_methods: Vec::new(),
+			};
+			builder.this_class = builder.class(class_name);
+			if let Some(superclass_name) = superclass_name {
+				builder.super_class = builder.class(superclass_name);
+			}
+			builder
+		}
+
+		fn add(&mut self, entry: Vec<u8>, slots: u16) -> u16 {
+			if let Some((index, _)) = self.constants.iter().find(|(_, e)| *e == entry) {
+				return *index;
+			}
+			let index = self.next_index;
+			self.constants.push((index, entry));
+			self.next_index += slots;
+			index
+		}
+
+		fn add_u16s(&mut self, tag: ConstantTag, values: &[u16]) -> u16 {
+			let mut entry = vec![tag as u8];
+			for value in values {
+				write_u16(&mut entry, *value);
+			}
+			self.add(entry, 1)
+		}
+
+		pub fn utf8(&mut self, value: &str) -> u16 {
+			let mut entry = vec![ConstantTag::Utf8 as u8];
+			write_u16(&mut entry, value.len() as u16);
+			entry.extend_from_slice(value.as_bytes());
+			self.add(entry, 1)
+		}
+
+		pub fn class(&mut self, class_name: &str) -> u16 {
+			let name_index = self.utf8(class_name);
+			self.add_u16s(ConstantTag::Class, &[name_index])
+		}
+
+		pub fn string(&mut self, value: &str) -> u16 {
+			let string_index = self.utf8(value);
+			self.add_u16s(ConstantTag::String, &[string_index])
+		}
+
+		pub fn integer(&mut self, value: i32) -> u16 {
+			let mut entry = vec![ConstantTag::Integer as u8];
+			write_u32(&mut entry, value as u32);
+			self.add(entry, 1)
+		}
+
+		pub fn float(&mut self, value: f32) -> u16 {
+			let mut entry = vec![ConstantTag::Float as u8];
+			write_u32(&mut entry, value.to_bits());
+			self.add(entry, 1)
+		}
+
+		pub fn long(&mut self, value: i64) -> u16 {
+			let mut entry = vec![ConstantTag::Long as u8];
+			entry.extend_from_slice(&value.to_be_bytes());
+			self.add(entry, 2)
+		}
+
+		pub fn double(&mut self, value: f64) -> u16 {
+			let mut entry = vec![ConstantTag::Double as u8];
+			entry.extend_from_slice(&value.to_bits().to_be_bytes());
+			self.add(entry, 2)
+		}
+
+		pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+			let name_index = self.utf8(name);
+			let descriptor_index = self.utf8(descriptor);
+			self.add_u16s(ConstantTag::NameAndType, &[name_index, descriptor_index])
+		}
+
+		fn member_ref(
+			&mut self,
+			tag: ConstantTag,
+			class_name: &str,
+			name: &str,
+			descriptor: &str,
+		) -> u16 {
+			let class_index = self.class(class_name);
+			let name_and_type_index = self.name_and_type(name, descriptor);
+			self.add_u16s(tag, &[class_index, name_and_type_index])
+		}
+
+		pub fn field_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> u16 {
+			self.member_ref(ConstantTag::Fieldref, class_name, name, descriptor)
+		}
+
+		pub fn method_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> u16 {
+			self.member_ref(ConstantTag::Methodref, class_name, name, descriptor)
+		}
+
+		pub fn interface_method_ref(
+			&mut self,
+			class_name: &str,
+			name: &str,
+			descriptor: &str,
+		) -> u16 {
+			self.member_ref(
+				ConstantTag::InterfaceMethodref,
+				class_name,
+				name,
+				descriptor,
+			)
+		}
+
+		pub fn method_handle(&mut self, reference_kind: u8, reference_index: u16) -> u16 {
+			let mut entry = vec![ConstantTag::MethodHandle as u8, reference_kind];
+			write_u16(&mut entry, reference_index);
+			self.add(entry, 1)
+		}
+
+		pub fn method_type(&mut self, descriptor: &str) -> u16 {
+			let descriptor_index = self.utf8(descriptor);
+			self.add_u16s(ConstantTag::MethodType, &[descriptor_index])
+		}
+
+		pub fn invoke_dynamic(
+			&mut self,
+			bootstrap_method: u16,
+			name: &str,
+			descriptor: &str,
+		) -> u16 {
+			let name_and_type_index = self.name_and_type(name, descriptor);
+			self.add_u16s(
+				ConstantTag::InvokeDynamic,
+				&[bootstrap_method, name_and_type_index],
+			)
+		}
+
+		pub fn dynamic(&mut self, bootstrap_method: u16, name: &str, descriptor: &str) -> u16 {
+			let name_and_type_index = self.name_and_type(name, descriptor);
+			self.add_u16s(
+				ConstantTag::Dynamic,
+				&[bootstrap_method, name_and_type_index],
+			)
+		}
+
+		/*
+		 * Add an entry to the BootstrapMethods attribute and return
+		 * its index.
+		 */
+		pub fn bootstrap_method(&mut self, method_handle: u16, arguments: &[u16]) -> u16 {
+			let mut entry = Vec::new();
+			write_u16(&mut entry, method_handle);
+			write_u16(&mut entry, arguments.len() as u16);
+			for argument in arguments {
+				write_u16(&mut entry, *argument);
+			}
+			self.bootstrap_methods.push(entry);
+			self.bootstrap_methods.len() as u16 - 1
+		}
+
+		pub fn access_flags(&mut self, access_flags: u16) -> &mut Self {
+			self.access_flags = access_flags;
+			self
+		}
+
+		pub fn interface(&mut self, interface_name: &str) -> &mut Self {
+			let interface = self.class(interface_name);
+			self.interfaces.push(interface);
+			self
+		}
+
+		pub fn field(&mut self, access_flags: u16, name: &str, descriptor: &str) -> &mut Self {
+			let mut field = Vec::new();
+			write_u16(&mut field, access_flags);
+			write_u16(&mut field, self.utf8(name));
+			write_u16(&mut field, self.utf8(descriptor));
+			write_u16(&mut field, 0);
+			self.fields.push(field);
+			self
+		}
+
+		/*
+		 * A method without a Code attribute (a native or abstract
+		 * one).
+		 */
+		pub fn method_without_code(
+			&mut self,
+			access_flags: u16,
+			name: &str,
+			descriptor: &str,
+		) -> &mut Self {
+			let mut method = Vec::new();
+			write_u16(&mut method, access_flags);
+			write_u16(&mut method, self.utf8(name));
+			write_u16(&mut method, self.utf8(descriptor));
+			write_u16(&mut method, 0);
+			self.methods.push(method);
+			self
+		}
+
+		pub fn method(
+			&mut self,
+			access_flags: u16,
+			name: &str,
+			descriptor: &str,
+			max_stack: u16,
+			max_locals: u16,
+			code: &[u8],
+		) -> &mut Self {
+			self.method_with_tables(
+				access_flags,
+				name,
+				descriptor,
+				max_stack,
+				max_locals,
+				code,
+				&[],
+				&[],
+			)
+		}
+
+		/*
+		 * A method whose Code attribute has the exception table
+		 * _exception_table_ (start_pc, end_pc, handler_pc and
+		 * catch_type) and, when there are any _line_numbers_
+		 * (start_pc and line_number), a LineNumberTable.
+		 */
+		pub fn method_with_tables(
+			&mut self,
+			access_flags: u16,
+			name: &str,
+			descriptor: &str,
+			max_stack: u16,
+			max_locals: u16,
+			code: &[u8],
+			exception_table: &[(u16, u16, u16, u16)],
+			line_numbers: &[(u16, u16)],
+		) -> &mut Self {
+			let mut attribute = Vec::new();
+			write_u16(&mut attribute, max_stack);
+			write_u16(&mut attribute, max_locals);
+			write_u32(&mut attribute, code.len() as u32);
+			attribute.extend_from_slice(code);
+			write_u16(&mut attribute, exception_table.len() as u16);
+			for (start_pc, end_pc, handler_pc, catch_type) in exception_table {
+				for value in [*start_pc, *end_pc, *handler_pc, *catch_type].iter() {
+					write_u16(&mut attribute, *value);
+				}
+			}
+			if line_numbers.is_empty() {
+				write_u16(&mut attribute, 0);
+			} else {
+				write_u16(&mut attribute, 1);
+				write_u16(&mut attribute, self.utf8("LineNumberTable"));
+				write_u32(&mut attribute, 2 + 4 * line_numbers.len() as u32);
+				write_u16(&mut attribute, line_numbers.len() as u16);
+				for (start_pc, line_number) in line_numbers {
+					write_u16(&mut attribute, *start_pc);
+					write_u16(&mut attribute, *line_number);
+				}
+			}
+
+			let mut method = Vec::new();
+			write_u16(&mut method, access_flags);
+			write_u16(&mut method, self.utf8(name));
+			write_u16(&mut method, self.utf8(descriptor));
+			write_u16(&mut method, 1);
+			write_u16(&mut method, self.utf8("Code"));
+			write_u32(&mut method, attribute.len() as u32);
+			method.extend_from_slice(&attribute);
+			self.methods.push(method);
+			self
+		}
+
+		pub fn bytes(&mut self) -> Vec<u8> {
+			let bootstrap_methods_name = if self.bootstrap_methods.is_empty() {
+				None
+			} else {
+				Some(self.utf8("BootstrapMethods"))
+			};
+
+			let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE];
+			write_u16(&mut bytes, 0);
+			write_u16(&mut bytes, 52);
+			write_u16(&mut bytes, self.next_index);
+			for (_, entry) in self.constants.iter() {
+				bytes.extend_from_slice(entry);
+			}
+			write_u16(&mut bytes, self.access_flags);
+			write_u16(&mut bytes, self.this_class);
+			write_u16(&mut bytes, self.super_class);
+			write_u16(&mut bytes, self.interfaces.len() as u16);
+			for interface in self.interfaces.iter() {
+				write_u16(&mut bytes, *interface);
+			}
+			for members in [&self.fields, &self.methods].iter() {
+				write_u16(&mut bytes, members.len() as u16);
+				for member in members.iter() {
+					bytes.extend_from_slice(member);
+				}
+			}
+			match bootstrap_methods_name {
+				Some(name_index) => {
+					let entries: Vec<u8> = self.bootstrap_methods.concat();
+					write_u16(&mut bytes, 1);
+					write_u16(&mut bytes, name_index);
+					write_u32(&mut bytes, 2 + entries.len() as u32);
+					write_u16(&mut bytes, self.bootstrap_methods.len() as u16);
+					bytes.extend_from_slice(&entries);
+				}
+				None => write_u16(&mut bytes, 0),
+			}
+			bytes
+		}
+
+		pub fn build(&mut self) -> Class {
+			Class::load_from_bytes(self.bytes()).unwrap()
+		}
+	}
+
+	/*
+	 * What javac makes of
+	 *
+	 *   static int add(int a, int b) {
+	 *     IntBinaryOperator add = (x, y) -> x + y;
+	 *     return add.applyAsInt(a, b);
+	 *   }
+	 */
+	pub fn lambda_class(class_name: &str) -> ClassFileBuilder {
+		let public_static = MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16;
+		let mut builder = ClassFileBuilder::new(class_name, Some("java/lang/Object"));
+		let metafactory = builder.method_ref(
+			"java/lang/invoke/LambdaMetafactory",
+			"metafactory",
+			"(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;",
+		);
+		let metafactory = builder.method_handle(6, metafactory);
+		let method_type = builder.method_type("(II)I");
+		let implementation = builder.method_ref(class_name, "lambda$add$0", "(II)I");
+		let implementation = builder.method_handle(6, implementation);
+		let bootstrap_method =
+			builder.bootstrap_method(metafactory, &[method_type, implementation, method_type]);
+		let [call_site_high, call_site_low] = builder
+			.invoke_dynamic(
+				bootstrap_method,
+				"applyAsInt",
+				"()Ljava/util/function/IntBinaryOperator;",
+			)
+			.to_be_bytes();
+		let [apply_high, apply_low] = builder
+			.interface_method_ref(
+				"java/util/function/IntBinaryOperator",
+				"applyAsInt",
+				"(II)I",
+			)
+			.to_be_bytes();
+		/*
+		 * invokedynamic applyAsInt; iload_0; iload_1; invokeinterface
+		 * applyAsInt; ireturn
+		 */
+		builder
+			.method(
+				public_static,
+				"add",
+				"(II)I",
+				3,
+				2,
+				&[
+					0xba,
+					call_site_high,
+					call_site_low,
+					0,
+					0,
+					0x1a,
+					0x1b,
+					0xb9,
+					apply_high,
+					apply_low,
+					3,
+					0,
+					0xac,
+				],
+			)
+			/*
+			 * iload_0; iload_1; iadd; ireturn
+			 */
+			.method(
+				MethodAccessFlags::Private as u16
+					| MethodAccessFlags::Static as u16
+					| MethodAccessFlags::Synthetic as u16,
+				"lambda$add$0",
+				"(II)I",
+				2,
+				2,
+				&[0x1a, 0x1b, 0x60, 0xac],
+			);
+		builder
+	}
+
+	#[test]
+	fn builds_class_files_that_parse() {
+		let mut builder = ClassFileBuilder::new("Built", Some("java/lang/Object"));
+		let long = builder.long(1 << 40);
+		let after_long = builder.integer(7);
+		assert_eq!(after_long, long + 2);
+		builder
+			.interface("java/lang/Runnable")
+			.field(FieldAccessFlags::Private as u16, "count", "I")
+			.method(
+				MethodAccessFlags::Public as u16,
+				"run",
+				"()V",
+				0,
+				1,
+				&[0xb1],
+			);
+		let class = builder.build();
+		assert_eq!(class.get_class_name(), Some(format!("Built")));
+		assert_eq!(class.fields_count, 1);
+		assert_eq!(class.methods_count, 1);
+		assert_eq!(class.interfaces_count, 1);
+		assert_eq!(
+			class
+				.get_method_rc_by_name_and_type(&format!("run"), &format!("()V"))
+				.unwrap()
+				.get_code(),
+			Some(&[0xb1u8][..])
+		);
+	}
+
+	fn object_class_file() -> Vec<u8> {
+		Class::synthetic_object().unwrap().bytes
+	}
+
+	fn parse(bytes: &[u8]) -> Result<Class, ClassFileError> {
+		let mut c = Class::default();
+		Class::parse(&mut c, &mut ClassFileReader::new(bytes)).map(|_| c)
+	}
+
+	#[test]
+	fn parses_a_whole_class_file() {
+		let class = parse(&object_class_file()).unwrap();
+		assert_eq!(class.get_class_name(), Some(format!("java/lang/Object")));
+		assert_eq!(class.methods_count, 5);
+	}
+
+	#[test]
+	fn rejects_truncated_class_files() {
+		let bytes = object_class_file();
+		for length in 0..bytes.len() {
+			assert!(matches!(
+				parse(&bytes[..length]),
+				Err(ClassFileError::Truncated(_))
+			));
+			assert!(Class::load_from_bytes(bytes[..length].to_vec()).is_none());
+		}
+	}
+
+	#[test]
+	fn rejects_bad_headers_and_constants() {
+		let mut bytes = object_class_file();
+		bytes[0] = 0;
+		assert!(matches!(parse(&bytes), Err(ClassFileError::BadMagic(_))));
+
+		let mut bytes = object_class_file();
+		bytes[7] = 99;
+		assert!(matches!(
+			parse(&bytes),
+			Err(ClassFileError::UnsupportedVersion(99, 0))
+		));
+
+		/*
+		 * The tag of constant #1.
+		 */
+		let mut bytes = object_class_file();
+		bytes[10] = 2;
+		assert!(matches!(
+			parse(&bytes),
+			Err(ClassFileError::BadConstantTag(2, 10))
+		));
+	}
+
+	#[test]
+	fn interface_names_are_in_order() {
+		let mut builder = ClassFileBuilder::new("Both", Some("java/lang/Object"));
+		let class = builder
+			.interface("java/lang/Runnable")
+			.interface("java/lang/Comparable")
+			.build();
+
+		assert_eq!(class.interfaces_count, 2);
+		assert_eq!(
+			class.get_interface_names(),
+			vec![
+				format!("java/lang/Runnable"),
+				format!("java/lang/Comparable")
+			]
+		);
+		assert!(ClassFileBuilder::new("Neither", Some("java/lang/Object"))
+			.build()
+			.get_interface_names()
+			.is_empty());
+	}
+
+	/*
+	 * Write _bytes_ to a class file of their own.
+	 */
+	fn class_file(test: &str, bytes: &[u8]) -> String {
+		let file = std::env::temp_dir().join(format!(
+			"rust-jvm-class-{}-{}.class",
+			test,
+			std::process::id()
+		));
+		fs::write(&file, bytes).unwrap();
+		file.to_str().unwrap().to_string()
+	}
+
+	#[test]
+	fn truncated_class_files_do_not_load() {
+		let mut builder = ClassFileBuilder::new("Cut", Some("java/lang/Object"));
+		builder
+			.interface("java/lang/Runnable")
+			.field(FieldAccessFlags::Private as u16, "count", "I")
+			.method(
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16,
+				"zero",
+				"()I",
+				1,
+				0,
+				&[0x03, 0xac],
+			);
+		let bytes = builder.bytes();
+
+		/*
+		 * Inside the magic, the constant pool, the members and the very
+		 * last attribute.
+		 */
+		for length in [2, 9, bytes.len() / 2, bytes.len() - 3, bytes.len() - 1].iter() {
+			assert!(matches!(
+				parse(&bytes[..*length]),
+				Err(ClassFileError::Truncated(_))
+			));
+			let file = class_file(&format!("cut-{}", length), &bytes[..*length]);
+			assert!(Class::load_from_file(&file).is_none());
+			fs::remove_file(&file).unwrap();
+		}
+		assert!(parse(&bytes).is_ok());
+	}
+
+	#[test]
+	fn loading_bytes_matches_loading_files() {
+		let mut builder = ClassFileBuilder::new("Loaded", Some("java/lang/Object"));
+		builder
+			.interface("java/lang/Runnable")
+			.field(FieldAccessFlags::Private as u16, "count", "I")
+			.method(
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16,
+				"zero",
+				"()I",
+				1,
+				0,
+				&[0x03, 0xac],
+			);
+		let file = class_file("loaded", &builder.bytes());
+
+		let mut bytes: Vec<u8> = Vec::new();
+		fs::File::open(&file)
+			.unwrap()
+			.read_to_end(&mut bytes)
+			.unwrap();
+		let from_bytes = Class::load_from_bytes(bytes.clone()).unwrap();
+		let from_file = Class::load_from_file(&file).unwrap();
+		let _ = fs::remove_file(&file);
+
+		assert_eq!(from_bytes.bytes, bytes);
+		assert_eq!(from_file.bytes, bytes);
+		assert_eq!(from_bytes.get_class_name(), Some(format!("Loaded")));
+		assert_eq!(format!("{}", from_bytes), format!("{}", from_file));
+	}
+
+	#[test]
+	fn loading_rejects_bad_magic_and_future_versions() {
+		let mut bytes = ClassFileBuilder::new("Bad", Some("java/lang/Object")).bytes();
+		bytes[..4].copy_from_slice(&[0xca, 0xfe, 0xd0, 0x0d]);
+		let file = class_file("bad-magic", &bytes);
+		assert!(Class::load_from_file(&file).is_none());
+		let _ = fs::remove_file(&file);
+
+		let mut bytes = ClassFileBuilder::new("Future", Some("java/lang/Object")).bytes();
+		bytes[6..8].copy_from_slice(&1000u16.to_be_bytes());
+		let file = class_file("future-version", &bytes);
+		assert!(Class::load_from_file(&file).is_none());
+		let _ = fs::remove_file(&file);
+
+		let mut bytes = ClassFileBuilder::new("Supported", Some("java/lang/Object")).bytes();
+		bytes[6..8].copy_from_slice(&MAX_SUPPORTED_MAJOR_VERSION.to_be_bytes());
+		assert!(Class::load_from_bytes(bytes).is_some());
+	}
+
+	#[test]
+	fn reader_reads_big_endian_values_in_order() {
+		let bytes = [0x01, 0x02, 0x03, 0xca, 0xfe, 0xba, 0xbe, 0x04, 0x05, 0x06];
+		let mut reader = ClassFileReader::new(&bytes);
+		assert_eq!(reader.read_u8().unwrap(), 0x01);
+		assert_eq!(reader.read_u16().unwrap(), 0x0203);
+		assert_eq!(reader.read_u32().unwrap(), 0xcafebabe);
+		assert_eq!(reader.offset(), 7);
+		assert_eq!(reader.remaining(), &[0x04, 0x05, 0x06]);
+		reader.skip(1).unwrap();
+		assert_eq!(reader.read_bytes(2).unwrap(), &[0x05, 0x06]);
+		assert_eq!(reader.offset(), bytes.len());
+		assert!(reader.remaining().is_empty());
+		assert_eq!(reader.read_bytes(0).unwrap(), &[] as &[u8]);
+	}
+
+	#[test]
+	fn reader_does_not_read_past_the_end() {
+		let bytes = [0x01, 0x02, 0x03];
+		let mut reader = ClassFileReader::new(&bytes);
+		assert!(matches!(
+			reader.read_u32(),
+			Err(ClassFileError::Truncated(4))
+		));
+		assert_eq!(reader.offset(), 0);
+		assert_eq!(reader.read_u16().unwrap(), 0x0102);
+		assert!(matches!(
+			reader.read_u16(),
+			Err(ClassFileError::Truncated(4))
+		));
+		assert!(matches!(reader.skip(2), Err(ClassFileError::Truncated(4))));
+		assert_eq!(reader.read_u8().unwrap(), 0x03);
+		assert!(matches!(
+			reader.read_u8(),
+			Err(ClassFileError::Truncated(4))
+		));
+		assert!(matches!(
+			reader.read_bytes(usize::max_value()),
+			Err(ClassFileError::Truncated(_))
+		));
+	}
+
+	#[test]
+	fn lists_method_signatures() {
+		let public = MethodAccessFlags::Public as u16;
+		let public_static = public | MethodAccessFlags::Static as u16;
+		let mut builder = ClassFileBuilder::new("Signed", Some("java/lang/Object"));
+		let class = builder
+			.method(public, "<init>", "()V", 1, 1, &[0xb1])
+			.method(
+				public_static,
+				"twice",
+				"(I)I",
+				2,
+				1,
+				&[0x1a, 0x05, 0x68, 0xac],
+			)
+			.method_without_code(
+				public | MethodAccessFlags::Native as u16,
+				"name",
+				"()Ljava/lang/String;",
+			)
+			.build();
+
+		assert_eq!(
+			class.get_method_signatures(),
+			vec![
+				(format!("<init>"), format!("()V"), public),
+				(format!("twice"), format!("(I)I"), public_static),
+				(
+					format!("name"),
+					format!("()Ljava/lang/String;"),
+					public | MethodAccessFlags::Native as u16
+				),
+			]
+		);
+	}
+}
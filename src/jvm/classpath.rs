@@ -25,7 +25,7 @@ use jvm::debug::Debug;
 use jvm::debug::DebugLevel;
 use jvm::error::FatalError;
 use jvm::error::FatalErrorType;
-use rjar::Jar;
+use jvm::jar::Jar;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
@@ -36,8 +36,17 @@ pub enum ClassLocation {
 	JarFile(String, String),
 }
 
+/*
+ * The places to look for classes, in the order in which to look.
+ */
+#[derive(Clone)]
 pub struct ClassPath {
-	classes: HashMap<String, ClassLocation>,
+	entries: Vec<String>,
+	/*
+	 * The classes in each entry's files, whatever their names, along
+	 * with the index of the entry they are in.
+	 */
+	classes: HashMap<String, (usize, ClassLocation)>,
 	debug_level: DebugLevel,
 }
 
@@ -49,18 +58,119 @@ fn class_name_from_class_file(class_file: &String) -> Option<String> {
 	}
 }
 
+/*
+ * Record the classes in the JAR file _jar_file_ as being in the
+ * classpath's _index_th entry.
+ */
+fn index_jar_file(
+	jar_file: &String,
+	index: usize,
+	classes: &mut HashMap<String, (usize, ClassLocation)>,
+	debug_level: &DebugLevel,
+) {
+	Debug(
+		format!("Loading JAR file {}", jar_file),
+		debug_level,
+		DebugLevel::Info,
+	);
+
+	match Jar::open(&jar_file.clone()) {
+		Ok(jar) => {
+			for file in jar.file_names() {
+				/*
+				 * Skip everything but class files.
+				 */
+				if !file.ends_with("class") {
+					continue;
+				}
+				if let Ok(file_bytes) = jar.file_contents_by_name(&file) {
+					if let Some(class) = Class::load_from_bytes(file_bytes) {
+						if let Some(class_name) = class.get_class_name() {
+							Debug(
+								format!("{}:{} contains object {}.", jar_file, file, class_name),
+								debug_level,
+								DebugLevel::Info,
+							);
+							classes.entry(class_name).or_insert((
+								index,
+								ClassLocation::JarFile(jar_file.clone(), file.clone()),
+							));
+						}
+					} else {
+						Debug(
+							format!(
+								"Could not read a class from bytes from {}:{}.",
+								jar_file, file
+							),
+							debug_level,
+							DebugLevel::Warning,
+						);
+					}
+				} else {
+					Debug(
+						format!("Could not read a class from {}:{}.", jar_file, file),
+						debug_level,
+						DebugLevel::Warning,
+					);
+				}
+			}
+		}
+		Err(_e) => {
+			Debug(
+				format!("Could not open JAR file {}.", jar_file),
+				debug_level,
+				DebugLevel::Warning,
+			);
+		}
+	};
+}
+
 impl ClassPath {
+	/*
+	 * Find _class_ (e.g., java/lang/String) in the first entry that
+	 * has it: either as <entry>/java/lang/String.class or as some
+	 * other file in the entry (or in the entry, if it is a JAR file)
+	 * that holds it.
+	 */
 	pub fn class_location_for_class(&self, class: &str) -> Option<ClassLocation> {
-		if let Some(class) = self.classes.get(class) {
-			Some(class.clone())
-		} else {
-			None
+		for (index, entry) in self.entries.iter().enumerate() {
+			if let Some((class_entry_index, location)) = self.classes.get(class) {
+				if *class_entry_index == index {
+					return Some(location.clone());
+				}
+			}
+			let class_file = Path::new(entry).join(format!("{}.class", class));
+			if class_file.is_file() {
+				if let Some(class_file) = class_file.to_str() {
+					Debug(
+						format!("{} classpath entry contains {}.", entry, class),
+						&self.debug_level,
+						DebugLevel::Info,
+					);
+					return Some(ClassLocation::ClassFile(class_file.to_string()));
+				}
+			}
 		}
+		None
 	}
+
+	/*
+	 * The entries, in the order in which they are searched.
+	 */
+	pub fn entries(&self) -> &[String] {
+		&self.entries
+	}
+
 	pub fn new(classpath: &[&str], debug_level: DebugLevel) -> Self {
-		let mut classes = HashMap::<String, ClassLocation>::new();
-		for path in classpath {
-			if let Ok(dir_list) = fs::read_dir(Path::new(&path)) {
+		let mut classes = HashMap::<String, (usize, ClassLocation)>::new();
+		for (index, path) in classpath.iter().enumerate() {
+			/*
+			 * An entry is either a JAR file or a directory of class
+			 * (and JAR) files.
+			 */
+			if path.ends_with(".jar") && Path::new(&path).is_file() {
+				index_jar_file(&path.to_string(), index, &mut classes, &debug_level);
+			} else if let Ok(dir_list) = fs::read_dir(Path::new(&path)) {
 				for dir in dir_list {
 					if let Ok(path_entry) = dir {
 						if let Some(path_entry) = path_entry.path().to_str() {
@@ -86,10 +196,10 @@ impl ClassPath {
 										&debug_level,
 										DebugLevel::Info,
 									);
-									classes.insert(
-										class_name,
+									classes.entry(class_name).or_insert((
+										index,
 										ClassLocation::ClassFile(path_entry_file),
-									);
+									));
 								} else {
 									Debug(
 										format!("Could not load a name for {}.", path_entry_file),
@@ -98,68 +208,7 @@ impl ClassPath {
 									);
 								}
 							} else if path_entry_file.ends_with("jar") {
-								Debug(
-									format!("Loading JAR file {}", path_entry_file),
-									&debug_level,
-									DebugLevel::Info,
-								);
-
-								match Jar::open(&path_entry_file.clone()) {
-									Ok(mut jar) => {
-										for file in jar.file_names() {
-											/*
-											 * Skip class files.
-											 */
-											if !file.ends_with("class") {
-												continue;
-											}
-											if let Ok(file_bytes) = jar.file_contents_by_name(&file)
-											{
-												if let Some(class) =
-													Class::load_from_bytes(file_bytes)
-												{
-													if let Some(class_name) = class.get_class_name()
-													{
-														classes.insert(
-															class_name,
-															ClassLocation::JarFile(
-																path_entry_file.clone(),
-																file.clone(),
-															),
-														);
-													}
-												} else {
-													Debug(
-														format!(
-														"Could not read a class from bytes from {}:{}.",
-														path_entry_file, file
-													),
-														&debug_level,
-														DebugLevel::Warning,
-													);
-												}
-											} else {
-												Debug(
-													format!(
-														"Could not read a class from {}:{}.",
-														path_entry_file, file
-													),
-													&debug_level,
-													DebugLevel::Warning,
-												);
-											}
-										}
-									}
-									Err(_e) => {}
-								};
-
-								/*
-								 * TODO: This has to use *something* from the JAR file to figure
-								 * out which classes are in this JAR file.
-								for f in xxxx {
-									classes.insert(f, path_entry_file);
-								}
-								 */
+								index_jar_file(&path_entry_file, index, &mut classes, &debug_level);
 							}
 						}
 					}
@@ -167,8 +216,120 @@ impl ClassPath {
 			}
 		}
 		ClassPath {
+			entries: classpath.iter().map(|entry| entry.to_string()).collect(),
 			classes,
 			debug_level,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::environment::Environment;
+	use jvm::jar::tests::stored_jar_file;
+	use jvm::methodarea::MethodArea;
+	use std::path::PathBuf;
+
+	/*
+	 * A fresh temporary directory that holds a class file for each
+	 * of _classes_, named after the class.
+	 */
+	fn class_directory(test: &str, classes: &[&str]) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!(
+			"rust-jvm-classpath-{}-{}",
+			test,
+			std::process::id()
+		));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+		for class in classes {
+			fs::write(
+				directory.join(format!("{}.class", class)),
+				ClassFileBuilder::new(class, Some("java/lang/Object")).bytes(),
+			)
+			.unwrap();
+		}
+		directory
+	}
+
+	fn class_file_directory(location: Option<ClassLocation>) -> Option<PathBuf> {
+		match location {
+			Some(ClassLocation::ClassFile(file)) => {
+				Path::new(&file).parent().map(|parent| parent.to_path_buf())
+			}
+			_ => None,
+		}
+	}
+
+	#[test]
+	fn earlier_entries_shadow_later_ones() {
+		let first = class_directory("first", &["Shared"]);
+		let second = class_directory("second", &["Shared", "OnlySecond"]);
+		let classpath = ClassPath::new(
+			&[first.to_str().unwrap(), second.to_str().unwrap()],
+			DebugLevel::Error,
+		);
+		assert_eq!(
+			classpath.entries(),
+			&[
+				first.to_str().unwrap().to_string(),
+				second.to_str().unwrap().to_string()
+			]
+		);
+		assert_eq!(
+			class_file_directory(classpath.class_location_for_class("Shared")),
+			Some(first.clone())
+		);
+		assert_eq!(
+			class_file_directory(classpath.class_location_for_class("OnlySecond")),
+			Some(second.clone())
+		);
+		assert!(classpath.class_location_for_class("Missing").is_none());
+
+		let reversed = ClassPath::new(
+			&[second.to_str().unwrap(), first.to_str().unwrap()],
+			DebugLevel::Error,
+		);
+		assert_eq!(
+			class_file_directory(reversed.class_location_for_class("Shared")),
+			Some(second.clone())
+		);
+		let _ = fs::remove_dir_all(&first);
+		let _ = fs::remove_dir_all(&second);
+	}
+
+	#[test]
+	fn loads_classes_out_of_jar_files() {
+		let directory = class_directory("jar", &[]);
+		let jar = directory.join("classes.jar");
+		fs::write(
+			&jar,
+			stored_jar_file(&[(
+				"pkg/Packed.class",
+				&ClassFileBuilder::new("pkg/Packed", Some("java/lang/Object")).bytes(),
+			)]),
+		)
+		.unwrap();
+
+		let classpath = ClassPath::new(&[jar.to_str().unwrap()], DebugLevel::Error);
+		match classpath.class_location_for_class("pkg/Packed") {
+			Some(ClassLocation::JarFile(jar_file, file)) => {
+				assert_eq!(jar_file, jar.to_str().unwrap());
+				assert_eq!(file, "pkg/Packed.class");
+			}
+			_ => panic!("pkg/Packed is not in the JAR file."),
+		}
+
+		let mut methodarea = MethodArea::new(
+			DebugLevel::Error,
+			Environment::with_classpath(classpath, &[]),
+		);
+		let class = methodarea
+			.maybe_load_class(&"pkg/Packed".to_string())
+			.unwrap();
+		assert_eq!(class.get_class_name(), Some("pkg/Packed".to_string()));
+		let _ = fs::remove_dir_all(&directory);
+	}
+}
@@ -26,9 +26,10 @@ pub enum Utf8Reserved {
 	Code,
 	ConstantValue,
 	StackMapTable,
-	BoostrapMethods,
+	BootstrapMethods,
 	NestHost,
 	NestMembers,
+	LineNumberTable,
 	NotReserved,
 }
 
@@ -39,6 +40,8 @@ impl fmt::Display for Utf8Reserved {
 			Utf8Reserved::Code => write!(f, "Code"),
 			Utf8Reserved::StackMapTable => write!(f, "StackMapTable"),
 			Utf8Reserved::ConstantValue => write!(f, "ConstantValue"),
+			Utf8Reserved::LineNumberTable => write!(f, "LineNumberTable"),
+			Utf8Reserved::BootstrapMethods => write!(f, "BootstrapMethods"),
 			_ => write!(f, "Unknown"),
 		}
 	}
@@ -59,9 +62,10 @@ pub enum Constant {
 	Utf8(u8, Utf8Reserved, u16, String),
 	MethodHandle(u8, u8, u16),
 	MethodType(u8, u16),
+	Dynamic(u8, u16, u16),
 	InvokeDynamic(u8, u16, u16),
-	Module(),
-	Package(),
+	Module(u8, u16),
+	Package(u8, u16),
 	Default(),
 }
 
@@ -103,6 +107,34 @@ impl fmt::Display for Constant {
 			Constant::Double(tag, value) => {
 				write!(f, "Double: tag: {}, value: 0x{:x} (ieee754)", tag, value)
 			}
+			Constant::MethodHandle(tag, reference_kind, reference_index) => write!(
+				f,
+				"MethodHandle: tag: {}, reference_kind: {}, reference_index: {}",
+				tag, reference_kind, reference_index
+			),
+			Constant::MethodType(tag, descriptor_index) => write!(
+				f,
+				"MethodType: tag: {}, descriptor_index: {}",
+				tag, descriptor_index
+			),
+			Constant::Dynamic(tag, bootstrap_method_attr_index, name_and_type_index) => write!(
+				f,
+				"Dynamic: tag: {}, bootstrap_method_attr_index: {}, name_and_type_index: {}",
+				tag, bootstrap_method_attr_index, name_and_type_index
+			),
+			Constant::InvokeDynamic(tag, bootstrap_method_attr_index, name_and_type_index) => {
+				write!(
+					f,
+					"InvokeDynamic: tag: {}, bootstrap_method_attr_index: {}, name_and_type_index: {}",
+					tag, bootstrap_method_attr_index, name_and_type_index
+				)
+			}
+			Constant::Module(tag, name_index) => {
+				write!(f, "Module: tag: {}, name_index: {}", tag, name_index)
+			}
+			Constant::Package(tag, name_index) => {
+				write!(f, "Package: tag: {}, name_index: {}", tag, name_index)
+			}
 			_ => write!(f, "Unknown"),
 		}
 	}
@@ -22,8 +22,13 @@
 #![allow(non_camel_case_types)]
 
 use enum_primitive::FromPrimitive;
+use jvm::class::ClassFileError;
+use jvm::class::ClassFileReader;
 use jvm::constant::Constant;
 use jvm::constant::Utf8Reserved;
+use jvm::error::FatalError;
+use jvm::error::FatalErrorType;
+use std::convert::TryFrom;
 use std::iter::repeat;
 use std::str;
 
@@ -42,6 +47,7 @@ pub enum ConstantTag {
 	Utf8 = 1,
 	MethodHandle= 15,
 	MethodType = 16,
+	Dynamic = 17,
 	InvokeDynamic = 18,
 	Module = 19,
 	Package = 20,
@@ -49,8 +55,12 @@ pub enum ConstantTag {
 
 #[derive(Clone, Default)]
 pub struct ConstantPool {
-	byte_len: usize,
 	constants: Vec<Constant>,
+	/*
+	 * The name of the class whose constant pool this is (for error
+	 * messages).
+	 */
+	class_name: String,
 }
 
 impl ConstantPool {
@@ -65,23 +75,187 @@ impl ConstantPool {
 		&self.constants[index]
 	}
 
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
-	}
-
 	pub fn constant_pool_count(&self) -> u16 {
 		self.constants.len() as u16
 	}
+
+	pub fn set_class_name(&mut self, class_name: String) {
+		self.class_name = class_name;
+	}
+
+	fn invalid_reference(&self, expected: &str, index: usize) -> FatalError {
+		FatalError::new(FatalErrorType::InvalidConstantReference(
+			self.class_name.clone(),
+			expected.to_string(),
+			index as u16,
+		))
+	}
+
+	/*
+	 * The value of the Utf8 constant at _index_.
+	 */
+	pub fn get_utf8(&self, index: usize) -> Result<&str, FatalError> {
+		match self.constants.get(index) {
+			Some(Constant::Utf8(_, _, _, value)) => Ok(value),
+			_ => Err(self.invalid_reference("Utf8", index)),
+		}
+	}
+
+	/*
+	 * The name of the class that the Class constant at _index_ names.
+	 */
+	pub fn get_class_name(&self, index: usize) -> Result<String, FatalError> {
+		match self.constants.get(index) {
+			Some(Constant::Class(_, name_index)) => self
+				.get_utf8(*name_index as usize)
+				.map(|name| name.to_string()),
+			_ => Err(self.invalid_reference("Class", index)),
+		}
+	}
+
+	/*
+	 * The value of the Integer constant at _index_.
+	 */
+	pub fn get_integer(&self, index: usize) -> Result<i32, FatalError> {
+		match self.constants.get(index) {
+			Some(Constant::Integer(_, value)) => Ok(*value as i32),
+			_ => Err(self.invalid_reference("Integer", index)),
+		}
+	}
+
+	/*
+	 * The string that the String constant at _index_ stands for.
+	 */
+	pub fn get_string(&self, index: usize) -> Result<&str, FatalError> {
+		match self.constants.get(index) {
+			Some(Constant::String(_, string_index)) => self.get_utf8(*string_index as usize),
+			_ => Err(self.invalid_reference("String", index)),
+		}
+	}
+
+	/*
+	 * The name of the class that the Class constant at _index_
+	 * refers to.
+	 */
+	pub fn resolve_class(&self, index: usize) -> Option<String> {
+		if let Constant::Class(_, name_index) = self.get_constant_ref(index) {
+			if let Constant::Utf8(_, _, _, name) = self.get_constant_ref(*name_index as usize) {
+				return Some(name.to_string());
+			}
+		}
+		None
+	}
+
+	/*
+	 * The name and the descriptor that the NameAndType constant at
+	 * _index_ refers to.
+	 */
+	pub fn resolve_name_and_type(&self, index: usize) -> Option<(String, String)> {
+		if let Constant::NameAndType(_, name_index, descriptor_index) = self.get_constant_ref(index)
+		{
+			if let (Constant::Utf8(_, _, _, name), Constant::Utf8(_, _, _, descriptor)) = (
+				self.get_constant_ref(*name_index as usize),
+				self.get_constant_ref(*descriptor_index as usize),
+			) {
+				return Some((name.to_string(), descriptor.to_string()));
+			}
+		}
+		None
+	}
+
+	/*
+	 * The class, name and descriptor of the field that the Fieldref
+	 * constant at _index_ refers to.
+	 */
+	pub fn resolve_fieldref(&self, index: usize) -> Option<(String, String, String)> {
+		if let Constant::Fieldref(_, class_index, name_and_type_index) =
+			self.get_constant_ref(index)
+		{
+			let class_name = self.resolve_class(*class_index as usize)?;
+			let (name, descriptor) = self.resolve_name_and_type(*name_and_type_index as usize)?;
+			return Some((class_name, name, descriptor));
+		}
+		None
+	}
+
+	/*
+	 * The name, descriptor and class of the method that the Methodref
+	 * (or InterfaceMethodref) constant at _index_ refers to.
+	 */
+	pub fn resolve_methodref(&self, index: usize) -> Option<(String, String, String)> {
+		/*
+		 * Interface methods (invokeinterface, but also static and
+		 * default interface methods) are referenced by an
+		 * InterfaceMethodref.
+		 */
+		if let Constant::Methodref(_, class_index, name_and_type_index)
+		| Constant::InterfaceMethodref(_, class_index, name_and_type_index) =
+			self.get_constant_ref(index)
+		{
+			let class_name = self.resolve_class(*class_index as usize)?;
+			let (name, descriptor) = self.resolve_name_and_type(*name_and_type_index as usize)?;
+			return Some((name, descriptor, class_name));
+		}
+		None
+	}
+
+	/*
+	 * What the constant at _index_ is, the way javap puts it in a
+	 * comment after an instruction that refers to it.
+	 */
+	pub fn describe(&self, index: usize) -> String {
+		let constant = match self.constants.get(index) {
+			Some(constant) => constant,
+			None => return format!("invalid constant #{}", index),
+		};
+		let described = match constant {
+			Constant::Class(_, _) => self
+				.resolve_class(index)
+				.map(|name| format!("class {}", name)),
+			Constant::String(_, _) => self
+				.get_string(index)
+				.ok()
+				.map(|value| format!("String {}", value)),
+			Constant::Integer(_, value) => Some(format!("int {}", *value as i32)),
+			Constant::Float(_, value) => Some(format!("float {}f", f32::from_bits(*value))),
+			Constant::Long(_, value) => Some(format!("long {}l", *value as i64)),
+			Constant::Double(_, value) => Some(format!("double {}d", f64::from_bits(*value))),
+			Constant::Fieldref(_, _, _) => {
+				self.resolve_fieldref(index)
+					.map(|(class, name, descriptor)| {
+						format!("Field {}.{}:{}", class, name, descriptor)
+					})
+			}
+			Constant::Methodref(_, _, _) => {
+				self.resolve_methodref(index)
+					.map(|(name, descriptor, class)| {
+						format!("Method {}.{}:{}", class, name, descriptor)
+					})
+			}
+			Constant::InterfaceMethodref(_, _, _) => {
+				self.resolve_methodref(index)
+					.map(|(name, descriptor, class)| {
+						format!("InterfaceMethod {}.{}:{}", class, name, descriptor)
+					})
+			}
+			Constant::InvokeDynamic(_, bootstrap_index, name_and_type_index) => self
+				.resolve_name_and_type(*name_and_type_index as usize)
+				.map(|(name, descriptor)| {
+					format!("InvokeDynamic #{}:{}:{}", bootstrap_index, name, descriptor)
+				}),
+			_ => None,
+		};
+		described.unwrap_or_else(|| format!("{}", constant))
+	}
 }
 
-impl<'l> From<&'l Vec<u8>> for ConstantPool {
-	fn from(bytes: &Vec<u8>) -> Self {
-		let mut offset = 0;
+impl<'l, 'a> TryFrom<&'l mut ClassFileReader<'a>> for ConstantPool {
+	type Error = ClassFileError;
+
+	fn try_from(reader: &'l mut ClassFileReader<'a>) -> Result<Self, ClassFileError> {
 		let mut constants: Vec<Constant>;
 		let mut skip = false;
-		let constants_pool_count: u16 =
-			(bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
-		offset += 2;
+		let constants_pool_count = reader.read_u16()?;
 
 		constants = repeat(Constant::Default())
 			.take(constants_pool_count as usize)
@@ -93,78 +267,46 @@ impl<'l> From<&'l Vec<u8>> for ConstantPool {
 				continue;
 			}
 
-			match ConstantTag::from_u8(bytes[offset]) {
+			let tag_offset = reader.offset();
+			let tag = reader.read_u8()?;
+			match ConstantTag::from_u8(tag) {
 				Some(ConstantTag::Class) => {
-					let tag: u8 = bytes[offset];
-					let name_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					offset += 3;
+					let name_index = reader.read_u16()?;
 					constants[i] = Constant::Class(tag, name_index);
 				}
 				Some(ConstantTag::Fieldref) => {
-					let tag: u8 = bytes[offset];
-					let index: u16 = (bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let name_and_type_index: u16 =
-						(bytes[offset + 3] as u16) << 8 | (bytes[offset + 4] as u16);
-					offset += 5;
+					let index = reader.read_u16()?;
+					let name_and_type_index = reader.read_u16()?;
 					constants[i] = Constant::Fieldref(tag, index, name_and_type_index);
 				}
 				Some(ConstantTag::Methodref) => {
-					let tag: u8 = bytes[offset];
-					let index: u16 = (bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let name_and_type_index: u16 =
-						(bytes[offset + 3] as u16) << 8 | (bytes[offset + 4] as u16);
-					offset += 5;
+					let index = reader.read_u16()?;
+					let name_and_type_index = reader.read_u16()?;
 					constants[i] = Constant::Methodref(tag, index, name_and_type_index);
 				}
 				Some(ConstantTag::InterfaceMethodref) => {
-					let tag: u8 = bytes[offset];
-					let class_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let name_and_type_index: u16 =
-						(bytes[offset + 3] as u16) << 8 | (bytes[offset + 4] as u16);
-					offset += 5;
+					let class_index = reader.read_u16()?;
+					let name_and_type_index = reader.read_u16()?;
 					constants[i] =
 						Constant::InterfaceMethodref(tag, class_index, name_and_type_index);
 				}
 				Some(ConstantTag::String) => {
-					let tag: u8 = bytes[offset];
-					let string_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					offset += 3;
+					let string_index = reader.read_u16()?;
 					constants[i] = Constant::String(tag, string_index);
 				}
 				Some(ConstantTag::Integer) => {
-					let tag: u8 = bytes[offset];
-					let bytes: u32 = (bytes[offset + 1] as u32) << 24
-						| (bytes[offset + 2] as u32) << 16
-						| (bytes[offset + 3] as u32) << 8
-						| (bytes[offset + 4] as u32) << 0;
-					offset += 5;
+					let bytes = reader.read_u32()?;
 					constants[i] = Constant::Integer(tag, bytes);
 				}
 				Some(ConstantTag::Float) => {
-					let tag: u8 = bytes[offset];
-					let bytes: u32 = (bytes[offset + 1] as u32) << 24
-						| (bytes[offset + 2] as u32) << 16
-						| (bytes[offset + 3] as u32) << 8
-						| (bytes[offset + 4] as u32) << 0;
-					offset += 5;
+					let bytes = reader.read_u32()?;
 					// TODO: Parse a constant float from bytes into value
 					// https://docs.oracle.com/javase/specs/jvms/se13/html/jvms-4.html#jvms-4.4.4
 					constants[i] = Constant::Float(tag, bytes);
 				}
 				Some(ConstantTag::Long) => {
-					let tag: u8 = bytes[offset];
-					let bytes: u64 = (bytes[offset + 1] as u64) << 56
-						| (bytes[offset + 2] as u64) << 48
-						| (bytes[offset + 3] as u64) << 40
-						| (bytes[offset + 4] as u64) << 32
-						| (bytes[offset + 5] as u64) << 24
-						| (bytes[offset + 6] as u64) << 16
-						| (bytes[offset + 7] as u64) << 8
-						| (bytes[offset + 8] as u64) << 0;
-					offset += 9;
+					let bytes = (reader.read_u32()? as u64) << 32 | reader.read_u32()? as u64;
+					constants[i] = Constant::Long(tag, bytes);
 					/*
 					 * From https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.5
 					 * "... then the next usable item in the pool is located at index n+2"
@@ -172,19 +314,9 @@ impl<'l> From<&'l Vec<u8>> for ConstantPool {
 					skip = true;
 				}
 				Some(ConstantTag::Double) => {
-					let tag: u8 = bytes[offset];
-					let bytes: u64 = (bytes[offset + 1] as u64) << 56
-						| (bytes[offset + 2] as u64) << 48
-						| (bytes[offset + 3] as u64) << 40
-						| (bytes[offset + 4] as u64) << 32
-						| (bytes[offset + 5] as u64) << 24
-						| (bytes[offset + 6] as u64) << 16
-						| (bytes[offset + 7] as u64) << 8
-						| (bytes[offset + 8] as u64) << 0;
-					offset += 9;
+					let bytes = (reader.read_u32()? as u64) << 32 | reader.read_u32()? as u64;
 					// TODO: Parse a constant double from bytes into value
 					// https://docs.oracle.com/javase/specs/jvms/se13/html/jvms-4.html#jvms-4.4.5
-					constants[i] = Constant::Long(tag, bytes);
 					constants[i] = Constant::Double(tag, bytes);
 					/*
 					 * From https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.5
@@ -193,22 +325,16 @@ impl<'l> From<&'l Vec<u8>> for ConstantPool {
 					skip = true;
 				}
 				Some(ConstantTag::NameAndType) => {
-					let tag: u8 = bytes[offset];
-					let name_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let descriptor_index: u16 =
-						(bytes[offset + 3] as u16) << 8 | (bytes[offset + 4] as u16);
-					offset += 5;
+					let name_index = reader.read_u16()?;
+					let descriptor_index = reader.read_u16()?;
 					constants[i] = Constant::NameAndType(tag, name_index, descriptor_index);
 				}
 				Some(ConstantTag::Utf8) => {
 					let mut reserved: Utf8Reserved = Utf8Reserved::NotReserved;
-					let tag: u8 = bytes[offset];
-					let length: u16 = (bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let value_range = offset + 3..offset + 3 + (length as usize);
+					let length = reader.read_u16()?;
 					let mut value: &str = "";
 
-					match str::from_utf8(&bytes[value_range]) {
+					match str::from_utf8(reader.read_bytes(length as usize)?) {
 						Ok(v) => {
 							value = v;
 						}
@@ -228,54 +354,197 @@ impl<'l> From<&'l Vec<u8>> for ConstantPool {
 						reserved = Utf8Reserved::StackMapTable;
 					} else if value == "ConstantValue".to_string() {
 						reserved = Utf8Reserved::ConstantValue;
+					} else if value == "LineNumberTable".to_string() {
+						reserved = Utf8Reserved::LineNumberTable;
+					} else if value == "BootstrapMethods".to_string() {
+						reserved = Utf8Reserved::BootstrapMethods;
 					}
 
-					offset += 1 + 2 + (length as usize);
 					constants[i] = Constant::Utf8(tag, reserved, length, value.to_string());
 				}
 				Some(ConstantTag::MethodHandle) => {
-					let tag: u8 = bytes[offset];
-					let reference_kind: u8 = bytes[offset + 1];
-					let reference_index: u16 =
-						(bytes[offset + 2] as u16) << 8 | (bytes[offset + 3] as u16);
+					let reference_kind = reader.read_u8()?;
+					let reference_index = reader.read_u16()?;
 					constants[i] = Constant::MethodHandle(tag, reference_kind, reference_index);
-					offset += 4;
 				}
 				Some(ConstantTag::MethodType) => {
-					let tag: u8 = bytes[offset];
-					let descriptor_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-
+					let descriptor_index = reader.read_u16()?;
 					constants[i] = Constant::MethodType(tag, descriptor_index);
-					offset += 3;
 				}
 				Some(ConstantTag::InvokeDynamic) => {
-					let tag: u8 = bytes[offset];
-					let bootstrap_method_attr_index: u16 =
-						(bytes[offset + 1] as u16) << 8 | (bytes[offset + 2] as u16);
-					let name_and_type_index: u16 =
-						(bytes[offset + 3] as u16) << 8 | (bytes[offset + 4] as u16);
+					let bootstrap_method_attr_index = reader.read_u16()?;
+					let name_and_type_index = reader.read_u16()?;
 					constants[i] = Constant::InvokeDynamic(
 						tag,
 						bootstrap_method_attr_index,
 						name_and_type_index,
 					);
-					offset += 5;
+				}
+				Some(ConstantTag::Dynamic) => {
+					let bootstrap_method_attr_index = reader.read_u16()?;
+					let name_and_type_index = reader.read_u16()?;
+					constants[i] =
+						Constant::Dynamic(tag, bootstrap_method_attr_index, name_and_type_index);
 				}
 				Some(ConstantTag::Module) => {
-					assert!(false, "TODO: Parse a module");
+					let name_index = reader.read_u16()?;
+					constants[i] = Constant::Module(tag, name_index);
 				}
 				Some(ConstantTag::Package) => {
-					assert!(false, "TODO: Parse a package");
-				}
-				_ => {
-					print!("oops: unhandled constant pool tag.\n");
+					let name_index = reader.read_u16()?;
+					constants[i] = Constant::Package(tag, name_index);
 				}
+				None => return Err(ClassFileError::BadConstantTag(tag, tag_offset)),
 			};
 		}
-		ConstantPool {
-			byte_len: offset,
+		Ok(ConstantPool {
 			constants: constants,
+			class_name: String::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::lambda_class;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::error::JvmError;
+
+	#[test]
+	fn resolves_member_references() {
+		let mut builder = ClassFileBuilder::new("Refs", Some("java/lang/Object"));
+		let field = builder.field_ref("Refs", "count", "I");
+		let method = builder.method_ref("java/io/PrintStream", "println", "(I)V");
+		let interface_method = builder.interface_method_ref("java/lang/Runnable", "run", "()V");
+		let class_name = builder.class("java/lang/String");
+		let class = builder.build();
+		let cp = class.get_constant_pool_ref();
+
+		assert_eq!(
+			cp.resolve_fieldref(field as usize),
+			Some((format!("Refs"), format!("count"), format!("I")))
+		);
+		assert_eq!(
+			cp.resolve_methodref(method as usize),
+			Some((
+				format!("println"),
+				format!("(I)V"),
+				format!("java/io/PrintStream")
+			))
+		);
+		assert_eq!(
+			cp.resolve_methodref(interface_method as usize),
+			Some((
+				format!("run"),
+				format!("()V"),
+				format!("java/lang/Runnable")
+			))
+		);
+		assert_eq!(
+			cp.resolve_class(class_name as usize),
+			Some(format!("java/lang/String"))
+		);
+
+		/*
+		 * A Fieldref is not a Methodref, and vice versa.
+		 */
+		assert_eq!(cp.resolve_methodref(field as usize), None);
+		assert_eq!(cp.resolve_fieldref(method as usize), None);
+		assert_eq!(cp.resolve_class(method as usize), None);
+	}
+
+	fn is_invalid_reference(error: FatalError, expected_kind: &str, expected_index: u16) -> bool {
+		match error.into_error() {
+			JvmError::Fatal(FatalErrorType::InvalidConstantReference(class, kind, index)) => {
+				class == "Typed" && kind == expected_kind && index == expected_index
+			}
+			_ => false,
+		}
+	}
+
+	#[test]
+	fn typed_accessors_check_the_tag() {
+		let mut builder = ClassFileBuilder::new("Typed", Some("java/lang/Object"));
+		let utf8 = builder.utf8("hello");
+		let class_name = builder.class("java/lang/String");
+		let integer = builder.integer(-42);
+		let string = builder.string("world");
+		let class = builder.build();
+		let cp = class.get_constant_pool_ref();
+
+		assert_eq!(cp.get_utf8(utf8 as usize).ok(), Some("hello"));
+		assert_eq!(
+			cp.get_class_name(class_name as usize).ok(),
+			Some(format!("java/lang/String"))
+		);
+		assert_eq!(cp.get_integer(integer as usize).ok(), Some(-42));
+		assert_eq!(cp.get_string(string as usize).ok(), Some("world"));
+
+		assert!(is_invalid_reference(
+			cp.get_utf8(integer as usize).err().unwrap(),
+			"Utf8",
+			integer
+		));
+		assert!(is_invalid_reference(
+			cp.get_class_name(utf8 as usize).err().unwrap(),
+			"Class",
+			utf8
+		));
+		assert!(is_invalid_reference(
+			cp.get_integer(string as usize).err().unwrap(),
+			"Integer",
+			string
+		));
+		assert!(is_invalid_reference(
+			cp.get_string(class_name as usize).err().unwrap(),
+			"String",
+			class_name
+		));
+		assert!(is_invalid_reference(
+			cp.get_utf8(1000).err().unwrap(),
+			"Utf8",
+			1000
+		));
+	}
+
+	#[test]
+	fn parses_the_constants_that_lambdas_need() {
+		let mut builder = lambda_class("Lambdas");
+		let call_site =
+			builder.invoke_dynamic(0, "applyAsInt", "()Ljava/util/function/IntBinaryOperator;");
+		let dynamic = builder.dynamic(0, "constant", "I");
+		let method_type = builder.method_type("(II)I");
+		let class = builder.build();
+		let cp = class.get_constant_pool_ref();
+
+		for index in 1..cp.constant_pool_count() as usize {
+			assert!(!cp.describe(index).starts_with("invalid"));
 		}
+		assert!(matches!(
+			cp.get_constant_ref(call_site as usize),
+			Constant::InvokeDynamic(18, 0, _)
+		));
+		assert!(matches!(
+			cp.get_constant_ref(dynamic as usize),
+			Constant::Dynamic(17, 0, _)
+		));
+		assert!(matches!(
+			cp.get_constant_ref(method_type as usize),
+			Constant::MethodType(16, _)
+		));
+		assert_eq!(
+			cp.describe(call_site as usize),
+			"InvokeDynamic #0:applyAsInt:()Ljava/util/function/IntBinaryOperator;"
+		);
+		let handles = (1..cp.constant_pool_count() as usize)
+			.filter(|index| {
+				matches!(
+					cp.get_constant_ref(*index),
+					Constant::MethodHandle(15, 6, _)
+				)
+			})
+			.count();
+		assert_eq!(handles, 2);
 	}
 }
@@ -21,11 +21,17 @@
  */
 use std::fmt;
 use std::fmt::Display;
+use std::io;
+use std::io::Write;
 
 #[derive(PartialOrd, PartialEq, Clone)]
 pub enum DebugLevel {
 	Verbose,
 	Info,
+	/*
+	 * Every instruction that the interpreter executes.
+	 */
+	Trace,
 	Warning,
 	Error,
 }
@@ -35,6 +41,7 @@ impl fmt::Display for DebugLevel {
 		match self {
 			DebugLevel::Verbose => write!(f, "Verbose"),
 			DebugLevel::Info => write!(f, "Info"),
+			DebugLevel::Trace => write!(f, "Trace"),
 			DebugLevel::Warning => write!(f, "Warning"),
 			DebugLevel::Error => write!(f, "Error"),
 		}
@@ -42,7 +49,19 @@ impl fmt::Display for DebugLevel {
 }
 
 pub fn Debug<I: Display>(message: I, output_level: &DebugLevel, filter_level: DebugLevel) {
+	debug_to(&mut io::stderr(), message, output_level, filter_level);
+}
+
+/*
+ * Debug, but to _out_ rather than to stderr.
+ */
+pub fn debug_to<W: Write + ?Sized, I: Display>(
+	out: &mut W,
+	message: I,
+	output_level: &DebugLevel,
+	filter_level: DebugLevel,
+) {
 	if filter_level >= *output_level {
-		eprintln!("{}", message);
+		let _ = writeln!(out, "{}", message);
 	}
 }
@@ -9,7 +9,10 @@ pub struct Environment {
 
 impl Environment {
 	pub fn new(cp: &[&str], args: &[&str], debug_level: DebugLevel) -> Self {
-		let classpath = ClassPath::new(cp, debug_level);
+		Environment::with_classpath(ClassPath::new(cp, debug_level), args)
+	}
+
+	pub fn with_classpath(classpath: ClassPath, args: &[&str]) -> Self {
 		let mut arguments = Vec::<String>::new();
 
 		for arg in args {
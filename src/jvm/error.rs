@@ -1,5 +1,7 @@
+use jvm::opcodes::opcode_info;
 use std::fmt;
 
+#[derive(Clone)]
 pub enum FatalErrorType {
 	ClassNotFound(String),
 	MethodNotFound(String, String),
@@ -27,6 +29,8 @@ pub enum FatalErrorType {
 	RequiredStackValueNotFound(String),
 	Exception(String),
 	Todo(String),
+	VerifyError(String),
+	UnimplementedOpcode(u8, usize),
 }
 
 impl fmt::Display for FatalErrorType {
@@ -92,6 +96,14 @@ impl fmt::Display for FatalErrorType {
 			}
 			FatalErrorType::Exception(exception_type) => write!(f, "Exception: {}", exception_type),
 			FatalErrorType::Todo(task) => write!(f, "TODO: {}", task),
+			FatalErrorType::VerifyError(reason) => write!(f, "VerifyError: {}.", reason),
+			FatalErrorType::UnimplementedOpcode(opcode, pc) => write!(
+				f,
+				"Opcode 0x{:x} ({}) at pc {} is not implemented.",
+				opcode,
+				opcode_info(*opcode).0,
+				pc
+			),
 			_ => write!(f, "Unhandled FatalErrorType."),
 		}
 	}
@@ -106,33 +118,64 @@ impl FatalError {
 		FatalError { error: error }
 	}
 
-	pub fn call(&self) {
-		eprintln!("Fatal Error: {}", self.error);
-		assert!(false);
+	pub fn into_error(self) -> JvmError {
+		JvmError::Fatal(self.error)
 	}
 }
-pub enum NonFatalErrorType {
-	NotImplemented(String),
+
+/*
+ * Why a JVM could not run a program to completion.
+ */
+pub enum JvmError {
+	ClassNotFound(String),
+	MethodNotFound(String, String),
+	VerificationFailed(String),
+	UncaughtException(String),
+	Fatal(FatalErrorType),
 }
 
-impl fmt::Display for NonFatalErrorType {
+impl fmt::Display for JvmError {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
-			NonFatalErrorType::NotImplemented(what) => write!(f, "{} not implemented.", what),
+			JvmError::ClassNotFound(c) => write!(f, "{}", FatalErrorType::ClassNotFound(c.clone())),
+			JvmError::MethodNotFound(m, c) => write!(
+				f,
+				"{}",
+				FatalErrorType::MethodNotFound(m.clone(), c.clone())
+			),
+			JvmError::VerificationFailed(reason) => write!(f, "Verification failed: {}.", reason),
+			JvmError::UncaughtException(exception) => {
+				write!(f, "Uncaught exception: {}.", exception)
+			}
+			JvmError::Fatal(error) => write!(f, "{}", error),
 		}
 	}
 }
 
-pub struct NonFatalError {
-	error: NonFatalErrorType,
+impl fmt::Debug for JvmError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self)
+	}
 }
 
-impl NonFatalError {
-	pub fn new(error: NonFatalErrorType) -> Self {
-		NonFatalError { error: error }
-	}
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	pub fn call(&self) {
-		eprintln!("Non Fatal Error: {}", self.error);
+	#[test]
+	fn fatal_errors_become_jvm_errors() {
+		let error = FatalError::new(FatalErrorType::ClassNotFound(format!("Missing"))).into_error();
+		assert!(matches!(
+			&error,
+			JvmError::Fatal(FatalErrorType::ClassNotFound(ref class_name)) if class_name == "Missing"
+		));
+		assert_eq!(
+			format!("{}", error),
+			"Class Missing is required, but couldn't be found."
+		);
+		assert_eq!(
+			format!("{}", JvmError::ClassNotFound(format!("Missing"))),
+			format!("{}", error)
+		);
 	}
 }
@@ -19,24 +19,351 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
+use jvm::class::Class;
+use jvm::class::ClassAccessFlags;
+use jvm::constantpool::ConstantTag;
+use jvm::field::FieldAccessFlags;
+use jvm::jvmthread::JvmThread;
+use jvm::jvmthread::OpcodeResult;
+use jvm::method::MethodAccessFlags;
 use std::fmt;
 use std::iter::repeat;
 
+/*
+ * Throw a new _class_name_ exception (with _message_, if there is
+ * one) just like athrow throws one: the result unwinds the call
+ * stack to the nearest handler for it. The exception object is made
+ * by its String constructor when there is a message and by its
+ * no-argument one otherwise. The message is left out when there is
+ * no java/lang/String to make it with. When the class cannot be
+ * loaded or its constructor cannot be run, the result is that error
+ * instead.
+ */
+pub fn throw_exception(
+	thread: &mut JvmThread,
+	class_name: &str,
+	message: Option<&str>,
+) -> OpcodeResult {
+	let class_name = class_name.to_string();
+	let message = message.and_then(|message| thread.create_string(message.to_string()).ok());
+	let exception = match message {
+		Some(message) => thread.construct(
+			&class_name,
+			&format!("(Ljava/lang/String;)V"),
+			vec![message],
+		),
+		None => thread.construct(&class_name, &format!("()V"), vec![]),
+	};
+	match exception {
+		Ok(exception) => OpcodeResult::Throw(exception),
+		Err(error) => OpcodeResult::Error(error),
+	}
+}
+
+/*
+ * The exceptions and errors that the JVM itself throws, and their
+ * superclasses, each with the name of its superclass. Superclasses
+ * come before their subclasses.
+ */
+const BUILT_IN_EXCEPTIONS: [(&str, &str); 20] = [
+	("java/lang/Throwable", "java/lang/Object"),
+	("java/lang/Exception", "java/lang/Throwable"),
+	("java/lang/RuntimeException", "java/lang/Exception"),
+	(
+		"java/lang/ArithmeticException",
+		"java/lang/RuntimeException",
+	),
+	(
+		"java/lang/ArrayStoreException",
+		"java/lang/RuntimeException",
+	),
+	("java/lang/ClassCastException", "java/lang/RuntimeException"),
+	(
+		"java/lang/IllegalMonitorStateException",
+		"java/lang/RuntimeException",
+	),
+	(
+		"java/lang/IndexOutOfBoundsException",
+		"java/lang/RuntimeException",
+	),
+	(
+		"java/lang/ArrayIndexOutOfBoundsException",
+		"java/lang/IndexOutOfBoundsException",
+	),
+	(
+		"java/lang/StringIndexOutOfBoundsException",
+		"java/lang/IndexOutOfBoundsException",
+	),
+	(
+		"java/lang/NegativeArraySizeException",
+		"java/lang/RuntimeException",
+	),
+	(
+		"java/lang/NullPointerException",
+		"java/lang/RuntimeException",
+	),
+	("java/lang/Error", "java/lang/Throwable"),
+	("java/lang/LinkageError", "java/lang/Error"),
+	(
+		"java/lang/IncompatibleClassChangeError",
+		"java/lang/LinkageError",
+	),
+	(
+		"java/lang/AbstractMethodError",
+		"java/lang/IncompatibleClassChangeError",
+	),
+	(
+		"java/lang/InstantiationError",
+		"java/lang/IncompatibleClassChangeError",
+	),
+	("java/lang/VirtualMachineError", "java/lang/Error"),
+	(
+		"java/lang/StackOverflowError",
+		"java/lang/VirtualMachineError",
+	),
+	(
+		"java/lang/OutOfMemoryError",
+		"java/lang/VirtualMachineError",
+	),
+];
+
+/*
+ * The constant pool of a synthetic class, as it is built. There are
+ * no Longs or Doubles, so the constant at index i is entries[i - 1].
+ */
+struct SyntheticConstantPool {
+	entries: Vec<Vec<u8>>,
+}
+
+impl SyntheticConstantPool {
+	fn add(&mut self, entry: Vec<u8>) -> u16 {
+		if let Some(index) = self.entries.iter().position(|e| *e == entry) {
+			return 1 + index as u16;
+		}
+		self.entries.push(entry);
+		self.entries.len() as u16
+	}
+
+	fn utf8(&mut self, value: &str) -> u16 {
+		let mut entry = vec![ConstantTag::Utf8 as u8];
+		write_u16(&mut entry, value.len() as u16);
+		entry.extend_from_slice(value.as_bytes());
+		self.add(entry)
+	}
+
+	fn class(&mut self, class_name: &str) -> u16 {
+		let mut entry = vec![ConstantTag::Class as u8];
+		let name_index = self.utf8(class_name);
+		write_u16(&mut entry, name_index);
+		self.add(entry)
+	}
+
+	fn member(&mut self, tag: ConstantTag, class_name: &str, name: &str, descriptor: &str) -> u16 {
+		let class_index = self.class(class_name);
+		let mut name_and_type = vec![ConstantTag::NameAndType as u8];
+		let name_index = self.utf8(name);
+		let descriptor_index = self.utf8(descriptor);
+		write_u16(&mut name_and_type, name_index);
+		write_u16(&mut name_and_type, descriptor_index);
+		let name_and_type_index = self.add(name_and_type);
+
+		let mut entry = vec![tag as u8];
+		write_u16(&mut entry, class_index);
+		write_u16(&mut entry, name_and_type_index);
+		self.add(entry)
+	}
+}
+
+fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+	bytes.extend_from_slice(&[(value >> 8) as u8, value as u8]);
+}
+
+/*
+ * A public method named _name_ of type _descriptor_ whose Code
+ * attribute holds _code_, with no exception table.
+ */
+fn write_method(
+	bytes: &mut Vec<u8>,
+	pool: &mut SyntheticConstantPool,
+	name: &str,
+	descriptor: &str,
+	max_stack: u16,
+	max_locals: u16,
+	code: &[u8],
+) {
+	write_u16(bytes, MethodAccessFlags::Public as u16);
+	write_u16(bytes, pool.utf8(name));
+	write_u16(bytes, pool.utf8(descriptor));
+	write_u16(bytes, 1);
+	write_u16(bytes, pool.utf8("Code"));
+	bytes.extend_from_slice(&(12 + code.len() as u32).to_be_bytes());
+	write_u16(bytes, max_stack);
+	write_u16(bytes, max_locals);
+	bytes.extend_from_slice(&(code.len() as u32).to_be_bytes());
+	bytes.extend_from_slice(code);
+	write_u16(bytes, 0);
+	write_u16(bytes, 0);
+}
+
+/*
+ * A minimal _class_name_ that extends _superclass_name_ and has a
+ * no-argument constructor and a String one that sets the detail
+ * message. Throwable, which declares the message, extends Object
+ * and has a native getMessage, too.
+ */
+fn synthetic_exception(class_name: &str, superclass_name: &str) -> Option<Class> {
+	let declares_message = superclass_name == "java/lang/Object";
+	let mut pool = SyntheticConstantPool {
+		entries: Vec::new(),
+	};
+	let mut body: Vec<u8> = Vec::new();
+
+	write_u16(
+		&mut body,
+		ClassAccessFlags::Public as u16 | ClassAccessFlags::Super as u16,
+	);
+	write_u16(&mut body, pool.class(class_name));
+	write_u16(&mut body, pool.class(superclass_name));
+	write_u16(&mut body, 0);
+
+	if declares_message {
+		write_u16(&mut body, 1);
+		write_u16(&mut body, FieldAccessFlags::Private as u16);
+		write_u16(&mut body, pool.utf8("detailMessage"));
+		write_u16(&mut body, pool.utf8("Ljava/lang/String;"));
+		write_u16(&mut body, 0);
+	} else {
+		write_u16(&mut body, 0);
+	}
+
+	let super_init = pool.member(ConstantTag::Methodref, superclass_name, "<init>", "()V");
+	let mut code: Vec<u8> = Vec::new();
+
+	write_u16(&mut body, if declares_message { 3 } else { 2 });
+
+	/*
+	 * <init>()V: aload_0, invokespecial the superclass' <init>()V and
+	 * return.
+	 */
+	code.push(0x2a);
+	code.push(0xb7);
+	write_u16(&mut code, super_init);
+	code.push(0xb1);
+	write_method(&mut body, &mut pool, "<init>", "()V", 1, 1, &code);
+
+	/*
+	 * <init>(Ljava/lang/String;)V: Throwable's calls Object's
+	 * <init>()V and stores the message; the others pass the message
+	 * to their superclass'.
+	 */
+	code.clear();
+	if declares_message {
+		let detail_message = pool.member(
+			ConstantTag::Fieldref,
+			class_name,
+			"detailMessage",
+			"Ljava/lang/String;",
+		);
+		code.push(0x2a);
+		code.push(0xb7);
+		write_u16(&mut code, super_init);
+		code.extend_from_slice(&[0x2a, 0x2b, 0xb5]);
+		write_u16(&mut code, detail_message);
+		code.push(0xb1);
+	} else {
+		let super_init_message = pool.member(
+			ConstantTag::Methodref,
+			superclass_name,
+			"<init>",
+			"(Ljava/lang/String;)V",
+		);
+		code.extend_from_slice(&[0x2a, 0x2b, 0xb7]);
+		write_u16(&mut code, super_init_message);
+		code.push(0xb1);
+	}
+	write_method(
+		&mut body,
+		&mut pool,
+		"<init>",
+		"(Ljava/lang/String;)V",
+		2,
+		2,
+		&code,
+	);
+
+	/*
+	 * getMessage()Ljava/lang/String; is native.
+	 */
+	if declares_message {
+		write_u16(
+			&mut body,
+			MethodAccessFlags::Public as u16 | MethodAccessFlags::Native as u16,
+		);
+		write_u16(&mut body, pool.utf8("getMessage"));
+		write_u16(&mut body, pool.utf8("()Ljava/lang/String;"));
+		write_u16(&mut body, 0);
+	}
+	write_u16(&mut body, 0);
+
+	let mut bytes: Vec<u8> = vec![0xCA, 0xFE, 0xBA, 0xBE];
+	write_u16(&mut bytes, 0);
+	write_u16(&mut bytes, 52);
+	write_u16(&mut bytes, 1 + pool.entries.len() as u16);
+	for entry in pool.entries.iter() {
+		bytes.extend_from_slice(entry);
+	}
+	bytes.extend_from_slice(&body);
+	Class::load_from_bytes(bytes)
+}
+
+/*
+ * The built-in class _class_name_, if it is one of the exceptions
+ * that the JVM throws, so that programs can throw and catch them
+ * without class files for them.
+ */
+pub fn synthetic_exception_class(class_name: &str) -> Option<Class> {
+	BUILT_IN_EXCEPTIONS
+		.iter()
+		.find(|(name, _)| *name == class_name)
+		.and_then(|(class_name, superclass_name)| synthetic_exception(class_name, superclass_name))
+}
+
+/*
+ * An entry in the exception table of a Code attribute: exceptions
+ * of class catch_type (any class when catch_type is 0) thrown from
+ * [start_pc, end_pc) are handled at handler_pc.
+ */
 #[derive(Default, Clone)]
-pub struct Exception {
+pub struct ExceptionTableEntry {
 	start_pc: u16,
 	end_pc: u16,
 	handler_pc: u16,
 	catch_type: u16,
 }
 
-impl Exception {
+impl ExceptionTableEntry {
 	pub fn byte_len(&self) -> usize {
 		8 as usize
 	}
+
+	pub fn start_pc(&self) -> u16 {
+		self.start_pc
+	}
+
+	pub fn end_pc(&self) -> u16 {
+		self.end_pc
+	}
+
+	pub fn handler_pc(&self) -> u16 {
+		self.handler_pc
+	}
+
+	pub fn catch_type(&self) -> u16 {
+		self.catch_type
+	}
 }
 
-impl<'l> From<&'l Vec<u8>> for Exception {
+impl<'l> From<&'l Vec<u8>> for ExceptionTableEntry {
 	fn from(bytes: &'l Vec<u8>) -> Self {
 		let mut offset: usize = 0;
 		let start_pc: u16;
@@ -55,7 +382,7 @@ impl<'l> From<&'l Vec<u8>> for Exception {
 
 		catch_type = (bytes[offset] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
 
-		Exception {
+		ExceptionTableEntry {
 			start_pc,
 			end_pc,
 			handler_pc,
@@ -67,7 +394,7 @@ impl<'l> From<&'l Vec<u8>> for Exception {
 #[derive(Default, Clone)]
 pub struct ExceptionTable {
 	byte_len: usize,
-	exceptions: Vec<Exception>,
+	exceptions: Vec<ExceptionTableEntry>,
 }
 
 impl ExceptionTable {
@@ -77,24 +404,27 @@ impl ExceptionTable {
 	pub fn exceptions_table_count(&self) -> u16 {
 		self.exceptions.len() as u16
 	}
+	pub fn entries(&self) -> &Vec<ExceptionTableEntry> {
+		&self.exceptions
+	}
 }
 
 impl<'l> From<&'l Vec<u8>> for ExceptionTable {
 	fn from(bytes: &'l Vec<u8>) -> Self {
 		let mut offset: usize = 0;
 		let exceptions_count: u16;
-		let mut exceptions: Vec<Exception>;
+		let mut exceptions: Vec<ExceptionTableEntry>;
 		exceptions_count =
 			(bytes[offset + 0] as u16) << 8 as u16 | (bytes[offset + 1] as u16) << 0 as u16;
 		offset += 2;
-		exceptions = repeat(Exception {
+		exceptions = repeat(ExceptionTableEntry {
 			..Default::default()
 		})
 		.take(exceptions_count as usize)
 		.collect();
 
 		for i in 0..exceptions_count as usize {
-			exceptions[i] = Exception::from(&bytes[offset..].to_vec());
+			exceptions[i] = ExceptionTableEntry::from(&bytes[offset..].to_vec());
 			offset += exceptions[i].byte_len();
 		}
 		ExceptionTable {
@@ -119,3 +449,75 @@ impl fmt::Display for ExceptionTable {
 		result
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::debug::DebugLevel;
+	use jvm::environment::Environment;
+	use jvm::error::JvmError;
+	use jvm::invokedynamic::BootstrapRegistry;
+	use jvm::methodarea::MethodArea;
+	use jvm::native::NativeRegistry;
+	use jvm::typevalues::JvmReferenceTargetType;
+	use jvm::typevalues::JvmValue;
+	use std::sync::Arc;
+	use std::sync::Mutex;
+
+	fn new_thread() -> JvmThread {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		)
+	}
+
+	#[test]
+	fn throw_exception_throws_a_new_object() {
+		let mut thread = new_thread();
+		assert!(matches!(
+			throw_exception(&mut thread, "java/lang/ArithmeticException", None),
+			OpcodeResult::Throw(JvmValue::Reference(_, JvmReferenceTargetType::Object(_), _))
+		));
+	}
+
+	#[test]
+	fn throw_exception_reports_a_failed_construction() {
+		let mut thread = new_thread();
+		assert!(matches!(
+			throw_exception(&mut thread, "does/not/Exist", None),
+			OpcodeResult::Error(JvmError::ClassNotFound(_))
+		));
+	}
+
+	#[test]
+	fn built_in_exceptions_are_synthesized() {
+		let arithmetic = synthetic_exception_class("java/lang/ArithmeticException").unwrap();
+		assert_eq!(
+			arithmetic.get_class_name(),
+			Some(format!("java/lang/ArithmeticException"))
+		);
+		assert_eq!(
+			arithmetic.superclass_name(),
+			Some(format!("java/lang/RuntimeException"))
+		);
+		let signatures = arithmetic
+			.get_method_signatures()
+			.into_iter()
+			.map(|(name, descriptor, _)| format!("{}{}", name, descriptor))
+			.collect::<Vec<String>>();
+		assert_eq!(
+			signatures,
+			vec![format!("<init>()V"), format!("<init>(Ljava/lang/String;)V")]
+		);
+
+		let throwable = synthetic_exception_class("java/lang/Throwable").unwrap();
+		assert!(throwable
+			.get_method_signatures()
+			.iter()
+			.any(|(name, _, _)| name == "getMessage"));
+		assert!(synthetic_exception_class("java/lang/String").is_none());
+	}
+}
@@ -20,11 +20,13 @@
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
 use jvm::attribute::Attributes;
+use jvm::class::ClassFileError;
+use jvm::class::ClassFileReader;
 use jvm::constant::Constant;
 use jvm::constantpool::ConstantPool;
 use jvm::typevalues::JvmValue;
+use std::convert::TryFrom;
 use std::fmt;
-use std::iter::repeat;
 use std::sync::{Arc, Mutex};
 
 #[repr(u16)]
@@ -42,7 +44,6 @@ pub enum FieldAccessFlags {
 
 #[derive(Default, Clone)]
 pub struct Field {
-	pub byte_len: usize,
 	pub access_flags: u16,
 	pub name_index: u16,
 	pub descriptor_index: u16,
@@ -51,12 +52,6 @@ pub struct Field {
 	pub value: Arc<Mutex<Option<JvmValue>>>,
 }
 
-impl Field {
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
-	}
-}
-
 impl fmt::Display for Field {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(f, "access_flags: {}, name_index: {}, descriptor_index: {}, attributes_count: {}, attributes: {}",
@@ -68,39 +63,28 @@ impl fmt::Display for Field {
 	}
 }
 
-impl<'l> From<&'l Vec<u8>> for Field {
-	fn from(bytes: &'l Vec<u8>) -> Self {
-		let mut offset = 0;
-		let access_flags: u16;
-		let name_index: u16;
-		let descriptor_index: u16;
-		let attributes: Attributes;
+impl<'l, 'a> TryFrom<&'l mut ClassFileReader<'a>> for Field {
+	type Error = ClassFileError;
 
-		access_flags = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16);
-		offset += 2;
-		name_index = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16);
-		offset += 2;
-		descriptor_index = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16);
-		offset += 2;
+	fn try_from(reader: &'l mut ClassFileReader<'a>) -> Result<Self, ClassFileError> {
+		let access_flags = reader.read_u16()?;
+		let name_index = reader.read_u16()?;
+		let descriptor_index = reader.read_u16()?;
+		let attributes = Attributes::try_from(reader)?;
 
-		attributes = Attributes::from(&bytes[offset..].to_vec());
-		offset += attributes.byte_len();
-
-		Field {
-			byte_len: offset,
+		Ok(Field {
 			access_flags,
 			name_index,
 			descriptor_index,
 			attributes_count: attributes.attributes_count(),
 			attributes,
 			value: Arc::new(Mutex::new(None)),
-		}
+		})
 	}
 }
 
 #[derive(Clone, Default)]
 pub struct Fields {
-	byte_len: usize,
 	fields: Vec<Field>,
 }
 
@@ -113,10 +97,6 @@ impl Fields {
 		self.fields[index].clone()
 	}
 
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
-	}
-
 	pub fn fields_count(&self) -> u16 {
 		self.fields.len() as u16
 	}
@@ -149,7 +129,6 @@ impl Fields {
 		cp: &ConstantPool,
 	) -> bool {
 		for Field {
-			byte_len: _,
 			access_flags: _,
 			name_index,
 			descriptor_index,
@@ -175,26 +154,16 @@ impl Fields {
 	}
 }
 
-impl<'l> From<&'l Vec<u8>> for Fields {
-	fn from(bytes: &'l Vec<u8>) -> Self {
-		let mut offset = 0;
-		let mut fields: Vec<Field>;
-		let fields_count: u16 = (bytes[offset + 0] as u16) << 8 | (bytes[offset + 1] as u16);
-		offset += 2;
+impl<'l, 'a> TryFrom<&'l mut ClassFileReader<'a>> for Fields {
+	type Error = ClassFileError;
 
-		fields = repeat(Field {
-			..Default::default()
-		})
-		.take(fields_count as usize)
-		.collect();
-		for field_index in 0..fields_count as usize {
-			fields[field_index] = Field::from(&bytes[offset..].to_vec());
-			offset += fields[field_index].byte_len();
-		}
-		Fields {
-			byte_len: offset,
-			fields: fields,
+	fn try_from(reader: &'l mut ClassFileReader<'a>) -> Result<Self, ClassFileError> {
+		let fields_count = reader.read_u16()?;
+		let mut fields: Vec<Field> = Vec::with_capacity(fields_count as usize);
+		for _ in 0..fields_count {
+			fields.push(Field::try_from(&mut *reader)?);
 		}
+		Ok(Fields { fields: fields })
 	}
 }
 
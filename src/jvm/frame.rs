@@ -20,15 +20,77 @@
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
 use jvm::class::Class;
+use jvm::error::FatalError;
+use jvm::error::FatalErrorType;
+use jvm::method::Method;
+use jvm::typevalues::JvmPrimitiveType;
 use jvm::typevalues::JvmValue;
 use std::fmt;
 use std::rc::Rc;
 
+/*
+ * Why a value could not be popped off (or pushed onto) an operand
+ * stack: there was nothing to pop, what was on top was not of the
+ * type (named) that the pop expected, or there was no room for the
+ * push. Either way, the stack is left as it was.
+ */
+#[derive(Debug, PartialEq)]
+pub enum OperandStackError {
+	Underflow,
+	WrongType(String),
+	/*
+	 * Pushing would make the stack deeper (in slots) than this.
+	 */
+	Overflow(usize),
+}
+
+impl OperandStackError {
+	/*
+	 * The fatal error for _instruction_ failing to pop (or push) an
+	 * operand.
+	 */
+	pub fn into_fatal_error(self, instruction: &str) -> FatalError {
+		match self {
+			OperandStackError::Underflow => FatalError::new(
+				FatalErrorType::RequiredStackValueNotFound(instruction.to_string()),
+			),
+			OperandStackError::WrongType(expected) => {
+				FatalError::new(FatalErrorType::WrongType(instruction.to_string(), expected))
+			}
+			OperandStackError::Overflow(max_stack) => {
+				FatalError::new(FatalErrorType::VerifyError(format!(
+					"{} overflows the operand stack (max_stack is {})",
+					instruction, max_stack
+				)))
+			}
+		}
+	}
+}
+
 #[derive(Clone, Default)]
 pub struct Frame {
 	pub operand_stack: Vec<JvmValue>,
 	pub class: Option<Rc<Class>>,
 	pub locals: Vec<JvmValue>,
+	/*
+	 * The offset (from the start of the method's code) of the
+	 * instruction being executed.
+	 */
+	pub pc: usize,
+	/*
+	 * How deep (in slots) the operand stack may get, according to the
+	 * Code attribute of the method that the frame is for.
+	 */
+	pub max_stack: Option<usize>,
+	/*
+	 * The method that the frame is for.
+	 */
+	pub method: Option<Rc<Method>>,
+	/*
+	 * The receiver of a synchronized instance method: the object
+	 * whose monitor the method holds until it returns.
+	 */
+	pub receiver: Option<JvmValue>,
 }
 
 impl Frame {
@@ -37,6 +99,10 @@ impl Frame {
 			operand_stack: Vec::<JvmValue>::new(),
 			class: None,
 			locals: Vec::<JvmValue>::new(),
+			pc: 0,
+			max_stack: None,
+			method: None,
+			receiver: None,
 		}
 	}
 
@@ -47,12 +113,170 @@ impl Frame {
 			None
 		}
 	}
+
+	/*
+	 * The depth of the operand stack, in slots. A long or a double
+	 * is a single entry on the stack but counts as two slots.
+	 */
+	pub fn operand_stack_depth(&self) -> usize {
+		self.operand_stack.iter().map(|value| value.size()).sum()
+	}
+
+	/*
+	 * Store a value in local x. A long or a double occupies locals x
+	 * and x + 1; local x + 1 is marked Invalid so that it cannot be
+	 * loaded on its own. Overwriting the first half of a long or a
+	 * double leaves the second half Invalid; overwriting the second
+	 * half makes the first half Invalid, too. Returns false when the
+	 * value does not fit in the locals.
+	 */
+	pub fn set_local(&mut self, x: usize, value: JvmValue) -> bool {
+		let size = value.size();
+		if x + size > self.locals.len() {
+			return false;
+		}
+		if x > 0 && self.locals[x - 1].size() == 2 {
+			self.locals[x - 1] = JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0);
+		}
+		self.locals[x] = value;
+		if size == 2 {
+			self.locals[x + 1] = JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0);
+		}
+		true
+	}
+
+	/*
+	 * Whether the operand stack is deeper than it may be.
+	 */
+	pub fn operand_stack_overflowed(&self) -> bool {
+		match self.max_stack {
+			Some(max_stack) => self.operand_stack_depth() > max_stack,
+			None => false,
+		}
+	}
+
+	pub fn push(&mut self, value: JvmValue) -> Result<(), OperandStackError> {
+		if let Some(max_stack) = self.max_stack {
+			if self.operand_stack_depth() + value.size() > max_stack {
+				return Err(OperandStackError::Overflow(max_stack));
+			}
+		}
+		self.operand_stack.push(value);
+		Ok(())
+	}
+
+	pub fn pop(&mut self) -> Option<JvmValue> {
+		self.operand_stack.pop()
+	}
+
+	/*
+	 * Pop the value on the top of the operand stack if _convert_
+	 * accepts it. If it does not, nothing is popped.
+	 */
+	fn pop_as<T, F>(&mut self, expected: &str, convert: F) -> Result<T, OperandStackError>
+	where
+		F: Fn(&JvmValue) -> Option<T>,
+	{
+		let converted = match self.operand_stack.last() {
+			Some(value) => convert(value),
+			None => return Err(OperandStackError::Underflow),
+		};
+		match converted {
+			Some(converted) => {
+				self.operand_stack.pop();
+				Ok(converted)
+			}
+			None => Err(OperandStackError::WrongType(expected.to_string())),
+		}
+	}
+
+	pub fn push_int(&mut self, value: i32) -> Result<(), OperandStackError> {
+		self.push(JvmValue::Primitive(
+			JvmPrimitiveType::Integer,
+			0,
+			value as u32,
+			0,
+		))
+	}
+
+	pub fn pop_int(&mut self) -> Result<i32, OperandStackError> {
+		self.pop_as("Integer", |value| match value {
+			JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _) => Some(*value as i32),
+			_ => None,
+		})
+	}
+
+	pub fn push_float(&mut self, value: f32) -> Result<(), OperandStackError> {
+		self.push(JvmValue::Primitive(
+			JvmPrimitiveType::Float,
+			0,
+			value.to_bits(),
+			0,
+		))
+	}
+
+	pub fn pop_float(&mut self) -> Result<f32, OperandStackError> {
+		self.pop_as("Float", |value| match value {
+			JvmValue::Primitive(JvmPrimitiveType::Float, _, value, _) => {
+				Some(f32::from_bits(*value))
+			}
+			_ => None,
+		})
+	}
+
+	/*
+	 * A long is one entry on the operand stack but two slots of its
+	 * depth.
+	 */
+	pub fn push_long(&mut self, value: i64) -> Result<(), OperandStackError> {
+		self.push(JvmValue::Primitive(
+			JvmPrimitiveType::LongInteger,
+			value as u64,
+			0,
+			0,
+		))
+	}
+
+	pub fn pop_long(&mut self) -> Result<i64, OperandStackError> {
+		self.pop_as("Long", |value| match value {
+			JvmValue::Primitive(JvmPrimitiveType::LongInteger, value, _, _) => Some(*value as i64),
+			_ => None,
+		})
+	}
+
+	pub fn push_double(&mut self, value: f64) -> Result<(), OperandStackError> {
+		self.push(JvmValue::Primitive(
+			JvmPrimitiveType::Double,
+			value.to_bits(),
+			0,
+			0,
+		))
+	}
+
+	pub fn pop_double(&mut self) -> Result<f64, OperandStackError> {
+		self.pop_as("Double", |value| match value {
+			JvmValue::Primitive(JvmPrimitiveType::Double, value, _, _) => {
+				Some(f64::from_bits(*value))
+			}
+			_ => None,
+		})
+	}
+
+	/*
+	 * Pop a reference (null or not).
+	 */
+	pub fn pop_reference(&mut self) -> Result<JvmValue, OperandStackError> {
+		self.pop_as("Reference", |value| match value {
+			JvmValue::Reference(_, _, _) => Some(value.clone()),
+			_ => None,
+		})
+	}
 }
 
 impl fmt::Display for Frame {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		let mut result: fmt::Result;
-		result = write!(f, "Operand stack:\n");
+		result = write!(f, "Operand stack ({} slots):\n", self.operand_stack_depth());
 		for i in 0..self.operand_stack.len() {
 			result = write!(f, "{}: {}\n", i, &self.operand_stack[i]);
 		}
@@ -70,3 +294,70 @@ impl fmt::Display for Frame {
 		result
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::typevalues::create_null_value;
+	use jvm::typevalues::JvmReferenceTargetType;
+
+	#[test]
+	fn typed_pushes_respect_max_stack() {
+		let mut frame = Frame::new();
+		frame.max_stack = Some(3);
+		assert_eq!(frame.push_int(1), Ok(()));
+		assert_eq!(frame.push_long(2), Ok(()));
+		assert_eq!(frame.push_float(3.0), Err(OperandStackError::Overflow(3)));
+		assert_eq!(frame.push_double(4.0), Err(OperandStackError::Overflow(3)));
+		assert_eq!(frame.operand_stack_depth(), 3);
+		assert_eq!(frame.pop_long(), Ok(2));
+		assert_eq!(frame.push_double(4.0), Ok(()));
+		assert_eq!(frame.pop_double(), Ok(4.0));
+		assert_eq!(frame.pop_int(), Ok(1));
+	}
+
+	#[test]
+	fn typed_pushes_are_unbounded_without_max_stack() {
+		let mut frame = Frame::new();
+		for value in 0..100 {
+			assert_eq!(frame.push_long(value), Ok(()));
+		}
+		assert_eq!(frame.operand_stack_depth(), 200);
+	}
+
+	#[test]
+	fn typed_pops_check_underflow_and_types() {
+		let mut frame = Frame::new();
+		assert!(frame.pop().is_none());
+		assert_eq!(frame.pop_int(), Err(OperandStackError::Underflow));
+		assert_eq!(frame.pop_long(), Err(OperandStackError::Underflow));
+		assert!(matches!(
+			frame.pop_reference(),
+			Err(OperandStackError::Underflow)
+		));
+
+		assert_eq!(frame.push_long(-5), Ok(()));
+		assert_eq!(frame.operand_stack_depth(), 2);
+		assert_eq!(
+			frame.pop_int(),
+			Err(OperandStackError::WrongType(format!("Integer")))
+		);
+		assert_eq!(frame.operand_stack_depth(), 2);
+		assert_eq!(frame.pop_long(), Ok(-5));
+		assert_eq!(frame.operand_stack_depth(), 0);
+
+		assert_eq!(frame.push(create_null_value()), Ok(()));
+		assert_eq!(frame.push_int(7), Ok(()));
+		assert_eq!(frame.operand_stack_depth(), 2);
+		assert!(matches!(
+			frame.pop_reference(),
+			Err(OperandStackError::WrongType(_))
+		));
+		assert_eq!(frame.pop_int(), Ok(7));
+		assert!(matches!(
+			frame.pop_reference(),
+			Ok(JvmValue::Reference(_, JvmReferenceTargetType::Null, _))
+		));
+		assert_eq!(frame.operand_stack_depth(), 0);
+	}
+}
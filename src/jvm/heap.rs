@@ -0,0 +1,98 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/*
+ * The identity that the next object gets. Identities are never
+ * reused, so no two objects (in any thread) ever have the same one.
+ */
+static NEXT_IDENTITY: AtomicU64 = AtomicU64::new(1);
+
+/*
+ * The identity of an object that is being allocated.
+ */
+pub fn allocate_identity() -> u64 {
+	NEXT_IDENTITY.fetch_add(1, Ordering::Relaxed)
+}
+
+/*
+ * How many bytes the objects and arrays that a VM allocates take up
+ * (roughly: the size of their fields and elements), and how many
+ * they may take up before allocating another one throws
+ * OutOfMemoryError.
+ */
+pub struct Heap {
+	limit: Option<usize>,
+	used: AtomicUsize,
+}
+
+impl Heap {
+	pub fn new(limit: Option<usize>) -> Self {
+		Heap {
+			limit,
+			used: AtomicUsize::new(0),
+		}
+	}
+
+	/*
+	 * Take _bytes_ of _heap_ for a new object or array. They are
+	 * given back when the Allocation is dropped (along with the object
+	 * or array that holds it). None when they do not fit.
+	 */
+	pub fn allocate(heap: &Arc<Heap>, bytes: usize) -> Option<Allocation> {
+		heap.used
+			.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+				let used = used.checked_add(bytes)?;
+				match heap.limit {
+					Some(limit) if used > limit => None,
+					_ => Some(used),
+				}
+			})
+			.ok()?;
+		Some(Allocation {
+			heap: Arc::clone(heap),
+			bytes,
+		})
+	}
+
+	/*
+	 * How many bytes are allocated right now.
+	 */
+	pub fn used(&self) -> usize {
+		self.used.load(Ordering::Relaxed)
+	}
+}
+
+/*
+ * The bytes of a Heap that an object or an array takes up.
+ */
+pub struct Allocation {
+	heap: Arc<Heap>,
+	bytes: usize,
+}
+
+impl Drop for Allocation {
+	fn drop(&mut self) {
+		self.heap.used.fetch_sub(self.bytes, Ordering::Relaxed);
+	}
+}
@@ -0,0 +1,227 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use jvm::class::Class;
+use jvm::constant::Constant;
+use jvm::constantpool::ConstantPool;
+use jvm::error::FatalError;
+use jvm::error::FatalErrorType;
+use jvm::error::JvmError;
+use jvm::jvmthread::JvmThread;
+use jvm::native::NativeResult;
+use jvm::object::JvmObject;
+use jvm::typevalues::JvmReferenceTargetType;
+use jvm::typevalues::JvmReferenceType;
+use jvm::typevalues::JvmType;
+use jvm::typevalues::JvmValue;
+use jvm::typevalues::MethodDescriptor;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/*
+ * What an invokedynamic instruction calls once its call site is
+ * bootstrapped: it gets the arguments that the instruction pops and
+ * returns the value that the instruction pushes.
+ */
+pub type CallSite = Rc<dyn Fn(&mut JvmThread, &[Rc<JvmValue>]) -> NativeResult>;
+
+/*
+ * A bootstrap method implemented in Rust. It links an invokedynamic
+ * call site to a CallSite or says why it could not.
+ */
+pub type BootstrapHandler =
+	Rc<dyn Fn(&mut JvmThread, &BootstrapCall) -> Result<CallSite, JvmError>>;
+
+/*
+ * Everything that a bootstrap method is given about the call site
+ * that it links: the class whose code has the invokedynamic, the
+ * name and descriptor of the call site and the (constant pool
+ * indexes of the) static arguments in its BootstrapMethods entry.
+ */
+pub struct BootstrapCall {
+	pub caller: Rc<Class>,
+	pub name: String,
+	pub descriptor: String,
+	pub arguments: Vec<u16>,
+}
+
+/*
+ * The bootstrap handlers, keyed by the class and name of the
+ * bootstrap method that they implement.
+ */
+#[derive(Clone, Default)]
+pub struct BootstrapRegistry {
+	handlers: HashMap<(String, String), BootstrapHandler>,
+}
+
+impl BootstrapRegistry {
+	/*
+	 * A registry with the bootstrap methods that javac uses for
+	 * lambdas and method references.
+	 */
+	pub fn new() -> Self {
+		let mut registry = BootstrapRegistry {
+			handlers: HashMap::new(),
+		};
+		registry.register(
+			"java/lang/invoke/LambdaMetafactory",
+			"metafactory",
+			lambda_metafactory,
+		);
+		registry.register(
+			"java/lang/invoke/LambdaMetafactory",
+			"altMetafactory",
+			lambda_metafactory,
+		);
+		registry
+	}
+
+	/*
+	 * A handler registered for a bootstrap method replaces whatever
+	 * was there before.
+	 */
+	pub fn register<F>(&mut self, class_name: &str, method_name: &str, handler: F)
+	where
+		F: Fn(&mut JvmThread, &BootstrapCall) -> Result<CallSite, JvmError> + 'static,
+	{
+		self.handlers.insert(
+			(class_name.to_string(), method_name.to_string()),
+			Rc::new(handler),
+		);
+	}
+
+	pub fn get(&self, class_name: &str, method_name: &str) -> Option<BootstrapHandler> {
+		self.handlers
+			.get(&(class_name.to_string(), method_name.to_string()))
+			.map(|handler| Rc::clone(handler))
+	}
+}
+
+/*
+ * A resolved MethodHandle constant: _kind_ is the reference kind
+ * (jvms 5.4.3.5) and the rest name the method that it refers to.
+ */
+#[derive(Clone)]
+pub struct MethodHandle {
+	pub kind: u8,
+	pub class_name: String,
+	pub method_name: String,
+	pub descriptor: String,
+}
+
+impl MethodHandle {
+	/*
+	 * Only method handles that refer to methods (not fields) are
+	 * resolved.
+	 */
+	pub fn resolve(cp: &ConstantPool, index: usize) -> Option<MethodHandle> {
+		if let Constant::MethodHandle(_, kind, reference_index) = cp.get_constant_ref(index) {
+			let (method_name, descriptor, class_name) =
+				cp.resolve_methodref(*reference_index as usize)?;
+			return Some(MethodHandle {
+				kind: *kind,
+				class_name,
+				method_name,
+				descriptor,
+			});
+		}
+		None
+	}
+
+	pub fn is_static(&self) -> bool {
+		self.kind == 6
+	}
+}
+
+/*
+ * What a lambda object implements: invoking _method_name_ of
+ * _interface_name_ on it invokes _implementation_ with the
+ * _captured_ values followed by the invocation's arguments.
+ */
+pub struct Lambda {
+	pub interface_name: String,
+	pub method_name: String,
+	pub implementation: MethodHandle,
+	pub captured: Vec<JvmValue>,
+}
+
+/*
+ * The lambda that _value_ is, if it is one.
+ */
+pub fn lambda_of(value: &JvmValue) -> Option<Rc<Lambda>> {
+	if let JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) = value {
+		if let Ok(object) = object.lock() {
+			return object.lambda();
+		}
+	}
+	None
+}
+
+/*
+ * LambdaMetafactory.metafactory (and altMetafactory, whose extra
+ * flags do not matter here): the call site captures its arguments
+ * into an object that implements the functional interface that it
+ * returns. The second static argument is the implementation method.
+ */
+fn lambda_metafactory(thread: &mut JvmThread, call: &BootstrapCall) -> Result<CallSite, JvmError> {
+	let interface_name = match MethodDescriptor::parse(call.descriptor.as_bytes()) {
+		Some(MethodDescriptor {
+			return_type: JvmType::Reference(JvmReferenceType::Class(interface_name)),
+			..
+		}) => interface_name,
+		_ => return Err(FatalError::new(FatalErrorType::InvalidMethodDescriptor).into_error()),
+	};
+	let implementation = match call.arguments.get(1).and_then(|argument| {
+		MethodHandle::resolve(call.caller.get_constant_pool_ref(), *argument as usize)
+	}) {
+		Some(implementation) => implementation,
+		None => {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				format!("LambdaMetafactory"),
+				format!("MethodHandle"),
+			))
+			.into_error())
+		}
+	};
+	let object_class = thread.load_class(&"java/lang/Object".to_string())?;
+	let method_name = call.name.clone();
+
+	Ok(Rc::new(
+		move |thread: &mut JvmThread, arguments: &[Rc<JvmValue>]| {
+			let mut object = JvmObject::new(Rc::clone(&object_class), thread.debug_level());
+			object.set_lambda(Rc::new(Lambda {
+				interface_name: interface_name.clone(),
+				method_name: method_name.clone(),
+				implementation: implementation.clone(),
+				captured: arguments
+					.iter()
+					.map(|argument| (**argument).clone())
+					.collect(),
+			}));
+			Ok(Some(Rc::new(JvmValue::Reference(
+				JvmReferenceType::Class(interface_name.clone()),
+				JvmReferenceTargetType::Object(Arc::new(Mutex::new(object))),
+				0,
+			))))
+		},
+	))
+}
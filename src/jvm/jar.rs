@@ -0,0 +1,616 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+use std::fs;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/*
+ * Why a JAR file (or a file in one) cannot be read.
+ */
+#[derive(Clone, Debug)]
+pub enum JarError {
+	Io(String),
+	NotAJar,
+	Truncated,
+	UnsupportedCompression(u16),
+	BadDeflateData,
+	FileNotFound(String),
+}
+
+impl fmt::Display for JarError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			JarError::Io(error) => write!(f, "could not read the JAR file: {}", error),
+			JarError::NotAJar => write!(f, "not a JAR file"),
+			JarError::Truncated => write!(f, "JAR file is cut short"),
+			JarError::UnsupportedCompression(method) => {
+				write!(f, "JAR file uses unsupported compression method {}", method)
+			}
+			JarError::BadDeflateData => write!(f, "JAR file has bad compressed data"),
+			JarError::FileNotFound(name) => write!(f, "JAR file has no file {}", name),
+		}
+	}
+}
+
+/*
+ * A file in a JAR file, as its central directory describes it.
+ */
+struct JarEntry {
+	name: String,
+	method: u16,
+	compressed_size: usize,
+	uncompressed_size: usize,
+	local_header_offset: usize,
+}
+
+/*
+ * A JAR (that is, zip) file whose files are either stored as they
+ * are or compressed with deflate.
+ */
+pub struct Jar {
+	bytes: Vec<u8>,
+	entries: Vec<JarEntry>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, JarError> {
+	match bytes.get(offset..offset + 2) {
+		Some(value) => Ok(value[0] as u16 | (value[1] as u16) << 8),
+		None => Err(JarError::Truncated),
+	}
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, JarError> {
+	Ok(read_u16(bytes, offset)? as u32 | (read_u16(bytes, offset + 2)? as u32) << 16)
+}
+
+impl Jar {
+	pub fn open(path: &str) -> Result<Jar, JarError> {
+		let bytes = fs::read(path).map_err(|error| JarError::Io(error.to_string()))?;
+		Jar::from_bytes(bytes)
+	}
+
+	/*
+	 * Read the JAR file whose contents are _bytes_: find the end of
+	 * its central directory (after which there can only be a comment)
+	 * and read the description of each file from there.
+	 */
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Jar, JarError> {
+		if bytes.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+			return Err(JarError::NotAJar);
+		}
+		let last = bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+		let first = last.saturating_sub(u16::max_value() as usize);
+		let end = (first..=last)
+			.rev()
+			.find(|offset| {
+				read_u32(&bytes, *offset).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+			})
+			.ok_or(JarError::NotAJar)?;
+
+		let entry_count = read_u16(&bytes, end + 10)?;
+		let mut offset = read_u32(&bytes, end + 16)? as usize;
+		let mut entries = Vec::with_capacity(entry_count as usize);
+		for _ in 0..entry_count {
+			if read_u32(&bytes, offset)? != CENTRAL_HEADER_SIGNATURE {
+				return Err(JarError::NotAJar);
+			}
+			let name_length = read_u16(&bytes, offset + 28)? as usize;
+			let extra_length = read_u16(&bytes, offset + 30)? as usize;
+			let comment_length = read_u16(&bytes, offset + 32)? as usize;
+			let name = bytes
+				.get(offset + 46..offset + 46 + name_length)
+				.ok_or(JarError::Truncated)?;
+			entries.push(JarEntry {
+				name: String::from_utf8_lossy(name).to_string(),
+				method: read_u16(&bytes, offset + 10)?,
+				compressed_size: read_u32(&bytes, offset + 20)? as usize,
+				uncompressed_size: read_u32(&bytes, offset + 24)? as usize,
+				local_header_offset: read_u32(&bytes, offset + 42)? as usize,
+			});
+			offset += 46 + name_length + extra_length + comment_length;
+		}
+		Ok(Jar { bytes, entries })
+	}
+
+	pub fn file_names(&self) -> Vec<String> {
+		self.entries
+			.iter()
+			.map(|entry| entry.name.clone())
+			.collect()
+	}
+
+	/*
+	 * The (uncompressed) contents of the file _name_.
+	 */
+	pub fn file_contents_by_name(&self, name: &str) -> Result<Vec<u8>, JarError> {
+		let entry = self
+			.entries
+			.iter()
+			.find(|entry| entry.name == name)
+			.ok_or(JarError::FileNotFound(name.to_string()))?;
+
+		/*
+		 * The sizes in the local header can be left out (and put
+		 * after the data instead), so only the lengths of its name
+		 * and extra field are taken from it.
+		 */
+		let header = entry.local_header_offset;
+		if read_u32(&self.bytes, header)? != LOCAL_HEADER_SIGNATURE {
+			return Err(JarError::NotAJar);
+		}
+		let start = header
+			+ 30 + read_u16(&self.bytes, header + 26)? as usize
+			+ read_u16(&self.bytes, header + 28)? as usize;
+		let data = self
+			.bytes
+			.get(start..start + entry.compressed_size)
+			.ok_or(JarError::Truncated)?;
+
+		let contents = match entry.method {
+			STORED => data.to_vec(),
+			DEFLATED => inflate(data)?,
+			method => return Err(JarError::UnsupportedCompression(method)),
+		};
+		if contents.len() != entry.uncompressed_size {
+			return Err(JarError::BadDeflateData);
+		}
+		Ok(contents)
+	}
+}
+
+/*
+ * The bits of deflate data, least significant bit first.
+ */
+struct BitReader<'a> {
+	bytes: &'a [u8],
+	offset: usize,
+	bit_buffer: u32,
+	bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+	fn bits(&mut self, count: u32) -> Result<u32, JarError> {
+		while self.bit_count < count {
+			let byte = *self.bytes.get(self.offset).ok_or(JarError::Truncated)?;
+			self.offset += 1;
+			self.bit_buffer |= (byte as u32) << self.bit_count;
+			self.bit_count += 8;
+		}
+		let value = self.bit_buffer & ((1u32 << count) - 1);
+		self.bit_buffer >>= count;
+		self.bit_count -= count;
+		Ok(value)
+	}
+
+	/*
+	 * Drop the bits that are left in the current byte.
+	 */
+	fn align(&mut self) {
+		self.bit_buffer = 0;
+		self.bit_count = 0;
+	}
+}
+
+const MAX_CODE_LENGTH: usize = 15;
+
+/*
+ * A canonical Huffman code: how many codes there are of each length
+ * and the symbols, ordered by their codes.
+ */
+struct Huffman {
+	counts: [u16; MAX_CODE_LENGTH + 1],
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	/*
+	 * The code in which symbol i has a code that is _lengths_[i] bits
+	 * long (and no code when that is 0).
+	 */
+	fn new(lengths: &[u8]) -> Result<Huffman, JarError> {
+		let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+		for length in lengths {
+			counts[*length as usize] += 1;
+		}
+
+		/*
+		 * There cannot be more codes of a length than there are
+		 * left.
+		 */
+		let mut left: i32 = 1;
+		for length in 1..=MAX_CODE_LENGTH {
+			left = (left << 1) - counts[length] as i32;
+			if left < 0 {
+				return Err(JarError::BadDeflateData);
+			}
+		}
+
+		let mut offsets = [0u16; MAX_CODE_LENGTH + 1];
+		for length in 1..MAX_CODE_LENGTH {
+			offsets[length + 1] = offsets[length] + counts[length];
+		}
+		let mut symbols = vec![0u16; lengths.len()];
+		for (symbol, length) in lengths.iter().enumerate() {
+			if *length != 0 {
+				symbols[offsets[*length as usize] as usize] = symbol as u16;
+				offsets[*length as usize] += 1;
+			}
+		}
+		Ok(Huffman { counts, symbols })
+	}
+
+	fn decode(&self, reader: &mut BitReader) -> Result<u16, JarError> {
+		let mut code: i32 = 0;
+		let mut first: i32 = 0;
+		let mut index: i32 = 0;
+		for length in 1..=MAX_CODE_LENGTH {
+			code |= reader.bits(1)? as i32;
+			let count = self.counts[length] as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize]);
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(JarError::BadDeflateData)
+	}
+}
+
+const LENGTH_BASES: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+
+/*
+ * The order in which the lengths of the code length code come.
+ */
+const CODE_LENGTH_ORDER: [usize; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/*
+ * Decompress the literals and length/distance pairs of a compressed
+ * block into _output_.
+ */
+fn inflate_codes(
+	reader: &mut BitReader,
+	literals: &Huffman,
+	distances: &Huffman,
+	output: &mut Vec<u8>,
+) -> Result<(), JarError> {
+	loop {
+		let symbol = literals.decode(reader)? as usize;
+		if symbol < 256 {
+			output.push(symbol as u8);
+		} else if symbol == 256 {
+			return Ok(());
+		} else {
+			let symbol = symbol - 257;
+			if symbol >= LENGTH_BASES.len() {
+				return Err(JarError::BadDeflateData);
+			}
+			let length =
+				LENGTH_BASES[symbol] as usize + reader.bits(LENGTH_EXTRA_BITS[symbol])? as usize;
+
+			let symbol = distances.decode(reader)? as usize;
+			if symbol >= DISTANCE_BASES.len() {
+				return Err(JarError::BadDeflateData);
+			}
+			let distance = DISTANCE_BASES[symbol] as usize
+				+ reader.bits(DISTANCE_EXTRA_BITS[symbol])? as usize;
+			if distance > output.len() {
+				return Err(JarError::BadDeflateData);
+			}
+			/*
+			 * The copy can overlap what it is copying.
+			 */
+			let start = output.len() - distance;
+			for i in 0..length {
+				let byte = output[start + i];
+				output.push(byte);
+			}
+		}
+	}
+}
+
+/*
+ * The literal/length and distance codes of a block that carries its
+ * own codes.
+ */
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), JarError> {
+	let literal_count = reader.bits(5)? as usize + 257;
+	let distance_count = reader.bits(5)? as usize + 1;
+	let code_length_count = reader.bits(4)? as usize + 4;
+
+	let mut code_lengths = [0u8; 19];
+	for i in 0..code_length_count {
+		code_lengths[CODE_LENGTH_ORDER[i]] = reader.bits(3)? as u8;
+	}
+	let code_length_code = Huffman::new(&code_lengths)?;
+
+	let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+	while lengths.len() < literal_count + distance_count {
+		let symbol = code_length_code.decode(reader)?;
+		let (length, repeat) = match symbol {
+			0..=15 => (symbol as u8, 1),
+			16 => (
+				*lengths.last().ok_or(JarError::BadDeflateData)?,
+				3 + reader.bits(2)? as usize,
+			),
+			17 => (0, 3 + reader.bits(3)? as usize),
+			_ => (0, 11 + reader.bits(7)? as usize),
+		};
+		if lengths.len() + repeat > literal_count + distance_count {
+			return Err(JarError::BadDeflateData);
+		}
+		lengths.extend(std::iter::repeat(length).take(repeat));
+	}
+
+	Ok((
+		Huffman::new(&lengths[..literal_count])?,
+		Huffman::new(&lengths[literal_count..])?,
+	))
+}
+
+/*
+ * The literal/length and distance codes that a block uses when it
+ * does not carry its own.
+ */
+fn fixed_codes() -> Result<(Huffman, Huffman), JarError> {
+	let mut lengths = [0u8; 288];
+	for (symbol, length) in lengths.iter_mut().enumerate() {
+		*length = match symbol {
+			0..=143 => 8,
+			144..=255 => 9,
+			256..=279 => 7,
+			_ => 8,
+		};
+	}
+	Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+/*
+ * Decompress the deflate (RFC 1951) data _bytes_.
+ */
+fn inflate(bytes: &[u8]) -> Result<Vec<u8>, JarError> {
+	let mut reader = BitReader {
+		bytes,
+		offset: 0,
+		bit_buffer: 0,
+		bit_count: 0,
+	};
+	let mut output: Vec<u8> = Vec::new();
+	loop {
+		let last = reader.bits(1)? == 1;
+		match reader.bits(2)? {
+			0 => {
+				reader.align();
+				let length = read_u16(reader.bytes, reader.offset)?;
+				let complement = read_u16(reader.bytes, reader.offset + 2)?;
+				if length != !complement {
+					return Err(JarError::BadDeflateData);
+				}
+				let start = reader.offset + 4;
+				let stored = reader
+					.bytes
+					.get(start..start + length as usize)
+					.ok_or(JarError::Truncated)?;
+				output.extend_from_slice(stored);
+				reader.offset = start + length as usize;
+			}
+			1 => {
+				let (literals, distances) = fixed_codes()?;
+				inflate_codes(&mut reader, &literals, &distances, &mut output)?;
+			}
+			2 => {
+				let (literals, distances) = dynamic_codes(&mut reader)?;
+				inflate_codes(&mut reader, &literals, &distances, &mut output)?;
+			}
+			_ => return Err(JarError::BadDeflateData),
+		}
+		if last {
+			return Ok(output);
+		}
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::*;
+
+	fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+		bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+		bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	/*
+	 * A JAR file that holds _files_: their names, compression
+	 * methods, (compressed) data and uncompressed sizes.
+	 */
+	fn jar_file(files: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+		let mut bytes: Vec<u8> = Vec::new();
+		let mut central_directory: Vec<u8> = Vec::new();
+		for (name, method, data, size) in files {
+			let header_offset = bytes.len();
+			write_u32(&mut bytes, LOCAL_HEADER_SIGNATURE);
+			bytes.extend_from_slice(&[20, 0, 0, 0]);
+			write_u16(&mut bytes, *method);
+			bytes.extend_from_slice(&[0; 8]);
+			write_u32(&mut bytes, data.len() as u32);
+			write_u32(&mut bytes, *size as u32);
+			write_u16(&mut bytes, name.len() as u16);
+			write_u16(&mut bytes, 0);
+			bytes.extend_from_slice(name.as_bytes());
+			bytes.extend_from_slice(data);
+
+			write_u32(&mut central_directory, CENTRAL_HEADER_SIGNATURE);
+			central_directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+			write_u16(&mut central_directory, *method);
+			central_directory.extend_from_slice(&[0; 8]);
+			write_u32(&mut central_directory, data.len() as u32);
+			write_u32(&mut central_directory, *size as u32);
+			write_u16(&mut central_directory, name.len() as u16);
+			central_directory.extend_from_slice(&[0; 12]);
+			write_u32(&mut central_directory, header_offset as u32);
+			central_directory.extend_from_slice(name.as_bytes());
+		}
+		let central_directory_offset = bytes.len();
+		bytes.extend_from_slice(&central_directory);
+		write_u32(&mut bytes, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+		bytes.extend_from_slice(&[0; 4]);
+		write_u16(&mut bytes, files.len() as u16);
+		write_u16(&mut bytes, files.len() as u16);
+		write_u32(&mut bytes, central_directory.len() as u32);
+		write_u32(&mut bytes, central_directory_offset as u32);
+		write_u16(&mut bytes, 0);
+		bytes
+	}
+
+	/*
+	 * A JAR file that holds _files_, uncompressed.
+	 */
+	pub fn stored_jar_file(files: &[(&str, &[u8])]) -> Vec<u8> {
+		jar_file(
+			&files
+				.iter()
+				.map(|(name, data)| (*name, STORED, *data, data.len()))
+				.collect::<Vec<_>>(),
+		)
+	}
+
+	/*
+	 * "Hello, Hello, Hello!" compressed with the fixed codes.
+	 */
+	const FIXED: [u8; 12] = [
+		0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xf0, 0x40, 0xa2, 0x14, 0x01,
+	];
+
+	/*
+	 * squares() compressed with its own codes.
+	 */
+	const DYNAMIC: [u8; 70] = [
+		0x55, 0x8e, 0xbb, 0x0d, 0xc0, 0x20, 0x10, 0x43, 0x57, 0xf1, 0x08, 0x7c, 0x0f, 0x90, 0xa7,
+		0x89, 0x94, 0x14, 0x94, 0x09, 0x62, 0xff, 0x5c, 0x69, 0xca, 0xf7, 0x64, 0xd9, 0x0e, 0x58,
+		0xef, 0xbe, 0xbe, 0xe7, 0xc6, 0x5c, 0x08, 0x44, 0x54, 0x8e, 0x44, 0x52, 0x2e, 0x44, 0x56,
+		0x1e, 0x44, 0x39, 0xf2, 0x46, 0x54, 0x15, 0xa9, 0x12, 0xa6, 0x22, 0x7b, 0xa2, 0x1d, 0x95,
+		0xde, 0xd1, 0x55, 0x98, 0x8f, 0x0c, 0x15, 0xdd, 0x5f, 0xfc,
+	];
+
+	fn squares() -> Vec<u8> {
+		(0..10)
+			.map(|i| format!("{} squared is {}; ", i, i * i))
+			.collect::<String>()
+			.into_bytes()
+	}
+
+	#[test]
+	fn inflates_every_kind_of_block() {
+		assert_eq!(
+			inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c']).unwrap(),
+			b"abc".to_vec()
+		);
+		assert_eq!(inflate(&FIXED).unwrap(), b"Hello, Hello, Hello!".to_vec());
+		assert_eq!(inflate(&DYNAMIC).unwrap(), squares());
+	}
+
+	#[test]
+	fn rejects_bad_deflate_data() {
+		assert!(inflate(&FIXED[..6]).is_err());
+		assert!(inflate(&[0x01, 0x03, 0x00, 0x00, 0x00]).is_err());
+		assert!(inflate(&[0x07]).is_err());
+	}
+
+	#[test]
+	fn reads_stored_and_deflated_files() {
+		let squares = squares();
+		let jar = Jar::from_bytes(jar_file(&[
+			(
+				"META-INF/MANIFEST.MF",
+				STORED,
+				b"Manifest-Version: 1.0\n",
+				22,
+			),
+			("Hello.txt", DEFLATED, &FIXED, 20),
+			("Squares.txt", DEFLATED, &DYNAMIC, squares.len()),
+		]))
+		.unwrap();
+
+		assert_eq!(
+			jar.file_names(),
+			vec![
+				format!("META-INF/MANIFEST.MF"),
+				format!("Hello.txt"),
+				format!("Squares.txt")
+			]
+		);
+		assert_eq!(
+			jar.file_contents_by_name("META-INF/MANIFEST.MF").unwrap(),
+			b"Manifest-Version: 1.0\n".to_vec()
+		);
+		assert_eq!(
+			jar.file_contents_by_name("Hello.txt").unwrap(),
+			b"Hello, Hello, Hello!".to_vec()
+		);
+		assert_eq!(jar.file_contents_by_name("Squares.txt").unwrap(), squares);
+		assert!(matches!(
+			jar.file_contents_by_name("Missing.txt"),
+			Err(JarError::FileNotFound(_))
+		));
+	}
+
+	#[test]
+	fn rejects_what_is_not_a_jar_file() {
+		assert!(matches!(Jar::from_bytes(vec![]), Err(JarError::NotAJar)));
+		assert!(matches!(
+			Jar::from_bytes(vec![0; 100]),
+			Err(JarError::NotAJar)
+		));
+
+		let jar = Jar::from_bytes(jar_file(&[("A.class", 12, b"xyz", 3)])).unwrap();
+		assert!(matches!(
+			jar.file_contents_by_name("A.class"),
+			Err(JarError::UnsupportedCompression(12))
+		));
+	}
+}
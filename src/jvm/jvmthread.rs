@@ -32,40 +32,327 @@ use jvm::class::ClassAccessFlags;
 use jvm::class::ClassInitializationStatus;
 use jvm::comparison::ComparisonType;
 use jvm::constant::Constant;
+use jvm::constant::Utf8Reserved;
+use jvm::debug::debug_to;
 use jvm::debug::Debug;
 use jvm::debug::DebugLevel;
 use jvm::error::FatalError;
 use jvm::error::FatalErrorType;
-use jvm::error::NonFatalError;
-use jvm::error::NonFatalErrorType;
+use jvm::error::JvmError;
+use jvm::exceptions::throw_exception;
+use jvm::field::FieldAccessFlags;
 use jvm::frame::Frame;
+use jvm::frame::OperandStackError;
+use jvm::heap::Allocation;
+use jvm::heap::Heap;
+use jvm::invokedynamic::lambda_of;
+use jvm::invokedynamic::BootstrapCall;
+use jvm::invokedynamic::BootstrapRegistry;
+use jvm::invokedynamic::CallSite;
+use jvm::invokedynamic::MethodHandle;
 use jvm::method::Method;
 use jvm::method::MethodAccessFlags;
 use jvm::methodarea::MethodArea;
-use jvm::object::{create_static_string_object, JvmObject};
+use jvm::monitor::Monitor;
+use jvm::native::Native;
+use jvm::native::NativeRegistry;
+use jvm::object::{create_object, create_static_string_object, JvmObject};
+use jvm::opcodes::opcode_info;
 use jvm::opcodes::OperandCode;
 use jvm::typevalues::create_null_value;
+use jvm::typevalues::create_return_address_value;
 use jvm::typevalues::JvmPrimitiveType;
 use jvm::typevalues::JvmReferenceTargetType;
 use jvm::typevalues::JvmReferenceType;
 use jvm::typevalues::JvmType;
 use jvm::typevalues::JvmValue;
+use jvm::typevalues::MethodDescriptor;
+use jvm::verifier::verify_stack_depths;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::mem;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::thread;
+
+/*
+ * How deep the call stack of a thread may get, unless it is told
+ * otherwise.
+ */
+pub const DEFAULT_MAX_FRAME_DEPTH: usize = 1024;
+
+/*
+ * How many frames past the maximum frame depth a thread may use to
+ * construct an exception that the JVM throws. Without them, there
+ * would be no room to construct a StackOverflowError, and a finally
+ * handler that rethrows it would throw a NullPointerException.
+ */
+pub const EXCEPTION_FRAME_RESERVE: usize = 8;
 
 pub struct JvmThread {
 	debug_level: DebugLevel,
 	methodarea: Arc<Mutex<MethodArea>>,
 	pc: usize,
 	initializing_class: Vec<String>,
+	natives: NativeRegistry,
+	bootstraps: BootstrapRegistry,
+	/*
+	 * The call sites that invokedynamic instructions have been linked
+	 * to, keyed by the class, method (name and descriptor indexes) and
+	 * pc of the instruction.
+	 */
+	call_sites: HashMap<(String, u16, u16, usize), CallSite>,
+	/*
+	 * Whether methods are verified before they first run and, if
+	 * they are, the methods (class, name and descriptor indexes) that
+	 * have been.
+	 */
+	verify: bool,
+	verified_methods: HashSet<(String, u16, u16)>,
+	/*
+	 * The boxes that Integer.valueOf hands out for -128 to 127, so
+	 * that boxing the same small value gives the same reference.
+	 */
+	integer_cache: HashMap<i32, JvmValue>,
+	call_stack: Vec<CallStackEntry>,
+	/*
+	 * The frames of the methods that are running, the innermost last.
+	 */
+	frames: Vec<Frame>,
+	/*
+	 * How deep the call stack may get before invoking another method
+	 * throws StackOverflowError.
+	 */
+	max_frame_depth: usize,
+	/*
+	 * How many frames past max_frame_depth the call stack may use
+	 * right now (see EXCEPTION_FRAME_RESERVE).
+	 */
+	frame_reserve: usize,
+	/*
+	 * The call stack at the point where the exception that is
+	 * unwinding the call stack was thrown.
+	 */
+	exception_stack_trace: Option<Vec<CallStackEntry>>,
+	/*
+	 * The exception (class and detail message) that the running
+	 * native wants thrown once it returns.
+	 */
+	pending_exception: Option<(String, Option<String>)>,
+	/*
+	 * Where the program's standard output and standard error go.
+	 */
+	out: Box<dyn Write>,
+	err: Box<dyn Write>,
+	/*
+	 * What the objects and arrays that the thread allocates count
+	 * against.
+	 */
+	heap: Arc<Heap>,
+}
+
+/*
+ * A method that the thread is executing, as much as a stack trace
+ * needs to know about it: _name_index_ and _descriptor_index_ are
+ * into _class_'s constant pool, and _pc_ is the instruction being
+ * executed.
+ */
+#[derive(Clone)]
+struct CallStackEntry {
+	class: Rc<Class>,
+	name_index: u16,
+	descriptor_index: u16,
+	pc: usize,
+}
+
+impl fmt::Display for CallStackEntry {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let constant_pool = self.class.get_constant_pool_ref();
+		let class_name = self.class.get_class_name().unwrap_or(format!("(unknown)"));
+		let method_name = match constant_pool.get_constant_ref(self.name_index as usize) {
+			Constant::Utf8(_, _, _, name) => name.clone(),
+			_ => format!("(unknown)"),
+		};
+		let method_descriptor = match constant_pool.get_constant_ref(self.descriptor_index as usize)
+		{
+			Constant::Utf8(_, _, _, descriptor) => descriptor.clone(),
+			_ => format!("(unknown)"),
+		};
+
+		/*
+		 * There is no SourceFile attribute to go on, so assume that
+		 * the class came from the file that javac would expect it in:
+		 * the one named for the (outermost) class.
+		 */
+		let simple_name = class_name.rsplit('/').next().unwrap_or("");
+		let source_file = format!("{}.java", simple_name.split('$').next().unwrap_or(""));
+
+		let line = self
+			.class
+			.get_method_rc_by_name_and_type(&method_name, &method_descriptor)
+			.and_then(|method| method.get_line_number_table(constant_pool))
+			.and_then(|line_number_table| line_number_table.line_for_pc(self.pc as u16));
+
+		match line {
+			Some(line) => write!(
+				f,
+				"{}.{}({}:{})",
+				class_name.replace('/', "."),
+				method_name,
+				source_file,
+				line
+			),
+			None => write!(
+				f,
+				"{}.{}({})",
+				class_name.replace('/', "."),
+				method_name,
+				source_file
+			),
+		}
+	}
+}
+
+fn print_stack_trace(call_stack: &[CallStackEntry], err: &mut dyn Write) {
+	for entry in call_stack.iter().rev() {
+		let _ = writeln!(err, "\tat {}", entry);
+	}
 }
 
-enum OpcodeResult {
-	Exception,
+pub enum OpcodeResult {
+	/*
+	 * An exception that the JVM raises: its class and, when there
+	 * is one, its detail message. It is made and thrown by
+	 * throw_exception.
+	 */
+	Exception(String, Option<String>),
+	/*
+	 * An exception object thrown by athrow.
+	 */
+	Throw(JvmValue),
 	Incr(usize),
 	Decr(usize),
-	Value(JvmValue),
+	/*
+	 * The method is done and returns this value (a Void primitive
+	 * when it returns nothing).
+	 */
+	Return(JvmValue),
+	/*
+	 * Run this method in this frame; its result is the result of the
+	 * invoking instruction (see execute_frames).
+	 */
+	Invoke(Rc<Method>, Frame),
+	/*
+	 * A fatal error that stops the thread.
+	 */
+	Error(JvmError),
+}
+
+/*
+ * Branch offsets are signed and relative to the address of the
+ * branching instruction itself.
+ */
+fn branch_to(offset: i32) -> OpcodeResult {
+	if offset < 0 {
+		OpcodeResult::Decr(offset.unsigned_abs() as usize)
+	} else {
+		OpcodeResult::Incr(offset as usize)
+	}
+}
+
+/*
+ * The result of an instruction that is _length_ bytes long and
+ * that either completes or fails with _result_'s error.
+ */
+fn advance(result: Result<(), JvmError>, length: usize) -> OpcodeResult {
+	match result {
+		Ok(()) => OpcodeResult::Incr(length),
+		Err(error) => OpcodeResult::Error(error),
+	}
+}
+
+/*
+ * The NullPointerException of an instruction that needs an object
+ * (or an array) but finds null. Like HotSpot's, _message_ says what
+ * the instruction could not do.
+ */
+fn null_pointer(message: String) -> OpcodeResult {
+	OpcodeResult::Exception(format!("java/lang/NullPointerException"), Some(message))
+}
+
+/*
+ * The NullPointerException of invoking _class_name_._method_name_ on
+ * a null receiver.
+ */
+fn null_receiver(class_name: &str, method_name: &str) -> OpcodeResult {
+	null_pointer(format!("Cannot invoke \"{}.{}\"", class_name, method_name))
+}
+
+/*
+ * The operands of tableswitch and lookupswitch start at the first
+ * offset after the opcode that is a multiple of four bytes from the
+ * start of the method's code. Returns their index relative to the
+ * opcode at _pc_.
+ */
+fn switch_operands_index(pc: usize) -> usize {
+	1 + (3 - pc % 4)
+}
+
+/*
+ * The big-endian i32 at _index_ among the operands of the switch
+ * _mnemonic_ (whose bytes start at its opcode).
+ */
+fn read_switch_i32(bytes: &[u8], index: usize, mnemonic: &str) -> Result<i32, JvmError> {
+	match bytes.get(index..index + 4) {
+		Some(operand) => Ok(i32::from_be_bytes([
+			operand[0], operand[1], operand[2], operand[3],
+		])),
+		None => Err(truncated_switch(mnemonic)),
+	}
+}
+
+/*
+ * The error of a switch whose operands run off the end of the code.
+ */
+fn truncated_switch(mnemonic: &str) -> JvmError {
+	FatalError::new(FatalErrorType::VerifyError(format!(
+		"the operands of {} run off the end of the code",
+		mnemonic
+	)))
+	.into_error()
+}
+
+/*
+ * The name of the class of an exception object.
+ */
+fn exception_class_name(exception: &JvmValue) -> String {
+	match exception {
+		JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) => {
+			if let Ok(object) = object.lock() {
+				if let Some(class_name) = object.get_class().get_class_name() {
+					return class_name;
+				}
+			}
+		}
+		_ => (),
+	}
+	format!("(unknown)")
+}
+
+fn floating_comparison(value1: f64, value2: f64, nan_result: i32) -> i32 {
+	if value1 > value2 {
+		1
+	} else if value1 == value2 {
+		0
+	} else if value1 < value2 {
+		-1
+	} else {
+		nan_result
+	}
 }
 
 pub fn move_parameters_to_locals(
@@ -75,6 +362,14 @@ pub fn move_parameters_to_locals(
 ) -> bool {
 	for i in 0..method.parameter_count {
 		if let Some(parameter) = invoking_frame.operand_stack.pop() {
+			/*
+			 * A long or a double parameter takes two locals.
+			 */
+			if parameter.size() == 2 {
+				invoked_frame
+					.locals
+					.insert(0, JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0));
+			}
 			invoked_frame.locals.insert(0, parameter);
 		} else {
 			return false;
@@ -88,16 +383,216 @@ impl JvmThread {
 		self.debug_level.clone()
 	}
 
-	pub fn new(debug_level: DebugLevel, methodarea: Arc<Mutex<MethodArea>>) -> Self {
+	pub fn new(
+		debug_level: DebugLevel,
+		methodarea: Arc<Mutex<MethodArea>>,
+		natives: NativeRegistry,
+		bootstraps: BootstrapRegistry,
+	) -> Self {
 		JvmThread {
 			debug_level: debug_level,
 			methodarea: methodarea,
 			pc: 0,
 			initializing_class: Vec::<String>::new(),
+			natives: natives,
+			bootstraps: bootstraps,
+			call_sites: HashMap::new(),
+			verify: false,
+			verified_methods: HashSet::new(),
+			integer_cache: HashMap::new(),
+			call_stack: Vec::<CallStackEntry>::new(),
+			frames: Vec::new(),
+			max_frame_depth: DEFAULT_MAX_FRAME_DEPTH,
+			frame_reserve: 0,
+			exception_stack_trace: None,
+			pending_exception: None,
+			out: Box::new(io::stdout()),
+			err: Box::new(io::stderr()),
+			heap: Arc::new(Heap::new(None)),
+		}
+	}
+
+	pub fn set_out(&mut self, out: Box<dyn Write>) {
+		self.out = out;
+	}
+
+	pub fn set_err(&mut self, err: Box<dyn Write>) {
+		self.err = err;
+	}
+
+	/*
+	 * Flush what the program has written to standard output and
+	 * standard error.
+	 */
+	pub fn flush(&mut self) {
+		let _ = self.out.flush();
+		let _ = self.err.flush();
+	}
+
+	/*
+	 * What System.out prints to.
+	 */
+	pub fn out(&mut self) -> &mut dyn Write {
+		&mut *self.out
+	}
+
+	pub fn set_heap(&mut self, heap: Arc<Heap>) {
+		self.heap = heap;
+	}
+
+	/*
+	 * Take _bytes_ (None when they are too many to count) of the heap
+	 * for a new object or array; an OutOfMemoryError when they do not
+	 * fit.
+	 */
+	fn allocate(&self, bytes: Option<usize>) -> Result<Allocation, OpcodeResult> {
+		bytes
+			.and_then(|bytes| Heap::allocate(&self.heap, bytes))
+			.ok_or(OpcodeResult::Exception(
+				format!("java/lang/OutOfMemoryError"),
+				None,
+			))
+	}
+
+	/*
+	 * What allocating an array of _count_ elements takes.
+	 */
+	fn allocate_array(&self, count: usize) -> Result<Allocation, OpcodeResult> {
+		self.allocate(count.checked_mul(mem::size_of::<Option<JvmValue>>()))
+	}
+
+	pub fn set_verify(&mut self, verify: bool) {
+		self.verify = verify;
+	}
+
+	pub fn push_frame(&mut self, frame: Frame) {
+		self.frames.push(frame);
+	}
+
+	pub fn pop_frame(&mut self) -> Option<Frame> {
+		self.frames.pop()
+	}
+
+	pub fn current_frame_mut(&mut self) -> Option<&mut Frame> {
+		self.frames.last_mut()
+	}
+
+	pub fn set_max_frame_depth(&mut self, max_frame_depth: usize) {
+		self.max_frame_depth = max_frame_depth;
+	}
+
+	/*
+	 * How many methods are running: the frame of the one that is
+	 * executing an instruction is off the stack while it does.
+	 */
+	pub fn frame_depth(&self) -> usize {
+		self.call_stack.len()
+	}
+
+	pub fn integer_cache_mut(&mut self) -> &mut HashMap<i32, JvmValue> {
+		&mut self.integer_cache
+	}
+
+	/*
+	 * A new object of class _class_name_.
+	 */
+	pub fn create_object(&mut self, class_name: &String) -> Result<JvmObject, JvmError> {
+		create_object(class_name, self, Arc::clone(&self.methodarea))
+	}
+
+	/*
+	 * A new object of class _class_name_, constructed by its <init>
+	 * of type _descriptor_ with _arguments_. A constructor that throws
+	 * is an UncaughtException error.
+	 */
+	pub fn construct(
+		&mut self,
+		class_name: &String,
+		descriptor: &String,
+		arguments: Vec<JvmValue>,
+	) -> Result<JvmValue, JvmError> {
+		let mut class: Option<Rc<Class>> = None;
+		if let Ok(mut methodarea) = self.methodarea.lock() {
+			class = (*methodarea).maybe_load_class(class_name);
+		}
+		let class = class.ok_or(JvmError::ClassNotFound(class_name.clone()))?;
+		let constructor = class
+			.get_method_rc_by_name_and_type(&format!("<init>"), descriptor)
+			.ok_or(JvmError::MethodNotFound(
+				format!("<init>"),
+				class_name.clone(),
+			))?;
+		let object = JvmValue::Reference(
+			JvmReferenceType::Class(class_name.clone()),
+			JvmReferenceTargetType::Object(Arc::new(Mutex::new(self.create_object(class_name)?))),
+			0,
+		);
+
+		let mut frame = Frame::new();
+		frame.class = Some(Rc::clone(&class));
+		frame.locals.push(object.clone());
+		for argument in arguments {
+			let size = argument.size();
+			frame.locals.push(argument);
+			if size == 2 {
+				frame
+					.locals
+					.push(JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0));
+			}
+		}
+		match self.execute_method(&constructor, frame) {
+			OpcodeResult::Exception(exception, _) => {
+				self.exception_stack_trace = None;
+				Err(JvmError::UncaughtException(exception))
+			}
+			OpcodeResult::Throw(exception) => {
+				self.exception_stack_trace = None;
+				Err(JvmError::UncaughtException(exception_class_name(
+					&exception,
+				)))
+			}
+			OpcodeResult::Error(error) => Err(error),
+			_ => Ok(object),
+		}
+	}
+
+	/*
+	 * A reference to a new String object for _value_.
+	 */
+	pub fn create_string(&mut self, value: String) -> Result<JvmValue, JvmError> {
+		let string_object = create_static_string_object(value, self, Arc::clone(&self.methodarea))?;
+		Ok(JvmValue::Reference(
+			JvmReferenceType::Class(format!("java/lang/String")),
+			JvmReferenceTargetType::Object(Arc::new(Mutex::new(string_object))),
+			0,
+		))
+	}
+
+	/*
+	 * Report an exception that unwound every frame, along with
+	 * where it was thrown.
+	 */
+	fn report_uncaught_exception(&mut self, exception_class_name: &String) {
+		let _ = writeln!(
+			self.err,
+			"Exception in thread \"main\" {}",
+			exception_class_name.replace('/', ".")
+		);
+		if let Some(stack_trace) = self.exception_stack_trace.take() {
+			print_stack_trace(&stack_trace, &mut *self.err);
 		}
 	}
 
-	pub fn run(&mut self, class_name: &String, method_name: &String) -> bool {
+	/*
+	 * Say where the fatal error that stopped the thread happened.
+	 */
+	fn report_fatal_error(&mut self) {
+		if let Some(stack_trace) = self.exception_stack_trace.take() {
+			print_stack_trace(&stack_trace, &mut *self.err);
+		}
+	}
+
+	pub fn run(&mut self, class_name: &String, method_name: &String) -> Result<(), JvmError> {
 		/*
 		 * 3. Load the method.
 		 * 4. Create a frame.
@@ -110,71 +605,193 @@ impl JvmThread {
 			(*methodarea).maybe_load_class(class_name);
 			main_class = (*methodarea).get_class_rc(class_name);
 		}
-		if let Some(main_class) = main_class {
-			Debug(
-				format!("Loaded class {}.\n", main_class),
-				&self.debug_level,
-				DebugLevel::Info,
+		let main_class = match main_class {
+			Some(main_class) => main_class,
+			None => return Err(JvmError::ClassNotFound(class_name.clone())),
+		};
+		Debug(
+			format!("Loaded class {}.\n", main_class),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+
+		/*
+		 * Per the spec, it is required that we initialize the main
+		 * class before calling the main method inside that class.
+		 */
+		if let Err(error) = self.maybe_initialize_class(&main_class) {
+			self.report_fatal_error();
+			return Err(error);
+		}
+
+		let main_method = match main_class
+			.get_method_rc_by_name_and_type(method_name, &"([Ljava/lang/String;)V".to_string())
+		{
+			Some(main_method) => main_method,
+			None => {
+				return Err(JvmError::MethodNotFound(
+					method_name.clone(),
+					class_name.clone(),
+				))
+			}
+		};
+		if main_method.access_flags
+			!= ((MethodAccessFlags::Public as u16) | (MethodAccessFlags::Static as u16))
+		{
+			return Err(FatalError::new(FatalErrorType::MainMethodNotPublicStatic).into_error());
+		}
+		if JvmType::Primitive(JvmPrimitiveType::Void) != main_method.return_type {
+			return Err(FatalError::new(FatalErrorType::MainMethodNotVoid).into_error());
+		}
+		let mut frame = Frame::new();
+		frame.class = Some(Rc::clone(&main_class));
+		/*
+		 * Load up the frame's locals with the CLI arguments: main's one
+		 * parameter is a String[] of them.
+		 */
+		let mut arguments: Vec<String> = vec![];
+		if let Ok(methodarea) = self.methodarea.lock() {
+			arguments = (*methodarea).arguments().clone();
+		}
+		let string_type = JvmReferenceType::Class(format!("java/lang/String"));
+		let mut array = JvmArray::new_with_value(
+			arguments.len(),
+			JvmValue::Reference(string_type.clone(), JvmReferenceTargetType::Null, 0),
+		);
+		for (index, argument) in arguments.iter().enumerate() {
+			let string_object =
+				create_static_string_object(argument.clone(), self, Arc::clone(&self.methodarea))?;
+			array.set_at(
+				index,
+				JvmValue::Reference(
+					string_type.clone(),
+					JvmReferenceTargetType::Object(Arc::new(Mutex::new(string_object))),
+					0,
+				),
 			);
+		}
+		frame.locals.push(JvmValue::Reference(
+			JvmReferenceType::Array(
+				Rc::new(JvmType::Reference(string_type)),
+				arguments.len() as u32,
+			),
+			JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),
+			0,
+		));
 
-			/*
-			 * Per the spec, it is required that we initialize the main
-			 * class before calling the main method inside that class.
-			 */
-			self.maybe_initialize_class(&main_class);
+		Debug(
+			format!("Frame: {}", frame),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
 
-			if let Some(main_method) = main_class
-				.get_method_rc_by_name_and_type(method_name, &"([Ljava/lang/String;)V".to_string())
-			{
-				if main_method.access_flags
-					!= ((MethodAccessFlags::Public as u16) | (MethodAccessFlags::Static as u16))
-				{
-					FatalError::new(FatalErrorType::MainMethodNotPublicStatic).call();
-				}
-				if JvmType::Primitive(JvmPrimitiveType::Void) != main_method.return_type {
-					FatalError::new(FatalErrorType::MainMethodNotVoid).call();
+		match self.execute_method(&main_method, frame) {
+			OpcodeResult::Return(v) => {
+				if JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0) != v {
+					return Err(
+						FatalError::new(FatalErrorType::VoidMethodReturnedValue).into_error()
+					);
 				}
-				let mut frame = Frame::new();
-				frame.class = Some(Rc::clone(&main_class));
-				/*
-				 * Load up the frame's stack with the CLI arguments.
-				 */
-				frame
-					.operand_stack
-					.push(JvmValue::Primitive(JvmPrimitiveType::Boolean, 0, 0, 0));
+			}
+			OpcodeResult::Exception(exception, _) => {
+				self.report_uncaught_exception(&exception);
+				return Err(JvmError::UncaughtException(exception));
+			}
+			OpcodeResult::Throw(exception) => {
+				let exception = exception_class_name(&exception);
+				self.report_uncaught_exception(&exception);
+				return Err(JvmError::UncaughtException(exception));
+			}
+			OpcodeResult::Error(error) => {
+				self.report_fatal_error();
+				return Err(error);
+			}
+			_ => {}
+		}
+		Ok(())
+	}
 
-				Debug(
-					format!("Frame: {}", frame),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
+	/*
+	 * Execute _method_ in _frame_. The result is the value that the
+	 * method returns, the exception that it did not catch or the
+	 * fatal error that stopped it. The methods that it invokes run in
+	 * the same loop (see execute_frames): only running a method from
+	 * outside of the loop (main, a class initializer, a constructor
+	 * that a native calls) takes another one.
+	 */
+	fn execute_method(&mut self, method: &Rc<Method>, frame: Frame) -> OpcodeResult {
+		let base = self.frames.len();
+		if let Err(result) = self.enter_method(method, frame) {
+			return result;
+		}
+		self.execute_frames(base)
+	}
 
-				if let Some(v) = self.execute_method(&main_method, frame) {
-					if JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0) != v {
-						FatalError::new(FatalErrorType::VoidMethodReturnedValue).call();
-					}
-				}
-				return true;
-			} else {
-				FatalError::new(FatalErrorType::MethodNotFound(
-					method_name.clone(),
-					class_name.clone(),
+	/*
+	 * Make _frame_ (for _method_) the frame on top of the stack, so
+	 * that the method starts running with the next instruction that
+	 * the thread executes. When the method cannot start, the result
+	 * is what the invoking instruction gets instead: a
+	 * StackOverflowError, say.
+	 */
+	fn enter_method(&mut self, method: &Rc<Method>, mut frame: Frame) -> Result<(), OpcodeResult> {
+		let class = frame.class().unwrap();
+		if self.call_stack.len() >= self.max_frame_depth.saturating_add(self.frame_reserve) {
+			return Err(OpcodeResult::Exception(
+				format!("java/lang/StackOverflowError"),
+				None,
+			));
+		}
+
+		if method.get_code().is_none() {
+			return Err(OpcodeResult::Error(
+				FatalError::new(FatalErrorType::MethodExecutionFailed(
+					class
+						.get_constant_pool_ref()
+						.get_utf8(method.name_index as usize)
+						.unwrap_or("?")
+						.to_string(),
 				))
-				.call()
+				.into_error(),
+			));
+		}
+
+		if self.verify {
+			let key = (
+				class.get_class_name().unwrap(),
+				method.name_index,
+				method.descriptor_index,
+			);
+			if !self.verified_methods.contains(&key) {
+				if let Err(reason) = verify_stack_depths(method, class.get_constant_pool_ref()) {
+					let method_name = class
+						.get_constant_pool_ref()
+						.get_utf8(method.name_index as usize)
+						.unwrap_or("?");
+					return Err(OpcodeResult::Error(JvmError::VerificationFailed(format!(
+						"{}.{}: {}",
+						key.0, method_name, reason
+					))));
+				}
+				self.verified_methods.insert(key);
 			}
-		} else {
-			FatalError::new(FatalErrorType::ClassNotFound(class_name.clone())).call()
 		}
-		false
-	}
 
-	fn execute_method(&mut self, method: &Method, mut frame: Frame) -> Option<JvmValue> {
+		if self.debug_level <= DebugLevel::Verbose {
+			Debug(
+				format!(
+					"Code:\n{}",
+					method.disassemble(class.get_constant_pool_ref())
+				),
+				&self.debug_level,
+				DebugLevel::Verbose,
+			);
+		}
+
 		/*
 		 * The locals are only going to have enough size for the parameters.
 		 * Resize as appropriate.
 		 */
-		let class = frame.class().unwrap();
-
 		Debug(
 			format!(
 				"Resizing local parameter array from {} to {}\n",
@@ -184,103 +801,492 @@ impl JvmThread {
 			&self.debug_level,
 			DebugLevel::Info,
 		);
-
 		frame.locals.resize(
 			method.max_locals,
 			JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0),
 		);
+		frame.max_stack = Some(method.max_stack);
 
-		if let Some(code) = method.get_code(class.get_constant_pool_ref()) {
-			let mut pc = 0;
-			while {
-				let mut pc_pos_delta = 0usize;
-				let mut pc_neg_delta = 0usize;
-				Debug(
-					format!("Doing next opcode\n"),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				match self.execute_opcode(&code[pc..], &mut frame) {
-					OpcodeResult::Incr(incr) => pc_pos_delta = incr,
-					OpcodeResult::Decr(decr) => pc_neg_delta = decr,
-					OpcodeResult::Value(v) => return Some(v),
-					OpcodeResult::Exception => {
-						/*
-						 * TODO: Handle exceptions!
-						 */
-						FatalError::new(FatalErrorType::NotImplemented(format!("Exceptions.")))
-							.call();
-					}
-				};
-				Debug(
-					format!("pc_pos_delta: {}\n", pc_pos_delta),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				Debug(
-					format!("pc_neg: {}\n", pc_neg_delta),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				pc += pc_pos_delta;
-				pc -= pc_neg_delta;
-				Debug(format!("pc: {}\n", pc), &self.debug_level, DebugLevel::Info);
-				pc_pos_delta != 0 || pc_neg_delta != 0
-			} {}
+		/*
+		 * A synchronized method holds the monitor of its receiver (or
+		 * of its class, when it is static) for as long as it runs,
+		 * however it finishes (see leave_method).
+		 */
+		if method.is_synchronized() && !method.is_static() {
+			frame.receiver = frame.locals.get(0).cloned();
+		}
+		if method.is_synchronized() {
+			match self.update_method_monitor(&class, &frame.receiver, true) {
+				Ok(true) => (),
+				Ok(false) => {
+					return Err(OpcodeResult::Error(
+						FatalError::new(FatalErrorType::NotImplemented(format!(
+							"Contended monitors"
+						)))
+						.into_error(),
+					))
+				}
+				Err(error) => return Err(OpcodeResult::Error(error)),
+			}
 		}
-		None
+
+		self.call_stack.push(CallStackEntry {
+			class: Rc::clone(&class),
+			name_index: method.name_index,
+			descriptor_index: method.descriptor_index,
+			pc: 0,
+		});
+
+		frame.pc = 0;
+		frame.method = Some(Rc::clone(method));
+		self.push_frame(frame);
+		Ok(())
 	}
 
-	fn execute_opcode(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+	/*
+	 * Finish the method that _frame_ (which is already off the stack)
+	 * is for with _result_. The result is what the instruction that
+	 * invoked the method gets: _result_, unless releasing the
+	 * method's monitor fails.
+	 */
+	fn leave_method(&mut self, frame: &Frame, result: OpcodeResult) -> OpcodeResult {
+		let mut result = result;
 		let class = frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
+		let synchronized = match &frame.method {
+			Some(method) => method.is_synchronized(),
+			None => false,
+		};
 
-		let opcode = bytes[0];
-		Debug(
-			format!("code: 0x{:X}\n", opcode),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-		match OperandCode::from_u8(opcode) {
-			Some(OperandCode::Aconst_null) => {
-				Debug(format!("aconst_null"), &self.debug_level, DebugLevel::Info);
-				frame.operand_stack.push(create_null_value());
-				OpcodeResult::Incr(1)
+		if synchronized {
+			match (
+				&result,
+				self.update_method_monitor(&class, &frame.receiver, false),
+			) {
+				(OpcodeResult::Error(_), _) | (_, Ok(true)) => (),
+				(_, Ok(false)) => {
+					result = OpcodeResult::Exception(
+						format!("java/lang/IllegalMonitorStateException"),
+						None,
+					)
+				}
+				(_, Err(error)) => result = OpcodeResult::Error(error),
 			}
-			Some(OperandCode::Iconst_m1) => {
-				Debug(format!("iconst_m1"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(-1, frame);
+		}
+
+		/*
+		 * Remember where an exception (or a fatal error) came from
+		 * while it unwinds: by the time that it is reported, its
+		 * frames are gone.
+		 */
+		match result {
+			OpcodeResult::Exception(_, _) | OpcodeResult::Throw(_) | OpcodeResult::Error(_) => {
+				if self.exception_stack_trace.is_none() {
+					self.exception_stack_trace = Some(self.call_stack.clone());
+				}
+			}
+			_ => (),
+		}
+
+		self.call_stack.pop();
+		result
+	}
+
+	/*
+	 * Say which instruction is about to execute, where, and how deep
+	 * the operand stack is when it does.
+	 */
+	fn trace_opcode(&mut self, class: &Rc<Class>, method: &Method, bytes: &[u8], frame: &Frame) {
+		let method_name = class
+			.get_constant_pool_ref()
+			.get_utf8(method.name_index as usize)
+			.unwrap_or("?");
+		let (mnemonic, _) = opcode_info(bytes[0]);
+		debug_to(
+			&mut self.err,
+			format!(
+				"{}.{} {}: {} (stack depth: {})",
+				class.get_class_name().unwrap(),
+				method_name,
+				frame.pc,
+				mnemonic,
+				frame.operand_stack_depth()
+			),
+			&self.debug_level,
+			DebugLevel::Trace,
+		);
+	}
+
+	/*
+	 * Execute the instruction at frame.pc in _frame_, the frame of
+	 * _method_.
+	 */
+	fn execute_instruction(&mut self, method: &Method, frame: &mut Frame) -> OpcodeResult {
+		let class = frame.class().unwrap();
+		let code = match method.get_code() {
+			Some(code) => code,
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::MethodExecutionFailed(format!("(no code)")))
+						.into_error(),
+				)
+			}
+		};
+		Debug(
+			format!("Doing next opcode\n"),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		/*
+		 * A branch (or the last instruction) can only take
+		 * execution off the end of the code when the code is
+		 * broken.
+		 */
+		if frame.pc >= code.len() {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::VerifyError(format!(
+					"execution falls off the end of the code at {}",
+					frame.pc
+				)))
+				.into_error(),
+			);
+		}
+		if let Some(entry) = self.call_stack.last_mut() {
+			entry.pc = frame.pc;
+		}
+		if self.debug_level <= DebugLevel::Trace {
+			self.trace_opcode(&class, method, &code[frame.pc..], frame);
+		}
+		self.execute_opcode(&code[frame.pc..], frame)
+	}
+
+	/*
+	 * Run the frames above the bottom _base_ of the stack until the
+	 * method of the lowest of them returns or throws. The frame on top
+	 * of the stack is the one that executes; while one of its
+	 * instructions executes, it is off the stack. An instruction that
+	 * invokes a method puts the method's frame on top of its own, and
+	 * the method's result is the result of the instruction: a return
+	 * value goes on to the operand stack of the invoking frame and an
+	 * exception is thrown from the invoking instruction.
+	 */
+	fn execute_frames(&mut self, base: usize) -> OpcodeResult {
+		/*
+		 * The result of the invoking instruction of the frame on top
+		 * of the stack, once the method that it invoked is done.
+		 */
+		let mut invoke_result: Option<OpcodeResult> = None;
+		loop {
+			let mut frame = match self.pop_frame() {
+				Some(frame) => frame,
+				None => {
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::MethodExecutionFailed(format!(
+							"(no frame)"
+						)))
+						.into_error(),
+					)
+				}
+			};
+			let method = match &frame.method {
+				Some(method) => Rc::clone(method),
+				None => {
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::MethodExecutionFailed(format!(
+							"(no method)"
+						)))
+						.into_error(),
+					)
+				}
+			};
+			let mut result = match invoke_result.take() {
+				Some(result) => result,
+				None => self.execute_instruction(&method, &mut frame),
+			};
+			/*
+			 * Most instructions push onto the operand stack
+			 * directly, so check that they stayed within max_stack
+			 * after the fact.
+			 */
+			if frame.operand_stack_overflowed() {
+				let (mnemonic, _) = opcode_info(method.get_code().unwrap()[frame.pc]);
+				result = OpcodeResult::Error(
+					OperandStackError::Overflow(method.max_stack)
+						.into_fatal_error(mnemonic)
+						.into_error(),
+				);
+			}
+			let finished = match result {
+				OpcodeResult::Incr(incr) => {
+					frame.pc += incr;
+					None
+				}
+				OpcodeResult::Decr(decr) => {
+					if decr > frame.pc {
+						Some(OpcodeResult::Error(
+							FatalError::new(FatalErrorType::VerifyError(format!(
+								"the branch at {} goes before the start of the code",
+								frame.pc
+							)))
+							.into_error(),
+						))
+					} else {
+						frame.pc -= decr;
+						None
+					}
+				}
+				OpcodeResult::Invoke(invoked_method, invoked_frame) => {
+					self.push_frame(frame);
+					if let Err(result) = self.enter_method(&invoked_method, invoked_frame) {
+						invoke_result = Some(result);
+					}
+					continue;
+				}
+				OpcodeResult::Return(v) => Some(OpcodeResult::Return(v)),
+				OpcodeResult::Error(error) => Some(OpcodeResult::Error(error)),
+				OpcodeResult::Exception(exception, message) => {
+					/*
+					 * An exception that the JVM raises (a
+					 * NullPointerException from getfield, say) is thrown
+					 * just like athrow throws one, from here on.
+					 */
+					let frame_reserve =
+						std::mem::replace(&mut self.frame_reserve, EXCEPTION_FRAME_RESERVE);
+					let thrown = throw_exception(
+						self,
+						&exception,
+						message.as_ref().map(|message| message.as_str()),
+					);
+					self.frame_reserve = frame_reserve;
+					match thrown {
+						OpcodeResult::Throw(exception) => {
+							self.throw_in_frame(&method, exception, &mut frame)
+						}
+						thrown => Some(thrown),
+					}
+				}
+				OpcodeResult::Throw(exception) => {
+					self.throw_in_frame(&method, exception, &mut frame)
+				}
+			};
+			match finished {
+				None => {
+					Debug(
+						format!("pc: {}\n", frame.pc),
+						&self.debug_level,
+						DebugLevel::Info,
+					);
+					self.push_frame(frame);
+				}
+				Some(result) => {
+					Debug(
+						format!("Returning from a method: {}", method),
+						&self.debug_level,
+						DebugLevel::Info,
+					);
+					let result = self.leave_method(&frame, result);
+					if self.frames.len() == base {
+						return result;
+					}
+					invoke_result = Some(self.complete_invoke(result));
+				}
+			}
+		}
+	}
+
+	/*
+	 * The result of the invoking instruction of the frame on top of
+	 * the stack when the method that it invoked finishes with
+	 * _result_. A return value goes on to the invoking frame's operand
+	 * stack.
+	 */
+	fn complete_invoke(&mut self, result: OpcodeResult) -> OpcodeResult {
+		let mut frame = match self.pop_frame() {
+			Some(frame) => frame,
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::MethodExecutionFailed(format!(
+						"(no invoking frame)"
+					)))
+					.into_error(),
+				)
+			}
+		};
+		/*
+		 * The invoking instruction is still at frame.pc.
+		 */
+		let length = match frame.method.as_ref().and_then(|method| method.get_code()) {
+			Some(code) if frame.pc < code.len() => 1 + opcode_info(code[frame.pc]).1,
+			_ => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::MethodExecutionFailed(format!(
+						"(no invoking instruction)"
+					)))
+					.into_error(),
+				)
+			}
+		};
+		let result = self.handle_invoke_result(result, &mut frame, length);
+		self.push_frame(frame);
+		result
+	}
+
+	/*
+	 * Look through _method_'s exception table for a handler that
+	 * covers the instruction at frame.pc and that catches exceptions
+	 * of class _exception_class_name_. If there is one, the operand
+	 * stack holds only _exception_ and execution continues at the
+	 * handler.
+	 */
+	/*
+	 * Throw _exception_ from the current instruction of _frame_ (which
+	 * runs _method_). None when a handler in _method_ catches it, and
+	 * otherwise the result that _method_ ends with.
+	 */
+	fn throw_in_frame(
+		&mut self,
+		method: &Method,
+		exception: JvmValue,
+		frame: &mut Frame,
+	) -> Option<OpcodeResult> {
+		let exception_class = exception_class_name(&exception);
+		match self.catch_exception(method, &exception_class, exception.clone(), frame) {
+			Ok(true) => None,
+			Ok(false) => Some(OpcodeResult::Throw(exception)),
+			Err(error) => Some(OpcodeResult::Error(error)),
+		}
+	}
+
+	fn catch_exception(
+		&mut self,
+		method: &Method,
+		exception_class_name: &String,
+		exception: JvmValue,
+		frame: &mut Frame,
+	) -> Result<bool, JvmError> {
+		let class = frame.class().unwrap();
+
+		let exception_table = match method.get_exception_table() {
+			Some(exception_table) => exception_table,
+			None => return Ok(false),
+		};
+
+		for entry in exception_table {
+			if frame.pc < entry.start_pc() as usize || frame.pc >= entry.end_pc() as usize {
+				continue;
+			}
+
+			/*
+			 * A catch type of 0 catches everything. javac uses it for
+			 * finally: the handler runs the finally block and then
+			 * rethrows the exception. (The paths that complete
+			 * normally have their own copies of the finally block.)
+			 */
+			let catches = if entry.catch_type() == 0 {
+				true
+			} else if let Some(catch_class_name) =
+				class.resolve_class_ref(entry.catch_type() as usize)
+			{
+				if let Ok(mut methodarea) = self.methodarea.lock() {
+					(*methodarea).is_subtype_of(exception_class_name, &catch_class_name)
+				} else {
+					return Err(FatalError::new(FatalErrorType::CouldNotLock(
+						"Method Area.".to_string(),
+						"catch_exception".to_string(),
+					))
+					.into_error());
+				}
+			} else {
+				return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Class".to_string(),
+					entry.catch_type(),
+				))
+				.into_error());
+			};
+
+			if catches {
+				Debug(
+					format!(
+						"Caught {} at {}; handler at {}",
+						exception_class_name,
+						frame.pc,
+						entry.handler_pc()
+					),
+					&self.debug_level,
+					DebugLevel::Info,
+				);
+				frame.operand_stack.clear();
+				frame.operand_stack.push(exception);
+				frame.pc = entry.handler_pc() as usize;
+				self.exception_stack_trace = None;
+				return Ok(true);
+			}
+		}
+		Ok(false)
+	}
+
+	fn execute_opcode(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let opcode = bytes[0];
+		Debug(
+			format!("code: 0x{:X}\n", opcode),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		match OperandCode::from_u8(opcode) {
+			Some(OperandCode::Nop) => {
+				Debug(format!("nop"), &self.debug_level, DebugLevel::Info);
+				OpcodeResult::Incr(1)
+			}
+			Some(OperandCode::Aconst_null) => {
+				Debug(format!("aconst_null"), &self.debug_level, DebugLevel::Info);
+				frame.operand_stack.push(create_null_value());
 				OpcodeResult::Incr(1)
 			}
+			Some(OperandCode::Iconst_m1) => {
+				Debug(format!("iconst_m1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_iconst_x(-1, frame), 1)
+			}
 			Some(OperandCode::Iconst_0) => {
 				Debug(format!("iconst_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(0, frame), 1)
 			}
 			Some(OperandCode::Iconst_1) => {
 				Debug(format!("iconst_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(1, frame), 1)
 			}
 			Some(OperandCode::Iconst_2) => {
 				Debug(format!("iconst_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(2, frame), 1)
 			}
 			Some(OperandCode::Iconst_3) => {
 				Debug(format!("iconst_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(3, frame), 1)
 			}
 			Some(OperandCode::Iconst_4) => {
 				Debug(format!("iconst_4"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(4, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(4, frame), 1)
 			}
 			Some(OperandCode::Iconst_5) => {
 				Debug(format!("iconst_5"), &self.debug_level, DebugLevel::Info);
-				self.execute_iconst_x(5, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iconst_x(5, frame), 1)
+			}
+			Some(OperandCode::Lconst_0) => {
+				Debug(format!("lconst_0"), &self.debug_level, DebugLevel::Info);
+				advance(
+					frame
+						.push_long(0)
+						.map_err(|error| error.into_fatal_error("lconst_0").into_error()),
+					1,
+				)
+			}
+			Some(OperandCode::Lconst_1) => {
+				Debug(format!("lconst_1"), &self.debug_level, DebugLevel::Info);
+				advance(
+					frame
+						.push_long(1)
+						.map_err(|error| error.into_fatal_error("lconst_1").into_error()),
+					1,
+				)
 			}
 			Some(OperandCode::Fconst_0) => {
 				Debug(format!("fconst_0"), &self.debug_level, DebugLevel::Info);
@@ -300,214 +1306,520 @@ impl JvmThread {
 					.push(JvmValue::Primitive(JvmPrimitiveType::Float, 0, uone, 0));
 				OpcodeResult::Incr(1)
 			}
+			Some(OperandCode::Fconst_2) => {
+				Debug(format!("fconst_2"), &self.debug_level, DebugLevel::Info);
+				let ftwo = 2.0f32;
+				let utwo = u32::from_le_bytes(ftwo.to_le_bytes());
+				frame
+					.operand_stack
+					.push(JvmValue::Primitive(JvmPrimitiveType::Float, 0, utwo, 0));
+				OpcodeResult::Incr(1)
+			}
+			Some(OperandCode::Dconst_0) => {
+				Debug(format!("dconst_0"), &self.debug_level, DebugLevel::Info);
+				advance(
+					frame
+						.push_double(0.0)
+						.map_err(|error| error.into_fatal_error("dconst_0").into_error()),
+					1,
+				)
+			}
+			Some(OperandCode::Dconst_1) => {
+				Debug(format!("dconst_1"), &self.debug_level, DebugLevel::Info);
+				advance(
+					frame
+						.push_double(1.0)
+						.map_err(|error| error.into_fatal_error("dconst_1").into_error()),
+					1,
+				)
+			}
 			Some(OperandCode::Bipush) => {
 				Debug(format!("bipush"), &self.debug_level, DebugLevel::Info);
-				frame.operand_stack.push(JvmValue::Primitive(
-					JvmPrimitiveType::Integer,
-					0,
-					bytes[1] as u32,
-					0,
-				));
-				OpcodeResult::Incr(2)
+				/*
+				 * The operand is signed: sign extend it to an int.
+				 */
+				advance(self.execute_iconst_x(bytes[1] as i8 as i32, frame), 2)
+			}
+			Some(OperandCode::Sipush) => {
+				Debug(format!("sipush"), &self.debug_level, DebugLevel::Info);
+				advance(
+					self.execute_iconst_x(i16::from_be_bytes([bytes[1], bytes[2]]) as i32, frame),
+					3,
+				)
 			}
 			Some(OperandCode::Ldc) => {
 				Debug(format!("ldc"), &self.debug_level, DebugLevel::Info);
-				self.execute_ldc(bytes, frame);
-				OpcodeResult::Incr(2)
+				advance(self.execute_ldc(bytes[1] as usize, frame), 2)
+			}
+			Some(OperandCode::Ldc_w) => {
+				Debug(format!("ldc_w"), &self.debug_level, DebugLevel::Info);
+				let index = u16::from_be_bytes([bytes[1], bytes[2]]);
+				advance(self.execute_ldc(index as usize, frame), 3)
+			}
+			Some(OperandCode::Ldc2_w) => {
+				Debug(format!("ldc2_w"), &self.debug_level, DebugLevel::Info);
+				let index = u16::from_be_bytes([bytes[1], bytes[2]]);
+				advance(self.execute_ldc2_w(index as usize, frame), 3)
+			}
+			Some(OperandCode::Iload) => {
+				Debug(format!("iload"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_iload_x(bytes[1] as usize, frame), 2)
 			}
 			Some(OperandCode::Iload_0) => {
 				Debug(format!("iload_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_iload_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iload_x(0, frame), 1)
 			}
 			Some(OperandCode::Iload_1) => {
 				Debug(format!("iload_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_iload_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iload_x(1, frame), 1)
 			}
 			Some(OperandCode::Iload_2) => {
 				Debug(format!("iload_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_iload_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iload_x(2, frame), 1)
 			}
 			Some(OperandCode::Iload_3) => {
 				Debug(format!("iload_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_iload_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iload_x(3, frame), 1)
+			}
+			Some(OperandCode::Lload) => {
+				Debug(format!("lload"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lload_x(bytes[1] as usize, frame), 2)
+			}
+			Some(OperandCode::Lload_0) => {
+				Debug(format!("lload_0"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lload_x(0, frame), 1)
+			}
+			Some(OperandCode::Lload_1) => {
+				Debug(format!("lload_1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lload_x(1, frame), 1)
+			}
+			Some(OperandCode::Lload_2) => {
+				Debug(format!("lload_2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lload_x(2, frame), 1)
+			}
+			Some(OperandCode::Lload_3) => {
+				Debug(format!("lload_3"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lload_x(3, frame), 1)
+			}
+			Some(OperandCode::Fload) => {
+				Debug(format!("fload"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_fload_x(bytes[1] as usize, frame), 2)
 			}
 			Some(OperandCode::Fload_0) => {
 				Debug(format!("fload_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_fload_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fload_x(0, frame), 1)
 			}
 			Some(OperandCode::Fload_1) => {
 				Debug(format!("fload_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_fload_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fload_x(1, frame), 1)
 			}
 			Some(OperandCode::Fload_2) => {
 				Debug(format!("fload_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_fload_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fload_x(2, frame), 1)
 			}
 			Some(OperandCode::Fload_3) => {
 				Debug(format!("fload_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_fload_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fload_x(3, frame), 1)
+			}
+			Some(OperandCode::Dload) => {
+				Debug(format!("dload"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dload_x(bytes[1] as usize, frame), 2)
+			}
+			Some(OperandCode::Dload_0) => {
+				Debug(format!("dload_0"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dload_x(0, frame), 1)
+			}
+			Some(OperandCode::Dload_1) => {
+				Debug(format!("dload_1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dload_x(1, frame), 1)
+			}
+			Some(OperandCode::Dload_2) => {
+				Debug(format!("dload_2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dload_x(2, frame), 1)
+			}
+			Some(OperandCode::Dload_3) => {
+				Debug(format!("dload_3"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dload_x(3, frame), 1)
+			}
+			Some(OperandCode::Aload) => {
+				Debug(format!("aload"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_aload_x(bytes[1] as usize, frame), 2)
 			}
 			Some(OperandCode::Aload_0) => {
 				Debug(format!("aload_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_aload_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_aload_x(0, frame), 1)
 			}
 			Some(OperandCode::Aload_1) => {
 				Debug(format!("aload_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_aload_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_aload_x(1, frame), 1)
 			}
 			Some(OperandCode::Aload_2) => {
 				Debug(format!("aload_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_aload_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_aload_x(2, frame), 1)
 			}
 			Some(OperandCode::Aload_3) => {
 				Debug(format!("aload_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_aload_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_aload_x(3, frame), 1)
+			}
+			Some(OperandCode::IaLoad) => {
+				Debug(format!("iaload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load("iaload", &[JvmPrimitiveType::Integer], frame)
+			}
+			Some(OperandCode::LaLoad) => {
+				Debug(format!("laload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load("laload", &[JvmPrimitiveType::LongInteger], frame)
+			}
+			Some(OperandCode::FaLoad) => {
+				Debug(format!("faload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load("faload", &[JvmPrimitiveType::Float], frame)
+			}
+			Some(OperandCode::DaLoad) => {
+				Debug(format!("daload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load("daload", &[JvmPrimitiveType::Double], frame)
 			}
 			Some(OperandCode::AaLoad) => {
 				Debug(format!("aaload"), &self.debug_level, DebugLevel::Info);
-				self.execute_aaload(frame);
-				OpcodeResult::Incr(1)
+				self.execute_aaload(frame)
+			}
+			Some(OperandCode::BaLoad) => {
+				Debug(format!("baload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load(
+					"baload",
+					&[JvmPrimitiveType::Byte, JvmPrimitiveType::Boolean],
+					frame,
+				)
 			}
 			Some(OperandCode::CaLoad) => {
 				Debug(format!("caload"), &self.debug_level, DebugLevel::Info);
-				self.execute_caload(frame);
-				OpcodeResult::Incr(1)
+				self.execute_primitive_array_load("caload", &[JvmPrimitiveType::Char], frame)
+			}
+			Some(OperandCode::SaLoad) => {
+				Debug(format!("saload"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_load("saload", &[JvmPrimitiveType::Short], frame)
 			}
 			Some(OperandCode::Fstore) => {
 				Debug(format!("fstore"), &self.debug_level, DebugLevel::Info);
 				let index = bytes[1];
-				self.execute_fstore(index as usize, frame);
-				OpcodeResult::Incr(2)
+				advance(self.execute_fstore(index as usize, frame), 2)
+			}
+			Some(OperandCode::Istore) => {
+				Debug(format!("istore"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_istore_x(bytes[1] as usize, frame), 2)
 			}
 			Some(OperandCode::Istore_0) => {
 				Debug(format!("istore_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_istore_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_istore_x(0, frame), 1)
 			}
 			Some(OperandCode::Istore_1) => {
 				Debug(format!("istore_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_istore_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_istore_x(1, frame), 1)
 			}
 			Some(OperandCode::Istore_2) => {
 				Debug(format!("istore_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_istore_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_istore_x(2, frame), 1)
 			}
 			Some(OperandCode::Istore_3) => {
 				Debug(format!("istore_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_istore_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_istore_x(3, frame), 1)
+			}
+			Some(OperandCode::Lstore) => {
+				Debug(format!("lstore"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lstore_x(bytes[1] as usize, frame), 2)
+			}
+			Some(OperandCode::Lstore_0) => {
+				Debug(format!("lstore_0"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lstore_x(0, frame), 1)
+			}
+			Some(OperandCode::Lstore_1) => {
+				Debug(format!("lstore_1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lstore_x(1, frame), 1)
+			}
+			Some(OperandCode::Lstore_2) => {
+				Debug(format!("lstore_2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lstore_x(2, frame), 1)
+			}
+			Some(OperandCode::Lstore_3) => {
+				Debug(format!("lstore_3"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lstore_x(3, frame), 1)
 			}
 			Some(OperandCode::Fstore_0) => {
 				Debug(format!("fstore_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_fstore_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fstore_x(0, frame), 1)
 			}
 			Some(OperandCode::Fstore_1) => {
 				Debug(format!("fstore_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_fstore_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fstore_x(1, frame), 1)
 			}
 			Some(OperandCode::Fstore_2) => {
 				Debug(format!("fstore_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_fstore_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fstore_x(2, frame), 1)
 			}
 			Some(OperandCode::Fstore_3) => {
 				Debug(format!("fstore_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_fstore_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fstore_x(3, frame), 1)
+			}
+			Some(OperandCode::Dstore) => {
+				Debug(format!("dstore"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dstore_x(bytes[1] as usize, frame), 2)
+			}
+			Some(OperandCode::Dstore_0) => {
+				Debug(format!("dstore_0"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dstore_x(0, frame), 1)
+			}
+			Some(OperandCode::Dstore_1) => {
+				Debug(format!("dstore_1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dstore_x(1, frame), 1)
+			}
+			Some(OperandCode::Dstore_2) => {
+				Debug(format!("dstore_2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dstore_x(2, frame), 1)
+			}
+			Some(OperandCode::Dstore_3) => {
+				Debug(format!("dstore_3"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dstore_x(3, frame), 1)
+			}
+			Some(OperandCode::Astore) => {
+				Debug(format!("astore"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_astore_x(bytes[1] as usize, frame), 2)
 			}
 			Some(OperandCode::Astore_0) => {
 				Debug(format!("astore_0"), &self.debug_level, DebugLevel::Info);
-				self.execute_astore_x(0, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_astore_x(0, frame), 1)
 			}
 			Some(OperandCode::Astore_1) => {
 				Debug(format!("astore_1"), &self.debug_level, DebugLevel::Info);
-				self.execute_astore_x(1, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_astore_x(1, frame), 1)
 			}
 			Some(OperandCode::Astore_2) => {
 				Debug(format!("astore_2"), &self.debug_level, DebugLevel::Info);
-				self.execute_astore_x(2, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_astore_x(2, frame), 1)
 			}
 			Some(OperandCode::Astore_3) => {
 				Debug(format!("astore_3"), &self.debug_level, DebugLevel::Info);
-				self.execute_astore_x(3, frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_astore_x(3, frame), 1)
+			}
+			Some(OperandCode::IaStore) => {
+				Debug(format!("iastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store("iastore", &[JvmPrimitiveType::Integer], frame)
+			}
+			Some(OperandCode::LaStore) => {
+				Debug(format!("lastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store(
+					"lastore",
+					&[JvmPrimitiveType::LongInteger],
+					frame,
+				)
+			}
+			Some(OperandCode::FaStore) => {
+				Debug(format!("fastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store("fastore", &[JvmPrimitiveType::Float], frame)
+			}
+			Some(OperandCode::DaStore) => {
+				Debug(format!("dastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store("dastore", &[JvmPrimitiveType::Double], frame)
 			}
 			Some(OperandCode::AaStore) => {
 				Debug(format!("aastore"), &self.debug_level, DebugLevel::Info);
-				self.execute_aastore(frame);
-				OpcodeResult::Incr(1)
+				self.execute_aastore(frame)
+			}
+			Some(OperandCode::BaStore) => {
+				Debug(format!("bastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store(
+					"bastore",
+					&[JvmPrimitiveType::Byte, JvmPrimitiveType::Boolean],
+					frame,
+				)
 			}
-
 			Some(OperandCode::CaStore) => {
 				Debug(format!("castore"), &self.debug_level, DebugLevel::Info);
-				self.execute_castore(frame);
-				OpcodeResult::Incr(1)
+				self.execute_primitive_array_store("castore", &[JvmPrimitiveType::Char], frame)
+			}
+			Some(OperandCode::SaStore) => {
+				Debug(format!("sastore"), &self.debug_level, DebugLevel::Info);
+				self.execute_primitive_array_store("sastore", &[JvmPrimitiveType::Short], frame)
 			}
 			Some(OperandCode::Pop) => {
 				Debug(format!("pop"), &self.debug_level, DebugLevel::Info);
-				frame.operand_stack.pop();
-				OpcodeResult::Incr(1)
+				advance(self.pop_slots("pop", 1, frame).map(|_| ()), 1)
+			}
+			Some(OperandCode::Pop2) => {
+				Debug(format!("pop2"), &self.debug_level, DebugLevel::Info);
+				advance(self.pop_slots("pop2", 2, frame).map(|_| ()), 1)
 			}
 			Some(OperandCode::Dup) => {
 				Debug(format!("dup"), &self.debug_level, DebugLevel::Info);
-				/*
-				 * TODO: The type on the stack must be a "category 1
-				 * computational type."
-				 */
-				if let Some(top) = frame.operand_stack.pop() {
-					frame.operand_stack.push(top.clone());
-					frame.operand_stack.push(top);
-				}
-				OpcodeResult::Incr(1)
+				advance(self.execute_dup("dup", 1, 0, frame), 1)
+			}
+			Some(OperandCode::Dup_x1) => {
+				Debug(format!("dup_x1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dup("dup_x1", 1, 1, frame), 1)
+			}
+			Some(OperandCode::Dup_x2) => {
+				Debug(format!("dup_x2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dup("dup_x2", 1, 2, frame), 1)
+			}
+			Some(OperandCode::Dup2) => {
+				Debug(format!("dup2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dup("dup2", 2, 0, frame), 1)
+			}
+			Some(OperandCode::Dup2_x1) => {
+				Debug(format!("dup2_x1"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dup("dup2_x1", 2, 1, frame), 1)
+			}
+			Some(OperandCode::Dup2_x2) => {
+				Debug(format!("dup2_x2"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dup("dup2_x2", 2, 2, frame), 1)
+			}
+			Some(OperandCode::Swap) => {
+				Debug(format!("swap"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_swap(frame), 1)
 			}
 			Some(OperandCode::Iadd) => {
 				Debug(format!("iadd"), &self.debug_level, DebugLevel::Info);
-				self.execute_iadd(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_iadd(frame), 1)
+			}
+			Some(OperandCode::Ladd) => {
+				Debug(format!("ladd"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_ladd(frame), 1)
 			}
 			Some(OperandCode::Fadd) => {
 				Debug(format!("fadd"), &self.debug_level, DebugLevel::Info);
-				self.execute_fadd(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fadd(frame), 1)
+			}
+			Some(OperandCode::Dadd) => {
+				Debug(format!("dadd"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dadd(frame), 1)
+			}
+			Some(OperandCode::Isub) => {
+				Debug(format!("isub"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_isub(frame), 1)
+			}
+			Some(OperandCode::Lsub) => {
+				Debug(format!("lsub"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lsub(frame), 1)
 			}
 			Some(OperandCode::Fsub) => {
 				Debug(format!("fsub"), &self.debug_level, DebugLevel::Info);
-				self.execute_fsub(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fsub(frame), 1)
+			}
+			Some(OperandCode::Dsub) => {
+				Debug(format!("dsub"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dsub(frame), 1)
 			}
 			Some(OperandCode::Imul) => {
 				Debug(format!("imul"), &self.debug_level, DebugLevel::Info);
-				self.execute_imul(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_imul(frame), 1)
+			}
+			Some(OperandCode::Lmul) => {
+				Debug(format!("lmul"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lmul(frame), 1)
 			}
 			Some(OperandCode::Fmul) => {
 				Debug(format!("fmul"), &self.debug_level, DebugLevel::Info);
-				self.execute_fmul(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fmul(frame), 1)
+			}
+			Some(OperandCode::Dmul) => {
+				Debug(format!("dmul"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dmul(frame), 1)
+			}
+			Some(OperandCode::Idiv) => {
+				Debug(format!("idiv"), &self.debug_level, DebugLevel::Info);
+				self.execute_idiv(frame)
+			}
+			Some(OperandCode::Ldiv) => {
+				Debug(format!("ldiv"), &self.debug_level, DebugLevel::Info);
+				self.execute_ldiv(frame)
 			}
 			Some(OperandCode::Fdiv) => {
 				Debug(format!("fdiv"), &self.debug_level, DebugLevel::Info);
-				self.execute_fdiv(frame);
-				OpcodeResult::Incr(1)
+				advance(self.execute_fdiv(frame), 1)
+			}
+			Some(OperandCode::Ddiv) => {
+				Debug(format!("ddiv"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_ddiv(frame), 1)
+			}
+			Some(OperandCode::Irem) => {
+				Debug(format!("irem"), &self.debug_level, DebugLevel::Info);
+				self.execute_irem(frame)
+			}
+			Some(OperandCode::Lrem) => {
+				Debug(format!("lrem"), &self.debug_level, DebugLevel::Info);
+				self.execute_lrem(frame)
+			}
+			Some(OperandCode::Frem) => {
+				Debug(format!("frem"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_frem(frame), 1)
+			}
+			Some(OperandCode::Drem) => {
+				Debug(format!("drem"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_drem(frame), 1)
+			}
+			Some(OperandCode::Iinc) => {
+				Debug(format!("iinc"), &self.debug_level, DebugLevel::Info);
+				advance(
+					self.execute_iinc(bytes[1] as usize, bytes[2] as i8 as i32, frame),
+					3,
+				)
+			}
+			Some(OperandCode::I2l) => {
+				Debug(format!("i2l"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2l(frame), 1)
+			}
+			Some(OperandCode::I2f) => {
+				Debug(format!("i2f"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2f(frame), 1)
+			}
+			Some(OperandCode::I2d) => {
+				Debug(format!("i2d"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2d(frame), 1)
+			}
+			Some(OperandCode::L2i) => {
+				Debug(format!("l2i"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_l2i(frame), 1)
+			}
+			Some(OperandCode::L2f) => {
+				Debug(format!("l2f"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_l2f(frame), 1)
+			}
+			Some(OperandCode::L2d) => {
+				Debug(format!("l2d"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_l2d(frame), 1)
+			}
+			Some(OperandCode::F2i) => {
+				Debug(format!("f2i"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_f2i(frame), 1)
+			}
+			Some(OperandCode::F2l) => {
+				Debug(format!("f2l"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_f2l(frame), 1)
+			}
+			Some(OperandCode::F2d) => {
+				Debug(format!("f2d"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_f2d(frame), 1)
+			}
+			Some(OperandCode::D2i) => {
+				Debug(format!("d2i"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_d2i(frame), 1)
+			}
+			Some(OperandCode::D2l) => {
+				Debug(format!("d2l"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_d2l(frame), 1)
+			}
+			Some(OperandCode::D2f) => {
+				Debug(format!("d2f"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_d2f(frame), 1)
+			}
+			Some(OperandCode::I2b) => {
+				Debug(format!("i2b"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2b(frame), 1)
+			}
+			Some(OperandCode::I2c) => {
+				Debug(format!("i2c"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2c(frame), 1)
+			}
+			Some(OperandCode::I2s) => {
+				Debug(format!("i2s"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_i2s(frame), 1)
 			}
 			cmpop @ Some(OperandCode::If_icmpeq)
 			| cmpop @ Some(OperandCode::If_icmpne)
@@ -518,40 +1830,90 @@ impl JvmThread {
 			Some(OperandCode::Goto) => {
 				Debug(format!("goto"), &self.debug_level, DebugLevel::Info);
 				let branch_target = i16::from_be_bytes([bytes[1], bytes[2]]);
-
-				if branch_target < 0 {
-					OpcodeResult::Decr(branch_target.abs() as usize)
-				} else {
-					OpcodeResult::Incr(branch_target.abs() as usize)
-				}
+				branch_to(branch_target as i32)
+			}
+			Some(OperandCode::If_acmpeq) => {
+				Debug(format!("if_acmpeq"), &self.debug_level, DebugLevel::Info);
+				self.execute_if_acmp(true, &[bytes[1], bytes[2]], frame)
+			}
+			Some(OperandCode::If_acmpne) => {
+				Debug(format!("if_acmpne"), &self.debug_level, DebugLevel::Info);
+				self.execute_if_acmp(false, &[bytes[1], bytes[2]], frame)
+			}
+			Some(OperandCode::Ifnull) => {
+				Debug(format!("ifnull"), &self.debug_level, DebugLevel::Info);
+				self.execute_ifnull(true, &[bytes[1], bytes[2]], frame)
+			}
+			Some(OperandCode::Ifnonnull) => {
+				Debug(format!("ifnonnull"), &self.debug_level, DebugLevel::Info);
+				self.execute_ifnull(false, &[bytes[1], bytes[2]], frame)
+			}
+			Some(OperandCode::Goto_w) => {
+				Debug(format!("goto_w"), &self.debug_level, DebugLevel::Info);
+				let branch_target = i32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+				branch_to(branch_target)
+			}
+			Some(OperandCode::Jsr) => {
+				Debug(format!("jsr"), &self.debug_level, DebugLevel::Info);
+				let branch_target = i16::from_be_bytes([bytes[1], bytes[2]]);
+				self.execute_jsr(3, branch_target as i32, frame)
+			}
+			Some(OperandCode::Jsr_w) => {
+				Debug(format!("jsr_w"), &self.debug_level, DebugLevel::Info);
+				let branch_target = i32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+				self.execute_jsr(5, branch_target, frame)
+			}
+			Some(OperandCode::Ret) => {
+				Debug(format!("ret"), &self.debug_level, DebugLevel::Info);
+				self.execute_ret(bytes[1] as usize, frame)
+			}
+			Some(OperandCode::Tableswitch) => {
+				Debug(format!("tableswitch"), &self.debug_level, DebugLevel::Info);
+				self.execute_tableswitch(bytes, frame)
+			}
+			Some(OperandCode::Lookupswitch) => {
+				Debug(format!("lookupswitch"), &self.debug_level, DebugLevel::Info);
+				self.execute_lookupswitch(bytes, frame)
 			}
 			Some(OperandCode::Ireturn) => {
 				Debug(format!("ireturn"), &self.debug_level, DebugLevel::Info);
-				OpcodeResult::Value(frame.operand_stack.pop().unwrap())
+				self.execute_return("ireturn", Some(JvmPrimitiveType::Integer), frame)
+			}
+			Some(OperandCode::Lreturn) => {
+				Debug(format!("lreturn"), &self.debug_level, DebugLevel::Info);
+				self.execute_return("lreturn", Some(JvmPrimitiveType::LongInteger), frame)
+			}
+			Some(OperandCode::Freturn) => {
+				Debug(format!("freturn"), &self.debug_level, DebugLevel::Info);
+				self.execute_return("freturn", Some(JvmPrimitiveType::Float), frame)
+			}
+			Some(OperandCode::Dreturn) => {
+				Debug(format!("dreturn"), &self.debug_level, DebugLevel::Info);
+				self.execute_return("dreturn", Some(JvmPrimitiveType::Double), frame)
+			}
+			Some(OperandCode::Areturn) => {
+				Debug(format!("areturn"), &self.debug_level, DebugLevel::Info);
+				self.execute_return("areturn", None, frame)
 			}
 			Some(OperandCode::r#Return) => {
 				Debug(format!("return"), &self.debug_level, DebugLevel::Info);
-				OpcodeResult::Value(JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0))
+				OpcodeResult::Return(JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0))
 			}
 			Some(OperandCode::GetStatic) => {
 				Debug(format!("getstatic"), &self.debug_level, DebugLevel::Info);
-				self.execute_getstatic(bytes, frame);
-				OpcodeResult::Incr(3)
+				advance(self.execute_getstatic(bytes, frame), 3)
 			}
 			Some(OperandCode::PutStatic) => {
 				Debug(format!("putstatic"), &self.debug_level, DebugLevel::Info);
-				self.execute_putstatic(bytes, frame);
-				OpcodeResult::Incr(3)
+				advance(self.execute_putstatic(bytes, frame), 3)
 			}
 			Some(OperandCode::GetField) => {
 				Debug(format!("getfield"), &self.debug_level, DebugLevel::Info);
-				self.execute_getfield(((bytes[1] as u16) << 8) | (bytes[2] as u16) as u16, frame);
-				OpcodeResult::Incr(3)
+				self.execute_getfield(((bytes[1] as u16) << 8) | (bytes[2] as u16) as u16, frame)
 			}
 			Some(OperandCode::PutField) => {
 				Debug(format!("putfield"), &self.debug_level, DebugLevel::Info);
-				self.execute_putfield(((bytes[1] as u16) << 8) | (bytes[2] as u16) as u16, frame);
-				OpcodeResult::Incr(3)
+				self.execute_putfield(((bytes[1] as u16) << 8) | (bytes[2] as u16) as u16, frame)
 			}
 			Some(OperandCode::Invokevirtual) => {
 				Debug(
@@ -559,14 +1921,8 @@ impl JvmThread {
 					&self.debug_level,
 					DebugLevel::Info,
 				);
-				/*
-				 * Start by assuming failure.
-				 */
-				let mut pc_incr: usize = 0;
-
 				let invokevirtual_result = self.execute_invokevirtual(bytes, frame);
-				pc_incr = self.handle_invoke_result(invokevirtual_result, frame, 3);
-				OpcodeResult::Incr(pc_incr)
+				self.handle_invoke_result(invokevirtual_result, frame, 3)
 			}
 			Some(OperandCode::Invokespecial) => {
 				Debug(
@@ -574,86 +1930,70 @@ impl JvmThread {
 					&self.debug_level,
 					DebugLevel::Info,
 				);
-				/*
-				 * Start by assuming failure.
-				 */
-				let mut pc_incr: usize = 0;
-
 				let invokespecial_result = self.execute_invokespecial(bytes, frame);
-				pc_incr = self.handle_invoke_result(invokespecial_result, frame, 3);
-				OpcodeResult::Incr(pc_incr)
+				self.handle_invoke_result(invokespecial_result, frame, 3)
 			}
 			Some(OperandCode::Invokestatic) => {
 				Debug(format!("invokestatic"), &self.debug_level, DebugLevel::Info);
+				let invokestatic_result = self.execute_invokestatic(bytes, frame);
+				self.handle_invoke_result(invokestatic_result, frame, 3)
+			}
+			Some(OperandCode::Invokeinterface) => {
+				Debug(
+					format!("invokeinterface"),
+					&self.debug_level,
+					DebugLevel::Info,
+				);
 				/*
-				 * Start by assuming failure.
+				 * The index is followed by a count byte and a zero byte.
 				 */
-				let mut pc_incr: usize = 0;
-
-				let invokestatic_result = self.execute_invokestatic(bytes, frame);
-				pc_incr = self.handle_invoke_result(invokestatic_result, frame, 3);
-				OpcodeResult::Incr(pc_incr)
+				let invokeinterface_result = self.execute_invokeinterface(bytes, frame);
+				self.handle_invoke_result(invokeinterface_result, frame, 5)
+			}
+			Some(OperandCode::Invokedynamic) => {
+				Debug(
+					format!("invokedynamic"),
+					&self.debug_level,
+					DebugLevel::Info,
+				);
+				/*
+				 * The index is followed by two zero bytes.
+				 */
+				let invokedynamic_result = self.execute_invokedynamic(bytes, frame);
+				self.handle_invoke_result(invokedynamic_result, frame, 5)
+			}
+			Some(OperandCode::Athrow) => {
+				Debug(format!("athrow"), &self.debug_level, DebugLevel::Info);
+				self.execute_athrow(frame)
+			}
+			Some(OperandCode::Checkcast) => {
+				Debug(format!("checkcast"), &self.debug_level, DebugLevel::Info);
+				self.execute_checkcast(bytes, frame)
+			}
+			Some(OperandCode::Instanceof) => {
+				Debug(format!("instanceof"), &self.debug_level, DebugLevel::Info);
+				self.execute_instanceof(bytes, frame)
+			}
+			Some(OperandCode::Monitorenter) => {
+				Debug(format!("monitorenter"), &self.debug_level, DebugLevel::Info);
+				self.execute_monitor("monitorenter", true, frame)
+			}
+			Some(OperandCode::Monitorexit) => {
+				Debug(format!("monitorexit"), &self.debug_level, DebugLevel::Info);
+				self.execute_monitor("monitorexit", false, frame)
 			}
 			Some(OperandCode::New) => {
 				Debug(format!("New"), &self.debug_level, DebugLevel::Info);
-				if let Some(object) = self.execute_new(bytes, frame) {
-					frame.operand_stack.push(object);
-					Debug(
-						format!("frame after new: {}", frame),
-						&self.debug_level,
-						DebugLevel::Info,
-					);
-				}
-				OpcodeResult::Incr(3)
+				self.execute_new(bytes, frame)
 			}
 			Some(OperandCode::NewArray) => {
 				Debug(format!("NewArray"), &self.debug_level, DebugLevel::Info);
-				let newarray_len = frame.operand_stack.pop();
 				let newarray_type = bytes[1];
 
-				// First, make sure that there is a reasonable array size on the stack.
-				if let Some(newarray_len) = newarray_len {
-					match newarray_len {
-						JvmValue::Primitive(JvmPrimitiveType::Integer, _, len, _) => {
-							match JvmArrayType::from_u8(newarray_type) {
-								Some(JvmArrayType::Char) /* Character */ => {
-									frame.operand_stack.push(
-										JvmValue::Reference(
-											JvmReferenceType::Array(Rc::new(JvmType::Primitive(JvmPrimitiveType::Char)), len),
-											JvmReferenceTargetType::Array(Arc::new(Mutex::new(JvmArray::new(len as usize)))),
-											0));
-								},
-								Some(_) => {
-									FatalError::new(FatalErrorType::NotImplemented(
-										format!("Cannot handle new arrays with that type")))
-										.call();
-								}
-								_ => {
-									// We were asked to make an array for an invalid type
-									FatalError::new(FatalErrorType::WrongType(
-										format!("newarray"),
-										format!("JvmArrayType")
-									))
-									.call();
-								}
-							}
-						}
-						_ => {
-							// The value on the stack for the len of the array should be an integer
-							FatalError::new(FatalErrorType::WrongType(
-								format!("newarray"),
-								format!("integer"),
-							))
-							.call();
-						}
-					}
-				} else {
-					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-						"newarray"
-					)))
-					.call();
+				match self.pop_int_operand("newarray", frame) {
+					Ok(count) => self.execute_newarray(newarray_type, count, frame),
+					Err(error) => OpcodeResult::Error(error),
 				}
-				OpcodeResult::Incr(2)
 			}
 			Some(OperandCode::ANewArray) => {
 				Debug(format!("ANewArray"), &self.debug_level, DebugLevel::Info);
@@ -661,7 +2001,7 @@ impl JvmThread {
 				if let Some(array_size) = frame.operand_stack.pop() {
 					if let JvmValue::Primitive(JvmPrimitiveType::Integer, _, count, _) = array_size
 					{
-						let res = self.execute_anewarray(type_index, count, frame);
+						let res = self.execute_anewarray(type_index, count as i32, frame);
 						Debug(
 							format!("frame after new: {}", frame),
 							&self.debug_level,
@@ -670,20 +2010,22 @@ impl JvmThread {
 						res
 					} else {
 						// Wrong type for the count
-						FatalError::new(FatalErrorType::WrongType(
-							format!("anewarray"),
-							format!("integer primitive"),
-						))
-						.call();
-						OpcodeResult::Incr(3)
+						OpcodeResult::Error(
+							FatalError::new(FatalErrorType::WrongType(
+								format!("anewarray"),
+								format!("integer primitive"),
+							))
+							.into_error(),
+						)
 					}
 				} else {
 					// Missing a count on the stack!
-					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-						"anewarray"
-					)))
-					.call();
-					OpcodeResult::Incr(3)
+					OpcodeResult::Error(
+						FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+							"anewarray"
+						)))
+						.into_error(),
+					)
 				}
 			}
 			Some(OperandCode::ArrayLength) => {
@@ -694,54 +2036,70 @@ impl JvmThread {
 					DebugLevel::Info,
 				);
 
-				if let Some(array_ref) = frame.operand_stack.pop() {
-					if let JvmValue::Reference(
+				match frame.operand_stack.pop() {
+					Some(JvmValue::Reference(
 						JvmReferenceType::Array(_, _),
 						JvmReferenceTargetType::Array(array),
 						_,
-					) = array_ref
-					{
+					)) => {
 						// Try to lock the array.
 						if let Ok(array) = array.lock() {
-							let array_length = array.dimension();
-							frame.operand_stack.push(JvmValue::Primitive(
-								JvmPrimitiveType::Integer,
-								0,
-								array_length as u32,
-								0,
-							));
+							advance(
+								frame.push_int(array.len() as i32).map_err(|error| {
+									error.into_fatal_error("arraylength").into_error()
+								}),
+								1,
+							)
+						} else {
+							OpcodeResult::Error(
+								FatalError::new(FatalErrorType::CouldNotLock(
+									format!("Array."),
+									format!("arraylength"),
+								))
+								.into_error(),
+							)
 						}
-						Debug(
-							format!("frame after new: {}", frame),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
-					} else {
-						// Wrong type for the count
+					}
+					Some(ref array_ref) if array_ref.is_null() => {
+						null_pointer(format!("Cannot read the array length"))
+					}
+					Some(_) => OpcodeResult::Error(
 						FatalError::new(FatalErrorType::WrongType(
 							format!("arraylength"),
 							format!("reference to an array"),
 						))
-						.call();
+						.into_error(),
+					),
+					None => {
+						// Missing an array on the stack!
+						OpcodeResult::Error(
+							FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+								"arraylength"
+							)))
+							.into_error(),
+						)
 					}
-				} else {
-					// Missing a count on the stack!
-					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-						"anewarray"
-					)))
-					.call();
 				}
-
-				OpcodeResult::Incr(1)
 			}
-			Some(OperandCode::Fcmpgt) | Some(OperandCode::Fcmplt) => {
-				Debug(
-					format!("Fcmpgt or Fcmplt"),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				self.execute_fcmp(frame);
-				OpcodeResult::Incr(1)
+			Some(OperandCode::Lcmp) => {
+				Debug(format!("lcmp"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_lcmp(frame), 1)
+			}
+			Some(OperandCode::Fcmpl) => {
+				Debug(format!("fcmpl"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_fcmp(-1, frame), 1)
+			}
+			Some(OperandCode::Fcmpg) => {
+				Debug(format!("fcmpg"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_fcmp(1, frame), 1)
+			}
+			Some(OperandCode::Dcmpl) => {
+				Debug(format!("dcmpl"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dcmp(-1, frame), 1)
+			}
+			Some(OperandCode::Dcmpg) => {
+				Debug(format!("dcmpg"), &self.debug_level, DebugLevel::Info);
+				advance(self.execute_dcmp(1, frame), 1)
 			}
 			Some(OperandCode::Ifeq) => {
 				Debug(format!("Ifeq"), &self.debug_level, DebugLevel::Info);
@@ -775,20 +2133,92 @@ impl JvmThread {
 					frame,
 				)
 			}
-			_ => {
-				FatalError::new(FatalErrorType::NotImplemented(format!("0x{:x}", opcode))).call();
-				OpcodeResult::Incr(0)
+			Some(OperandCode::Wide) => {
+				Debug(format!("wide"), &self.debug_level, DebugLevel::Info);
+				self.execute_wide(bytes, frame)
 			}
+			Some(OperandCode::Multianewarray) => {
+				Debug(
+					format!("multianewarray"),
+					&self.debug_level,
+					DebugLevel::Info,
+				);
+				self.execute_multianewarray(bytes, frame)
+			}
+			_ => OpcodeResult::Error(
+				FatalError::new(FatalErrorType::UnimplementedOpcode(opcode, frame.pc)).into_error(),
+			),
+		}
+	}
+
+	/*
+	 * Pop the return value off of the returning frame. The caller
+	 * (see complete_invoke) pushes it on to the invoking frame's
+	 * operand stack. expected_type is
+	 * None for areturn. Booleans, bytes, chars and shorts are
+	 * returned by ireturn.
+	 */
+	fn execute_return(
+		&mut self,
+		instruction: &str,
+		expected_type: Option<JvmPrimitiveType>,
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let top = frame.operand_stack.pop();
+		let matches = match (&top, &expected_type) {
+			(Some(JvmValue::Reference(_, _, _)), None) => true,
+			(Some(JvmValue::Primitive(pt, _, _, _)), Some(JvmPrimitiveType::Integer)) => match pt {
+				JvmPrimitiveType::Integer
+				| JvmPrimitiveType::Boolean
+				| JvmPrimitiveType::Byte
+				| JvmPrimitiveType::Char
+				| JvmPrimitiveType::Short => true,
+				_ => false,
+			},
+			(Some(JvmValue::Primitive(pt, _, _, _)), Some(expected_type)) => pt == expected_type,
+			_ => false,
+		};
+		if let (true, Some(value)) = (matches, top) {
+			OpcodeResult::Return(value)
+		} else {
+			OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					instruction.to_string(),
+					match expected_type {
+						Some(expected_type) => format!("{}", expected_type),
+						None => format!("Reference"),
+					},
+				))
+				.into_error(),
+			)
 		}
 	}
 
 	fn handle_invoke_result(
 		&self,
-		result: Option<OpcodeResult>,
+		result: OpcodeResult,
 		frame: &mut Frame,
 		step: usize,
-	) -> usize {
-		if let Some(OpcodeResult::Value(tv)) = result {
+	) -> OpcodeResult {
+		/*
+		 * An exception raised by the invoke itself (e.g., a null
+		 * receiver) or one that the invoked method did not catch is
+		 * the invoking method's to handle.
+		 */
+		match result {
+			OpcodeResult::Exception(exception, message) => {
+				return OpcodeResult::Exception(exception, message);
+			}
+			OpcodeResult::Throw(exception) => return OpcodeResult::Throw(exception),
+			OpcodeResult::Error(error) => return OpcodeResult::Error(error),
+			/*
+			 * A method that runs in a frame of its own has not
+			 * returned yet (see complete_invoke).
+			 */
+			OpcodeResult::Invoke(method, frame) => return OpcodeResult::Invoke(method, frame),
+			_ => (),
+		};
+		if let OpcodeResult::Return(tv) = result {
 			/*
 			 * Push the result of the invocation onto
 			 * the operand stack. Do not push anything
@@ -822,9 +2252,9 @@ impl JvmThread {
 					frame.operand_stack.push(tv);
 				}
 			}
-			return step;
+			return OpcodeResult::Incr(step);
 		}
-		return 0;
+		OpcodeResult::Error(FatalError::new(FatalErrorType::MethodResolutionFailed).into_error())
 	}
 	fn execute_if(
 		&mut self,
@@ -841,6 +2271,9 @@ impl JvmThread {
 		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _)) =
 			frame.operand_stack.pop()
 		{
+			/*
+			 * The comparison is signed.
+			 */
 			let ivalue = i32::from_le_bytes(value.to_le_bytes());
 			let mut take_branch = false;
 			match comparison {
@@ -876,232 +2309,847 @@ impl JvmThread {
 				}
 			};
 			if take_branch {
-				if branch_offset < 0 {
-					OpcodeResult::Decr(branch_offset.abs() as usize)
-				} else {
-					OpcodeResult::Incr(branch_offset.abs() as usize)
-				}
+				branch_to(branch_offset as i32)
 			} else {
 				// Not taking the branch, so we just start at the next instruction after this one!
 				OpcodeResult::Incr(3)
 			}
 		} else {
-			FatalError::new(FatalErrorType::WrongType(format!("1"), format!("2"))).call();
-			OpcodeResult::Value(JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0))
+			OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					format!("if<cond>"),
+					format!("Integer"),
+				))
+				.into_error(),
+			)
 		}
 	}
 
-	fn execute_fsub(&mut self, frame: &mut Frame) {
-		Debug(
-			format!("fsub frame (pre): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op2, _)) =
-			frame.operand_stack.pop()
-		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op1, _)) =
-				frame.operand_stack.pop()
-			{
-				let fop1 = f32::from_le_bytes(op1.to_le_bytes());
-				let fop2 = f32::from_le_bytes(op2.to_le_bytes());
-				let res = fop1 - fop2;
-				let res_value = u32::from_le_bytes(res.to_le_bytes());
-
-				frame.operand_stack.push(JvmValue::Primitive(
-					JvmPrimitiveType::Float,
-					0,
-					res_value,
-					0,
-				));
+	/*
+	 * Implement ifnull (when `null` is true) and ifnonnull (when
+	 * `null` is false).
+	 */
+	fn execute_ifnull(
+		&mut self,
+		null: bool,
+		branch_bytes: &[u8; 2],
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let branch_offset = i16::from_be_bytes(branch_bytes.clone());
+		match frame.pop_reference() {
+			Ok(reference) => {
+				if reference.is_null() == null {
+					branch_to(branch_offset as i32)
+				} else {
+					OpcodeResult::Incr(3)
+				}
+			}
+			Err(error) => {
+				OpcodeResult::Error(error.into_fatal_error("ifnull/ifnonnull").into_error())
 			}
 		}
-		Debug(
-			format!("fsub frame (post): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
 	}
 
-	fn execute_fdiv(&mut self, frame: &mut Frame) {
-		Debug(
-			format!("fdiv frame (pre): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op2, _)) =
-			frame.operand_stack.pop()
-		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op1, _)) =
-				frame.operand_stack.pop()
-			{
-				let fop1 = f32::from_le_bytes(op1.to_le_bytes());
-				let fop2 = f32::from_le_bytes(op2.to_le_bytes());
-				let res = fop1 / fop2;
-				let res_value = u32::from_le_bytes(res.to_le_bytes());
-
-				frame.operand_stack.push(JvmValue::Primitive(
-					JvmPrimitiveType::Float,
-					0,
-					res_value,
-					0,
-				));
+	/*
+	 * Implement if_acmpeq (when `equal` is true) and if_acmpne (when
+	 * `equal` is false). Two references are equal when both are null
+	 * or both refer to the same object (by identity) or array.
+	 */
+	fn execute_if_acmp(
+		&mut self,
+		equal: bool,
+		branch_bytes: &[u8; 2],
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let branch_offset = i16::from_be_bytes(branch_bytes.clone());
+		let value2 = match frame.pop_reference() {
+			Ok(value) => value,
+			Err(error) => {
+				return OpcodeResult::Error(
+					error.into_fatal_error("if_acmpeq/if_acmpne").into_error(),
+				)
+			}
+		};
+		let value1 = match frame.pop_reference() {
+			Ok(value) => value,
+			Err(error) => {
+				return OpcodeResult::Error(
+					error.into_fatal_error("if_acmpeq/if_acmpne").into_error(),
+				)
 			}
+		};
+		/*
+		 * Two references are the same when they refer to the same object
+		 * (or array), which is what their identities say. null is only
+		 * the same as null, and Class mirrors are the same when they
+		 * mirror the same class.
+		 */
+		let same = match (&value1, &value2) {
+			(
+				JvmValue::Reference(_, JvmReferenceTargetType::Null, _),
+				JvmValue::Reference(_, JvmReferenceTargetType::Null, _),
+			) => true,
+			(
+				JvmValue::Reference(_, class1 @ JvmReferenceTargetType::Class(_), _),
+				JvmValue::Reference(_, class2 @ JvmReferenceTargetType::Class(_), _),
+			) => class1 == class2,
+			_ => match (value1.identity(), value2.identity()) {
+				(Some(identity1), Some(identity2)) => identity1 == identity2,
+				_ => false,
+			},
+		};
+		if same == equal {
+			branch_to(branch_offset as i32)
+		} else {
+			OpcodeResult::Incr(3)
 		}
-		Debug(
-			format!("fdiv frame (post): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
 	}
 
-	fn execute_fmul(&mut self, frame: &mut Frame) {
+	/*
+	 * Pop the two float operands of a binary float operation. They
+	 * are returned in the order that they were pushed.
+	 */
+	fn pop_float_operands(
+		&self,
+		instruction: &str,
+		frame: &mut Frame,
+	) -> Result<(f32, f32), JvmError> {
+		frame
+			.pop_float()
+			.and_then(|value2| Ok((frame.pop_float()?, value2)))
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	fn execute_fsub(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Fsub", frame)?;
+		frame
+			.push_float(value1 - value2)
+			.map_err(|error| error.into_fatal_error("Fsub").into_error())
+	}
+
+	fn execute_fdiv(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Fdiv", frame)?;
+		frame
+			.push_float(value1 / value2)
+			.map_err(|error| error.into_fatal_error("Fdiv").into_error())
+	}
+
+	fn execute_frem(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Frem", frame)?;
+		/*
+		 * frem is not the IEEE 754 remainder: like C's fmod, the
+		 * result takes the sign of the dividend and comes from
+		 * truncating division. Rust's % has exactly that behavior.
+		 */
+		frame
+			.push_float(value1 % value2)
+			.map_err(|error| error.into_fatal_error("Frem").into_error())
+	}
+
+	fn execute_fmul(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Fmul", frame)?;
+		frame
+			.push_float(value1 * value2)
+			.map_err(|error| error.into_fatal_error("Fmul").into_error())
+	}
+
+	fn execute_fadd(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Fadd", frame)?;
+		frame
+			.push_float(value1 + value2)
+			.map_err(|error| error.into_fatal_error("Fadd").into_error())
+	}
+
+	/*
+	 * When either value is NaN the comparison is unordered and the
+	 * result is nan_result: -1 for fcmpl and 1 for fcmpg.
+	 */
+	fn execute_fcmp(&mut self, nan_result: i32, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_float_operands("Fcmp", frame)?;
+		frame
+			.push_int(floating_comparison(
+				value1 as f64,
+				value2 as f64,
+				nan_result,
+			))
+			.map_err(|error| error.into_fatal_error("Fcmp").into_error())?;
 		Debug(
-			format!("fmul frame (pre): {}", frame),
+			format!("fcmp frame (post): {}", frame),
 			&self.debug_level,
 			DebugLevel::Info,
 		);
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op2, _)) =
-			frame.operand_stack.pop()
-		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op1, _)) =
-				frame.operand_stack.pop()
-			{
-				let fop1 = f32::from_le_bytes(op1.to_le_bytes());
-				let fop2 = f32::from_le_bytes(op2.to_le_bytes());
-				let res = fop1 * fop2;
-				let res_value = u32::from_le_bytes(res.to_le_bytes());
+		Ok(())
+	}
 
-				frame.operand_stack.push(JvmValue::Primitive(
-					JvmPrimitiveType::Float,
-					0,
-					res_value,
-					0,
-				));
+	/*
+	 * See execute_fcmp: dcmpl and dcmpg differ only in their NaN result.
+	 */
+	fn execute_dcmp(&mut self, nan_result: i32, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Dcmp", frame)?;
+		frame
+			.push_int(floating_comparison(value1, value2, nan_result))
+			.map_err(|error| error.into_fatal_error("Dcmp").into_error())?;
+		Ok(())
+	}
+
+	fn execute_tableswitch(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let operands = switch_operands_index(frame.pc);
+		let (default, low, high) = match (
+			read_switch_i32(bytes, operands, "tableswitch"),
+			read_switch_i32(bytes, operands + 4, "tableswitch"),
+			read_switch_i32(bytes, operands + 8, "tableswitch"),
+		) {
+			(Ok(default), Ok(low), Ok(high)) => (default, low, high),
+			(Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+				return OpcodeResult::Error(error)
 			}
+		};
+
+		/*
+		 * There is a jump offset for every index from low to high, and
+		 * all of them have to be in the code.
+		 */
+		if low > high {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::VerifyError(format!(
+					"the low ({}) of tableswitch is greater than its high ({})",
+					low, high
+				)))
+				.into_error(),
+			);
 		}
-		Debug(
-			format!("fmul frame (post): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
+		let offsets = (high as i64 - low as i64 + 1) as usize;
+		if offsets > (bytes.len().saturating_sub(operands + 12)) / 4 {
+			return OpcodeResult::Error(truncated_switch("tableswitch"));
+		}
+
+		let index = match frame.pop_int() {
+			Ok(index) => index,
+			Err(error) => {
+				return OpcodeResult::Error(error.into_fatal_error("tableswitch").into_error())
+			}
+		};
+
+		let offset = if index < low || index > high {
+			Ok(default)
+		} else {
+			read_switch_i32(
+				bytes,
+				operands + 12 + 4 * index.wrapping_sub(low) as u32 as usize,
+				"tableswitch",
+			)
+		};
+		match offset {
+			Ok(offset) => branch_to(offset),
+			Err(error) => OpcodeResult::Error(error),
+		}
+	}
+
+	fn execute_lookupswitch(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let operands = switch_operands_index(frame.pc);
+		let (default, npairs) = match (
+			read_switch_i32(bytes, operands, "lookupswitch"),
+			read_switch_i32(bytes, operands + 4, "lookupswitch"),
+		) {
+			(Ok(default), Ok(npairs)) => (default, npairs),
+			(Err(error), _) | (_, Err(error)) => return OpcodeResult::Error(error),
+		};
+
+		/*
+		 * Every one of the npairs match-offset pairs has to be in the
+		 * code.
+		 */
+		let pairs = operands + 8;
+		if npairs < 0 {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::VerifyError(format!(
+					"the npairs of lookupswitch is negative ({})",
+					npairs
+				)))
+				.into_error(),
+			);
+		}
+		if npairs as usize > bytes.len().saturating_sub(pairs) / 8 {
+			return OpcodeResult::Error(truncated_switch("lookupswitch"));
+		}
+
+		let key = match frame.pop_int() {
+			Ok(key) => key,
+			Err(error) => {
+				return OpcodeResult::Error(error.into_fatal_error("lookupswitch").into_error())
+			}
+		};
+
+		/*
+		 * The pairs are sorted by their match value.
+		 */
+		let (mut low, mut high) = (0usize, npairs as usize);
+		while low < high {
+			let middle = low + (high - low) / 2;
+			let pair_match = match read_switch_i32(bytes, pairs + 8 * middle, "lookupswitch") {
+				Ok(pair_match) => pair_match,
+				Err(error) => return OpcodeResult::Error(error),
+			};
+			if pair_match == key {
+				return match read_switch_i32(bytes, pairs + 8 * middle + 4, "lookupswitch") {
+					Ok(offset) => branch_to(offset),
+					Err(error) => OpcodeResult::Error(error),
+				};
+			} else if pair_match < key {
+				low = middle + 1;
+			} else {
+				high = middle;
+			}
+		}
+		branch_to(default)
 	}
 
-	fn execute_fadd(&mut self, frame: &mut Frame) {
+	fn execute_lcmp(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_long_operands("Lcmp", frame)?;
+		frame
+			.push_int(if value1 > value2 {
+				1
+			} else if value1 == value2 {
+				0
+			} else {
+				-1
+			})
+			.map_err(|error| error.into_fatal_error("Lcmp").into_error())?;
+		Ok(())
+	}
+
+	fn execute_iadd(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
 		Debug(
-			format!("fadd frame (pre): {}", frame),
+			format!("iadd frame: {}", frame),
 			&self.debug_level,
 			DebugLevel::Info,
 		);
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op2, _)) =
+		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value2, _)) =
 			frame.operand_stack.pop()
 		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, op1, _)) =
+			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value1, _)) =
 				frame.operand_stack.pop()
 			{
-				let fop1 = f32::from_le_bytes(op1.to_le_bytes());
-				let fop2 = f32::from_le_bytes(op2.to_le_bytes());
-				let res = fop1 + fop2;
-				let res_value = u32::from_le_bytes(res.to_le_bytes());
-
+				/*
+				 * The result is the low 32 bits of the true result; overflow
+				 * is not an error.
+				 */
+				let res = (value1 as i32).wrapping_add(value2 as i32);
 				frame.operand_stack.push(JvmValue::Primitive(
-					JvmPrimitiveType::Float,
+					JvmPrimitiveType::Integer,
 					0,
-					res_value,
+					res as u32,
 					0,
 				));
+			} else {
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					"Iadd".to_string(),
+					"Integer".to_string(),
+				))
+				.into_error());
 			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				"Iadd".to_string(),
+				"Integer".to_string(),
+			))
+			.into_error());
 		}
-		Debug(
-			format!("fadd frame (post): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
+		Ok(())
 	}
 
-	fn execute_fcmp(&mut self, frame: &mut Frame) {
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, val2, _)) =
+	fn execute_isub(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value2, _)) =
 			frame.operand_stack.pop()
 		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Float, _, val1, _)) =
+			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value1, _)) =
 				frame.operand_stack.pop()
 			{
-				let fval1 = f32::from_le_bytes(val1.to_le_bytes());
-				let fval2 = f32::from_le_bytes(val2.to_le_bytes());
-				let mut res = 0i32;
-				if fval1 > fval2 {
-					res = 1i32;
-				} else if fval1 == fval2 {
-					res = 0i32;
-				} else if fval1 < fval2 {
-					res = -1i32;
-				}
-
+				let res = (value1 as i32).wrapping_sub(value2 as i32);
 				frame.operand_stack.push(JvmValue::Primitive(
 					JvmPrimitiveType::Integer,
 					0,
-					u32::from_le_bytes(res.to_le_bytes()),
+					res as u32,
 					0,
 				));
+			} else {
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					"Isub".to_string(),
+					"Integer".to_string(),
+				))
+				.into_error());
 			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				"Isub".to_string(),
+				"Integer".to_string(),
+			))
+			.into_error());
 		}
-		Debug(
-			format!("fcmp frame (post): {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
+		Ok(())
 	}
 
-	fn execute_iadd(&mut self, frame: &mut Frame) {
-		Debug(
-			format!("iadd frame: {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, _op1, _)) =
+	fn execute_imul(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value2, _)) =
 			frame.operand_stack.pop()
 		{
-			let op1 = _op1 as i32;
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, _op2, _)) =
+			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value1, _)) =
 				frame.operand_stack.pop()
 			{
-				let op2 = _op2 as i32;
+				let res = (value1 as i32).wrapping_mul(value2 as i32);
 				frame.operand_stack.push(JvmValue::Primitive(
 					JvmPrimitiveType::Integer,
 					0,
-					(op1 + op2) as u32,
+					res as u32,
 					0,
 				));
 			} else {
-				FatalError::new(FatalErrorType::WrongType(
-					"Iadd".to_string(),
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					"Imul".to_string(),
 					"Integer".to_string(),
 				))
-				.call();
+				.into_error());
 			}
 		} else {
-			FatalError::new(FatalErrorType::WrongType(
-				"Iadd".to_string(),
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				"Imul".to_string(),
 				"Integer".to_string(),
 			))
-			.call();
+			.into_error());
+		}
+		Ok(())
+	}
+
+	fn execute_idiv(&mut self, frame: &mut Frame) -> OpcodeResult {
+		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value2, _)) =
+			frame.operand_stack.pop()
+		{
+			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value1, _)) =
+				frame.operand_stack.pop()
+			{
+				if value2 == 0 {
+					return OpcodeResult::Exception(format!("java/lang/ArithmeticException"), None);
+				}
+				/*
+				 * The one overflow case (the most negative int divided
+				 * by -1) results in the dividend.
+				 */
+				let res = (value1 as i32).wrapping_div(value2 as i32);
+				frame.operand_stack.push(JvmValue::Primitive(
+					JvmPrimitiveType::Integer,
+					0,
+					res as u32,
+					0,
+				));
+			} else {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						"Idiv".to_string(),
+						"Integer".to_string(),
+					))
+					.into_error(),
+				);
+			}
+		} else {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					"Idiv".to_string(),
+					"Integer".to_string(),
+				))
+				.into_error(),
+			);
 		}
+		OpcodeResult::Incr(1)
 	}
-	fn execute_imul(&mut self, frame: &mut Frame) {
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, op1, _)) =
+
+	fn execute_irem(&mut self, frame: &mut Frame) -> OpcodeResult {
+		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value2, _)) =
 			frame.operand_stack.pop()
 		{
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, op2, _)) =
+			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value1, _)) =
 				frame.operand_stack.pop()
 			{
+				if value2 == 0 {
+					return OpcodeResult::Exception(format!("java/lang/ArithmeticException"), None);
+				}
+				let res = (value1 as i32).wrapping_rem(value2 as i32);
 				frame.operand_stack.push(JvmValue::Primitive(
 					JvmPrimitiveType::Integer,
 					0,
-					op1 * op2,
+					res as u32,
 					0,
 				));
+			} else {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						"Irem".to_string(),
+						"Integer".to_string(),
+					))
+					.into_error(),
+				);
+			}
+		} else {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					"Irem".to_string(),
+					"Integer".to_string(),
+				))
+				.into_error(),
+			);
+		}
+		OpcodeResult::Incr(1)
+	}
+
+	/*
+	 * Pop the two long operands of a binary long operation. They
+	 * are returned in the order that they were pushed.
+	 */
+	fn pop_long_operands(
+		&self,
+		instruction: &str,
+		frame: &mut Frame,
+	) -> Result<(i64, i64), JvmError> {
+		frame
+			.pop_long()
+			.and_then(|value2| Ok((frame.pop_long()?, value2)))
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	fn execute_ladd(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_long_operands("Ladd", frame)?;
+		frame
+			.push_long(value1.wrapping_add(value2))
+			.map_err(|error| error.into_fatal_error("Ladd").into_error())?;
+		Ok(())
+	}
+
+	fn execute_lsub(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_long_operands("Lsub", frame)?;
+		frame
+			.push_long(value1.wrapping_sub(value2))
+			.map_err(|error| error.into_fatal_error("Lsub").into_error())?;
+		Ok(())
+	}
+
+	fn execute_lmul(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_long_operands("Lmul", frame)?;
+		frame
+			.push_long(value1.wrapping_mul(value2))
+			.map_err(|error| error.into_fatal_error("Lmul").into_error())?;
+		Ok(())
+	}
+
+	fn execute_ldiv(&mut self, frame: &mut Frame) -> OpcodeResult {
+		let (value1, value2) = match self.pop_long_operands("Ldiv", frame) {
+			Ok(operands) => operands,
+			Err(error) => return OpcodeResult::Error(error),
+		};
+		if value2 == 0 {
+			return OpcodeResult::Exception(format!("java/lang/ArithmeticException"), None);
+		}
+		advance(
+			frame
+				.push_long(value1.wrapping_div(value2))
+				.map_err(|error| error.into_fatal_error("Ldiv").into_error()),
+			1,
+		)
+	}
+
+	fn execute_lrem(&mut self, frame: &mut Frame) -> OpcodeResult {
+		let (value1, value2) = match self.pop_long_operands("Lrem", frame) {
+			Ok(operands) => operands,
+			Err(error) => return OpcodeResult::Error(error),
+		};
+		if value2 == 0 {
+			return OpcodeResult::Exception(format!("java/lang/ArithmeticException"), None);
+		}
+		advance(
+			frame
+				.push_long(value1.wrapping_rem(value2))
+				.map_err(|error| error.into_fatal_error("Lrem").into_error()),
+			1,
+		)
+	}
+
+	/*
+	 * Pop the two double operands of a binary double operation. They
+	 * are returned in the order that they were pushed.
+	 */
+	fn pop_double_operands(
+		&self,
+		instruction: &str,
+		frame: &mut Frame,
+	) -> Result<(f64, f64), JvmError> {
+		frame
+			.pop_double()
+			.and_then(|value2| Ok((frame.pop_double()?, value2)))
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	/*
+	 * The double operations follow IEEE 754: there is no trapping on
+	 * division by zero and NaN propagates, just as with Rust's f64.
+	 */
+	fn execute_dadd(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Dadd", frame)?;
+		frame
+			.push_double(value1 + value2)
+			.map_err(|error| error.into_fatal_error("Dadd").into_error())?;
+		Ok(())
+	}
+
+	fn execute_dsub(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Dsub", frame)?;
+		frame
+			.push_double(value1 - value2)
+			.map_err(|error| error.into_fatal_error("Dsub").into_error())?;
+		Ok(())
+	}
+
+	fn execute_dmul(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Dmul", frame)?;
+		frame
+			.push_double(value1 * value2)
+			.map_err(|error| error.into_fatal_error("Dmul").into_error())?;
+		Ok(())
+	}
+
+	fn execute_ddiv(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Ddiv", frame)?;
+		frame
+			.push_double(value1 / value2)
+			.map_err(|error| error.into_fatal_error("Ddiv").into_error())?;
+		Ok(())
+	}
+
+	fn execute_drem(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let (value1, value2) = self.pop_double_operands("Drem", frame)?;
+		frame
+			.push_double(value1 % value2)
+			.map_err(|error| error.into_fatal_error("Drem").into_error())?;
+		Ok(())
+	}
+
+	fn pop_int_operand(&self, instruction: &str, frame: &mut Frame) -> Result<i32, JvmError> {
+		frame
+			.pop_int()
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	fn pop_long_operand(&self, instruction: &str, frame: &mut Frame) -> Result<i64, JvmError> {
+		frame
+			.pop_long()
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	fn pop_float_operand(&self, instruction: &str, frame: &mut Frame) -> Result<f32, JvmError> {
+		frame
+			.pop_float()
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	fn pop_double_operand(&self, instruction: &str, frame: &mut Frame) -> Result<f64, JvmError> {
+		frame
+			.pop_double()
+			.map_err(|error| error.into_fatal_error(instruction).into_error())
+	}
+
+	/*
+	 * Rust's float-to-integer casts saturate and turn NaN into 0,
+	 * exactly as f2i, f2l, d2i and d2l require. The remaining
+	 * conversions round to nearest, as the JVM does.
+	 */
+	fn execute_i2l(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2l", frame)?;
+		frame
+			.push_long(value as i64)
+			.map_err(|error| error.into_fatal_error("I2l").into_error())?;
+		Ok(())
+	}
+
+	fn execute_i2f(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2f", frame)?;
+		frame
+			.push_float(value as f32)
+			.map_err(|error| error.into_fatal_error("I2f").into_error())?;
+		Ok(())
+	}
+
+	fn execute_i2d(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2d", frame)?;
+		frame
+			.push_double(value as f64)
+			.map_err(|error| error.into_fatal_error("I2d").into_error())?;
+		Ok(())
+	}
+
+	fn execute_l2i(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_long_operand("L2i", frame)?;
+		frame
+			.push_int(value as i32)
+			.map_err(|error| error.into_fatal_error("L2i").into_error())?;
+		Ok(())
+	}
+
+	fn execute_l2f(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_long_operand("L2f", frame)?;
+		frame
+			.push_float(value as f32)
+			.map_err(|error| error.into_fatal_error("L2f").into_error())?;
+		Ok(())
+	}
+
+	fn execute_l2d(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_long_operand("L2d", frame)?;
+		frame
+			.push_double(value as f64)
+			.map_err(|error| error.into_fatal_error("L2d").into_error())?;
+		Ok(())
+	}
+
+	fn execute_f2i(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_float_operand("F2i", frame)?;
+		frame
+			.push_int(value as i32)
+			.map_err(|error| error.into_fatal_error("F2i").into_error())?;
+		Ok(())
+	}
+
+	fn execute_f2l(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_float_operand("F2l", frame)?;
+		frame
+			.push_long(value as i64)
+			.map_err(|error| error.into_fatal_error("F2l").into_error())?;
+		Ok(())
+	}
+
+	fn execute_f2d(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_float_operand("F2d", frame)?;
+		frame
+			.push_double(value as f64)
+			.map_err(|error| error.into_fatal_error("F2d").into_error())?;
+		Ok(())
+	}
+
+	fn execute_d2i(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_double_operand("D2i", frame)?;
+		frame
+			.push_int(value as i32)
+			.map_err(|error| error.into_fatal_error("D2i").into_error())?;
+		Ok(())
+	}
+
+	fn execute_d2l(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_double_operand("D2l", frame)?;
+		frame
+			.push_long(value as i64)
+			.map_err(|error| error.into_fatal_error("D2l").into_error())?;
+		Ok(())
+	}
+
+	fn execute_d2f(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_double_operand("D2f", frame)?;
+		frame
+			.push_float(value as f32)
+			.map_err(|error| error.into_fatal_error("D2f").into_error())?;
+		Ok(())
+	}
+
+	/*
+	 * Truncate to a byte, char or short and widen back to an int. A
+	 * char is unsigned, so i2c zero extends; the others sign extend.
+	 */
+	fn execute_i2b(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2b", frame)?;
+		frame
+			.push_int(value as i8 as i32)
+			.map_err(|error| error.into_fatal_error("I2b").into_error())?;
+		Ok(())
+	}
+
+	fn execute_i2c(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2c", frame)?;
+		frame
+			.push_int(value as u16 as i32)
+			.map_err(|error| error.into_fatal_error("I2c").into_error())?;
+		Ok(())
+	}
+
+	fn execute_i2s(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let value = self.pop_int_operand("I2s", frame)?;
+		frame
+			.push_int(value as i16 as i32)
+			.map_err(|error| error.into_fatal_error("I2s").into_error())?;
+		Ok(())
+	}
+
+	/*
+	 * Pop values totalling exactly slots stack slots; the values are
+	 * returned top first. A long or a double is one value but two
+	 * slots, so it may not be split: that is a type error.
+	 */
+	fn pop_slots(
+		&self,
+		instruction: &str,
+		slots: usize,
+		frame: &mut Frame,
+	) -> Result<Vec<JvmValue>, JvmError> {
+		let mut values = Vec::<JvmValue>::new();
+		let mut popped = 0usize;
+		while popped < slots {
+			if let Some(value) = frame.operand_stack.pop() {
+				popped += value.size();
+				values.push(value);
+			} else {
+				return Err(FatalError::new(FatalErrorType::RequiredStackValueNotFound(
+					instruction.to_string(),
+				))
+				.into_error());
 			}
 		}
+		if popped != slots {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				instruction.to_string(),
+				format!("{} slots of category 1 values", slots),
+			))
+			.into_error());
+		}
+		Ok(values)
+	}
+
+	/*
+	 * Duplicate the top dup_slots slots and insert the copy beneath
+	 * the under_slots slots below them. Counting in slots covers
+	 * every form of each dup opcode: dup2 copies one long or double,
+	 * or two category 1 values, and so on.
+	 */
+	fn execute_dup(
+		&mut self,
+		instruction: &str,
+		dup_slots: usize,
+		under_slots: usize,
+		frame: &mut Frame,
+	) -> Result<(), JvmError> {
+		let duplicated = self.pop_slots(instruction, dup_slots, frame)?;
+		let under = self.pop_slots(instruction, under_slots, frame)?;
+		for value in duplicated.iter().rev() {
+			frame.operand_stack.push(value.clone());
+		}
+		for value in under.into_iter().rev() {
+			frame.operand_stack.push(value);
+		}
+		for value in duplicated.into_iter().rev() {
+			frame.operand_stack.push(value);
+		}
+		Ok(())
+	}
+
+	fn execute_swap(&mut self, frame: &mut Frame) -> Result<(), JvmError> {
+		let mut first = self.pop_slots("swap", 1, frame)?;
+		let mut second = self.pop_slots("swap", 1, frame)?;
+		frame.operand_stack.append(&mut first);
+		frame.operand_stack.append(&mut second);
+		Ok(())
 	}
 
 	fn execute_icmp(
@@ -1110,9 +3158,9 @@ impl JvmThread {
 		bytes: &[u8],
 		operation: OperandCode,
 	) -> OpcodeResult {
-		let mut pc_incr: usize = 3;
-		let success_incr: usize = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
-		let fail_incr: usize = 3 as usize;
+		let branch_offset = i16::from_be_bytes([bytes[1], bytes[2]]) as i32;
+		let success_incr: i32 = branch_offset;
+		let fail_incr: i32 = 3;
 		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, _value2, _)) =
 			frame.operand_stack.pop()
 		{
@@ -1121,7 +3169,7 @@ impl JvmThread {
 				frame.operand_stack.pop()
 			{
 				let value1 = _value1 as i32;
-				pc_incr = match operation {
+				let pc_incr = match operation {
 					OperandCode::If_icmpeq => {
 						Debug(format!("If_icmpeq"), &self.debug_level, DebugLevel::Info);
 						if value1 == value2 {
@@ -1171,385 +3219,432 @@ impl JvmThread {
 						}
 					}
 					_ => fail_incr,
-				}
+				};
+				branch_to(pc_incr)
 			} else {
+				OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						"If_icmpeq".to_string(),
+						"Integer".to_string(),
+					))
+					.into_error(),
+				)
+			}
+		} else {
+			OpcodeResult::Error(
 				FatalError::new(FatalErrorType::WrongType(
 					"If_icmpeq".to_string(),
 					"Integer".to_string(),
 				))
-				.call();
-			}
-		} else {
-			FatalError::new(FatalErrorType::WrongType(
-				"If_icmpeq".to_string(),
-				"Integer".to_string(),
-			))
-			.call();
+				.into_error(),
+			)
 		}
-		OpcodeResult::Incr(pc_incr)
 	}
 
-	fn execute_caload(&mut self, frame: &mut Frame) {
-		let index = frame.operand_stack.pop();
-		let mut arrayref = frame.operand_stack.pop();
+	/*
+	 * Pop the index and the array reference that every array load and
+	 * store works with. A null array or an index outside of the array
+	 * is the exception that the instruction throws.
+	 */
+	fn pop_array_and_index(
+		&self,
+		instruction: &str,
+		frame: &mut Frame,
+	) -> Result<(Arc<Mutex<JvmArray>>, Rc<JvmType>, usize), OpcodeResult> {
+		let index = match self.pop_int_operand(instruction, frame) {
+			Ok(index) => index,
+			Err(error) => return Err(OpcodeResult::Error(error)),
+		};
 
-		if let Some(index) = index {
-			if let Some(arrayref) = &mut arrayref {
-				// The array reference must:
-				// 1. be a non-null reference
-				// 2. point to an array of characters.
-				// Check (1) first ...
-				match &arrayref {
-					JvmValue::Reference(
-						JvmReferenceType::Array(arrayreftype, _),
-						JvmReferenceTargetType::Array(array),
-						_,
-					) => {
-						if let JvmType::Primitive(JvmPrimitiveType::Char) = **arrayreftype {
-							// The index must be a Primitive Integer.
-							if let JvmValue::Primitive(JvmPrimitiveType::Integer, _, index, _) =
-								index
-							{
-								// We need a lock even though we are just reading.
-								if let Ok(mut exclusive_array) = array.lock() {
-									// Check that the access is inbounds.
-									if exclusive_array.inbounds(index as usize) {
-										if let Some(value) = exclusive_array.get_at(index as usize)
-										{
-											if let JvmValue::Primitive(
-												JvmPrimitiveType::Char,
-												char,
-												_,
-												char_access,
-											) = *value
-											{
-												let integer = JvmValue::Primitive(
-													JvmPrimitiveType::Integer,
-													char as u64,
-													0,
-													char_access,
-												);
-												frame.operand_stack.push(integer);
-											} else {
-												// The value that we retrieved from the array *should* be a character,
-												// but it is not.
-												FatalError::new(FatalErrorType::WrongType(
-													format!("caload loaded value from array"),
-													format!("Primitive Character"),
-												))
-												.call();
-											}
-										} else {
-											// The value that we retrieved from the array *should* be a character,
-											// but it was empty.
-											FatalError::new(FatalErrorType::WrongType(
-												format!("caload loaded value from array"),
-												format!("Primitive Character"),
-											))
-											.call();
-										}
-									} else {
-										// The load is from a position outside the size of the array.
-										FatalError::new(FatalErrorType::Exception(format!(
-											"IndexOutOfBounds"
-										)))
-										.call();
-									}
-								} else {
-									FatalError::new(FatalErrorType::CouldNotLock(
-										format!("Array."),
-										format!("execute_caload"),
-									))
-									.call();
-								}
-							} else {
-								// index is wrong type.
-								FatalError::new(FatalErrorType::WrongType(
-									format!("caload index"),
-									format!("Integer"),
-								))
-								.call();
-							}
-						} else {
-							// Yes, it is a reference but not to a character array
-							FatalError::new(FatalErrorType::WrongType(
-								format!("caload array reference not character reference"),
-								format!("Integer"),
-							))
-							.call();
-						}
-					}
-					JvmValue::Reference(
-						JvmReferenceType::Null,
-						JvmReferenceTargetType::Null,
-						_,
-					) => {
-						// arrayreference is Null!
-						FatalError::new(FatalErrorType::Exception(format!("NullPointerException")))
-							.call();
-					}
-					_ => {
-						// arrayreference is of the wrong type!
-						FatalError::new(FatalErrorType::WrongType(
-							format!("caload array reference"),
-							format!("Reference to an array"),
+		match frame.operand_stack.pop() {
+			Some(JvmValue::Reference(
+				JvmReferenceType::Array(component_type, _),
+				JvmReferenceTargetType::Array(array),
+				_,
+			)) => {
+				let inbounds = if let Ok(exclusive_array) = array.lock() {
+					index >= 0 && exclusive_array.inbounds(index as usize)
+				} else {
+					return Err(OpcodeResult::Error(
+						FatalError::new(FatalErrorType::CouldNotLock(
+							format!("Array."),
+							instruction.to_string(),
 						))
-						.call();
-					}
+						.into_error(),
+					));
+				};
+				if !inbounds {
+					return Err(OpcodeResult::Exception(
+						format!("java/lang/ArrayIndexOutOfBoundsException"),
+						None,
+					));
 				}
-			} else {
-				// Missing a reference to an array on the stack.
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"Reference to an array."
-				)))
-				.call();
+				Ok((array, component_type, index as usize))
 			}
-		} else {
-			// Missing an index on the stack.
-			FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-				"Primitive"
-			)))
-			.call();
-		}
-	}
-	fn execute_aaload(&self, frame: &mut Frame) {
-		Debug(
-			format!("Frame before aaload: {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-
-		// Pull the three things on the top of the stack.
-		let index = frame.operand_stack.pop();
-		let arrayref = frame.operand_stack.pop();
-
-		if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, index, _)) = index {
-			if let Some(arrayref) = arrayref {
-				// I now have everything that I need!
-
-				// Check to make sure that the arrayref is to something of the right type.
-				if let JvmValue::Reference(
-					JvmReferenceType::Array(_ /* check me*/, _),
-					JvmReferenceTargetType::Array(array),
-					_,
-				) = arrayref
-				{
-					if let Ok(mut array) = array.lock() {
-						if let Some(value_from_array) = array.get_at(index as usize) {
-							frame.operand_stack.push(value_from_array.clone());
-						}
-					} else {
-						// The load is from a position outside the size of the array.
-						FatalError::new(FatalErrorType::Exception(format!("IndexOutOfBounds")))
-							.call();
-					}
+			Some(ref arrayref) if arrayref.is_null() => Err(null_pointer(format!(
+				"Cannot {} a null array",
+				if instruction.ends_with("load") {
+					"load from"
 				} else {
+					"store to"
+				}
+			))),
+			Some(_) => {
+				return Err(OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("reference to an array"),
+					))
+					.into_error(),
+				));
+			}
+			None => {
+				return Err(OpcodeResult::Error(
 					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-						"Primitive Integer index into an array."
+						"{}",
+						instruction
 					)))
-					.call();
-				}
-			} else {
-				// Missing an index on the stack.
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"Index into an array."
-				)))
-				.call();
+					.into_error(),
+				));
 			}
 		}
 	}
 
-	fn execute_aastore(&self, frame: &mut Frame) {
-		Debug(
-			format!("Frame before aastore: {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
+	/*
+	 * Load an element of a primitive array whose components are one of
+	 * _component_types_. Booleans, bytes, chars and shorts are already
+	 * stored sign (or zero) extended, so they only need to become ints.
+	 */
+	fn execute_primitive_array_load(
+		&self,
+		instruction: &str,
+		component_types: &[JvmPrimitiveType],
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let (array, component_type, index) = match self.pop_array_and_index(instruction, frame) {
+			Ok(array_and_index) => array_and_index,
+			Err(result) => return result,
+		};
+
+		match *component_type {
+			JvmType::Primitive(ref component_type) if component_types.contains(component_type) => {
+				()
+			}
+			_ => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("array of {}", component_types[0]),
+					))
+					.into_error(),
+				);
+			}
+		};
 
-		// Pull the three things on the top of the stack.
-		let value = frame.operand_stack.pop();
-		let index = frame.operand_stack.pop();
-		let arrayref = frame.operand_stack.pop();
+		if let Ok(mut array) = array.lock() {
+			if let Some(value) = array.get_at(index) {
+				frame.operand_stack.push(value.to_computational_type());
+			}
+		}
+		OpcodeResult::Incr(1)
+	}
 
-		if let Some(value) = value {
-			if let Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, index, _)) = index {
-				if let Some(arrayref) = arrayref {
-					// I now have everything that I need!
+	/*
+	 * Store into an element of a primitive array whose components are
+	 * one of _component_types_. An int is narrowed to the component
+	 * type of the array before it is stored.
+	 */
+	fn execute_primitive_array_store(
+		&self,
+		instruction: &str,
+		component_types: &[JvmPrimitiveType],
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let (value64, value32) = match component_types[0] {
+			JvmPrimitiveType::LongInteger => match self.pop_long_operand(instruction, frame) {
+				Ok(value) => (value as u64, 0),
+				Err(error) => return OpcodeResult::Error(error),
+			},
+			JvmPrimitiveType::Float => match self.pop_float_operand(instruction, frame) {
+				Ok(value) => (0, value.to_bits()),
+				Err(error) => return OpcodeResult::Error(error),
+			},
+			JvmPrimitiveType::Double => match self.pop_double_operand(instruction, frame) {
+				Ok(value) => (value.to_bits(), 0),
+				Err(error) => return OpcodeResult::Error(error),
+			},
+			_ => match self.pop_int_operand(instruction, frame) {
+				Ok(value) => (0, value as u32),
+				Err(error) => return OpcodeResult::Error(error),
+			},
+		};
+		let (array, component_type, index) = match self.pop_array_and_index(instruction, frame) {
+			Ok(array_and_index) => array_and_index,
+			Err(result) => return result,
+		};
 
-					// Check to make sure that the arrayref is to something of the right type.
-					if let JvmValue::Reference(
-						JvmReferenceType::Array(_ /* check me*/, _),
-						JvmReferenceTargetType::Array(array),
-						_,
-					) = arrayref
-					{
-						if let Ok(mut array) = array.lock() {
-							array.set_at(index as usize, value);
-						} else {
-							FatalError::new(FatalErrorType::CouldNotLock(
-								format!("Array."),
-								format!("execute_aastore"),
-							))
-							.call();
-						}
-					}
-				} else {
-					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-						"Primitive Integer index into an array."
-					)))
-					.call();
-				}
-			} else {
-				// Missing an index on the stack.
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"Index into an array."
-				)))
-				.call();
+		let component_type = match *component_type {
+			JvmType::Primitive(ref component_type) if component_types.contains(component_type) => {
+				component_type.clone()
+			}
+			_ => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("array of {}", component_types[0]),
+					))
+					.into_error(),
+				);
+			}
+		};
+
+		let value32 = match component_type {
+			JvmPrimitiveType::Boolean => value32 & 1,
+			JvmPrimitiveType::Byte => value32 as i8 as i32 as u32,
+			JvmPrimitiveType::Char => value32 as u16 as u32,
+			JvmPrimitiveType::Short => value32 as i16 as i32 as u32,
+			_ => value32,
+		};
+
+		if let Ok(mut array) = array.lock() {
+			array.set_at(
+				index,
+				JvmValue::Primitive(component_type, value64, value32, 0),
+			);
+		}
+		OpcodeResult::Incr(1)
+	}
+
+	fn execute_aaload(&self, frame: &mut Frame) -> OpcodeResult {
+		let (array, component_type, index) = match self.pop_array_and_index("aaload", frame) {
+			Ok(array_and_index) => array_and_index,
+			Err(result) => return result,
+		};
+
+		if let JvmType::Primitive(_) = *component_type {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					format!("aaload"),
+					format!("array of references"),
+				))
+				.into_error(),
+			);
+		}
+
+		if let Ok(mut array) = array.lock() {
+			if let Some(value) = array.get_at(index) {
+				frame.operand_stack.push(value.clone());
 			}
-		} else {
-			// Missing a value on the stack.
-			FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-				"Primitive"
-			)))
-			.call();
 		}
+		OpcodeResult::Incr(1)
 	}
 
-	fn execute_castore(&self, frame: &mut Frame) {
+	fn execute_aastore(&self, frame: &mut Frame) -> OpcodeResult {
+		let value = match frame.pop_reference() {
+			Ok(value) => value,
+			Err(error) => {
+				return OpcodeResult::Error(error.into_fatal_error("aastore").into_error());
+			}
+		};
+		let (array, component_type, index) = match self.pop_array_and_index("aastore", frame) {
+			Ok(array_and_index) => array_and_index,
+			Err(result) => return result,
+		};
+
+		if let JvmType::Primitive(_) = *component_type {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					format!("aastore"),
+					format!("array of references"),
+				))
+				.into_error(),
+			);
+		}
+
 		/*
-		 * From the Java spec:
-		 * stack: arrayref, index, value →
-		 * The arrayref must be of type reference and must refer to an array whose components
-		 * are of type char. The index and the value must both be of type int. The arrayref,
-		 * index, and value are popped from the operand stack. The int value is truncated to a char
-		 * and stored as the component of the array indexed by index.
-		 * */
+		 * null goes into any array of references. Anything else has
+		 * to be of the array's component type.
+		 */
+		if !value.is_null() {
+			let value_type = match self.runtime_type(&value) {
+				Ok(value_type) => value_type,
+				Err(error) => return OpcodeResult::Error(error),
+			};
+			match self.is_assignable(&value_type, &component_type) {
+				Ok(true) => (),
+				Ok(false) => {
+					return OpcodeResult::Exception(format!("java/lang/ArrayStoreException"), None)
+				}
+				Err(error) => return OpcodeResult::Error(error),
+			}
+		}
 
-		// Pull the three things on the top of the stack.
-		let value = frame.operand_stack.pop();
-		let index = frame.operand_stack.pop();
-		let mut arrayref = frame.operand_stack.pop();
+		if let Ok(mut array) = array.lock() {
+			array.set_at(index, value);
+		}
+		OpcodeResult::Incr(1)
+	}
 
-		if let Some(value) = value {
-			if let Some(index) = index {
-				match &mut arrayref {
-					// The array reference must:
-					// 1. be a non-null reference
-					// 2. point to an array of characters.
-					// Check (1) first ...
-					Some(JvmValue::Reference(
-						JvmReferenceType::Array(arrayreftype, _),
-						JvmReferenceTargetType::Array(array),
-						_,
-					)) => {
-						// Check (2) second ...
-						if let JvmType::Primitive(JvmPrimitiveType::Char) = **arrayreftype {
-							// The index must be a primitive Integer.
-							if let JvmValue::Primitive(JvmPrimitiveType::Integer, _, index, _) =
-								index
-							{
-								// Finally, the value must be a primitive Integer.
-								if let JvmValue::Primitive(
-									JvmPrimitiveType::Integer,
-									_,
-									value,
-									_len_access,
-								) = value
-								{
-									// All preconditions for this work are met! We can do the work now.
-
-									// Convert the integer from the stack and make it a jvm char value
-									let value_as_character = JvmValue::Primitive(
-										JvmPrimitiveType::Char,
-										value as u64,
-										0,
-										0,
-									);
+	/*
+	 * The type of what a (non-null) reference refers to: an object's
+	 * class rather than whatever type the reference was made with.
+	 */
+	pub fn runtime_type(&self, value: &JvmValue) -> Result<JvmType, JvmError> {
+		match value {
+			JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) => {
+				match object.lock() {
+					/*
+					 * A lambda is of the functional interface that it
+					 * implements.
+					 */
+					Ok(object) => Ok(JvmType::Reference(JvmReferenceType::Class(
+						match object.lambda() {
+							Some(lambda) => lambda.interface_name.clone(),
+							None => object.get_class().get_class_name().unwrap(),
+						},
+					))),
+					_ => Err(FatalError::new(FatalErrorType::CouldNotLock(
+						format!("Object."),
+						format!("runtime_type"),
+					))
+					.into_error()),
+				}
+			}
+			JvmValue::Reference(reference_type, _, _) => {
+				Ok(JvmType::Reference(reference_type.clone()))
+			}
+			_ => Err(FatalError::new(FatalErrorType::WrongType(
+				format!("runtime_type"),
+				format!("reference"),
+			))
+			.into_error()),
+		}
+	}
 
-									// Since we are writing to the array, we need exclusive access to it.
-									if let Ok(mut exclusive_array) = array.lock() {
-										// Check to make sure that the array access is inbounds.
-										if exclusive_array.inbounds(index as usize) {
-											// Write to the array with the new value.
-											exclusive_array
-												.set_at(index as usize, value_as_character);
-										} else {
-											// array index out of bounds exception.
-											FatalError::new(FatalErrorType::Exception(format!(
-												"IndexOutOfBounds"
-											)))
-											.call();
-										}
-									} else {
-										// We could not get an exclusive lock on the array to which we are
-										// writing.
-										FatalError::new(FatalErrorType::CouldNotLock(
-											format!("Array."),
-											format!("execute_castore"),
-										))
-										.call();
-									}
-								} else {
-									// value should be a character but it is not.
-									FatalError::new(FatalErrorType::WrongType(
-										format!("castore value"),
-										format!("Character"),
-									))
-									.call();
-								}
-							} else {
-								// index should be a primitive Integer but it is not.
-								FatalError::new(FatalErrorType::WrongType(
-									format!("castore index"),
-									format!("Integer"),
-								))
-								.call();
-							}
-						} else {
-							// What should be an a reference to an array of characters
-							// is an array reference but it does not refer to array of characters.
-							FatalError::new(FatalErrorType::WrongType(
-								format!("castore arrayreference"),
-								format!("reference to an array of characters"),
-							))
-							.call();
-						}
-					}
-					Some(JvmValue::Reference(
-						JvmReferenceType::Null,
-						JvmReferenceTargetType::Null,
-						_,
-					)) => {
-						// What should be a reference to an array of characters is Null.
-						FatalError::new(FatalErrorType::Exception(format!("NullPointerException")))
-							.call();
-					}
-					_ => {
-						// What should be an a reference to an array of characters is not even a reference.
-						FatalError::new(FatalErrorType::WrongType(
-							format!("castore arrayreference"),
-							format!("reference to an array"),
-						))
-						.call();
-					}
+	/*
+	 * The type that a Class constant names: a class (or interface)
+	 * or, given as a descriptor, an array type.
+	 */
+	fn resolve_type_ref(&self, class: &Rc<Class>, index: u16) -> Result<JvmType, JvmError> {
+		match class.resolve_class_ref(index as usize) {
+			Some(name) => {
+				if name.starts_with("[") {
+					Ok(JvmType::from(name.as_bytes()))
+				} else {
+					Ok(JvmType::Reference(JvmReferenceType::Class(name)))
 				}
-			} else {
-				// Missing an index on the stack.
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"Index into an array."
-				)))
-				.call();
 			}
+			None => {
+				return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Class".to_string(),
+					index,
+				))
+				.into_error());
+			}
+		}
+	}
+
+	/*
+	 * checkcast leaves the reference on the stack; null passes any
+	 * check.
+	 */
+	fn execute_checkcast(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let class = frame.class().unwrap();
+		let index = ((bytes[1] as u16) << 8) | (bytes[2] as u16);
+
+		let value = match frame.operand_stack.last() {
+			Some(value @ JvmValue::Reference(_, _, _)) => value.clone(),
+			Some(_) => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("checkcast"),
+						format!("reference"),
+					))
+					.into_error(),
+				);
+			}
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"checkcast"
+					)))
+					.into_error(),
+				);
+			}
+		};
+		if value.is_null() {
+			return OpcodeResult::Incr(3);
+		}
+
+		match self.is_instance(&value, &class, index) {
+			Ok(true) => OpcodeResult::Incr(3),
+			Ok(false) => OpcodeResult::Exception(format!("java/lang/ClassCastException"), None),
+			Err(error) => OpcodeResult::Error(error),
+		}
+	}
+
+	/*
+	 * Whether the object or array that _value_ refers to is of the
+	 * type that Class constant _index_ of _class_ names.
+	 */
+	fn is_instance(
+		&self,
+		value: &JvmValue,
+		class: &Rc<Class>,
+		index: u16,
+	) -> Result<bool, JvmError> {
+		let value_type = self.runtime_type(value)?;
+		let target_type = self.resolve_type_ref(class, index)?;
+		self.is_assignable(&value_type, &target_type)
+	}
+
+	/*
+	 * Unlike checkcast, instanceof consumes the reference, and null
+	 * is not an instance of anything.
+	 */
+	fn execute_instanceof(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let class = frame.class().unwrap();
+		let index = ((bytes[1] as u16) << 8) | (bytes[2] as u16);
+
+		let value = match frame.pop_reference() {
+			Ok(value) => value,
+			Err(error) => {
+				return OpcodeResult::Error(error.into_fatal_error("instanceof").into_error());
+			}
+		};
+
+		let is_instance = !value.is_null()
+			&& match self.is_instance(&value, &class, index) {
+				Ok(is_instance) => is_instance,
+				Err(error) => return OpcodeResult::Error(error),
+			};
+		frame.operand_stack.push(JvmValue::Primitive(
+			JvmPrimitiveType::Integer,
+			0,
+			is_instance as u32,
+			0,
+		));
+		OpcodeResult::Incr(3)
+	}
+
+	pub fn is_assignable(&self, from: &JvmType, to: &JvmType) -> Result<bool, JvmError> {
+		if let Ok(mut methodarea) = self.methodarea.lock() {
+			Ok(from.is_assignable_to(to, &mut methodarea))
 		} else {
-			// Missing a value on the stack.
-			FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-				"Primitive"
-			)))
-			.call();
+			Err(FatalError::new(FatalErrorType::CouldNotLock(
+				"Method Area.".to_string(),
+				"is_assignable".to_string(),
+			))
+			.into_error())
 		}
 	}
 
-	fn execute_astore_x(&self, x: usize, frame: &mut Frame) {
+	/*
+	 * A subroutine (jsr) stores its return address with astore, so
+	 * astore takes return addresses as well as references.
+	 */
+	fn execute_astore_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
 		Debug(
 			format!("Frame before astore_x: {}", frame),
 			&self.debug_level,
@@ -1558,74 +3653,110 @@ impl JvmThread {
 		if x < frame.locals.len() {
 			if let Some(top) = frame.operand_stack.pop() {
 				if let JvmValue::Reference(rt, reference, access) = top {
-					frame.locals[x] = JvmValue::Reference(rt, reference, access);
+					frame.set_local(x, JvmValue::Reference(rt, reference, access));
+				} else if top.return_address().is_some() {
+					frame.set_local(x, top);
 				} else {
-					FatalError::new(FatalErrorType::WrongType(
+					return Err(FatalError::new(FatalErrorType::WrongType(
 						format!("astore"),
 						format!("reference"),
 					))
-					.call();
+					.into_error());
 				}
 			} else {
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"astore"
-				)))
-				.call();
+				return Err(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"astore"
+					)))
+					.into_error(),
+				);
 			}
 		} else {
-			FatalError::new(FatalErrorType::NotEnough(
+			return Err(FatalError::new(FatalErrorType::NotEnough(
 				format!("astore"),
 				x,
 				format!("locals"),
 			))
-			.call();
+			.into_error());
+		}
+		Ok(())
+	}
+
+	/*
+	 * Push the address of the instruction after the jsr (which is
+	 * _length_ bytes long) and branch to the subroutine.
+	 */
+	fn execute_jsr(&self, length: usize, branch_target: i32, frame: &mut Frame) -> OpcodeResult {
+		frame
+			.operand_stack
+			.push(create_return_address_value(frame.pc + length));
+		branch_to(branch_target)
+	}
+
+	/*
+	 * Return from a subroutine to the return address in local _x_.
+	 */
+	fn execute_ret(&self, x: usize, frame: &mut Frame) -> OpcodeResult {
+		match frame.locals.get(x).map(|local| local.return_address()) {
+			Some(Some(return_address)) => branch_to(return_address as i32 - frame.pc as i32),
+			Some(None) => OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					format!("ret"),
+					format!("returnAddress"),
+				))
+				.into_error(),
+			),
+			None => OpcodeResult::Error(
+				FatalError::new(FatalErrorType::NotEnough(
+					format!("ret"),
+					x,
+					format!("locals"),
+				))
+				.into_error(),
+			),
 		}
 	}
 
-	fn execute_aload_x(&self, x: usize, frame: &mut Frame) {
+	/*
+	 * Unlike astore, aload does not take return addresses: only ret
+	 * can use one that is in a local.
+	 */
+	fn execute_aload_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
 		if x < frame.locals.len() {
 			if let JvmValue::Reference(_, _, _) = frame.locals[x] {
 				frame.operand_stack.push(frame.locals[x].clone());
 			} else {
-				FatalError::new(FatalErrorType::WrongType(
+				return Err(FatalError::new(FatalErrorType::WrongType(
 					format!("aload_{}", x),
 					"Reference".to_string(),
 				))
-				.call();
+				.into_error());
 			}
 		} else {
-			FatalError::new(FatalErrorType::NotEnough(
+			return Err(FatalError::new(FatalErrorType::NotEnough(
 				format!("aload_{}", x),
 				x,
 				"locals".to_string(),
 			))
-			.call();
+			.into_error());
 		}
+		Ok(())
 	}
 
-	fn execute_ldc(&mut self, bytes: &[u8], frame: &mut Frame) {
+	fn execute_ldc(&mut self, index: usize, frame: &mut Frame) -> Result<(), JvmError> {
 		let class = frame.class().unwrap();
 		let constant_pool = class.get_constant_pool_ref();
-		let instantiated_class_index = (bytes[1] as u16) as usize;
-
-		match constant_pool.get_constant_ref(instantiated_class_index) {
-			Constant::String(_, string_index) => {
-				if let Some(string_object) = create_static_string_object(
-					format!("Testing"),
-					self,
-					Arc::clone(&self.methodarea),
-				) {
-					frame.operand_stack.push(JvmValue::Reference(
-						JvmReferenceType::Class(format!("java/lang/String")),
-						JvmReferenceTargetType::Object(Arc::new(Mutex::new(string_object))),
-						0,
-					));
-				} else {
-					FatalError::new(FatalErrorType::Todo(format!(
-						"Handle this abnormal condition."
-					)))
-					.call();
-				}
+
+		match constant_pool.get_constant_ref(index) {
+			Constant::String(_, _) => {
+				let value = match constant_pool.get_string(index) {
+					Ok(value) => value.to_string(),
+					Err(error) => {
+						return Err(error.into_error());
+					}
+				};
+				let string = self.create_string(value)?;
+				frame.operand_stack.push(string);
 			}
 			Constant::Integer(_, value) => {
 				let constant_int = JvmValue::Primitive(JvmPrimitiveType::Integer, 0, *value, 0);
@@ -1635,11 +3766,19 @@ impl JvmThread {
 				let constant_float = JvmValue::Primitive(JvmPrimitiveType::Float, 0, *value, 0);
 				frame.operand_stack.push(constant_float);
 			}
+			Constant::Long(_, _) | Constant::Double(_, _) => {
+				return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Integer, Float or String".to_string(),
+					index as u16,
+				))
+				.into_error());
+			}
 			_ => {
-				FatalError::new(FatalErrorType::NotImplemented(format!(
+				return Err(FatalError::new(FatalErrorType::NotImplemented(format!(
 					"execute_ldc (class, method type or method handle.)"
 				)))
-				.call();
+				.into_error());
 			}
 		}
 		Debug(
@@ -1647,84 +3786,339 @@ impl JvmThread {
 			&self.debug_level,
 			DebugLevel::Info,
 		);
+		Ok(())
 	}
 
-	fn execute_fload_x(&mut self, x: usize, frame: &mut Frame) {
-		frame.operand_stack.push(frame.locals[x].clone());
-	}
-
-	fn execute_iload_x(&mut self, x: usize, frame: &mut Frame) {
-		frame.operand_stack.push(frame.locals[x].clone());
-	}
+	/*
+	 * ldc2_w only loads longs and doubles; each takes two stack slots.
+	 */
+	fn execute_ldc2_w(&mut self, index: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		let class = frame.class().unwrap();
+		let constant_pool = class.get_constant_pool_ref();
 
-	fn execute_fstore_x(&self, x: usize, frame: &mut Frame) {
+		match constant_pool.get_constant_ref(index) {
+			Constant::Long(_, value) => {
+				frame
+					.push_long(*value as i64)
+					.map_err(|error| error.into_fatal_error("ldc2_w").into_error())?;
+			}
+			Constant::Double(_, value) => {
+				frame
+					.push_double(f64::from_bits(*value))
+					.map_err(|error| error.into_fatal_error("ldc2_w").into_error())?;
+			}
+			_ => {
+				return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Long or Double".to_string(),
+					index as u16,
+				))
+				.into_error());
+			}
+		}
 		Debug(
-			format!("Frame before fstore_x: {}", frame),
+			format!("Frame after ldc2_w: {}", frame),
 			&self.debug_level,
 			DebugLevel::Info,
 		);
+		Ok(())
+	}
+
+	fn execute_fload_x(&mut self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
 		if x < frame.locals.len() {
-			if let Some(top) = frame.operand_stack.pop() {
-				if let JvmValue::Primitive(JvmPrimitiveType::Float, _, value, access) = top {
-					frame.locals[x] =
-						JvmValue::Primitive(JvmPrimitiveType::Float, 0, value, access);
-				} else {
-					FatalError::new(FatalErrorType::WrongType(
-						format!("fstore"),
-						format!("float primitive"),
-					))
-					.call();
-				}
+			if let JvmValue::Primitive(JvmPrimitiveType::Float, _, _, _) = frame.locals[x] {
+				frame.operand_stack.push(frame.locals[x].clone());
 			} else {
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"fstore"
-				)))
-				.call();
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					format!("fload"),
+					format!("float primitive"),
+				))
+				.into_error());
 			}
 		} else {
-			FatalError::new(FatalErrorType::NotEnough(
-				format!("fstore"),
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				format!("fload"),
 				x,
 				format!("locals"),
 			))
-			.call();
+			.into_error());
 		}
+		Ok(())
 	}
-	fn execute_fstore(&self, index: usize, frame: &mut Frame) {
-		Debug(
-			format!("Frame before fstore_x: {}", frame),
-			&self.debug_level,
-			DebugLevel::Info,
-		);
-		if index < frame.locals.len() {
-			if let Some(top) = frame.operand_stack.pop() {
-				if let JvmValue::Primitive(JvmPrimitiveType::Float, value64, value32, access) = top
-				{
-					frame.locals[index] =
-						JvmValue::Primitive(JvmPrimitiveType::Float, value64, value32, access);
-				} else {
-					FatalError::new(FatalErrorType::WrongType(
-						format!("fstore"),
-						format!("float primitive"),
-					))
-					.call();
+
+	fn execute_iload_x(&mut self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		if x < frame.locals.len() {
+			match frame.locals[x] {
+				JvmValue::Primitive(JvmPrimitiveType::Integer, _, _, _)
+				| JvmValue::Primitive(JvmPrimitiveType::Boolean, _, _, _)
+				| JvmValue::Primitive(JvmPrimitiveType::Byte, _, _, _)
+				| JvmValue::Primitive(JvmPrimitiveType::Char, _, _, _)
+				| JvmValue::Primitive(JvmPrimitiveType::Short, _, _, _) => {
+					frame.operand_stack.push(frame.locals[x].clone());
+				}
+				_ => {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						format!("iload"),
+						format!("integer primitive"),
+					))
+					.into_error());
+				}
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				format!("iload"),
+				x,
+				format!("locals"),
+			))
+			.into_error());
+		}
+		Ok(())
+	}
+
+	/*
+	 * Add _constant_ to the int in local _x_. The operand stack is
+	 * left alone.
+	 */
+	fn execute_iinc(&mut self, x: usize, constant: i32, frame: &mut Frame) -> Result<(), JvmError> {
+		if x < frame.locals.len() {
+			match frame.locals[x] {
+				JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, access)
+				| JvmValue::Primitive(JvmPrimitiveType::Boolean, _, value, access)
+				| JvmValue::Primitive(JvmPrimitiveType::Byte, _, value, access)
+				| JvmValue::Primitive(JvmPrimitiveType::Char, _, value, access)
+				| JvmValue::Primitive(JvmPrimitiveType::Short, _, value, access) => {
+					frame.locals[x] = JvmValue::Primitive(
+						JvmPrimitiveType::Integer,
+						0,
+						(value as i32).wrapping_add(constant) as u32,
+						access,
+					);
+				}
+				_ => {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						format!("iinc"),
+						format!("integer primitive"),
+					))
+					.into_error());
+				}
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				format!("iinc"),
+				x,
+				format!("locals"),
+			))
+			.into_error());
+		}
+		Ok(())
+	}
+
+	/*
+	 * wide gives the instruction after it a two-byte local index
+	 * and, for iinc, a two-byte constant as well.
+	 */
+	fn execute_wide(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
+		let index = (((bytes[2] as u16) << 8) | (bytes[3] as u16)) as usize;
+
+		let result = match OperandCode::from_u8(bytes[1]) {
+			Some(OperandCode::Iload) => self.execute_iload_x(index, frame),
+			Some(OperandCode::Lload) => self.execute_lload_x(index, frame),
+			Some(OperandCode::Fload) => self.execute_fload_x(index, frame),
+			Some(OperandCode::Dload) => self.execute_dload_x(index, frame),
+			Some(OperandCode::Aload) => self.execute_aload_x(index, frame),
+			Some(OperandCode::Istore) => self.execute_istore_x(index, frame),
+			Some(OperandCode::Lstore) => self.execute_lstore_x(index, frame),
+			Some(OperandCode::Fstore) => self.execute_fstore_x(index, frame),
+			Some(OperandCode::Dstore) => self.execute_dstore_x(index, frame),
+			Some(OperandCode::Astore) => self.execute_astore_x(index, frame),
+			Some(OperandCode::Ret) => return self.execute_ret(index, frame),
+			Some(OperandCode::Iinc) => {
+				let constant = (((bytes[4] as u16) << 8) | (bytes[5] as u16)) as i16 as i32;
+				return advance(self.execute_iinc(index, constant, frame), 6);
+			}
+			_ => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::NotImplemented(format!(
+						"wide 0x{:x}",
+						bytes[1]
+					)))
+					.into_error(),
+				);
+			}
+		};
+		advance(result, 4)
+	}
+
+	fn execute_lload_x(&mut self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		self.execute_wide_load_x("lload", JvmPrimitiveType::LongInteger, x, frame)
+	}
+
+	fn execute_dload_x(&mut self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		self.execute_wide_load_x("dload", JvmPrimitiveType::Double, x, frame)
+	}
+
+	/*
+	 * A long or a double lives in locals x and x + 1. Local x + 1
+	 * only holds an Invalid marker, so a load from it fails the type
+	 * check below.
+	 */
+	fn execute_wide_load_x(
+		&mut self,
+		instruction: &str,
+		expected_type: JvmPrimitiveType,
+		x: usize,
+		frame: &mut Frame,
+	) -> Result<(), JvmError> {
+		if x + 1 < frame.locals.len() {
+			match frame.locals[x] {
+				JvmValue::Primitive(ref pt, _, _, _) if *pt == expected_type => {
+					frame.operand_stack.push(frame.locals[x].clone());
+				}
+				_ => {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("{} primitive", expected_type),
+					))
+					.into_error());
+				}
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				instruction.to_string(),
+				x,
+				format!("locals"),
+			))
+			.into_error());
+		}
+		Ok(())
+	}
+
+	fn execute_fstore_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		Debug(
+			format!("Frame before fstore_x: {}", frame),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		if x < frame.locals.len() {
+			if let Some(top) = frame.operand_stack.pop() {
+				if let JvmValue::Primitive(JvmPrimitiveType::Float, _, value, access) = top {
+					frame.set_local(
+						x,
+						JvmValue::Primitive(JvmPrimitiveType::Float, 0, value, access),
+					);
+				} else {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						format!("fstore"),
+						format!("float primitive"),
+					))
+					.into_error());
 				}
 			} else {
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"fstore"
-				)))
-				.call();
+				return Err(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"fstore"
+					)))
+					.into_error(),
+				);
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				format!("fstore"),
+				x,
+				format!("locals"),
+			))
+			.into_error());
+		}
+		Ok(())
+	}
+	fn execute_fstore(&self, index: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		Debug(
+			format!("Frame before fstore_x: {}", frame),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		if index < frame.locals.len() {
+			if let Some(top) = frame.operand_stack.pop() {
+				if let JvmValue::Primitive(JvmPrimitiveType::Float, value64, value32, access) = top
+				{
+					frame.set_local(
+						index,
+						JvmValue::Primitive(JvmPrimitiveType::Float, value64, value32, access),
+					);
+				} else {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						format!("fstore"),
+						format!("float primitive"),
+					))
+					.into_error());
+				}
+			} else {
+				return Err(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"fstore"
+					)))
+					.into_error(),
+				);
 			}
 		} else {
-			FatalError::new(FatalErrorType::NotEnough(
+			return Err(FatalError::new(FatalErrorType::NotEnough(
 				format!("fstore"),
 				index,
 				format!("locals"),
 			))
-			.call();
+			.into_error());
+		}
+		Ok(())
+	}
+	fn execute_lstore_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		self.execute_wide_store_x("lstore", JvmPrimitiveType::LongInteger, x, frame)
+	}
+
+	fn execute_dstore_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
+		self.execute_wide_store_x("dstore", JvmPrimitiveType::Double, x, frame)
+	}
+
+	fn execute_wide_store_x(
+		&self,
+		instruction: &str,
+		expected_type: JvmPrimitiveType,
+		x: usize,
+		frame: &mut Frame,
+	) -> Result<(), JvmError> {
+		Debug(
+			format!("Frame before {}: {}", instruction, frame),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		if let Some(top) = frame.operand_stack.pop() {
+			match top {
+				JvmValue::Primitive(ref pt, _, _, _) if *pt == expected_type => {}
+				_ => {
+					return Err(FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("{} primitive", expected_type),
+					))
+					.into_error());
+				}
+			}
+			if !frame.set_local(x, top) {
+				return Err(FatalError::new(FatalErrorType::NotEnough(
+					instruction.to_string(),
+					x,
+					format!("locals"),
+				))
+				.into_error());
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::RequiredStackValueNotFound(
+				instruction.to_string(),
+			))
+			.into_error());
 		}
+		Ok(())
 	}
-	fn execute_istore_x(&self, x: usize, frame: &mut Frame) {
+
+	fn execute_istore_x(&self, x: usize, frame: &mut Frame) -> Result<(), JvmError> {
 		Debug(
 			format!("Frame before istore_x: {}", frame),
 			&self.debug_level,
@@ -1733,48 +4127,65 @@ impl JvmThread {
 		if x < frame.locals.len() {
 			if let Some(top) = frame.operand_stack.pop() {
 				if let JvmValue::Primitive(pt, value64, value32, access) = top {
-					frame.locals[x] = JvmValue::Primitive(pt, value64, value32, access);
+					match pt {
+						JvmPrimitiveType::Integer
+						| JvmPrimitiveType::Boolean
+						| JvmPrimitiveType::Byte
+						| JvmPrimitiveType::Char
+						| JvmPrimitiveType::Short => (),
+						_ => {
+							return Err(FatalError::new(FatalErrorType::WrongType(
+								format!("istore"),
+								format!("integer primitive"),
+							))
+							.into_error());
+						}
+					}
+					frame.set_local(x, JvmValue::Primitive(pt, value64, value32, access));
 				} else {
-					FatalError::new(FatalErrorType::WrongType(
+					return Err(FatalError::new(FatalErrorType::WrongType(
 						format!("istore"),
 						format!("integer primitive"),
 					))
-					.call();
+					.into_error());
 				}
 			} else {
-				FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-					"istore"
-				)))
-				.call();
+				return Err(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"istore"
+					)))
+					.into_error(),
+				);
 			}
 		} else {
-			FatalError::new(FatalErrorType::NotEnough(
-				format!("fstore"),
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				format!("istore"),
 				x,
 				format!("locals"),
 			))
-			.call();
+			.into_error());
 		}
+		Ok(())
 	}
 
-	fn execute_iconst_x(&mut self, x: i32, frame: &mut Frame) {
+	fn execute_iconst_x(&mut self, x: i32, frame: &mut Frame) -> Result<(), JvmError> {
 		frame.operand_stack.push(JvmValue::Primitive(
 			JvmPrimitiveType::Integer,
 			0,
 			x as u32,
 			0,
 		));
+		Ok(())
 	}
 
-	pub fn maybe_initialize_class(&mut self, class: &Rc<Class>) {
+	pub fn maybe_initialize_class(&mut self, class: &Rc<Class>) -> Result<(), JvmError> {
 		/*
 		 * Get the class' name and fail if we cannot.
 		 */
 		let class_name: String = match class.get_class_name() {
 			Some(class_name) => class_name,
 			_ => {
-				FatalError::new(FatalErrorType::ClassNoName).call();
-				return;
+				return Err(FatalError::new(FatalErrorType::ClassNoName).into_error());
 			}
 		};
 
@@ -1788,19 +4199,20 @@ impl JvmThread {
 			match self.methodarea.lock() {
 				Ok(mut methodarea) => (*methodarea).get_loaded_class(&class_name),
 				_ => {
-					FatalError::new(FatalErrorType::CouldNotLock(
+					return Err(FatalError::new(FatalErrorType::CouldNotLock(
 						"Method Area.".to_string(),
 						"initialize_class".to_string(),
 					))
-					.call();
-					None
+					.into_error());
 				}
 			}
 		} {
 			Some(loaded_class) => loaded_class,
 			_ => {
-				FatalError::new(FatalErrorType::ClassNotFound(class_name.to_string())).call();
-				return;
+				return Err(
+					FatalError::new(FatalErrorType::ClassNotFound(class_name.to_string()))
+						.into_error(),
+				);
 			}
 		};
 
@@ -1810,12 +4222,11 @@ impl JvmThread {
 		let mut lc = match (*loaded_class).lc.lock() {
 			Ok(lc) => lc,
 			_ => {
-				FatalError::new(FatalErrorType::CouldNotLock(
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
 					"Class LC.".to_string(),
 					"maybe_initialize_class".to_string(),
 				))
-				.call();
-				return;
+				.into_error());
 			}
 		};
 
@@ -1827,30 +4238,27 @@ impl JvmThread {
 
 		match *lc {
 			ClassInitializationStatus::BeingInitialized => {
-				if let Some(class_being_initialized_by_current_thread) =
-					self.initializing_class.last()
-				{
-					if *class_being_initialized_by_current_thread == class_name.to_string() {
-						/*
-						 * We are the ones doing the current initialization, so
-						 * we just return.
-						 */
-						Debug(
-							format!("Recursive initialization; returning"),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
+				if self.initializing_class.contains(&class_name) {
+					/*
+					 * We are the ones doing the current initialization
+					 * (somewhere up our call stack, perhaps while
+					 * initializing another class), so we just return.
+					 */
+					Debug(
+						format!("Recursive initialization; returning"),
+						&self.debug_level,
+						DebugLevel::Info,
+					);
 
-						Debug(
-							format!("Unlocked LC of: {}", class_name),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
-						return;
-					}
+					Debug(
+						format!("Unlocked LC of: {}", class_name),
+						&self.debug_level,
+						DebugLevel::Info,
+					);
+					return Ok(());
 				} else {
 					/*
-					 * This thread is not initializing a class. Therefore, this class
+					 * This thread is not initializing this class. Therefore, it
 					 * must be initializing in another thread; wait for it to finish.
 					 */
 					Debug(
@@ -1863,7 +4271,8 @@ impl JvmThread {
 					);
 					while {
 						match *lc {
-							ClassInitializationStatus::Initialized => false,
+							ClassInitializationStatus::Initialized
+							| ClassInitializationStatus::Error => false,
 							_ => true,
 						}
 					} {
@@ -1879,7 +4288,7 @@ impl JvmThread {
 						&self.debug_level,
 						DebugLevel::Info,
 					);
-					return;
+					return Ok(());
 				}
 			}
 			ClassInitializationStatus::Initialized => {
@@ -1894,7 +4303,7 @@ impl JvmThread {
 					&self.debug_level,
 					DebugLevel::Info,
 				);
-				return;
+				return Ok(());
 			}
 			ClassInitializationStatus::VerifiedPreparedNotInitialized => {
 				Debug(
@@ -1904,8 +4313,10 @@ impl JvmThread {
 				);
 			}
 			_ => {
-				FatalError::new(FatalErrorType::ClassInstantiationFailed(class_name)).call();
-				return;
+				return Err(
+					FatalError::new(FatalErrorType::ClassInstantiationFailed(class_name))
+						.into_error(),
+				);
 			}
 		};
 
@@ -1923,37 +4334,11 @@ impl JvmThread {
 			DebugLevel::Info,
 		);
 
-		let clinit: String = "<clinit>".into();
-
 		/*
-		 * We must invoke the clinit method, if one exists.
+		 * A class whose initialization fails stays uninitialized for
+		 * good.
 		 */
-		if let Some(clinit_method) = class.get_methods_ref().get_by_name_and_type(
-			&clinit,
-			&"()V".to_string(),
-			class.get_constant_pool_ref(),
-		) {
-			Debug(
-				format!("clinit Method: {}", clinit_method),
-				&self.debug_level,
-				DebugLevel::Info,
-			);
-
-			let mut clinit_frame = Frame::new();
-			clinit_frame.class = Some(Rc::clone(&class));
-
-			Debug(
-				format!("clinit Frame: {}", clinit_frame),
-				&self.debug_level,
-				DebugLevel::Info,
-			);
-
-			if let Some(v) = self.execute_method(&clinit_method, clinit_frame) {
-				if JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0) != v {
-					FatalError::new(FatalErrorType::ClassInitMethodReturnedValue).call();
-				}
-			}
-		}
+		let result = self.initialize_class(&class);
 
 		/*
 		 * Now, let's acquire the lock again, update it's status and then
@@ -1966,7 +4351,11 @@ impl JvmThread {
 					&self.debug_level,
 					DebugLevel::Info,
 				);
-				*lc = ClassInitializationStatus::Initialized;
+				*lc = if result.is_ok() {
+					ClassInitializationStatus::Initialized
+				} else {
+					ClassInitializationStatus::Error
+				};
 				self.initializing_class.pop();
 				(*loaded_class).lc_waitq.notify_all();
 				/*
@@ -1979,141 +4368,507 @@ impl JvmThread {
 				);
 			}
 			_ => {
-				FatalError::new(FatalErrorType::CouldNotLock(
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
 					"Class LC.".to_string(),
 					"maybe_initialize_class".to_string(),
 				))
-				.call();
-				return;
+				.into_error());
 			}
 		};
+		result
 	}
 
-	fn execute_new(&mut self, bytes: &[u8], source_frame: &mut Frame) -> Option<JvmValue> {
-		let class = source_frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
-		let instantiated_class_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
-
-		match constant_pool.get_constant_ref(instantiated_class_index) {
-			Constant::Class(_, instantiated_class_name_index) => {
-				match constant_pool.get_constant_ref(*instantiated_class_name_index as usize) {
-					Constant::Utf8(_, _, _, instantiated_class_name) => {
-						Debug(
-							format!("Make a new {}.", instantiated_class_name),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
-
-						let mut result: Option<JvmValue> = None;
-						let mut instantiated_class: Option<Rc<Class>> = None;
-						if let Ok(mut methodarea) = self.methodarea.lock() {
-							(*methodarea).maybe_load_class(&instantiated_class_name);
-							instantiated_class =
-								(*methodarea).get_class_rc(instantiated_class_name);
-						} else {
-							FatalError::new(FatalErrorType::CouldNotLock(
-								"Method Area.".to_string(),
-								"execute_new".to_string(),
-							))
-							.call();
-						}
-						if let Some(instantiated_class) = instantiated_class {
-							self.maybe_initialize_class(&instantiated_class);
-
-							let mut object =
-								JvmObject::new(instantiated_class, self.debug_level.clone());
-
-							object.instantiate(self, Arc::clone(&self.methodarea));
-							Debug(
-								format!("Made a new {}.", instantiated_class_name),
-								&self.debug_level,
-								DebugLevel::Info,
-							);
-
-							Debug(
-								format!("hierarchy: {}", object.hierarchy()),
-								&self.debug_level,
-								DebugLevel::Info,
-							);
-
-							result = Some(JvmValue::Reference(
-								JvmReferenceType::Class(instantiated_class_name.to_string()),
-								JvmReferenceTargetType::Object(Arc::new(Mutex::new(object))),
-								0,
-							));
-						} else {
-							FatalError::new(FatalErrorType::ClassNotLoaded(
-								instantiated_class_name.to_string(),
-							))
-							.call();
-						}
-						result
-					}
+	/*
+	 * Initialize _class_, which this thread has marked as being
+	 * initialized: its superclass first, then its static fields and
+	 * then its clinit method.
+	 */
+	fn initialize_class(&mut self, class: &Rc<Class>) -> Result<(), JvmError> {
+		/*
+		 * A class' superclass is initialized before the class is (an
+		 * interface's superinterfaces are not).
+		 */
+		if class.access_flags & (ClassAccessFlags::Interface as u16) == 0 {
+			if let Some(superclass_name) = class.resolve_superclass() {
+				let superclass = match self.methodarea.lock() {
+					Ok(mut methodarea) => (*methodarea).maybe_load_class(&superclass_name),
 					_ => {
-						FatalError::new(FatalErrorType::InvalidConstantReference(
-							class.get_class_name().unwrap(),
-							"Utf8".to_string(),
-							*instantiated_class_name_index,
+						return Err(FatalError::new(FatalErrorType::CouldNotLock(
+							"Method Area.".to_string(),
+							"maybe_initialize_class".to_string(),
 						))
-						.call();
-						None
+						.into_error());
+					}
+				};
+				match superclass {
+					Some(superclass) => self.maybe_initialize_class(&superclass)?,
+					None => {
+						return Err(
+							FatalError::new(FatalErrorType::ClassNotFound(superclass_name))
+								.into_error(),
+						)
 					}
 				}
 			}
-			_ => {
-				FatalError::new(FatalErrorType::InvalidConstantReference(
-					class.get_class_name().unwrap(),
-					"Classref".to_string(),
-					instantiated_class_index as u16,
-				))
-				.call();
-				None
-			}
 		}
-	}
 
-	fn execute_getstatic(&mut self, bytes: &[u8], source_frame: &mut Frame) {
-		let class = source_frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
-		let field_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
+		/*
+		 * Static fields must hold their initial values before the
+		 * clinit method gets to see them.
+		 */
+		self.prepare_static_fields(&class)?;
 
-		if let Some((field_class_name, field_name, field_type)) =
-			class.resolve_field_ref(field_index)
-		{
+		let clinit: String = "<clinit>".into();
+
+		/*
+		 * We must invoke the clinit method, if one exists.
+		 */
+		if let Some(clinit_method) = class.get_methods_ref().get_by_name_and_type(
+			&clinit,
+			&"()V".to_string(),
+			class.get_constant_pool_ref(),
+		) {
 			Debug(
-				format!(
-					"get static: {}.{} ({})",
-					field_class_name, field_name, field_type
-				),
+				format!("clinit Method: {}", clinit_method),
 				&self.debug_level,
 				DebugLevel::Info,
 			);
-			let mut resolved_field_class: Option<Rc<Class>> = None;
 
-			if let Ok(mut methodarea) = self.methodarea.lock() {
-				(*methodarea).maybe_load_class(&field_class_name);
+			let mut clinit_frame = Frame::new();
+			clinit_frame.class = Some(Rc::clone(&class));
+
+			Debug(
+				format!("clinit Frame: {}", clinit_frame),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+
+			match self.execute_method(&clinit_method, clinit_frame) {
+				OpcodeResult::Return(v) => {
+					if JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0) != v {
+						return Err(
+							FatalError::new(FatalErrorType::ClassInitMethodReturnedValue)
+								.into_error(),
+						);
+					}
+				}
+				/*
+				 * TODO: Wrap the exception in an ExceptionInInitializerError.
+				 */
+				OpcodeResult::Exception(exception, _) => {
+					self.report_uncaught_exception(&exception);
+					return Err(FatalError::new(FatalErrorType::Exception(exception)).into_error());
+				}
+				OpcodeResult::Throw(exception) => {
+					let exception = exception_class_name(&exception);
+					self.report_uncaught_exception(&exception);
+					return Err(FatalError::new(FatalErrorType::Exception(exception)).into_error());
+				}
+				OpcodeResult::Error(error) => return Err(error),
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	/*
+	 * Give every static field of the class its initial value: the
+	 * constant from its ConstantValue attribute when it has one and
+	 * the default value for its type otherwise.
+	 */
+	fn prepare_static_fields(&mut self, class: &Rc<Class>) -> Result<(), JvmError> {
+		let fields = class.get_fields_ref();
+		let constant_pool = class.get_constant_pool_ref();
+
+		for i in 0..fields.fields_count() {
+			let field = fields.get(i as usize);
+
+			if field.access_flags & (FieldAccessFlags::Static as u16) == 0 {
+				continue;
+			}
+
+			let mut value = match constant_pool.get_utf8(field.descriptor_index as usize) {
+				Ok(descriptor) => match JvmType::from(descriptor.as_bytes()) {
+					JvmType::Primitive(primitive) => JvmValue::Primitive(primitive, 0, 0, 0),
+					JvmType::Reference(reference) => {
+						JvmValue::Reference(reference, JvmReferenceTargetType::Null, 0)
+					}
+				},
+				Err(error) => {
+					return Err(error.into_error());
+				}
+			};
+
+			for j in 0..field.attributes.len() {
+				let attribute = field.attributes.get_ref(j);
+				match constant_pool.get_constant_ref(attribute.attribute_name_index as usize) {
+					Constant::Utf8(_, Utf8Reserved::ConstantValue, _, _) => (),
+					_ => continue,
+				};
+
+				let constant_index = ((attribute.info[0] as u16) << 8) | (attribute.info[1] as u16);
+				match constant_pool.get_constant_ref(constant_index as usize) {
+					Constant::Integer(_, constant) => {
+						value = JvmValue::Primitive(JvmPrimitiveType::Integer, 0, *constant, 0);
+					}
+					Constant::Float(_, constant) => {
+						value = JvmValue::Primitive(JvmPrimitiveType::Float, 0, *constant, 0);
+					}
+					Constant::Long(_, constant) => {
+						value = JvmValue::Primitive(JvmPrimitiveType::LongInteger, *constant, 0, 0);
+					}
+					Constant::Double(_, constant) => {
+						value = JvmValue::Primitive(JvmPrimitiveType::Double, *constant, 0, 0);
+					}
+					Constant::String(_, _) => {
+						let string = match constant_pool.get_string(constant_index as usize) {
+							Ok(string) => string.to_string(),
+							Err(error) => {
+								return Err(error.into_error());
+							}
+						};
+						let string_object = create_static_string_object(
+							string,
+							self,
+							Arc::clone(&self.methodarea),
+						)?;
+						value = JvmValue::Reference(
+							JvmReferenceType::Class(format!("java/lang/String")),
+							JvmReferenceTargetType::Object(Arc::new(Mutex::new(string_object))),
+							0,
+						);
+					}
+					_ => {
+						return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
+							class.get_class_name().unwrap(),
+							"ConstantValue".to_string(),
+							constant_index,
+						))
+						.into_error());
+					}
+				}
+			}
+
+			if let Ok(mut field_value) = field.value.lock() {
+				*field_value = Some(value);
+			} else {
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
+					"Static field.".to_string(),
+					"prepare_static_fields".to_string(),
+				))
+				.into_error());
+			};
+		}
+		Ok(())
+	}
+
+	fn execute_athrow(&mut self, frame: &mut Frame) -> OpcodeResult {
+		match frame.operand_stack.pop() {
+			Some(ref exception) if exception.is_null() => {
+				null_pointer(format!("Cannot throw exception"))
+			}
+			Some(exception @ JvmValue::Reference(_, JvmReferenceTargetType::Object(_), _)) => {
+				OpcodeResult::Throw(exception)
+			}
+			Some(_) => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("athrow"),
+						format!("reference to an object"),
+					))
+					.into_error(),
+				);
+			}
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+						"athrow"
+					)))
+					.into_error(),
+				);
+			}
+		}
+	}
+
+	/*
+	 * Enter (or exit) the monitor that a synchronized method holds:
+	 * _receiver_'s or, without one, _class_'s. Returns false when the
+	 * monitor belongs to another thread or, on exit, to no thread.
+	 */
+	fn update_method_monitor(
+		&mut self,
+		class: &Rc<Class>,
+		receiver: &Option<JvmValue>,
+		enter: bool,
+	) -> Result<bool, JvmError> {
+		let thread = thread::current().id();
+		let enter_or_exit = |monitor: &mut Monitor| {
+			if enter {
+				monitor.enter(thread)
+			} else {
+				monitor.exit(thread)
+			}
+		};
+
+		let updated = match receiver {
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _)) => {
+				match object.lock() {
+					Ok(mut object) => Some(enter_or_exit(object.monitor_mut())),
+					_ => None,
+				}
+			}
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Array(array), _)) => {
+				match array.lock() {
+					Ok(mut array) => Some(enter_or_exit(array.monitor_mut())),
+					_ => None,
+				}
+			}
+			Some(_) => {
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					format!("synchronized method"),
+					format!("reference to an object"),
+				))
+				.into_error());
+			}
+			None => {
+				let class_name = class.get_class_name().unwrap();
+				let loaded_class = match self.methodarea.lock() {
+					Ok(mut methodarea) => (*methodarea).get_loaded_class(&class_name),
+					_ => None,
+				};
+				match loaded_class {
+					Some(loaded_class) => match loaded_class.monitor.lock() {
+						Ok(mut monitor) => Some(enter_or_exit(&mut monitor)),
+						_ => None,
+					},
+					None => None,
+				}
+			}
+		};
+
+		match updated {
+			Some(updated) => Ok(updated),
+			None => Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("monitor"),
+				format!("update_method_monitor"),
+			))
+			.into_error()),
+		}
+	}
+
+	/*
+	 * Enter (or exit) the monitor of the object on top of the stack.
+	 * There is only one thread, so no other thread can own a monitor
+	 * that this one tries to enter.
+	 */
+	fn execute_monitor(
+		&mut self,
+		instruction: &str,
+		enter: bool,
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let thread = thread::current().id();
+		let enter_or_exit = |monitor: &mut Monitor| {
+			if enter {
+				monitor.enter(thread)
+			} else {
+				monitor.exit(thread)
+			}
+		};
+
+		let updated = match frame.operand_stack.pop() {
+			Some(ref object) if object.is_null() => {
+				return null_pointer(format!(
+					"Cannot {} synchronized block",
+					if enter { "enter" } else { "exit" }
+				));
+			}
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _)) => {
+				match object.lock() {
+					Ok(mut object) => Some(enter_or_exit(object.monitor_mut())),
+					_ => None,
+				}
+			}
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Array(array), _)) => {
+				match array.lock() {
+					Ok(mut array) => Some(enter_or_exit(array.monitor_mut())),
+					_ => None,
+				}
+			}
+			Some(_) => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						instruction.to_string(),
+						format!("reference to an object"),
+					))
+					.into_error(),
+				);
+			}
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::RequiredStackValueNotFound(
+						instruction.to_string(),
+					))
+					.into_error(),
+				);
+			}
+		};
+
+		match updated {
+			Some(true) => OpcodeResult::Incr(1),
+			Some(false) if enter => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::NotImplemented(format!(
+						"Contended monitors"
+					)))
+					.into_error(),
+				);
+			}
+			/*
+			 * The monitor belongs to another thread or to no thread.
+			 */
+			Some(false) => {
+				OpcodeResult::Exception(format!("java/lang/IllegalMonitorStateException"), None)
+			}
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::CouldNotLock(
+						format!("Object"),
+						instruction.to_string(),
+					))
+					.into_error(),
+				);
+			}
+		}
+	}
+
+	/*
+	 * Push a new object or, when the class cannot be instantiated,
+	 * throw.
+	 */
+	fn execute_new(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
+		let class = source_frame.class().unwrap();
+		let constant_pool = class.get_constant_pool_ref();
+		let instantiated_class_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
+
+		let instantiated_class_name = match constant_pool.get_class_name(instantiated_class_index) {
+			Ok(instantiated_class_name) => instantiated_class_name,
+			Err(error) => {
+				return OpcodeResult::Error(error.into_error());
+			}
+		};
+
+		Debug(
+			format!("Make a new {}.", instantiated_class_name),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+
+		let instantiated_class: Option<Rc<Class>>;
+		if let Ok(mut methodarea) = self.methodarea.lock() {
+			(*methodarea).maybe_load_class(&instantiated_class_name);
+			instantiated_class = (*methodarea).get_class_rc(&instantiated_class_name);
+		} else {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::CouldNotLock(
+					"Method Area.".to_string(),
+					"execute_new".to_string(),
+				))
+				.into_error(),
+			);
+		}
+		if let Some(instantiated_class) = instantiated_class {
+			if instantiated_class.is_interface() || instantiated_class.is_abstract() {
+				return OpcodeResult::Exception(format!("java/lang/InstantiationError"), None);
+			}
+			if let Err(error) = self.maybe_initialize_class(&instantiated_class) {
+				return OpcodeResult::Error(error);
+			}
+
+			let mut object = JvmObject::new(instantiated_class, self.debug_level.clone());
+
+			if let Err(error) = object.instantiate(self, Arc::clone(&self.methodarea)) {
+				return OpcodeResult::Error(error);
+			}
+			match self.allocate(Some(object.size())) {
+				Ok(allocation) => object.set_allocation(allocation),
+				Err(result) => return result,
+			}
+			Debug(
+				format!("Made a new {}.", instantiated_class_name),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+
+			Debug(
+				format!("hierarchy: {}", object.hierarchy()),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+
+			source_frame.operand_stack.push(JvmValue::Reference(
+				JvmReferenceType::Class(instantiated_class_name.to_string()),
+				JvmReferenceTargetType::Object(Arc::new(Mutex::new(object))),
+				0,
+			));
+			Debug(
+				format!("frame after new: {}", source_frame),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+			OpcodeResult::Incr(3)
+		} else {
+			OpcodeResult::Error(
+				FatalError::new(FatalErrorType::ClassNotLoaded(
+					instantiated_class_name.to_string(),
+				))
+				.into_error(),
+			)
+		}
+	}
+
+	fn execute_getstatic(
+		&mut self,
+		bytes: &[u8],
+		source_frame: &mut Frame,
+	) -> Result<(), JvmError> {
+		let class = source_frame.class().unwrap();
+		let field_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
+
+		if let Some((field_class_name, field_name, field_type)) =
+			class.resolve_field_ref(field_index)
+		{
+			Debug(
+				format!(
+					"get static: {}.{} ({})",
+					field_class_name, field_name, field_type
+				),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+			let mut resolved_field_class: Option<Rc<Class>> = None;
+
+			if let Ok(mut methodarea) = self.methodarea.lock() {
+				(*methodarea).maybe_load_class(&field_class_name);
 				if let Some(field_class) = (*methodarea).get_class_rc(&field_class_name) {
 					if let Some(resolved_field_class_name) =
 						(*methodarea).resolve_field(&field_class, &field_name, &field_type)
 					{
-						// TODO: I think that this should be resolved_field_class_name
-						// As it stands now, this is a repetition of the maybe_load_class
-						// on line 2023.
-						(*methodarea).maybe_load_class(&field_class_name);
+						(*methodarea).maybe_load_class(&resolved_field_class_name);
 						resolved_field_class =
 							(*methodarea).get_class_rc(&resolved_field_class_name);
 					}
 				}
 			} else {
-				FatalError::new(FatalErrorType::CouldNotLock(
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
 					"Method Area.".to_string(),
 					"execute_getstatic".to_string(),
 				))
-				.call();
+				.into_error());
 			}
 
 			if let Some(resolved_field_class) = resolved_field_class {
-				self.maybe_initialize_class(&resolved_field_class);
+				self.maybe_initialize_class(&resolved_field_class)?;
 				let resolved_field_class_constant_pool =
 					resolved_field_class.get_constant_pool_ref();
 				if let Some(_field_ref) = resolved_field_class.get_fields_ref().get_field_ref(
@@ -2123,39 +4878,52 @@ impl JvmThread {
 				) {
 					if let Ok(_field_ref_value) = _field_ref.value.lock() {
 						if let Some(field_ref_value) = (*_field_ref_value).clone() {
-							source_frame.operand_stack.push(field_ref_value);
+							source_frame
+								.operand_stack
+								.push(field_ref_value.to_computational_type());
 						} else {
-							FatalError::new(FatalErrorType::UninitializedField(
+							return Err(FatalError::new(FatalErrorType::UninitializedField(
 								field_name,
 								field_class_name,
 							))
-							.call();
+							.into_error());
 						}
 					} else {
-						FatalError::new(FatalErrorType::CouldNotLock(
+						return Err(FatalError::new(FatalErrorType::CouldNotLock(
 							field_name,
-							"PutStatic".to_string(),
+							"GetStatic".to_string(),
 						))
-						.call();
+						.into_error());
 					}
 				} else {
-					FatalError::new(FatalErrorType::FieldNotFound(field_name, field_class_name))
-						.call();
+					return Err(FatalError::new(FatalErrorType::FieldNotFound(
+						field_name,
+						field_class_name,
+					))
+					.into_error());
 				}
 			} else {
-				FatalError::new(FatalErrorType::ClassResolutionFailed(field_class_name)).call();
+				return Err(FatalError::new(FatalErrorType::ClassResolutionFailed(
+					field_class_name,
+				))
+				.into_error());
 			}
 		} else {
-			FatalError::new(FatalErrorType::InvalidConstantReference(
+			return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
 				class.get_class_name().unwrap(),
 				"FieldRef".to_string(),
 				field_index as u16,
 			))
-			.call();
+			.into_error());
 		}
+		Ok(())
 	}
 
-	fn execute_putstatic(&mut self, bytes: &[u8], source_frame: &mut Frame) {
+	fn execute_putstatic(
+		&mut self,
+		bytes: &[u8],
+		source_frame: &mut Frame,
+	) -> Result<(), JvmError> {
 		let class = source_frame.class().unwrap();
 		let field_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
 
@@ -2184,15 +4952,15 @@ impl JvmThread {
 					}
 				}
 			} else {
-				FatalError::new(FatalErrorType::CouldNotLock(
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
 					"Method Area.".to_string(),
 					"execute_putstatic".to_string(),
 				))
-				.call();
+				.into_error());
 			}
 
 			if let Some(resolved_field_class) = resolved_field_class {
-				self.maybe_initialize_class(&resolved_field_class);
+				self.maybe_initialize_class(&resolved_field_class)?;
 				let resolved_field_class_constant_pool =
 					resolved_field_class.get_constant_pool_ref();
 				if let Some(field_ref) = resolved_field_class.get_fields_ref().get_field_ref(
@@ -2201,275 +4969,281 @@ impl JvmThread {
 					resolved_field_class_constant_pool,
 				) {
 					if let Some(top) = source_frame.operand_stack.pop() {
+						let top = match top {
+							/*
+							 * A boolean is stored as the low bit of the int.
+							 */
+							JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, access)
+								if field_type == "Z" =>
+							{
+								JvmValue::Primitive(JvmPrimitiveType::Integer, 0, value & 1, access)
+							}
+							top => top,
+						};
 						if let Ok(mut field_value) = field_ref.value.lock() {
 							*field_value = Some(top);
+						} else {
+							return Err(FatalError::new(FatalErrorType::CouldNotLock(
+								field_name,
+								"PutStatic".to_string(),
+							))
+							.into_error());
 						}
 					} else {
-						FatalError::new(FatalErrorType::CouldNotLock(
-							field_name,
-							"GetStatic".to_string(),
+						return Err(FatalError::new(FatalErrorType::RequiredStackValueNotFound(
+							format!("putstatic"),
 						))
-						.call();
+						.into_error());
 					}
 				} else {
-					FatalError::new(FatalErrorType::FieldNotFound(field_name, field_class_name))
-						.call();
+					return Err(FatalError::new(FatalErrorType::FieldNotFound(
+						field_name,
+						field_class_name,
+					))
+					.into_error());
 				}
 			} else {
-				FatalError::new(FatalErrorType::ClassResolutionFailed(field_class_name)).call();
+				return Err(FatalError::new(FatalErrorType::ClassResolutionFailed(
+					field_class_name,
+				))
+				.into_error());
 			}
 		} else {
-			FatalError::new(FatalErrorType::InvalidConstantReference(
+			return Err(FatalError::new(FatalErrorType::InvalidConstantReference(
 				class.get_class_name().unwrap(),
 				"FieldRef".to_string(),
 				field_index as u16,
 			))
-			.call();
+			.into_error());
 		}
+		Ok(())
 	}
 
-	fn execute_getfield(&mut self, index: u16, frame: &mut Frame) {
-		/*
-		 * 1.
-		 */
+	/*
+	 * Resolve an instance field to the name of the class that declares
+	 * it. That is where it lives in an object (see JvmObject::get_field).
+	 */
+	fn resolve_instance_field(
+		&mut self,
+		instruction: &str,
+		field_class_name: &String,
+		field_name: &String,
+		field_type: &String,
+	) -> Result<String, JvmError> {
+		let mut resolved_field_class_name: Option<String> = None;
+		if let Ok(mut methodarea) = self.methodarea.lock() {
+			(*methodarea).maybe_load_class(field_class_name);
+			if let Some(field_class) = (*methodarea).get_class_rc(field_class_name) {
+				resolved_field_class_name =
+					(*methodarea).resolve_field(&field_class, field_name, field_type);
+			}
+		} else {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				"Method Area.".to_string(),
+				instruction.to_string(),
+			))
+			.into_error());
+		}
+		resolved_field_class_name.ok_or_else(|| {
+			FatalError::new(FatalErrorType::FieldNotFound(
+				field_name.clone(),
+				field_class_name.clone(),
+			))
+			.into_error()
+		})
+	}
+
+	fn execute_getfield(&mut self, index: u16, frame: &mut Frame) -> OpcodeResult {
 		let class = frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
 		let field_index = index as usize;
 
-		let objectref = frame.operand_stack.pop();
-
-		if let Some(objectref) = objectref {
-			if let Constant::Fieldref(_, class_ref, name_and_type_ref) =
-				constant_pool.get_constant_ref(field_index)
-			{
-				// Let's get the name and the type of the field!
-
-				// Let's get the class name
-				let mut field_class_name = None;
-				let mut field_name = None;
-				let mut field_type = None;
-
-				if let Constant::Class(_, class_name_index) =
-					constant_pool.get_constant_ref(*class_ref as usize)
-				{
-					if let Constant::Utf8(_, _, _, class_name) =
-						constant_pool.get_constant_ref(*class_name_index as usize)
-					{
-						// class_name is the name of the class where the field exists.
-						field_class_name = Some(class_name);
+		if let Some((field_class_name, field_name, field_type)) =
+			class.resolve_field_ref(field_index)
+		{
+			Debug(
+				format!(
+					"getfield: {}.{} ({})",
+					field_class_name, field_name, field_type
+				),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+			match self.resolve_instance_field(
+				"getfield",
+				&field_class_name,
+				&field_name,
+				&field_type,
+			) {
+				Err(error) => return OpcodeResult::Error(error),
+				Ok(resolved_field_class_name) => {
+					// Value on the top of the stack must be a reference.
+					match frame.operand_stack.pop() {
+						Some(JvmValue::Reference(
+							_,
+							JvmReferenceTargetType::Object(objectref_object),
+							_,
+						)) => {
+							if let Ok(objectref_object) = objectref_object.lock() {
+								if let Some(field_value) = objectref_object
+									.get_field(&resolved_field_class_name, &field_name)
+								{
+									frame
+										.operand_stack
+										.push(field_value.to_computational_type());
+								} else {
+									return OpcodeResult::Error(
+										FatalError::new(FatalErrorType::FieldNotFound(
+											field_name,
+											resolved_field_class_name,
+										))
+										.into_error(),
+									);
+								}
+							} else {
+								return OpcodeResult::Error(
+									FatalError::new(FatalErrorType::CouldNotLock(
+										field_class_name,
+										"getfield".to_string(),
+									))
+									.into_error(),
+								);
+							}
+						}
+						Some(JvmValue::Reference(_, JvmReferenceTargetType::Null, _)) => {
+							return null_pointer(format!("Cannot read field \"{}\"", field_name));
+						}
+						_ => {
+							return OpcodeResult::Error(
+								FatalError::new(FatalErrorType::WrongType(
+									format!("getfield"),
+									format!("Reference"),
+								))
+								.into_error(),
+							);
+						}
 					}
 				}
-
-				if let Constant::NameAndType(_, field_name_index, field_type_index) =
-					constant_pool.get_constant_ref(*name_and_type_ref as usize)
-				{
-					if let Constant::Utf8(_, _, _, field_name_constant) =
-						constant_pool.get_constant_ref(*field_name_index as usize)
-					{
-						field_name = Some(field_name_constant);
-					}
-					if let Constant::Utf8(_, _, _, field_type_constant) =
-						constant_pool.get_constant_ref(*field_type_index as usize)
-					{
-						field_type = Some(field_type_constant);
-					}
-				}
-
-				Debug(
-					format!("field_class_name: {}", field_class_name.unwrap()),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				Debug(
-					format!("field_name: {}", field_name.unwrap()),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				Debug(
-					format!("field_type: {}", field_type.unwrap()),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-
-				// Value on the top of the stack must be a reference.
-				if let JvmValue::Reference(
-					JvmReferenceType::Class(objectref_class_name),
-					JvmReferenceTargetType::Object(objectref_object),
-					_,
-				) = objectref
-				{
-					// Lock the object on the top of the stack so that it can be mutated.
-					if let Ok(mut objectref_object) = objectref_object.lock() {
-						// We lock the methodarea because is_type_of may have to load superclasses to check whether
-						// the type of the field matches the type of the object on the top of the stack.
-						// TODO: This could be done more precisely. I.e., do not lock the methodarea until
-						// we have to walk up the object's class hierarchy.
-						if let Ok(mut methodarea) = self.methodarea.lock() {
-							// Check whether the class of the object on the top of the stack (or its superclasses)
-							// matches the class of the field that is being gotten.
-							if objectref_object
-								.get_class()
-								.is_type_of(field_class_name.unwrap(), &mut *methodarea)
-							{
-								if let Some(field_value) =
-									objectref_object.get_field(field_name.unwrap())
-								{
-									frame.operand_stack.push((*field_value).clone())
-								} else {
-									FatalError::new(FatalErrorType::FieldNotFound(
-										field_name.unwrap().clone(),
-										field_class_name.unwrap().clone(),
-									))
-									.call();
-								}
-							} else {
-								FatalError::new(FatalErrorType::WrongType(
-									format!("execute_getfield"),
-									field_class_name.unwrap().clone(),
-								))
-								.call();
-							}
-						} else {
-							FatalError::new(FatalErrorType::CouldNotLock(
-								"Method Area.".to_string(),
-								"get_field".to_string(),
-							))
-							.call();
-						}
-					} else {
-						FatalError::new(FatalErrorType::CouldNotLock(
-							objectref_class_name,
-							"get_field".to_string(),
-						))
-						.call();
-					}
-				} else {
-					FatalError::new(FatalErrorType::WrongType(
-						format!("execute_getfield"),
-						format!("Reference"),
-					))
-					.call();
-				}
 			}
+		} else {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Fieldref".to_string(),
+					index,
+				))
+				.into_error(),
+			);
 		}
+		OpcodeResult::Incr(3)
 	}
 
-	fn execute_putfield(&mut self, index: u16, frame: &mut Frame) {
+	fn execute_putfield(&mut self, index: u16, frame: &mut Frame) -> OpcodeResult {
 		// GENERAL: See comments in execute_getfield -- the skeleton of the functions' operations
 		// are the same.
 		let class = frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
 		let field_index = index as usize;
 
-		let value = frame.operand_stack.pop();
-		let objectref = frame.operand_stack.pop();
-
-		if let Some(value) = value {
-			if let Some(objectref) = objectref {
-				if let Constant::Fieldref(_, class_ref, name_and_type_ref) =
-					constant_pool.get_constant_ref(field_index)
-				{
-					let mut field_class_name = None;
-					let mut field_name = None;
-					let mut field_type = None;
-
-					if let Constant::Class(_, class_name_index) =
-						constant_pool.get_constant_ref(*class_ref as usize)
-					{
-						if let Constant::Utf8(_, _, _, class_name) =
-							constant_pool.get_constant_ref(*class_name_index as usize)
-						{
-							field_class_name = Some(class_name);
-						}
-					}
-
-					if let Constant::NameAndType(_, field_name_index, field_type_index) =
-						constant_pool.get_constant_ref(*name_and_type_ref as usize)
-					{
-						if let Constant::Utf8(_, _, _, field_name_constant) =
-							constant_pool.get_constant_ref(*field_name_index as usize)
+		if let Some((field_class_name, field_name, field_type)) =
+			class.resolve_field_ref(field_index)
+		{
+			Debug(
+				format!(
+					"putfield: {}.{} ({})",
+					field_class_name, field_name, field_type
+				),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+			match self.resolve_instance_field(
+				"putfield",
+				&field_class_name,
+				&field_name,
+				&field_type,
+			) {
+				Err(error) => return OpcodeResult::Error(error),
+				Ok(resolved_field_class_name) => {
+					let value = frame.operand_stack.pop();
+					let objectref = frame.operand_stack.pop();
+
+					let value = match value {
+						/*
+						 * A boolean is stored as the low bit of the int.
+						 */
+						Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, access))
+							if field_type == "Z" =>
 						{
-							field_name = Some(field_name_constant);
+							JvmValue::Primitive(JvmPrimitiveType::Integer, 0, value & 1, access)
 						}
-						if let Constant::Utf8(_, _, _, field_type_constant) =
-							constant_pool.get_constant_ref(*field_type_index as usize)
-						{
-							field_type = Some(field_type_constant);
+						Some(value) => value,
+						None => {
+							return OpcodeResult::Error(
+								FatalError::new(FatalErrorType::RequiredStackValueNotFound(
+									format!("putfield"),
+								))
+								.into_error(),
+							);
 						}
-					}
-
-					Debug(
-						format!("field_class_name: {}", field_class_name.unwrap()),
-						&self.debug_level,
-						DebugLevel::Info,
-					);
-					Debug(
-						format!("field_name: {}", field_name.unwrap()),
-						&self.debug_level,
-						DebugLevel::Info,
-					);
-					Debug(
-						format!("field_type: {}", field_type.unwrap()),
-						&self.debug_level,
-						DebugLevel::Info,
-					);
-
-					if let JvmValue::Reference(
-						JvmReferenceType::Class(objectref_class_name),
-						JvmReferenceTargetType::Object(objectref_object),
-						_,
-					) = objectref
-					{
-						Debug(
-							format!("objectref_class_name: {}", objectref_class_name),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
-						if let Ok(mut objectref_object) = objectref_object.lock() {
-							if let Ok(mut methodarea) = self.methodarea.lock() {
-								if objectref_object
-									.get_class()
-									.is_type_of(field_class_name.unwrap(), &mut *methodarea)
-								{
-									objectref_object.set_field(field_name.unwrap(), Rc::new(value))
-								} else {
-									FatalError::new(FatalErrorType::WrongType(
-										format!("execute_putfield"),
-										field_class_name.unwrap().clone(),
-									))
-									.call();
+					};
+
+					match objectref {
+						Some(JvmValue::Reference(
+							_,
+							JvmReferenceTargetType::Object(objectref_object),
+							_,
+						)) => {
+							if let Ok(mut objectref_object) = objectref_object.lock() {
+								if !objectref_object.set_field(
+									&resolved_field_class_name,
+									&field_name,
+									Rc::new(value),
+								) {
+									return OpcodeResult::Error(
+										FatalError::new(FatalErrorType::FieldNotFound(
+											field_name,
+											resolved_field_class_name,
+										))
+										.into_error(),
+									);
 								}
 							} else {
-								FatalError::new(FatalErrorType::CouldNotLock(
-									"Method Area.".to_string(),
-									"put_field".to_string(),
-								))
-								.call();
+								return OpcodeResult::Error(
+									FatalError::new(FatalErrorType::CouldNotLock(
+										field_class_name,
+										"putfield".to_string(),
+									))
+									.into_error(),
+								);
 							}
-						} else {
-							FatalError::new(FatalErrorType::CouldNotLock(
-								objectref_class_name,
-								"put_field".to_string(),
-							))
-							.call();
 						}
-					} else {
-						FatalError::new(FatalErrorType::WrongType(
-							format!("execute_putfield"),
-							format!("Reference"),
-						))
-						.call();
+						Some(JvmValue::Reference(_, JvmReferenceTargetType::Null, _)) => {
+							return null_pointer(format!("Cannot assign field \"{}\"", field_name));
+						}
+						_ => {
+							return OpcodeResult::Error(
+								FatalError::new(FatalErrorType::WrongType(
+									format!("putfield"),
+									format!("Reference"),
+								))
+								.into_error(),
+							);
+						}
 					}
 				}
 			}
+		} else {
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::InvalidConstantReference(
+					class.get_class_name().unwrap(),
+					"Fieldref".to_string(),
+					index,
+				))
+				.into_error(),
+			);
 		}
+		OpcodeResult::Incr(3)
 	}
 
-	fn execute_invokevirtual(
-		&mut self,
-		bytes: &[u8],
-		source_frame: &mut Frame,
-	) -> Option<OpcodeResult> {
+	fn execute_invokevirtual(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
 		let class = source_frame.class().unwrap();
 		let _constant_pool = class.get_constant_pool_ref();
 		let method_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
@@ -2480,8 +5254,7 @@ impl JvmThread {
 		if let Some((method_name, method_type, invoked_class_name)) =
 			class.resolve_method_ref(method_index)
 		{
-			let mut invoked_class: Option<Rc<Class>> = None;
-			let mut resolved_method: Option<Rc<Method>> = None;
+			let mut resolved_class_method: Option<(Rc<Class>, Rc<Method>)> = None;
 			let mut invoked_frame: Frame = Frame::new();
 
 			Debug(
@@ -2490,12 +5263,23 @@ impl JvmThread {
 				DebugLevel::Info,
 			);
 
+			if let Some(result) = self.invoke_native(
+				"invokevirtual",
+				&invoked_class_name,
+				&method_name,
+				&method_type,
+				true,
+				source_frame,
+			) {
+				return result;
+			}
+
 			// We know the names of the method (and its class) that we are supposed to execute.
 			// Now we need to resolve those so that we can actually execute them.
 			if let Ok(mut methodarea) = self.methodarea.lock() {
 				(*methodarea).maybe_load_class(&invoked_class_name);
-				invoked_class = (*methodarea).get_class_rc(&invoked_class_name);
-				resolved_method = if let Some(invoked_class) = &invoked_class {
+				let invoked_class = (*methodarea).get_class_rc(&invoked_class_name);
+				resolved_class_method = if let Some(invoked_class) = &invoked_class {
 					(*methodarea).resolve_method(&class, invoked_class, &method_name, &method_type)
 				} else {
 					None
@@ -2503,12 +5287,20 @@ impl JvmThread {
 			}
 
 			// If that resolution process was successful, we will have a class and a method.
-			if let (Some(invoked_class), Some(resolved_method)) = (invoked_class, resolved_method) {
+			if let Some((resolved_class, resolved_method)) = resolved_class_method {
 				let mut object_class_name: Option<String> = None;
 
-				if resolved_method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
-					// We know how to execute non-native methods.
-
+				/*
+				 * Select the method to run from the receiver's class unless
+				 * the resolved method is a private native. A native that is
+				 * not private (Object.hashCode, say) may be overridden there
+				 * by a method with code. When it is not, select_native has
+				 * already invoked its native, so the selection only ends at
+				 * a native that has no registered native (an error, below).
+				 * A private native cannot be overridden, so getting here
+				 * means that there is no native for it either.
+				 */
+				if !resolved_method.is_native() || !resolved_method.is_private() {
 					/*
 						* Let's build a frame! Values from the stack will become local variables when we start
 						* executing the invoked method.
@@ -2518,94 +5310,122 @@ impl JvmThread {
 						source_frame,
 						&mut invoked_frame,
 					) {
-						FatalError::new(FatalErrorType::NotEnough(
-							format!("invokevirtual"),
-							resolved_method.parameter_count,
-							format!("stack operands"),
-						))
-						.call();
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::NotEnough(
+								format!("invokevirtual"),
+								resolved_method.parameter_count,
+								format!("stack operands"),
+							))
+							.into_error(),
+						);
 					}
 					/*
 						* The first value on the stack is an object reference. It becomes
-						* the 0th local variable to the invoked method.
+						* the 0th local variable to the invoked method. Dispatch is on the
+						* class of the object itself, not on the type of the reference.
 						*/
 					if let Some(top) = source_frame.operand_stack.pop() {
-						if let JvmValue::Reference(JvmReferenceType::Class(ocn), _, _) = &top {
+						if top.is_null() {
+							return null_receiver(&invoked_class_name, &method_name);
+						}
+						if let JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) =
+							&top
+						{
+							if let Ok(object) = object.lock() {
+								object_class_name = (*object).get_class().get_class_name();
+							}
+							invoked_frame.locals.insert(0, top.clone());
+						} else if let JvmValue::Reference(JvmReferenceType::Class(ocn), _, _) = &top
+						{
 							object_class_name = Some(ocn.to_string());
-							invoked_frame.locals.insert(0, top);
+							invoked_frame.locals.insert(0, top.clone());
 						} else {
-							FatalError::new(FatalErrorType::WrongType(
-								format!("execute_invokevirtual"),
-								format!("Reference"),
-							))
-							.call();
+							return OpcodeResult::Error(
+								FatalError::new(FatalErrorType::WrongType(
+									format!("execute_invokevirtual"),
+									format!("Reference"),
+								))
+								.into_error(),
+							);
 						}
 					} else {
 						// Missing a reference on the top of the stack to an object upon which to invoke this method
-						FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
-							"Reference to an object."
-						)))
-						.call();
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+								"Reference to an object."
+							)))
+							.into_error(),
+						);
 					}
 
 					/*
 						* Check to see if the resolved method is private. If so, it's the one
 						* that we invoke.
 						*/
-					if ((MethodAccessFlags::Protected as u16) & resolved_method.access_flags) != 0 {
-						invoked_frame.class = Some(invoked_class);
-						if let Some(v) = self.execute_method(&resolved_method, invoked_frame) {
-							Debug(
-								format!("Returning from a method: {}", resolved_method.clone()),
-								&self.debug_level,
-								DebugLevel::Info,
-							);
-							return Some(OpcodeResult::Value(v));
-						} else {
-							FatalError::new(FatalErrorType::MethodExecutionFailed(method_name))
-								.call();
-						}
+					if resolved_method.is_private() {
+						invoked_frame.class = Some(resolved_class);
+						return OpcodeResult::Invoke(resolved_method, invoked_frame);
 					} else if let Some(object_class_name) = object_class_name {
 						let mut selected_class_method: Option<(Rc<Class>, Rc<Method>)> = None;
-						let mut object_class: Option<Rc<Class>> = None;
 
 						if let Ok(mut methodarea) = self.methodarea.lock() {
-							object_class = (*methodarea).get_class_rc(&object_class_name);
+							let object_class = (*methodarea).get_class_rc(&object_class_name);
 							selected_class_method = if let Some(object_class) = &object_class {
 								(*methodarea).select_method(
+									&class,
 									&object_class,
 									&method_name,
 									&method_type,
 								)
 							} else {
-								FatalError::new(FatalErrorType::MethodSelectionFailed).call();
-								None
+								return OpcodeResult::Error(
+									FatalError::new(FatalErrorType::MethodSelectionFailed)
+										.into_error(),
+								);
 							};
 						}
 
+						/*
+						 * Nothing selected or an abstract method selected means
+						 * that the receiver's class has no implementation.
+						 */
+						let selected_class_method = match selected_class_method {
+							Some((_, selected_method)) if selected_method.is_abstract() => None,
+							selected_class_method => selected_class_method,
+						};
+						if selected_class_method.is_none() {
+							return OpcodeResult::Exception(
+								format!("java/lang/AbstractMethodError"),
+								None,
+							);
+						}
 						if let Some((selected_class, selected_method)) = selected_class_method {
-							invoked_frame.class = Some(selected_class);
-							if let Some(v) = self.execute_method(&selected_method, invoked_frame) {
-								Debug(
-									format!("Returning from a method: {}", resolved_method.clone()),
-									&self.debug_level,
-									DebugLevel::Info,
+							if selected_method.is_native() {
+								// There is no native registered for it.
+								return OpcodeResult::Error(
+									FatalError::new(FatalErrorType::NotImplemented(
+										"Native methods".to_string(),
+									))
+									.into_error(),
 								);
-								return Some(OpcodeResult::Value(v));
-							} else {
-								FatalError::new(FatalErrorType::MethodExecutionFailed(method_name))
-									.call();
 							}
+							invoked_frame.class = Some(selected_class);
+							return OpcodeResult::Invoke(selected_method, invoked_frame);
 						}
 					} else {
-						FatalError::new(FatalErrorType::MethodExecutionFailed(method_name)).call();
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::MethodExecutionFailed(method_name))
+								.into_error(),
+						);
 					}
 				} else {
-					// We do not know how to execute native methods.
-					NonFatalError::new(NonFatalErrorType::NotImplemented(
-						"Native methods".to_string(),
-					))
-					.call();
+					// A private native without a registered native.
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::NotImplemented(
+							"Native methods".to_string(),
+						))
+						.into_error(),
+					);
 				}
 			}
 			/*
@@ -2613,74 +5433,255 @@ impl JvmThread {
 			 * how to qualify it.
 			 */
 		}
-		FatalError::new(FatalErrorType::MethodResolutionFailed).call();
-		None
+		OpcodeResult::Error(FatalError::new(FatalErrorType::MethodResolutionFailed).into_error())
+	}
+
+	/*
+	 * Have the running native throw an exception of class
+	 * _class_name_ (with _message_, if there is one) when it returns
+	 * (its return value is dropped).
+	 */
+	pub fn set_pending_exception(&mut self, class_name: &str, message: Option<String>) {
+		self.pending_exception = Some((class_name.to_string(), message));
 	}
 
-	fn execute_invokespecial(
+	/*
+	 * Invoke the native registered for _class_name_._method_name_ (if
+	 * there is one): pop its arguments (and receiver, when
+	 * _has_receiver_) and push what it returns. Returns None when
+	 * there is no such native.
+	 */
+	fn invoke_native(
 		&mut self,
-		bytes: &[u8],
+		instruction: &str,
+		class_name: &String,
+		method_name: &String,
+		method_type: &String,
+		has_receiver: bool,
 		source_frame: &mut Frame,
 	) -> Option<OpcodeResult> {
+		let native = if instruction == "invokevirtual" {
+			self.select_native(class_name, method_name, method_type, source_frame)?
+		} else {
+			self.natives.get(class_name, method_name, method_type)?
+		};
+
+		Debug(
+			format!(
+				"Invoke Native: {}.{}{}",
+				class_name, method_name, method_type
+			),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+
+		let arguments =
+			match self.pop_arguments(instruction, method_type, has_receiver, source_frame) {
+				Ok(arguments) => arguments,
+				Err(error) => return Some(OpcodeResult::Error(error)),
+			};
+		if has_receiver && arguments[0].is_null() {
+			return Some(null_receiver(class_name, method_name));
+		}
+
+		Some(self.call_native(&native, &arguments))
+	}
+
+	/*
+	 * Select the native that invokevirtual invokes: walk up from the
+	 * receiver's class to the first class that either has a native for
+	 * the method or declares it. A method declared in a class file
+	 * overrides the natives of its superclasses (Object.equals, say).
+	 * When the receiver's class is unknown, fall back to the native of
+	 * _class_name_ itself.
+	 */
+	fn select_native(
+		&self,
+		class_name: &String,
+		method_name: &String,
+		method_type: &String,
+		source_frame: &Frame,
+	) -> Option<Native> {
+		let parameter_count = MethodDescriptor::parse(method_type.as_bytes())
+			.map(|descriptor| descriptor.parameter_types.len())
+			.unwrap_or(0);
+		let receiver = source_frame
+			.operand_stack
+			.len()
+			.checked_sub(parameter_count + 1)
+			.and_then(|index| source_frame.operand_stack.get(index));
+		let mut selected_class_name =
+			match receiver.and_then(|receiver| self.runtime_type(receiver).ok()) {
+				Some(JvmType::Reference(JvmReferenceType::Class(receiver_class_name))) => {
+					receiver_class_name
+				}
+				_ => return self.natives.get(class_name, method_name, method_type),
+			};
+
+		loop {
+			if let Some(native) = self
+				.natives
+				.get(&selected_class_name, method_name, method_type)
+			{
+				return Some(native);
+			}
+			let mut selected_class: Option<Rc<Class>> = None;
+			if let Ok(mut methodarea) = self.methodarea.lock() {
+				selected_class = (*methodarea).maybe_load_class(&selected_class_name);
+			}
+			let selected_class = match selected_class {
+				Some(selected_class) => selected_class,
+				None => return self.natives.get(class_name, method_name, method_type),
+			};
+			if let Some(method) =
+				selected_class.get_method_rc_by_name_and_type(method_name, method_type)
+			{
+				if method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
+					return None;
+				}
+			}
+			selected_class_name = selected_class.resolve_superclass()?;
+		}
+	}
+
+	/*
+	 * Pop the arguments of a method of type _method_type_ (and its
+	 * receiver first, when _has_receiver_) off the operand stack.
+	 */
+	fn pop_arguments(
+		&self,
+		instruction: &str,
+		method_type: &String,
+		has_receiver: bool,
+		source_frame: &mut Frame,
+	) -> Result<Vec<Rc<JvmValue>>, JvmError> {
+		let parameter_count = match MethodDescriptor::parse(method_type.as_bytes()) {
+			Some(descriptor) => descriptor.parameter_types.len(),
+			None => {
+				return Err(FatalError::new(FatalErrorType::InvalidMethodDescriptor).into_error());
+			}
+		};
+		let argument_count = parameter_count + if has_receiver { 1 } else { 0 };
+		if source_frame.operand_stack.len() < argument_count {
+			return Err(FatalError::new(FatalErrorType::NotEnough(
+				instruction.to_string(),
+				argument_count,
+				format!("stack operands"),
+			))
+			.into_error());
+		}
+		Ok(source_frame
+			.operand_stack
+			.split_off(source_frame.operand_stack.len() - argument_count)
+			.into_iter()
+			.map(|argument| Rc::new(argument))
+			.collect())
+	}
+
+	/*
+	 * Run _native_ and turn what it returns (or throws) into the
+	 * result of the invocation.
+	 */
+	fn call_native(&mut self, native: &Native, arguments: &[Rc<JvmValue>]) -> OpcodeResult {
+		let result = native(self, arguments);
+		let pending_exception = self.pending_exception.take();
+		let result = match result {
+			Ok(result) => result,
+			Err(error) => return OpcodeResult::Error(error),
+		};
+		if let Some((exception, message)) = pending_exception {
+			return OpcodeResult::Exception(exception, message);
+		}
+		OpcodeResult::Return(match result {
+			Some(value) => (*value).clone(),
+			None => JvmValue::Primitive(JvmPrimitiveType::Void, 0, 0, 0),
+		})
+	}
+
+	fn execute_invokespecial(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
 		let class = source_frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
 		let method_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
 
 		if let Some((method_name, method_type, invoked_class_name)) =
 			class.resolve_method_ref(method_index)
 		{
 			let mut invoked_class: Option<Rc<Class>> = None;
-			let mut resolved_method: Option<Rc<Method>> = None;
+			let mut selected_class_method: Option<(Rc<Class>, Rc<Method>)> = None;
 
 			Debug(
 				format!("Invoke Special: {}.{}", invoked_class_name, method_name),
 				&self.debug_level,
 				DebugLevel::Info,
 			);
+			if let Some(result) = self.invoke_native(
+				"invokespecial",
+				&invoked_class_name,
+				&method_name,
+				&method_type,
+				true,
+				source_frame,
+			) {
+				return result;
+			}
 
 			if let Ok(mut methodarea) = self.methodarea.lock() {
 				(*methodarea).maybe_load_class(&invoked_class_name);
 				invoked_class = (*methodarea).get_class_rc(&invoked_class_name);
-				resolved_method = if let Some(invoked_class) = &invoked_class {
+				let resolved_class_method = if let Some(invoked_class) = &invoked_class {
 					(*methodarea).resolve_method(&class, &invoked_class, &method_name, &method_type)
 				} else {
 					None
+				};
+
+				/*
+				 * The resolved method is selected for invocation unless all
+				 * of the following conditions are true:
+				 *
+				 * The ACC_SUPER flag (Table 4.1) is set for the current class.
+				 * The resolved method is not an instance initialization method (§2.9).
+				 * The class of the resolved method is a superclass of the current class.
+				 *
+				 * If they are, let C be the direct superclass of the current class
+				 * and look up the method in C and then its superclasses. That is the
+				 * same walk that method resolution does, so reuse it.
+				 */
+				if let Some((resolved_class, resolved_method)) = &resolved_class_method {
+					let resolved_class_name = resolved_class.get_class_name().unwrap();
+					if ((ClassAccessFlags::Super as u16) & class.access_flags) != 0
+						&& method_name != "<init>"
+						&& resolved_class_name != class.get_class_name().unwrap()
+						&& class.is_type_of(&resolved_class_name, &mut methodarea)
+					{
+						if let Some(superclass_name) = class.resolve_superclass() {
+							if let Some(superclass) = (*methodarea).get_class_rc(&superclass_name) {
+								selected_class_method = (*methodarea).resolve_method(
+									&class,
+									&superclass,
+									&method_name,
+									&method_type,
+								);
+							}
+						}
+					} else {
+						selected_class_method =
+							Some((Rc::clone(resolved_class), Rc::clone(resolved_method)));
+					}
 				}
 			}
 
-			if let (Some(invoked_class), Some(resolved_method)) = (invoked_class, resolved_method) {
-				if ((MethodAccessFlags::Protected as u16) & resolved_method.access_flags) != 0 {
-					FatalError::new(FatalErrorType::Todo(format!("Finally, if the resolved method is protected (§4.6), and it is a member of a superclass of the current class, and the method is not declared in the same run-time package (§5.3) as the current class, then the class of objectref must be either the current class or a subclass of the current class."))).call();
+			if let Some((resolved_class, resolved_method)) = selected_class_method {
+				if resolved_method.is_abstract() {
+					return OpcodeResult::Exception(format!("java/lang/AbstractMethodError"), None);
 				}
-				/* TODO:
-					Next, the resolved method is selected for invocation unless all of the following conditions are true:
-
-				   The ACC_SUPER flag (Table 4.1) is set for the current class.
-
-					 The resolved method is not an instance initialization method (§2.9).
-				   ...
-				*/
-
-				if ((ClassAccessFlags::Super as u16) & class.access_flags) != 0
-					&& method_name != "<init>"
+				if ((MethodAccessFlags::Protected as u16) & resolved_method.access_flags) != 0
+					&& resolved_class.get_package_name() != class.get_package_name()
 				{
-					/*
-						...
-
-						The class of the resolved method is a superclass of the current class.
-
-						If the above conditions are true, the actual method to be invoked is selected by the following lookup procedure. Let C be the direct superclass of the current class:
-
-						If C contains a declaration for an instance method with the same name and descriptor as the resolved method, then this method will be invoked. The lookup procedure terminates.
-
-						Otherwise, if C has a superclass, this same lookup procedure is performed recursively using the direct superclass of C. The method to be invoked is the result of the recursive invocation of this lookup procedure.
+					return OpcodeResult::Error(FatalError::new(FatalErrorType::Todo(format!("Finally, if the resolved method is protected (§4.6), and it is a member of a superclass of the current class, and the method is not declared in the same run-time package (§5.3) as the current class, then the class of objectref must be either the current class or a subclass of the current class."))).into_error());
+				}
 
-						Otherwise, an AbstractMethodError is raised.
-					*/
-					FatalError::new(FatalErrorType::Todo(format!(""))).call();
-				} else if resolved_method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
+				if resolved_method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
 					let mut invoked_frame = Frame::new();
-					invoked_frame.class = Some(Rc::clone(&invoked_class));
+					invoked_frame.class = Some(Rc::clone(&resolved_class));
 
 					/*
 						* The other parameters are on the stack, too. Move the parameters
@@ -2691,26 +5692,41 @@ impl JvmThread {
 						source_frame,
 						&mut invoked_frame,
 					) {
-						FatalError::new(FatalErrorType::NotEnough(
-							"invokespecial".to_string(),
-							resolved_method.parameter_count,
-							"stack operands".to_string(),
-						))
-						.call();
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::NotEnough(
+								"invokespecial".to_string(),
+								resolved_method.parameter_count,
+								"stack operands".to_string(),
+							))
+							.into_error(),
+						);
 					}
 					/*
 						* The first value on the stack is an object reference. It becomes
 						* the 0th local variable to the special method.
 						*/
 					if let Some(top) = source_frame.operand_stack.pop() {
+						if top.is_null() {
+							return null_receiver(&invoked_class_name, &method_name);
+						}
 						if let JvmValue::Reference(_, _, _) = top {
 							invoked_frame.locals.insert(0, top);
 						} else {
-							/*
-								* TODO: This is a fatal error: The first value on
-								* the stack at this point must be a reference.
-								*/
+							return OpcodeResult::Error(
+								FatalError::new(FatalErrorType::WrongType(
+									format!("invokespecial"),
+									format!("Reference"),
+								))
+								.into_error(),
+							);
 						}
+					} else {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::RequiredStackValueNotFound(format!(
+								"invokespecial"
+							)))
+							.into_error(),
+						);
 					}
 
 					Debug(
@@ -2724,33 +5740,485 @@ impl JvmThread {
 						DebugLevel::Info,
 					);
 
-					if let Some(v) = self.execute_method(&resolved_method, invoked_frame) {
-						Debug(
-							format!("Returning from a method: {}", resolved_method.clone()),
-							&self.debug_level,
-							DebugLevel::Info,
-						);
-						return Some(OpcodeResult::Value(v));
-					} else {
-						FatalError::new(FatalErrorType::MethodExecutionFailed(method_name)).call();
-					}
+					return OpcodeResult::Invoke(resolved_method, invoked_frame);
 				} else {
 					// We do not know how to execute native methods.
-					FatalError::new(FatalErrorType::NotImplemented("Native methods".to_string()))
-						.call();
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::NotImplemented(
+							"Native methods".to_string(),
+						))
+						.into_error(),
+					);
+				}
+			}
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::ClassNotFound(invoked_class_name.clone()))
+					.into_error(),
+			);
+		}
+		OpcodeResult::Error(FatalError::new(FatalErrorType::MethodResolutionFailed).into_error())
+	}
+
+	fn execute_invokeinterface(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
+		let class = source_frame.class().unwrap();
+		let method_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
+
+		if let Some((method_name, method_type, interface_name)) =
+			class.resolve_method_ref(method_index)
+		{
+			Debug(
+				format!("Invoke Interface: {}.{}", interface_name, method_name),
+				&self.debug_level,
+				DebugLevel::Info,
+			);
+			if let Some(result) = self.invoke_native(
+				"invokeinterface",
+				&interface_name,
+				&method_name,
+				&method_type,
+				true,
+				source_frame,
+			) {
+				return result;
+			}
+
+			if let Ok(mut methodarea) = self.methodarea.lock() {
+				(*methodarea).maybe_load_class(&interface_name);
+			}
+
+			/*
+			 * The count operand is the number of argument slots, including
+			 * the receiver. That tells us where the receiver is without
+			 * having to parse the descriptor.
+			 */
+			let count = bytes[3] as usize;
+			let mut slots = 0usize;
+			let mut receiver: Option<JvmValue> = None;
+			for value in source_frame.operand_stack.iter().rev() {
+				slots += value.size();
+				if slots >= count {
+					receiver = Some(value.clone());
+					break;
+				}
+			}
+
+			let receiver = if let Some(receiver) = receiver {
+				receiver
+			} else {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::NotEnough(
+						format!("invokeinterface"),
+						count,
+						format!("stack operands"),
+					))
+					.into_error(),
+				);
+			};
+			if receiver.is_null() {
+				return null_receiver(&interface_name, &method_name);
+			}
+
+			/*
+			 * Invoking the method that a lambda implements invokes the
+			 * lambda's implementation method instead.
+			 */
+			if let Some(lambda) = lambda_of(&receiver) {
+				if lambda.method_name == method_name {
+					let arguments = match self.pop_arguments(
+						"invokeinterface",
+						&method_type,
+						true,
+						source_frame,
+					) {
+						Ok(arguments) => arguments,
+						Err(error) => return OpcodeResult::Error(error),
+					};
+					let arguments: Vec<JvmValue> = lambda
+						.captured
+						.iter()
+						.cloned()
+						.chain(arguments[1..].iter().map(|argument| (**argument).clone()))
+						.collect();
+					return self.invoke_method_handle(&class, &lambda.implementation, arguments);
+				}
+			}
+
+			let receiver_class = if let JvmValue::Reference(
+				_,
+				JvmReferenceTargetType::Object(object),
+				_,
+			) = &receiver
+			{
+				if let Ok(object) = object.lock() {
+					Some((*object).get_class())
+				} else {
+					None
+				}
+			} else {
+				None
+			};
+			let receiver_class = if let Some(receiver_class) = receiver_class {
+				receiver_class
+			} else {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("invokeinterface"),
+						format!("Reference to an object"),
+					))
+					.into_error(),
+				);
+			};
+
+			/*
+			 * Dispatch on the receiver's class, just like invokevirtual.
+			 * There is no implementation when nothing is selected or when
+			 * the selected method is abstract.
+			 */
+			let mut selected_class_method: Option<(Rc<Class>, Rc<Method>)> = None;
+			if let Ok(mut methodarea) = self.methodarea.lock() {
+				selected_class_method = (*methodarea).select_method(
+					&class,
+					&receiver_class,
+					&method_name,
+					&method_type,
+				);
+			}
+			let (selected_class, selected_method) = match selected_class_method {
+				Some((selected_class, selected_method)) => {
+					if selected_method.is_abstract() {
+						return OpcodeResult::Exception(
+							format!("java/lang/AbstractMethodError"),
+							None,
+						);
+					}
+					(selected_class, selected_method)
+				}
+				None => {
+					return OpcodeResult::Exception(format!("java/lang/AbstractMethodError"), None);
+				}
+			};
+
+			if selected_method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
+				let mut invoked_frame = Frame::new();
+				invoked_frame.class = Some(selected_class);
+
+				if !move_parameters_to_locals(&selected_method, source_frame, &mut invoked_frame) {
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::NotEnough(
+							format!("invokeinterface"),
+							selected_method.parameter_count,
+							format!("stack operands"),
+						))
+						.into_error(),
+					);
 				}
+				/*
+				 * The receiver becomes the 0th local variable.
+				 */
+				if let Some(receiver) = source_frame.operand_stack.pop() {
+					invoked_frame.locals.insert(0, receiver);
+				}
+
+				return OpcodeResult::Invoke(selected_method, invoked_frame);
+			} else {
+				// We do not know how to execute native methods.
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::NotImplemented("Native methods".to_string()))
+						.into_error(),
+				);
 			}
-			FatalError::new(FatalErrorType::ClassNotFound(invoked_class_name.clone())).call()
 		}
-		FatalError::new(FatalErrorType::MethodResolutionFailed).call();
-		None
+		OpcodeResult::Error(FatalError::new(FatalErrorType::MethodResolutionFailed).into_error())
+	}
+
+	/*
+	 * The first time that an invokedynamic instruction runs, its
+	 * bootstrap method links it to a call site; every time, the call
+	 * site gets the arguments that the instruction pops.
+	 */
+	fn execute_invokedynamic(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
+		let class = source_frame.class().unwrap();
+		let constant_pool = class.get_constant_pool_ref();
+		let invokedynamic_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
+
+		let (bootstrap_index, name_and_type_index) =
+			match constant_pool.get_constant_ref(invokedynamic_index) {
+				Constant::InvokeDynamic(_, bootstrap_index, name_and_type_index) => {
+					(*bootstrap_index as usize, *name_and_type_index as usize)
+				}
+				_ => {
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::InvalidConstantReference(
+							class.get_class_name().unwrap(),
+							"InvokeDynamic".to_string(),
+							invokedynamic_index as u16,
+						))
+						.into_error(),
+					);
+				}
+			};
+		let (name, descriptor) = match constant_pool.resolve_name_and_type(name_and_type_index) {
+			Some(name_and_type) => name_and_type,
+			None => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::InvalidConstantReference(
+						class.get_class_name().unwrap(),
+						"NameAndType".to_string(),
+						name_and_type_index as u16,
+					))
+					.into_error(),
+				);
+			}
+		};
+
+		Debug(
+			format!("Invoke Dynamic: {}{}", name, descriptor),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+
+		let call_site_key = match self.call_stack.last() {
+			Some(entry) => (
+				class.get_class_name().unwrap(),
+				entry.name_index,
+				entry.descriptor_index,
+				entry.pc,
+			),
+			None => (class.get_class_name().unwrap(), 0, 0, 0),
+		};
+		let call_site = match self.call_sites.get(&call_site_key) {
+			Some(call_site) => Rc::clone(call_site),
+			None => {
+				let bootstrap_method = match class
+					.get_bootstrap_methods()
+					.and_then(|bootstrap_methods| bootstrap_methods.get(bootstrap_index).cloned())
+				{
+					Some(bootstrap_method) => bootstrap_method,
+					None => {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::InvalidConstantReference(
+								class.get_class_name().unwrap(),
+								"BootstrapMethods".to_string(),
+								bootstrap_index as u16,
+							))
+							.into_error(),
+						);
+					}
+				};
+				let bootstrap_handle = match MethodHandle::resolve(
+					constant_pool,
+					bootstrap_method.bootstrap_method_ref() as usize,
+				) {
+					Some(bootstrap_handle) => bootstrap_handle,
+					None => {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::InvalidConstantReference(
+								class.get_class_name().unwrap(),
+								"MethodHandle".to_string(),
+								bootstrap_method.bootstrap_method_ref(),
+							))
+							.into_error(),
+						);
+					}
+				};
+				let handler = match self
+					.bootstraps
+					.get(&bootstrap_handle.class_name, &bootstrap_handle.method_name)
+				{
+					Some(handler) => handler,
+					None => {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::NotImplemented(format!(
+								"Bootstrap method {}.{}",
+								bootstrap_handle.class_name, bootstrap_handle.method_name
+							)))
+							.into_error(),
+						);
+					}
+				};
+				let call = BootstrapCall {
+					caller: Rc::clone(&class),
+					name: name.clone(),
+					descriptor: descriptor.clone(),
+					arguments: bootstrap_method.bootstrap_arguments().clone(),
+				};
+				let call_site = match handler(self, &call) {
+					Ok(call_site) => call_site,
+					Err(error) => return OpcodeResult::Error(error),
+				};
+				if let Some((exception, message)) = self.pending_exception.take() {
+					return OpcodeResult::Exception(exception, message);
+				}
+				self.call_sites.insert(call_site_key, Rc::clone(&call_site));
+				call_site
+			}
+		};
+
+		let arguments = match self.pop_arguments("invokedynamic", &descriptor, false, source_frame)
+		{
+			Ok(arguments) => arguments,
+			Err(error) => return OpcodeResult::Error(error),
+		};
+		self.call_native(&call_site, &arguments)
 	}
 
-	fn execute_invokestatic(
+	/*
+	 * Invoke the method that _handle_ refers to with _arguments_ (the
+	 * receiver first, unless the method is static).
+	 */
+	fn invoke_method_handle(
 		&mut self,
-		bytes: &[u8],
-		source_frame: &mut Frame,
-	) -> Option<OpcodeResult> {
+		invoking_class: &Rc<Class>,
+		handle: &MethodHandle,
+		arguments: Vec<JvmValue>,
+	) -> OpcodeResult {
+		Debug(
+			format!(
+				"Invoke Method Handle: {}.{}{}",
+				handle.class_name, handle.method_name, handle.descriptor
+			),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		if let Some(native) =
+			self.natives
+				.get(&handle.class_name, &handle.method_name, &handle.descriptor)
+		{
+			let arguments: Vec<Rc<JvmValue>> = arguments
+				.into_iter()
+				.map(|argument| Rc::new(argument))
+				.collect();
+			return self.call_native(&native, &arguments);
+		}
+
+		let handle_class = match self.load_class(&handle.class_name) {
+			Ok(handle_class) => handle_class,
+			Err(error) => return OpcodeResult::Error(error),
+		};
+		let (invoked_class, method) = match handle.kind {
+			/*
+			 * REF_invokeStatic and REF_invokeSpecial.
+			 */
+			6 | 7 => {
+				match handle_class.get_methods_ref().get_by_name_and_type(
+					&handle.method_name,
+					&handle.descriptor,
+					handle_class.get_constant_pool_ref(),
+				) {
+					Some(method) => (handle_class, method),
+					None => {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::MethodNotFound(
+								handle.method_name.clone(),
+								handle.class_name.clone(),
+							))
+							.into_error(),
+						);
+					}
+				}
+			}
+			/*
+			 * REF_invokeVirtual and REF_invokeInterface dispatch on
+			 * the receiver's class.
+			 */
+			5 | 9 => {
+				let receiver = match arguments.get(0) {
+					Some(receiver) if receiver.is_null() => {
+						return null_receiver(&handle.class_name, &handle.method_name)
+					}
+					Some(JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _)) => {
+						match object.lock() {
+							Ok(object) => object.get_class(),
+							Err(_) => {
+								return OpcodeResult::Error(
+									FatalError::new(FatalErrorType::CouldNotLock(
+										format!("Object."),
+										format!("invoke_method_handle"),
+									))
+									.into_error(),
+								);
+							}
+						}
+					}
+					_ => {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::WrongType(
+								format!("invoke_method_handle"),
+								format!("Reference to an object"),
+							))
+							.into_error(),
+						);
+					}
+				};
+				let mut selected_class_method: Option<(Rc<Class>, Rc<Method>)> = None;
+				if let Ok(mut methodarea) = self.methodarea.lock() {
+					selected_class_method = (*methodarea).select_method(
+						invoking_class,
+						&receiver,
+						&handle.method_name,
+						&handle.descriptor,
+					);
+				}
+				match selected_class_method {
+					Some(selected_class_method) => selected_class_method,
+					None => {
+						return OpcodeResult::Exception(
+							format!("java/lang/AbstractMethodError"),
+							None,
+						);
+					}
+				}
+			}
+			kind => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::NotImplemented(format!(
+						"Method handles of kind {}",
+						kind
+					)))
+					.into_error(),
+				);
+			}
+		};
+		if method.is_static() {
+			if let Err(error) = self.maybe_initialize_class(&invoked_class) {
+				return OpcodeResult::Error(error);
+			}
+		}
+
+		let mut invoked_frame = Frame::new();
+		invoked_frame.class = Some(invoked_class);
+		for argument in arguments {
+			let size = argument.size();
+			invoked_frame.locals.push(argument);
+			if size == 2 {
+				invoked_frame
+					.locals
+					.push(JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0));
+			}
+		}
+		OpcodeResult::Invoke(method, invoked_frame)
+	}
+
+	/*
+	 * Load (if it is not already) and initialize the class _class_name_.
+	 */
+	pub fn load_class(&mut self, class_name: &String) -> Result<Rc<Class>, JvmError> {
+		let mut class: Option<Rc<Class>> = None;
+		if let Ok(mut methodarea) = self.methodarea.lock() {
+			class = (*methodarea).maybe_load_class(class_name);
+		}
+		match class {
+			Some(class) => {
+				self.maybe_initialize_class(&class)?;
+				Ok(class)
+			}
+			None => {
+				Err(FatalError::new(FatalErrorType::ClassNotFound(class_name.clone())).into_error())
+			}
+		}
+	}
+
+	fn execute_invokestatic(&mut self, bytes: &[u8], source_frame: &mut Frame) -> OpcodeResult {
 		let class = source_frame.class().unwrap();
 		let constant_pool = class.get_constant_pool_ref();
 		let method_index = (((bytes[1] as u16) << 8) | (bytes[2] as u16)) as usize;
@@ -2763,8 +6231,19 @@ impl JvmThread {
 				&self.debug_level,
 				DebugLevel::Info,
 			);
+			if let Some(result) = self.invoke_native(
+				"invokestatic",
+				&invoked_class_name,
+				&method_name,
+				&method_type,
+				false,
+				source_frame,
+			) {
+				return result;
+			}
 			let mut invoked_class: Option<Rc<Class>> = None;
 			if let Ok(mut methodarea) = self.methodarea.lock() {
+				(*methodarea).maybe_load_class(&invoked_class_name);
 				invoked_class = (*methodarea).get_class_rc(&invoked_class_name);
 			}
 			if let Some(invoked_class) = invoked_class {
@@ -2785,7 +6264,9 @@ impl JvmThread {
 					 * This is an operation that requires the target class
 					 * be initialized.
 					 */
-					self.maybe_initialize_class(&invoked_class);
+					if let Err(error) = self.maybe_initialize_class(&invoked_class) {
+						return OpcodeResult::Error(error);
+					}
 
 					if method.access_flags & (MethodAccessFlags::Native as u16) == 0 {
 						// We know how to execute non-native methods.
@@ -2798,17 +6279,15 @@ impl JvmThread {
 							* invoked stack.
 							*/
 						let parameter_count = method.parameter_count;
-						for i in 0..parameter_count {
-							if let Some(parameter) = source_frame.operand_stack.pop() {
-								invoked_frame.locals.insert(0, parameter);
-							} else {
+						if !move_parameters_to_locals(&method, source_frame, &mut invoked_frame) {
+							return OpcodeResult::Error(
 								FatalError::new(FatalErrorType::NotEnough(
 									"invokestatic".to_string(),
-									i,
+									parameter_count,
 									"stack operands".to_string(),
 								))
-								.call();
-							}
+								.into_error(),
+							);
 						}
 
 						Debug(
@@ -2822,98 +6301,5297 @@ impl JvmThread {
 							DebugLevel::Info,
 						);
 
-						if let Some(v) = self.execute_method(&method, invoked_frame) {
+						return OpcodeResult::Invoke(method, invoked_frame);
+					} else {
+						// We do not know how to execute native methods.
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::NotImplemented(
+								"Native methods".to_string(),
+							))
+							.into_error(),
+						);
+					}
+				} else {
+					let constant_pool = invoked_class.get_constant_pool_ref();
+					for overload in invoked_class
+						.get_methods_ref()
+						.get_by_name(&method_name, constant_pool)
+					{
+						if let Constant::Utf8(_, _, _, overload_type) =
+							constant_pool.get_constant_ref(overload.descriptor_index as usize)
+						{
 							Debug(
-								format!("Returning from a method{}", method.clone()),
+								format!(
+									"{}.{} has no type {} but has type {}.",
+									invoked_class_name, method_name, method_type, overload_type
+								),
 								&self.debug_level,
-								DebugLevel::Info,
+								DebugLevel::Error,
 							);
-							return Some(OpcodeResult::Value(v));
 						}
-					} else {
-						// We do not know how to execute native methods.
-						FatalError::new(FatalErrorType::NotImplemented(
-							"Native methods".to_string(),
-						))
-						.call();
 					}
+					return OpcodeResult::Error(
+						FatalError::new(FatalErrorType::MethodNotFound(
+							method_name.clone(),
+							invoked_class_name.clone(),
+						))
+						.into_error(),
+					);
 				}
 			} else {
-				FatalError::new(FatalErrorType::ClassNotFound(invoked_class_name.clone())).call()
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::ClassNotFound(invoked_class_name.clone()))
+						.into_error(),
+				);
 			}
 		}
-		None
+		OpcodeResult::Error(
+			FatalError::new(FatalErrorType::InvalidConstantReference(
+				class.get_class_name().unwrap(),
+				"Methodref".to_string(),
+				method_index as u16,
+			))
+			.into_error(),
+		)
 	}
-	fn execute_anewarray(
+	fn execute_newarray(
 		&mut self,
-		type_index: u16,
-		count: u32,
+		newarray_type: u8,
+		count: i32,
 		frame: &mut Frame,
 	) -> OpcodeResult {
+		let element_type = match JvmArrayType::from_u8(newarray_type) {
+			Some(JvmArrayType::Boolean) => JvmPrimitiveType::Boolean,
+			Some(JvmArrayType::Char) => JvmPrimitiveType::Char,
+			Some(JvmArrayType::Float) => JvmPrimitiveType::Float,
+			Some(JvmArrayType::Double) => JvmPrimitiveType::Double,
+			Some(JvmArrayType::Byte) => JvmPrimitiveType::Byte,
+			Some(JvmArrayType::Short) => JvmPrimitiveType::Short,
+			Some(JvmArrayType::Integer) => JvmPrimitiveType::Integer,
+			Some(JvmArrayType::Long) => JvmPrimitiveType::LongInteger,
+			_ => {
+				// We were asked to make an array for an invalid type
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("newarray"),
+						format!("JvmArrayType"),
+					))
+					.into_error(),
+				);
+			}
+		};
+
+		if count < 0 {
+			return OpcodeResult::Exception(format!("java/lang/NegativeArraySizeException"), None);
+		}
+
+		let allocation = match self.allocate_array(count as usize) {
+			Ok(allocation) => allocation,
+			Err(result) => return result,
+		};
+		/*
+		 * Every element starts out as zero.
+		 */
+		let mut array = JvmArray::new_with_value(
+			count as usize,
+			JvmValue::Primitive(element_type.clone(), 0, 0, 0),
+		);
+		array.set_allocation(allocation);
+		frame.operand_stack.push(JvmValue::Reference(
+			JvmReferenceType::Array(Rc::new(JvmType::Primitive(element_type)), count as u32),
+			JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),
+			0,
+		));
+		OpcodeResult::Incr(2)
+	}
+
+	/*
+	 * Only as many dimensions as the instruction gives are allocated;
+	 * the elements of the innermost of them are zero or null.
+	 */
+	fn execute_multianewarray(&mut self, bytes: &[u8], frame: &mut Frame) -> OpcodeResult {
 		let class = frame.class().unwrap();
-		let constant_pool = class.get_constant_pool_ref();
-		let newarray_type = constant_pool.get_constant_ref(type_index as usize);
+		let type_index = ((bytes[1] as u16) << 8) | (bytes[2] as u16);
+		let dimensions = bytes[3] as usize;
 
-		if let Constant::Class(_, newarray_class_name_index) = newarray_type {
-			let newarray_class_name_constant =
-				constant_pool.get_constant_ref(*newarray_class_name_index as usize);
-			if let Constant::Utf8(_, _, _, new_array_class_name) = newarray_class_name_constant {
-				// Resolve the class.
-				// TODO: This could throw an exception!
-				let mut new_array_class = if let Ok(mut methodarea) = self.methodarea.lock() {
-					(*methodarea).maybe_load_class(&new_array_class_name);
-					(*methodarea).get_class_rc(&new_array_class_name)
-				} else {
-					FatalError::new(FatalErrorType::CouldNotLock(
-						format!("Class."),
-						format!("execute_anewarray"),
+		let array_type = match class.resolve_class_ref(type_index as usize) {
+			Some(array_class_name) if array_class_name.starts_with("[") => {
+				JvmType::from(array_class_name.as_bytes())
+			}
+			_ => {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("multianewarray"),
+						format!("symbolic reference to an array class."),
 					))
-					.call();
-					None
-				};
-				if let Some(new_array_class) = new_array_class {
-					// Make the array
-					let mut array = JvmArray::new(count as usize);
-					for i in 0..count {
-						array.push(create_null_value());
-					}
-
-					let jvmtype = JvmType::Reference(JvmReferenceType::Array(
-						Rc::new(JvmType::Reference(JvmReferenceType::Class(
-							new_array_class_name.clone(),
-						))),
-						count,
-					));
-					let v = JvmValue::Reference(
-						JvmReferenceType::Array(Rc::new(jvmtype), count), // type
-						JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))), //target type
-						0,                                                // access,
-					);
-					frame.operand_stack.push(v);
+					.into_error(),
+				);
+			}
+		};
+
+		/*
+		 * The count for the outermost dimension is deepest in the
+		 * stack.
+		 */
+		let mut counts: Vec<i32> = vec![0; dimensions];
+		for dimension in (0..dimensions).rev() {
+			match self.pop_int_operand("multianewarray", frame) {
+				Ok(count) => counts[dimension] = count,
+				Err(error) => return OpcodeResult::Error(error),
+			}
+		}
+		if counts.iter().any(|count| *count < 0) {
+			return OpcodeResult::Exception(format!("java/lang/NegativeArraySizeException"), None);
+		}
+
+		/*
+		 * Like anewarray, make sure that a class element type is loaded.
+		 */
+		let mut element_type = &array_type;
+		while let JvmType::Reference(JvmReferenceType::Array(component_type, _)) = element_type {
+			element_type = component_type;
+		}
+		if let JvmType::Reference(JvmReferenceType::Class(element_class_name)) = element_type {
+			let element_class = if let Ok(mut methodarea) = self.methodarea.lock() {
+				(*methodarea).maybe_load_class(&element_class_name);
+				(*methodarea).get_class_rc(&element_class_name)
+			} else {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::CouldNotLock(
+						format!("Class."),
+						format!("execute_multianewarray"),
+					))
+					.into_error(),
+				);
+			};
+			if element_class.is_none() {
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::ClassNotFound(element_class_name.clone()))
+						.into_error(),
+				);
+			}
+		}
+
+		match self.allocate_multidimensional_array(&array_type, &counts) {
+			Ok(array) => frame.operand_stack.push(array),
+			Err(result) => return result,
+		}
+		OpcodeResult::Incr(4)
+	}
+
+	/*
+	 * Make an array of _array_type_ that is counts[0] long. When there
+	 * are more counts, each element is an array made the same way for
+	 * the next dimension. The result is what multianewarray gets when
+	 * that fails.
+	 */
+	fn allocate_multidimensional_array(
+		&self,
+		array_type: &JvmType,
+		counts: &[i32],
+	) -> Result<JvmValue, OpcodeResult> {
+		let component_type = match array_type {
+			JvmType::Reference(JvmReferenceType::Array(component_type, _)) => component_type,
+			_ => {
+				return Err(OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("multianewarray"),
+						format!("array class with at least {} dimensions", counts.len()),
+					))
+					.into_error(),
+				))
+			}
+		};
+		let count = counts[0] as usize;
+
+		let allocation = self.allocate_array(count)?;
+		let mut array = JvmArray::new(count);
+		array.set_allocation(allocation);
+		for index in 0..count {
+			let element = if counts.len() > 1 {
+				self.allocate_multidimensional_array(component_type, &counts[1..])?
+			} else {
+				match &**component_type {
+					JvmType::Primitive(primitive_type) => {
+						JvmValue::Primitive(primitive_type.clone(), 0, 0, 0)
+					}
+					JvmType::Reference(reference) => {
+						JvmValue::Reference(reference.clone(), JvmReferenceTargetType::Null, 0)
+					}
+				}
+			};
+			array.set_at(index, element);
+		}
+
+		Ok(JvmValue::Reference(
+			JvmReferenceType::Array(Rc::clone(component_type), count as u32),
+			JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),
+			0,
+		))
+	}
+
+	fn execute_anewarray(
+		&mut self,
+		type_index: u16,
+		count: i32,
+		frame: &mut Frame,
+	) -> OpcodeResult {
+		let class = frame.class().unwrap();
+		let constant_pool = class.get_constant_pool_ref();
+		let newarray_type = constant_pool.get_constant_ref(type_index as usize);
+
+		if let Constant::Class(_, newarray_class_name_index) = newarray_type {
+			let newarray_class_name_constant =
+				constant_pool.get_constant_ref(*newarray_class_name_index as usize);
+			if let Constant::Utf8(_, _, _, new_array_class_name) = newarray_class_name_constant {
+				/*
+				 * The component type is either an array type (given
+				 * as a descriptor) or a class that we have to resolve.
+				 */
+				let component_type = if new_array_class_name.starts_with("[") {
+					JvmType::from(new_array_class_name.as_bytes())
 				} else {
-					FatalError::new(FatalErrorType::ClassNotFound(new_array_class_name.clone()))
-						.call()
+					// TODO: This could throw an exception!
+					let new_array_class = if let Ok(mut methodarea) = self.methodarea.lock() {
+						(*methodarea).maybe_load_class(&new_array_class_name);
+						(*methodarea).get_class_rc(&new_array_class_name)
+					} else {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::CouldNotLock(
+								format!("Class."),
+								format!("execute_anewarray"),
+							))
+							.into_error(),
+						);
+					};
+					if new_array_class.is_none() {
+						return OpcodeResult::Error(
+							FatalError::new(FatalErrorType::ClassNotFound(
+								new_array_class_name.clone(),
+							))
+							.into_error(),
+						);
+					}
+					JvmType::Reference(JvmReferenceType::Class(new_array_class_name.clone()))
+				};
+
+				if count < 0 {
+					return OpcodeResult::Exception(
+						format!("java/lang/NegativeArraySizeException"),
+						None,
+					);
 				}
+
+				/*
+				 * Every element starts out as a null reference of
+				 * the component type.
+				 */
+				let null_element = match &component_type {
+					JvmType::Reference(reference) => {
+						JvmValue::Reference(reference.clone(), JvmReferenceTargetType::Null, 0)
+					}
+					_ => create_null_value(),
+				};
+				let allocation = match self.allocate_array(count as usize) {
+					Ok(allocation) => allocation,
+					Err(result) => return result,
+				};
+				let mut array = JvmArray::new_with_value(count as usize, null_element);
+				array.set_allocation(allocation);
+
+				let v = JvmValue::Reference(
+					JvmReferenceType::Array(Rc::new(component_type), count as u32), // type
+					JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),     //target type
+					0,                                                              // access,
+				);
+				frame.operand_stack.push(v);
 			} else {
-				FatalError::new(FatalErrorType::WrongType(
-					format!("anewarray"),
-					format!("symbolic reference to class."),
-				))
-				.call();
+				return OpcodeResult::Error(
+					FatalError::new(FatalErrorType::WrongType(
+						format!("anewarray"),
+						format!("symbolic reference to class."),
+					))
+					.into_error(),
+				);
 			}
 		} else if let Constant::InterfaceMethodref(_, _, _) = newarray_type {
-			FatalError::new(FatalErrorType::NotImplemented(format!(
-				"execute_anewarray of interface/method references."
-			)))
-			.call();
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::NotImplemented(format!(
+					"execute_anewarray of interface/method references."
+				)))
+				.into_error(),
+			);
 		} else {
 			// We need to also handle an array. See the description of this type. And then, say that there is an error for the wrong type.
-			FatalError::new(FatalErrorType::WrongType(
-				format!("anewarray"),
-				format!("reference to class, interface/method or array."),
-			))
-			.call();
+			return OpcodeResult::Error(
+				FatalError::new(FatalErrorType::WrongType(
+					format!("anewarray"),
+					format!("reference to class, interface/method or array."),
+				))
+				.into_error(),
+			);
 		}
 		OpcodeResult::Incr(3)
 	}
 }
+
+/*
+ * A FatalError panics, which unwinds the thread out from under
+ * whatever it was executing. Say where that was.
+ */
+impl Drop for JvmThread {
+	fn drop(&mut self) {
+		if std::thread::panicking() {
+			print_stack_trace(&self.call_stack, &mut *self.err);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::lambda_class;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::environment::Environment;
+	use jvm::object::string_object_value;
+
+	fn new_thread() -> JvmThread {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		)
+	}
+
+	fn int_value(value: i32) -> JvmValue {
+		JvmValue::Primitive(JvmPrimitiveType::Integer, 0, value as u32, 0)
+	}
+
+	fn float_value(value: f32) -> JvmValue {
+		JvmValue::Primitive(JvmPrimitiveType::Float, 0, value.to_bits(), 0)
+	}
+
+	fn frame_with_locals(locals: Vec<JvmValue>) -> Frame {
+		let mut frame = Frame::new();
+		frame.locals = locals;
+		frame
+	}
+
+	#[test]
+	fn fload_loads_only_floats() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![float_value(1.5), int_value(2)]);
+
+		/*
+		 * fload_0, fload_1 and fload 7.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x22], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.pop_float(), Ok(1.5));
+		assert!(matches!(
+			thread.execute_opcode(&[0x23], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x17, 7], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn wide_istore_and_iload_reach_local_300() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0); 301]);
+
+		/*
+		 * wide istore 300 and wide iload 300 (0x012c).
+		 */
+		frame.push_int(1234).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x36, 0x01, 0x2c], &mut frame),
+			OpcodeResult::Incr(4)
+		));
+		assert!(frame.locals[300] == int_value(1234));
+		assert!(frame.locals[44] == int_value(0));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x15, 0x01, 0x2c], &mut frame),
+			OpcodeResult::Incr(4)
+		));
+		assert_eq!(frame.pop_int(), Ok(1234));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn wide_fload_checks_its_local() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(2), float_value(3.0)]);
+
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x17, 0, 1], &mut frame),
+			OpcodeResult::Incr(4)
+		));
+		assert_eq!(frame.pop_float(), Ok(3.0));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x17, 0, 0], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x17, 1, 0], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	#[test]
+	fn istore_and_iload_round_trip_through_a_local() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0); 3]);
+
+		/*
+		 * istore_2 and iload_2, then istore 2 and iload 2.
+		 */
+		frame.push_int(-42).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x3d], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(frame.locals[2] == int_value(-42));
+		assert!(matches!(
+			thread.execute_opcode(&[0x1c], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.pop_int(), Ok(-42));
+
+		frame.push_int(i32::max_value()).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x36, 2], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x15, 2], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert_eq!(frame.pop_int(), Ok(i32::max_value()));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn iload_loads_only_int_types() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![
+			int_value(1),
+			JvmValue::Primitive(JvmPrimitiveType::Char, 0, 65, 0),
+			float_value(1.0),
+			JvmValue::Primitive(JvmPrimitiveType::LongInteger, 2, 0, 0),
+		]);
+
+		/*
+		 * iload_0 through iload_3.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x1a], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x1b], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x1c], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x1d], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert_eq!(frame.operand_stack.len(), 2);
+	}
+
+	#[test]
+	fn istore_stores_only_int_types() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0), int_value(0)]);
+
+		/*
+		 * istore_0 takes a boolean; istore_1 rejects a float and
+		 * leaves the local alone.
+		 */
+		frame
+			.operand_stack
+			.push(JvmValue::Primitive(JvmPrimitiveType::Boolean, 0, 1, 0));
+		assert!(matches!(
+			thread.execute_opcode(&[0x3b], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		frame.operand_stack.push(float_value(2.0));
+		assert!(matches!(
+			thread.execute_opcode(&[0x3c], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(frame.locals[0] == JvmValue::Primitive(JvmPrimitiveType::Boolean, 0, 1, 0));
+		assert!(frame.locals[1] == int_value(0));
+	}
+
+	#[test]
+	fn float_arithmetic() {
+		assert_eq!(
+			float_result(&[0x62], vec![float_value(1.5), float_value(2.25)]),
+			3.75
+		);
+
+		/*
+		 * Dividing by zero does not throw, and 0.0 / 0.0 is NaN.
+		 */
+		assert_eq!(
+			float_result(&[0x6e], vec![float_value(1.0), float_value(0.0)]),
+			f32::INFINITY
+		);
+		assert!(float_result(&[0x6e], vec![float_value(0.0), float_value(0.0)]).is_nan());
+	}
+
+	#[test]
+	fn float_arithmetic_rejects_other_operands() {
+		let mut thread = new_thread();
+
+		/*
+		 * fadd, fsub, fmul, fdiv and frem.
+		 */
+		for opcode in [0x62, 0x66, 0x6a, 0x6e, 0x72].iter() {
+			let mut frame = Frame::new();
+			frame.operand_stack.push(float_value(7.0));
+			frame.operand_stack.push(int_value(2));
+			assert!(matches!(
+				thread.execute_opcode(&[*opcode], &mut frame),
+				OpcodeResult::Error(_)
+			));
+
+			let mut frame = Frame::new();
+			frame.operand_stack.push(float_value(7.0));
+			assert!(matches!(
+				thread.execute_opcode(&[*opcode], &mut frame),
+				OpcodeResult::Error(_)
+			));
+		}
+	}
+
+	#[test]
+	fn frem_takes_the_sign_of_the_dividend() {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+		frame.operand_stack.push(float_value(-7.0));
+		frame.operand_stack.push(float_value(2.0));
+		assert!(matches!(
+			thread.execute_opcode(&[0x72], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.pop_float(), Ok(-1.0));
+	}
+
+	/*
+	 * Run the single instruction _bytes_ on a frame whose operand
+	 * stack holds _operands_.
+	 */
+	fn execute_with_operands(bytes: &[u8], operands: Vec<JvmValue>) -> (OpcodeResult, Frame) {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+		for operand in operands {
+			frame.operand_stack.push(operand);
+		}
+		let result = thread.execute_opcode(bytes, &mut frame);
+		(result, frame)
+	}
+
+	fn int_result(bytes: &[u8], operands: Vec<JvmValue>) -> i32 {
+		let (result, mut frame) = execute_with_operands(bytes, operands);
+		assert!(matches!(result, OpcodeResult::Incr(_)));
+		frame.pop_int().unwrap()
+	}
+
+	#[test]
+	fn integer_arithmetic() {
+		/*
+		 * iadd, isub, imul and idiv.
+		 */
+		assert_eq!(int_result(&[0x60], vec![int_value(3), int_value(4)]), 7);
+		assert_eq!(int_result(&[0x64], vec![int_value(3), int_value(4)]), -1);
+		assert_eq!(int_result(&[0x68], vec![int_value(-3), int_value(4)]), -12);
+		assert_eq!(int_result(&[0x6c], vec![int_value(-7), int_value(2)]), -3);
+
+		/*
+		 * They wrap around on overflow.
+		 */
+		assert_eq!(
+			int_result(&[0x60], vec![int_value(i32::MAX), int_value(1)]),
+			i32::MIN
+		);
+		assert_eq!(
+			int_result(&[0x68], vec![int_value(i32::MAX), int_value(2)]),
+			-2
+		);
+		assert_eq!(
+			int_result(&[0x6c], vec![int_value(i32::MIN), int_value(-1)]),
+			i32::MIN
+		);
+	}
+
+	#[test]
+	fn integer_division_by_zero_throws() {
+		let (result, _) = execute_with_operands(&[0x6c], vec![int_value(1), int_value(0)]);
+		assert!(matches!(
+			result,
+			OpcodeResult::Exception(ref exception, _) if exception == "java/lang/ArithmeticException"
+		));
+	}
+
+	#[test]
+	fn integer_arithmetic_needs_two_ints() {
+		for opcode in [0x60, 0x64, 0x68, 0x6c].iter() {
+			let (result, _) = execute_with_operands(&[*opcode], vec![int_value(1)]);
+			assert!(matches!(result, OpcodeResult::Error(_)));
+			let (result, _) =
+				execute_with_operands(&[*opcode], vec![int_value(1), float_value(1.0)]);
+			assert!(matches!(result, OpcodeResult::Error(_)));
+		}
+	}
+
+	fn long_value(value: i64) -> JvmValue {
+		JvmValue::Primitive(JvmPrimitiveType::LongInteger, value as u64, 0, 0)
+	}
+
+	fn double_value(value: f64) -> JvmValue {
+		JvmValue::Primitive(JvmPrimitiveType::Double, value.to_bits(), 0, 0)
+	}
+
+	fn long_result(bytes: &[u8], operands: Vec<JvmValue>) -> i64 {
+		let (result, mut frame) = execute_with_operands(bytes, operands);
+		assert!(matches!(result, OpcodeResult::Incr(_)));
+		frame.pop_long().unwrap()
+	}
+
+	#[test]
+	fn long_arithmetic() {
+		/*
+		 * ladd, lsub, lmul, ldiv and lrem.
+		 */
+		let big = 1i64 << 40;
+		assert_eq!(
+			long_result(&[0x61], vec![long_value(big), long_value(3)]),
+			big + 3
+		);
+		assert_eq!(
+			long_result(&[0x65], vec![long_value(3), long_value(big)]),
+			3 - big
+		);
+		assert_eq!(
+			long_result(&[0x69], vec![long_value(big), long_value(-2)]),
+			-2 * big
+		);
+		assert_eq!(
+			long_result(&[0x6d], vec![long_value(-7), long_value(2)]),
+			-3
+		);
+		assert_eq!(
+			long_result(&[0x71], vec![long_value(-7), long_value(2)]),
+			-1
+		);
+		assert_eq!(
+			long_result(&[0x61], vec![long_value(i64::MAX), long_value(1)]),
+			i64::MIN
+		);
+		assert_eq!(
+			long_result(&[0x6d], vec![long_value(i64::MIN), long_value(-1)]),
+			i64::MIN
+		);
+	}
+
+	#[test]
+	fn long_division_by_zero_throws() {
+		for opcode in [0x6d, 0x71].iter() {
+			let (result, _) = execute_with_operands(&[*opcode], vec![long_value(1), long_value(0)]);
+			assert!(matches!(
+				result,
+				OpcodeResult::Exception(ref exception, _) if exception == "java/lang/ArithmeticException"
+			));
+		}
+	}
+
+	#[test]
+	fn long_arithmetic_takes_two_slots_per_operand() {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+		frame.max_stack = Some(4);
+		frame.push_long(1).unwrap();
+		frame.push_long(2).unwrap();
+		assert_eq!(frame.operand_stack_depth(), 4);
+		assert!(matches!(
+			thread.execute_opcode(&[0x61], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.operand_stack_depth(), 2);
+		assert_eq!(frame.pop_long(), Ok(3));
+
+		/*
+		 * An int is not half of a long.
+		 */
+		for opcode in [0x61, 0x65, 0x69, 0x6d, 0x71].iter() {
+			let (result, _) =
+				execute_with_operands(&[*opcode], vec![int_value(1), int_value(2), long_value(3)]);
+			assert!(matches!(result, OpcodeResult::Error(_)));
+		}
+	}
+
+	fn double_result(bytes: &[u8], operands: Vec<JvmValue>) -> f64 {
+		let (result, mut frame) = execute_with_operands(bytes, operands);
+		assert!(matches!(result, OpcodeResult::Incr(_)));
+		frame.pop_double().unwrap()
+	}
+
+	#[test]
+	fn double_arithmetic() {
+		/*
+		 * dadd, dsub, dmul, ddiv and drem.
+		 */
+		let operands = || vec![double_value(-7.5), double_value(2.0)];
+		assert_eq!(double_result(&[0x63], operands()), -5.5);
+		assert_eq!(double_result(&[0x67], operands()), -9.5);
+		assert_eq!(double_result(&[0x6b], operands()), -15.0);
+		assert_eq!(double_result(&[0x6f], operands()), -3.75);
+		assert_eq!(double_result(&[0x73], operands()), -1.5);
+
+		/*
+		 * Dividing by zero does not throw.
+		 */
+		assert_eq!(
+			double_result(&[0x6f], vec![double_value(1.0), double_value(0.0)]),
+			f64::INFINITY
+		);
+		assert!(double_result(&[0x73], vec![double_value(1.0), double_value(0.0)]).is_nan());
+	}
+
+	#[test]
+	fn double_arithmetic_takes_two_slots_per_operand() {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+		frame.max_stack = Some(4);
+		frame.push_double(1.5).unwrap();
+		frame.push_double(2.0).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x6b], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.operand_stack_depth(), 2);
+		assert_eq!(frame.pop_double(), Ok(3.0));
+
+		for opcode in [0x63, 0x67, 0x6b, 0x6f, 0x73].iter() {
+			let (result, _) = execute_with_operands(
+				&[*opcode],
+				vec![float_value(1.0), float_value(2.0), double_value(3.0)],
+			);
+			assert!(matches!(result, OpcodeResult::Error(_)));
+		}
+	}
+
+	/*
+	 * Whether the branch instruction _opcode_ (with an offset of 16)
+	 * branches when the operand stack holds _operands_.
+	 */
+	fn branches(opcode: u8, operands: Vec<JvmValue>) -> bool {
+		match execute_with_operands(&[opcode, 0x00, 0x10], operands).0 {
+			OpcodeResult::Incr(16) => true,
+			OpcodeResult::Incr(3) => false,
+			_ => panic!("0x{:x} did not branch or fall through", opcode),
+		}
+	}
+
+	#[test]
+	fn if_icmp_compares_signed_ints() {
+		let comparisons: [(u8, fn(i32, i32) -> bool); 6] = [
+			(0x9f, |a, b| a == b),
+			(0xa0, |a, b| a != b),
+			(0xa1, |a, b| a < b),
+			(0xa2, |a, b| a >= b),
+			(0xa3, |a, b| a > b),
+			(0xa4, |a, b| a <= b),
+		];
+		for (opcode, compare) in comparisons.iter() {
+			for (a, b) in [(1, 2), (2, 2), (2, 1), (-1, 1), (i32::MIN, i32::MAX)].iter() {
+				assert_eq!(
+					branches(*opcode, vec![int_value(*a), int_value(*b)]),
+					compare(*a, *b),
+					"0x{:x} {} {}",
+					opcode,
+					a,
+					b
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn if_icmp_branches_backwards() {
+		let (result, _) =
+			execute_with_operands(&[0x9f, 0xff, 0xf0], vec![int_value(1), int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Decr(16)));
+
+		let (result, _) = execute_with_operands(&[0x9f, 0x00, 0x10], vec![int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn goto_branches_by_its_offset() {
+		let (result, _) = execute_with_operands(&[0xa7, 0x01, 0x00], vec![]);
+		assert!(matches!(result, OpcodeResult::Incr(256)));
+		let (result, _) = execute_with_operands(&[0xa7, 0xff, 0xfd], vec![]);
+		assert!(matches!(result, OpcodeResult::Decr(3)));
+
+		/*
+		 * A goto to itself is an (infinite) loop, not a return.
+		 */
+		let (result, _) = execute_with_operands(&[0xa7, 0x00, 0x00], vec![]);
+		assert!(matches!(result, OpcodeResult::Incr(0)));
+	}
+
+	#[test]
+	fn goto_w_has_a_four_byte_offset() {
+		let (result, _) = execute_with_operands(&[0xc8, 0x00, 0x01, 0x00, 0x00], vec![]);
+		assert!(matches!(result, OpcodeResult::Incr(65536)));
+		let (result, _) = execute_with_operands(&[0xc8, 0xff, 0xff, 0xff, 0xfb], vec![]);
+		assert!(matches!(result, OpcodeResult::Decr(5)));
+	}
+
+	fn object_value() -> JvmValue {
+		let class = Rc::new(Class::synthetic_object().unwrap());
+		JvmValue::Reference(
+			JvmReferenceType::Class(format!("java/lang/Object")),
+			JvmReferenceTargetType::Object(Arc::new(Mutex::new(JvmObject::new(
+				class,
+				DebugLevel::Error,
+			)))),
+			0,
+		)
+	}
+
+	#[test]
+	fn ifnull_and_ifnonnull() {
+		assert!(branches(0xc6, vec![create_null_value()]));
+		assert!(!branches(0xc6, vec![object_value()]));
+		assert!(!branches(0xc7, vec![create_null_value()]));
+		assert!(branches(0xc7, vec![object_value()]));
+
+		let (result, _) = execute_with_operands(&[0xc6, 0x00, 0x10], vec![int_value(0)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn if_compares_an_int_with_zero() {
+		let comparisons: [(u8, fn(i32) -> bool); 6] = [
+			(0x99, |a| a == 0),
+			(0x9a, |a| a != 0),
+			(0x9b, |a| a < 0),
+			(0x9c, |a| a >= 0),
+			(0x9d, |a| a > 0),
+			(0x9e, |a| a <= 0),
+		];
+		for (opcode, compare) in comparisons.iter() {
+			for a in [-1, 0, 1, i32::MIN, i32::MAX].iter() {
+				assert_eq!(
+					branches(*opcode, vec![int_value(*a)]),
+					compare(*a),
+					"0x{:x} {}",
+					opcode,
+					a
+				);
+			}
+			let (result, _) = execute_with_operands(&[*opcode, 0x00, 0x10], vec![]);
+			assert!(matches!(result, OpcodeResult::Error(_)));
+		}
+	}
+
+	fn same_object(first: &JvmValue, second: &JvmValue) -> bool {
+		match (first, second) {
+			(JvmValue::Reference(_, first, _), JvmValue::Reference(_, second, _)) => {
+				first.identity().is_some() && first.identity() == second.identity()
+			}
+			_ => false,
+		}
+	}
+
+	#[test]
+	fn aload_and_astore_move_references() {
+		let mut thread = new_thread();
+		let object = object_value();
+		let mut frame = frame_with_locals(vec![
+			object.clone(),
+			int_value(1),
+			create_null_value(),
+			int_value(3),
+			int_value(4),
+		]);
+
+		/*
+		 * aload_0 and astore_3 move the object to local 3; aload 2 and
+		 * astore 4 move null to local 4.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x2a], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x4e], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(same_object(&frame.locals[3], &object));
+		assert!(matches!(
+			thread.execute_opcode(&[0x19, 2], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x3a, 4], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert!(frame.locals[4].is_null());
+		assert!(frame.operand_stack.is_empty());
+
+		/*
+		 * Local 1 is an int; there is no local 5; astore_0 needs a
+		 * reference.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x2b], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x19, 5], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		frame.operand_stack.push(int_value(1));
+		assert!(matches!(
+			thread.execute_opcode(&[0x4b], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	fn invalid_value() -> JvmValue {
+		JvmValue::Primitive(JvmPrimitiveType::Invalid, 0, 0, 0)
+	}
+
+	#[test]
+	fn long_and_double_locals_take_two_slots() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0); 4]);
+
+		/*
+		 * lstore_1 and dstore 2 (which overwrites the second half of
+		 * the long).
+		 */
+		frame.push_long(1 << 40).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x40], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(frame.locals[1] == long_value(1 << 40));
+		assert!(frame.locals[2] == invalid_value());
+		frame.push_double(2.5).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x39, 2], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert!(frame.locals[1] == invalid_value());
+		assert!(frame.locals[2] == double_value(2.5));
+		assert!(frame.locals[3] == invalid_value());
+
+		/*
+		 * dload_2 and lload 1 (whose long is gone).
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x28], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.pop_double(), Ok(2.5));
+		assert!(matches!(
+			thread.execute_opcode(&[0x16, 1], &mut frame),
+			OpcodeResult::Error(_)
+		));
+
+		/*
+		 * The second half of a double cannot be loaded on its own,
+		 * and a long does not fit in the last local.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x29], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		frame.push_long(7).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x42], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	#[test]
+	fn long_and_double_stores_check_their_type() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0); 2]);
+		frame.push_double(1.0).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x3f], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		frame.push_long(1).unwrap();
+		assert!(matches!(
+			thread.execute_opcode(&[0x47], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x1e], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	#[test]
+	fn constants_push_their_values() {
+		for (opcode, value) in (0x02..=0x08).zip(-1..=5) {
+			assert_eq!(int_result(&[opcode], vec![]), value);
+		}
+		assert_eq!(long_result(&[0x09], vec![]), 0);
+		assert_eq!(long_result(&[0x0a], vec![]), 1);
+		for (opcode, value) in [(0x0b, 0.0), (0x0c, 1.0), (0x0d, 2.0)].iter() {
+			let (result, mut frame) = execute_with_operands(&[*opcode], vec![]);
+			assert!(matches!(result, OpcodeResult::Incr(1)));
+			assert_eq!(frame.pop_float(), Ok(*value));
+		}
+		assert_eq!(double_result(&[0x0e], vec![]), 0.0);
+		assert_eq!(double_result(&[0x0f], vec![]), 1.0);
+	}
+
+	#[test]
+	fn constants_respect_max_stack() {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+		frame.max_stack = Some(1);
+		assert!(matches!(
+			thread.execute_opcode(&[0x0a], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x04], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		/*
+		 * The interpreter loop catches the instructions that push
+		 * directly.
+		 */
+		let result = thread.execute_opcode(&[0x0c], &mut frame);
+		assert!(matches!(result, OpcodeResult::Error(_)) || frame.operand_stack_overflowed());
+	}
+
+	#[test]
+	fn bipush_and_sipush_sign_extend() {
+		assert_eq!(int_result(&[0x10, 0x7f], vec![]), 127);
+		assert_eq!(int_result(&[0x10, 0x80], vec![]), -128);
+		assert_eq!(int_result(&[0x11, 0x7f, 0xff], vec![]), 32767);
+		assert_eq!(int_result(&[0x11, 0x80, 0x00], vec![]), -32768);
+		assert_eq!(int_result(&[0x11, 0x01, 0x00], vec![]), 256);
+
+		let (result, _) = execute_with_operands(&[0x10, 0x01], vec![]);
+		assert!(matches!(result, OpcodeResult::Incr(2)));
+		let (result, _) = execute_with_operands(&[0x11, 0x00, 0x01], vec![]);
+		assert!(matches!(result, OpcodeResult::Incr(3)));
+	}
+
+	/*
+	 * Load the class that _builder_ puts together into _thread_'s
+	 * method area.
+	 */
+	fn load_class(thread: &JvmThread, builder: &mut ClassFileBuilder) -> Rc<Class> {
+		thread
+			.methodarea
+			.lock()
+			.unwrap()
+			.load_class_from_bytes(builder.bytes())
+			.unwrap()
+	}
+
+	/*
+	 * A java/lang/String with just the value field that String
+	 * objects keep their characters in.
+	 */
+	fn load_string_class(thread: &JvmThread) {
+		let mut builder = ClassFileBuilder::new("java/lang/String", Some("java/lang/Object"));
+		builder.field(FieldAccessFlags::Private as u16, "value", "[C");
+		load_class(thread, &mut builder);
+	}
+
+	#[test]
+	fn ldc_loads_integers_floats_and_strings() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let mut builder = ClassFileBuilder::new("Constants", Some("java/lang/Object"));
+		let integer = builder.integer(-42);
+		let float = builder.float(2.5);
+		let string = builder.string("constant");
+		let long = builder.long(1);
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0x12, integer as u8], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		assert!(frame.operand_stack.last() == Some(&int_value(-42)));
+		assert_eq!(frame.pop_int(), Ok(-42));
+
+		/*
+		 * ldc_w takes a two-byte index.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x13, 0, float as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(frame.pop_float(), Ok(2.5));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0x12, string as u8], &mut frame),
+			OpcodeResult::Incr(2)
+		));
+		match frame.operand_stack.pop() {
+			Some(JvmValue::Reference(
+				JvmReferenceType::Class(class_name),
+				JvmReferenceTargetType::Object(object),
+				_,
+			)) => {
+				assert_eq!(class_name, "java/lang/String");
+				assert_eq!(
+					string_object_value(&object.lock().unwrap()),
+					Some(format!("constant"))
+				);
+			}
+			_ => panic!("ldc did not push a String"),
+		}
+
+		/*
+		 * Longs and doubles are for ldc2_w.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x12, long as u8], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn ldc2_w_loads_longs_and_doubles() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Constants", Some("java/lang/Object"));
+		let long = builder.long(-(1 << 40));
+		let double = builder.double(0.125);
+		let integer = builder.integer(1);
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0x14, 0, long as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0x14, 0, double as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(frame.operand_stack_depth(), 4);
+		assert_eq!(frame.pop_double(), Ok(0.125));
+		assert_eq!(frame.pop_long(), Ok(-(1 << 40)));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0x14, 0, integer as u8], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	fn float_result(bytes: &[u8], operands: Vec<JvmValue>) -> f32 {
+		let (result, mut frame) = execute_with_operands(bytes, operands);
+		assert!(matches!(result, OpcodeResult::Incr(1)));
+		frame.pop_float().unwrap()
+	}
+
+	#[test]
+	fn numeric_conversions() {
+		/*
+		 * i2l, i2f and i2d; i2d is exact for every int.
+		 */
+		assert_eq!(long_result(&[0x85], vec![int_value(-5)]), -5);
+		assert_eq!(float_result(&[0x86], vec![int_value(3)]), 3.0);
+		assert_eq!(
+			double_result(&[0x87], vec![int_value(i32::max_value())]),
+			2147483647.0
+		);
+		assert_eq!(
+			double_result(&[0x87], vec![int_value(i32::min_value())]),
+			-2147483648.0
+		);
+
+		/*
+		 * l2i keeps the low 32 bits; l2f and l2d round.
+		 */
+		assert_eq!(int_result(&[0x88], vec![long_value((1 << 32) + 7)]), 7);
+		assert_eq!(
+			float_result(&[0x89], vec![long_value(1 << 40)]),
+			1099511627776.0
+		);
+		assert_eq!(double_result(&[0x8a], vec![long_value(-3)]), -3.0);
+
+		/*
+		 * f2i, f2l and f2d.
+		 */
+		assert_eq!(int_result(&[0x8b], vec![float_value(-2.75)]), -2);
+		assert_eq!(long_result(&[0x8c], vec![float_value(1e10)]), 10000000000);
+		assert_eq!(double_result(&[0x8d], vec![float_value(0.5)]), 0.5);
+
+		/*
+		 * d2i, d2l and d2f.
+		 */
+		assert_eq!(int_result(&[0x8e], vec![double_value(9.99)]), 9);
+		assert_eq!(
+			long_result(&[0x8f], vec![double_value(-1e12)]),
+			-1000000000000
+		);
+		assert_eq!(float_result(&[0x90], vec![double_value(0.25)]), 0.25);
+	}
+
+	#[test]
+	fn narrowing_to_integers_saturates() {
+		/*
+		 * NaN goes to 0 and anything out of range to the nearest
+		 * bound.
+		 */
+		assert_eq!(int_result(&[0x8b], vec![float_value(std::f32::NAN)]), 0);
+		assert_eq!(
+			int_result(&[0x8b], vec![float_value(-1e20)]),
+			i32::min_value()
+		);
+		assert_eq!(long_result(&[0x8c], vec![float_value(std::f32::NAN)]), 0);
+		assert_eq!(
+			int_result(&[0x8e], vec![double_value(1e300)]),
+			i32::max_value()
+		);
+		assert_eq!(
+			int_result(&[0x8e], vec![double_value(std::f64::INFINITY)]),
+			i32::max_value()
+		);
+		assert_eq!(
+			long_result(&[0x8f], vec![double_value(std::f64::NEG_INFINITY)]),
+			i64::min_value()
+		);
+	}
+
+	#[test]
+	fn conversions_check_their_operand() {
+		let (result, _) = execute_with_operands(&[0x85], vec![float_value(1.0)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+		let (result, _) = execute_with_operands(&[0x8e], vec![long_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+		let (result, _) = execute_with_operands(&[0x88], vec![]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn narrowing_to_bytes_chars_and_shorts() {
+		/*
+		 * i2b and i2s keep the sign; i2c does not.
+		 */
+		assert_eq!(int_result(&[0x91], vec![int_value(0x1FF)]), -1);
+		assert_eq!(int_result(&[0x91], vec![int_value(0x7F)]), 127);
+		assert_eq!(int_result(&[0x92], vec![int_value(-1)]), 65535);
+		assert_eq!(int_result(&[0x92], vec![int_value(0x1_0041)]), 0x41);
+		assert_eq!(int_result(&[0x93], vec![int_value(0x18000)]), -32768);
+		assert_eq!(int_result(&[0x93], vec![int_value(-2)]), -2);
+
+		let (result, _) = execute_with_operands(&[0x92], vec![float_value(1.0)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn lcmp_compares_longs() {
+		assert_eq!(int_result(&[0x94], vec![long_value(5), long_value(5)]), 0);
+		assert_eq!(
+			int_result(&[0x94], vec![long_value(-1 << 40), long_value(1)]),
+			-1
+		);
+		assert_eq!(
+			int_result(&[0x94], vec![long_value(1 << 40), long_value(1)]),
+			1
+		);
+		let (result, _) = execute_with_operands(&[0x94], vec![long_value(1), int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn float_and_double_comparisons_handle_nan() {
+		let nan = std::f32::NAN;
+		for (opcode, nan_result) in [(0x95, -1), (0x96, 1)].iter() {
+			assert_eq!(
+				int_result(&[*opcode], vec![float_value(1.5), float_value(1.5)]),
+				0
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![float_value(1.0), float_value(2.0)]),
+				-1
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![float_value(2.0), float_value(1.0)]),
+				1
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![float_value(nan), float_value(1.0)]),
+				*nan_result
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![float_value(1.0), float_value(nan)]),
+				*nan_result
+			);
+		}
+
+		let nan = std::f64::NAN;
+		for (opcode, nan_result) in [(0x97, -1), (0x98, 1)].iter() {
+			assert_eq!(
+				int_result(&[*opcode], vec![double_value(0.0), double_value(-0.0)]),
+				0
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![double_value(-3.0), double_value(2.0)]),
+				-1
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![double_value(3.0), double_value(2.0)]),
+				1
+			);
+			assert_eq!(
+				int_result(&[*opcode], vec![double_value(nan), double_value(nan)]),
+				*nan_result
+			);
+		}
+	}
+
+	/*
+	 * The operand stack, bottom first, after _opcode_ runs on
+	 * _operands_.
+	 */
+	fn stack_after(opcode: u8, operands: Vec<JvmValue>) -> Vec<JvmValue> {
+		let (result, frame) = execute_with_operands(&[opcode], operands);
+		assert!(matches!(result, OpcodeResult::Incr(1)));
+		frame.operand_stack
+	}
+
+	#[test]
+	fn dup_and_swap() {
+		assert!(stack_after(0x59, vec![int_value(1)]) == vec![int_value(1), int_value(1)]);
+		assert!(
+			stack_after(0x5f, vec![int_value(1), float_value(2.0)])
+				== vec![float_value(2.0), int_value(1)]
+		);
+
+		/*
+		 * dup_x1 and dup_x2 put the copy beneath one or two slots.
+		 */
+		assert!(
+			stack_after(0x5a, vec![int_value(1), int_value(2)])
+				== vec![int_value(2), int_value(1), int_value(2)]
+		);
+		assert!(
+			stack_after(0x5b, vec![long_value(1), int_value(2)])
+				== vec![int_value(2), long_value(1), int_value(2)]
+		);
+
+		/*
+		 * Neither dup nor swap splits a long.
+		 */
+		let (result, _) = execute_with_operands(&[0x59], vec![long_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+		let (result, _) = execute_with_operands(&[0x5f], vec![long_value(1), int_value(2)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn dup2_counts_slots() {
+		/*
+		 * dup2 copies one long or two ints.
+		 */
+		assert!(stack_after(0x5c, vec![long_value(7)]) == vec![long_value(7), long_value(7)]);
+		assert!(
+			stack_after(0x5c, vec![int_value(1), int_value(2)])
+				== vec![int_value(1), int_value(2), int_value(1), int_value(2)]
+		);
+		assert!(
+			stack_after(0x5d, vec![int_value(1), double_value(2.0)])
+				== vec![double_value(2.0), int_value(1), double_value(2.0)]
+		);
+
+		/*
+		 * The four shapes of dup2_x2.
+		 */
+		assert!(
+			stack_after(0x5e, vec![long_value(1), long_value(2)])
+				== vec![long_value(2), long_value(1), long_value(2)]
+		);
+		assert!(
+			stack_after(0x5e, vec![long_value(1), int_value(2), int_value(3)])
+				== vec![
+					int_value(2),
+					int_value(3),
+					long_value(1),
+					int_value(2),
+					int_value(3)
+				]
+		);
+		assert!(
+			stack_after(0x5e, vec![int_value(1), int_value(2), long_value(3)])
+				== vec![long_value(3), int_value(1), int_value(2), long_value(3)]
+		);
+		assert!(
+			stack_after(
+				0x5e,
+				vec![int_value(1), int_value(2), int_value(3), int_value(4)]
+			) == vec![
+				int_value(3),
+				int_value(4),
+				int_value(1),
+				int_value(2),
+				int_value(3),
+				int_value(4),
+			]
+		);
+
+		let (result, _) = execute_with_operands(&[0x5c], vec![long_value(1), int_value(2)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn pop_and_pop2() {
+		assert!(stack_after(0x57, vec![int_value(1), int_value(2)]) == vec![int_value(1)]);
+		assert!(stack_after(0x58, vec![int_value(1), long_value(2)]) == vec![int_value(1)]);
+		assert!(stack_after(0x58, vec![int_value(1), int_value(2)]).is_empty());
+
+		let (result, _) = execute_with_operands(&[0x57], vec![double_value(1.0)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+		let (result, _) = execute_with_operands(&[0x58], vec![int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	const PUBLIC_STATIC: u16 = MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16;
+
+	/*
+	 * Run _class_'s method _name_ with _descriptor_, with _locals_
+	 * as its arguments, on _thread_.
+	 */
+	fn run_method(
+		thread: &mut JvmThread,
+		class: &Rc<Class>,
+		name: &str,
+		descriptor: &str,
+		locals: Vec<JvmValue>,
+	) -> OpcodeResult {
+		let method = class
+			.get_method_rc_by_name_and_type(&name.to_string(), &descriptor.to_string())
+			.unwrap();
+		let mut frame = frame_with_locals(locals);
+		frame.class = Some(Rc::clone(class));
+		thread.execute_method(&method, frame)
+	}
+
+	fn returned_int(result: OpcodeResult) -> i32 {
+		match result {
+			OpcodeResult::Return(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _)) => {
+				value as i32
+			}
+			_ => panic!("the method did not return an int"),
+		}
+	}
+
+	#[test]
+	fn pushing_beyond_max_stack_is_detected() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Deep", Some("java/lang/Object"));
+		/*
+		 * iconst_1; iconst_2; iadd; ireturn, with room for one and for
+		 * two values; and bipush 7; dup; ireturn with room for one.
+		 */
+		builder
+			.method(
+				PUBLIC_STATIC,
+				"narrow",
+				"()I",
+				1,
+				0,
+				&[0x04, 0x05, 0x60, 0xac],
+			)
+			.method(
+				PUBLIC_STATIC,
+				"wide",
+				"()I",
+				2,
+				0,
+				&[0x04, 0x05, 0x60, 0xac],
+			)
+			.method(
+				PUBLIC_STATIC,
+				"duplicate",
+				"()I",
+				1,
+				0,
+				&[0x10, 7, 0x59, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert!(matches!(
+			run_method(&mut thread, &class, "narrow", "()I", vec![]),
+			OpcodeResult::Error(_)
+		));
+		assert!(matches!(
+			run_method(&mut thread, &class, "duplicate", "()I", vec![]),
+			OpcodeResult::Error(_)
+		));
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "wide", "()I", vec![])),
+			3
+		);
+	}
+
+	#[test]
+	fn returns_land_on_the_invoking_frame() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Returns", Some("java/lang/Object"));
+		let int_callee = builder.method_ref("Returns", "intCallee", "()I");
+		let long_callee = builder.method_ref("Returns", "longCallee", "()J");
+		let void_callee = builder.method_ref("Returns", "voidCallee", "()V");
+		let [int_high, int_low] = int_callee.to_be_bytes();
+		let [long_high, long_low] = long_callee.to_be_bytes();
+		let [void_high, void_low] = void_callee.to_be_bytes();
+		builder
+			.method(PUBLIC_STATIC, "intCallee", "()I", 1, 0, &[0x10, 42, 0xac])
+			.method(PUBLIC_STATIC, "longCallee", "()J", 2, 0, &[0x0a, 0xad])
+			.method(PUBLIC_STATIC, "voidCallee", "()V", 0, 0, &[0xb1])
+			/*
+			 * invokestatic intCallee; iconst_1; iadd; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"intCaller",
+				"()I",
+				2,
+				0,
+				&[0xb8, int_high, int_low, 0x04, 0x60, 0xac],
+			)
+			/*
+			 * invokestatic longCallee; lconst_1; ladd; lreturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"longCaller",
+				"()J",
+				4,
+				0,
+				&[0xb8, long_high, long_low, 0x0a, 0x61, 0xad],
+			)
+			/*
+			 * iconst_2; invokestatic voidCallee; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"voidCaller",
+				"()I",
+				1,
+				0,
+				&[0x05, 0xb8, void_high, void_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "intCaller", "()I", vec![])),
+			43
+		);
+		match run_method(&mut thread, &class, "longCaller", "()J", vec![]) {
+			OpcodeResult::Return(value) => assert!(value == long_value(2)),
+			_ => panic!("longCaller did not return"),
+		}
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "voidCaller", "()I", vec![])),
+			2
+		);
+		assert!(thread.frames.is_empty());
+	}
+
+	#[test]
+	fn returns_check_the_type_of_their_value() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Returns", Some("java/lang/Object"));
+		builder
+			.method(PUBLIC_STATIC, "floatAsInt", "()I", 1, 0, &[0x0c, 0xac])
+			.method(PUBLIC_STATIC, "float", "()F", 1, 0, &[0x0d, 0xae])
+			.method(PUBLIC_STATIC, "double", "()D", 2, 0, &[0x0f, 0xaf])
+			.method(
+				PUBLIC_STATIC,
+				"null",
+				"()Ljava/lang/Object;",
+				1,
+				0,
+				&[0x01, 0xb0],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		match run_method(&mut thread, &class, "float", "()F", vec![]) {
+			OpcodeResult::Return(value) => assert!(value == float_value(2.0)),
+			_ => panic!("float did not return"),
+		}
+		match run_method(&mut thread, &class, "double", "()D", vec![]) {
+			OpcodeResult::Return(value) => assert!(value == double_value(1.0)),
+			_ => panic!("double did not return"),
+		}
+		assert!(matches!(
+			run_method(&mut thread, &class, "null", "()Ljava/lang/Object;", vec![]),
+			OpcodeResult::Return(JvmValue::Reference(JvmReferenceType::Null, _, _))
+		));
+		assert!(matches!(
+			run_method(&mut thread, &class, "floatAsInt", "()I", vec![]),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	#[test]
+	fn invokestatic_passes_arguments_in_order() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Statics", Some("java/lang/Object"));
+		let add = builder.method_ref("Statics", "add", "(II)I");
+		let mix = builder.method_ref("Statics", "mix", "(IJI)I");
+		let [add_high, add_low] = add.to_be_bytes();
+		let [mix_high, mix_low] = mix.to_be_bytes();
+		builder
+			/*
+			 * iload_0; iload_1; iadd; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"add",
+				"(II)I",
+				2,
+				2,
+				&[0x1a, 0x1b, 0x60, 0xac],
+			)
+			/*
+			 * The long takes locals 1 and 2, so the last int is in
+			 * local 3: iload_0; iload_3; isub; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"mix",
+				"(IJI)I",
+				2,
+				4,
+				&[0x1a, 0x1d, 0x64, 0xac],
+			)
+			/*
+			 * bipush 30; bipush 12; invokestatic add; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"callAdd",
+				"()I",
+				2,
+				0,
+				&[0x10, 30, 0x10, 12, 0xb8, add_high, add_low, 0xac],
+			)
+			/*
+			 * bipush 10; lconst_1; iconst_3; invokestatic mix; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"callMix",
+				"()I",
+				4,
+				0,
+				&[0x10, 10, 0x0a, 0x06, 0xb8, mix_high, mix_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "callAdd", "()I", vec![])),
+			42
+		);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "callMix", "()I", vec![])),
+			7
+		);
+	}
+
+	#[test]
+	fn invokestatic_needs_its_arguments() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Statics", Some("java/lang/Object"));
+		let add = builder.method_ref("Statics", "add", "(II)I");
+		let [add_high, add_low] = add.to_be_bytes();
+		builder
+			.method(
+				PUBLIC_STATIC,
+				"add",
+				"(II)I",
+				2,
+				2,
+				&[0x1a, 0x1b, 0x60, 0xac],
+			)
+			.method(
+				PUBLIC_STATIC,
+				"callAdd",
+				"()I",
+				1,
+				0,
+				&[0x04, 0xb8, add_high, add_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert!(matches!(
+			run_method(&mut thread, &class, "callAdd", "()I", vec![]),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	/*
+	 * A reference to a new object of class _class_name_, without
+	 * running a constructor.
+	 */
+	fn new_object(thread: &mut JvmThread, class_name: &str) -> JvmValue {
+		let object = thread.create_object(&class_name.to_string()).unwrap();
+		JvmValue::Reference(
+			JvmReferenceType::Class(class_name.to_string()),
+			JvmReferenceTargetType::Object(Arc::new(Mutex::new(object))),
+			0,
+		)
+	}
+
+	fn thrown_class_name(result: OpcodeResult) -> String {
+		exception_class_name(&thrown(result))
+	}
+
+	fn thrown(result: OpcodeResult) -> JvmValue {
+		match result {
+			OpcodeResult::Throw(exception) => exception,
+			_ => panic!("nothing was thrown"),
+		}
+	}
+
+	/*
+	 * Base.value() returns 1 and Derived overrides it to return 2;
+	 * Base.base() is not overridden. Base.call(Base) invokes value()
+	 * on its argument and Base.callBase(Base) invokes base().
+	 */
+	fn load_base_and_derived(thread: &JvmThread) -> Rc<Class> {
+		let mut base = ClassFileBuilder::new("Base", Some("java/lang/Object"));
+		let value = base.method_ref("Base", "value", "()I");
+		let base_method = base.method_ref("Base", "base", "()I");
+		let [value_high, value_low] = value.to_be_bytes();
+		let [base_high, base_low] = base_method.to_be_bytes();
+		base.method(
+			MethodAccessFlags::Public as u16,
+			"value",
+			"()I",
+			1,
+			1,
+			&[0x04, 0xac],
+		)
+		.method(
+			MethodAccessFlags::Public as u16,
+			"base",
+			"()I",
+			1,
+			1,
+			&[0x08, 0xac],
+		)
+		.method(
+			PUBLIC_STATIC,
+			"call",
+			"(LBase;)I",
+			1,
+			1,
+			&[0x2a, 0xb6, value_high, value_low, 0xac],
+		)
+		.method(
+			PUBLIC_STATIC,
+			"callBase",
+			"(LBase;)I",
+			1,
+			1,
+			&[0x2a, 0xb6, base_high, base_low, 0xac],
+		);
+		let base = load_class(thread, &mut base);
+
+		let mut derived = ClassFileBuilder::new("Derived", Some("Base"));
+		derived.method(
+			MethodAccessFlags::Public as u16,
+			"value",
+			"()I",
+			1,
+			1,
+			&[0x05, 0xac],
+		);
+		load_class(thread, &mut derived);
+		base
+	}
+
+	#[test]
+	fn invokevirtual_runs_the_most_derived_method() {
+		let mut thread = new_thread();
+		let base = load_base_and_derived(&thread);
+		let base_object = new_object(&mut thread, "Base");
+		let derived_object = new_object(&mut thread, "Derived");
+
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&base,
+				"call",
+				"(LBase;)I",
+				vec![base_object]
+			)),
+			1
+		);
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&base,
+				"call",
+				"(LBase;)I",
+				vec![derived_object.clone()]
+			)),
+			2
+		);
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&base,
+				"callBase",
+				"(LBase;)I",
+				vec![derived_object]
+			)),
+			5
+		);
+	}
+
+	#[test]
+	fn invokevirtual_on_null_throws() {
+		let mut thread = new_thread();
+		let base = load_base_and_derived(&thread);
+
+		assert_eq!(
+			thrown_class_name(run_method(
+				&mut thread,
+				&base,
+				"call",
+				"(LBase;)I",
+				vec![create_null_value()]
+			)),
+			"java/lang/NullPointerException"
+		);
+		assert!(thread.frames.is_empty());
+	}
+
+	#[test]
+	fn invokespecial_runs_constructors() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Point", Some("java/lang/Object"));
+		let object_init = builder.method_ref("java/lang/Object", "<init>", "()V");
+		let init = builder.method_ref("Point", "<init>", "(I)V");
+		let x = builder.field_ref("Point", "x", "I");
+		let point = builder.class("Point");
+		let [object_init_high, object_init_low] = object_init.to_be_bytes();
+		let [init_high, init_low] = init.to_be_bytes();
+		let [x_high, x_low] = x.to_be_bytes();
+		let [point_high, point_low] = point.to_be_bytes();
+		builder
+			.field(FieldAccessFlags::Private as u16, "x", "I")
+			/*
+			 * aload_0; invokespecial Object.<init>; aload_0; iload_1;
+			 * putfield x; return
+			 */
+			.method(
+				MethodAccessFlags::Public as u16,
+				"<init>",
+				"(I)V",
+				2,
+				2,
+				&[
+					0x2a,
+					0xb7,
+					object_init_high,
+					object_init_low,
+					0x2a,
+					0x1b,
+					0xb5,
+					x_high,
+					x_low,
+					0xb1,
+				],
+			)
+			/*
+			 * new Point; dup; iload_0; invokespecial Point.<init>;
+			 * getfield x; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"make",
+				"(I)I",
+				3,
+				1,
+				&[
+					0xbb, point_high, point_low, 0x59, 0x1a, 0xb7, init_high, init_low, 0xb4,
+					x_high, x_low, 0xac,
+				],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"make",
+				"(I)I",
+				vec![int_value(17)]
+			)),
+			17
+		);
+	}
+
+	#[test]
+	fn invokespecial_does_not_dispatch_virtually() {
+		let mut thread = new_thread();
+		let base = load_base_and_derived(&thread);
+
+		/*
+		 * Sub.value() is super.value() + 10: aload_0; invokespecial
+		 * Base.value; bipush 10; iadd; ireturn
+		 */
+		let mut builder = ClassFileBuilder::new("Sub", Some("Base"));
+		let value = builder.method_ref("Base", "value", "()I");
+		let [value_high, value_low] = value.to_be_bytes();
+		builder.method(
+			MethodAccessFlags::Public as u16,
+			"value",
+			"()I",
+			2,
+			1,
+			&[0x2a, 0xb7, value_high, value_low, 0x10, 10, 0x60, 0xac],
+		);
+		load_class(&thread, &mut builder);
+		let sub = new_object(&mut thread, "Sub");
+
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&base,
+				"call",
+				"(LBase;)I",
+				vec![sub]
+			)),
+			11
+		);
+	}
+
+	/*
+	 * The interface Shape with area(), and Shapes.call(Shape), which
+	 * invokes area() on its argument.
+	 */
+	fn load_shape(thread: &JvmThread) -> Rc<Class> {
+		let mut shape = ClassFileBuilder::new("Shape", Some("java/lang/Object"));
+		shape
+			.access_flags(
+				ClassAccessFlags::Public as u16
+					| ClassAccessFlags::Interface as u16
+					| ClassAccessFlags::Abstract as u16,
+			)
+			.method_without_code(
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Abstract as u16,
+				"area",
+				"()I",
+			);
+		load_class(thread, &mut shape);
+
+		let mut shapes = ClassFileBuilder::new("Shapes", Some("java/lang/Object"));
+		let area = shapes.interface_method_ref("Shape", "area", "()I");
+		let [area_high, area_low] = area.to_be_bytes();
+		shapes.method(
+			PUBLIC_STATIC,
+			"call",
+			"(LShape;)I",
+			1,
+			1,
+			&[0x2a, 0xb9, area_high, area_low, 1, 0, 0xac],
+		);
+		load_class(thread, &mut shapes)
+	}
+
+	#[test]
+	fn invokeinterface_dispatches_on_the_receiver() {
+		let mut thread = new_thread();
+		let shapes = load_shape(&thread);
+		let mut square = ClassFileBuilder::new("Square", Some("java/lang/Object"));
+		square.interface("Shape").method(
+			MethodAccessFlags::Public as u16,
+			"area",
+			"()I",
+			1,
+			1,
+			&[0x07, 0xac],
+		);
+		load_class(&thread, &mut square);
+		let square = new_object(&mut thread, "Square");
+
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&shapes,
+				"call",
+				"(LShape;)I",
+				vec![square]
+			)),
+			4
+		);
+	}
+
+	#[test]
+	fn invokeinterface_without_an_implementation_throws() {
+		let mut thread = new_thread();
+		let shapes = load_shape(&thread);
+		let mut broken = ClassFileBuilder::new("Broken", Some("java/lang/Object"));
+		broken.interface("Shape");
+		load_class(&thread, &mut broken);
+		let broken = new_object(&mut thread, "Broken");
+
+		assert_eq!(
+			thrown_class_name(run_method(
+				&mut thread,
+				&shapes,
+				"call",
+				"(LShape;)I",
+				vec![broken]
+			)),
+			"java/lang/AbstractMethodError"
+		);
+		assert_eq!(
+			thrown_class_name(run_method(
+				&mut thread,
+				&shapes,
+				"call",
+				"(LShape;)I",
+				vec![create_null_value()]
+			)),
+			"java/lang/NullPointerException"
+		);
+	}
+
+	/*
+	 * The object that _value_ refers to.
+	 */
+	fn object_of(value: &JvmValue) -> Arc<Mutex<JvmObject>> {
+		match value {
+			JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) => Arc::clone(object),
+			_ => panic!("not a reference to an object"),
+		}
+	}
+
+	fn field_value(object: &JvmValue, class_name: &str, field_name: &str) -> JvmValue {
+		let object = object_of(object);
+		let object = object.lock().unwrap();
+		let value = object
+			.get_field(&class_name.to_string(), &field_name.to_string())
+			.unwrap();
+		(*value).clone()
+	}
+
+	#[test]
+	fn new_makes_objects_with_default_fields() {
+		let mut thread = new_thread();
+		let mut parent = ClassFileBuilder::new("Parent", Some("java/lang/Object"));
+		parent.field(FieldAccessFlags::Protected as u16, "inherited", "J");
+		load_class(&thread, &mut parent);
+		let mut builder = ClassFileBuilder::new("Fresh", Some("Parent"));
+		let fresh = builder.class("Fresh");
+		builder
+			.field(FieldAccessFlags::Private as u16, "count", "I")
+			.field(FieldAccessFlags::Private as u16, "ratio", "D")
+			.field(FieldAccessFlags::Private as u16, "flag", "Z")
+			.field(FieldAccessFlags::Private as u16, "next", "LFresh;");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0xbb, 0, fresh as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		let object = frame.operand_stack.pop().unwrap();
+		assert!(
+			matches!(&object, JvmValue::Reference(JvmReferenceType::Class(name), _, _) if name == "Fresh")
+		);
+		assert_eq!(
+			object_of(&object)
+				.lock()
+				.unwrap()
+				.get_class()
+				.get_class_name(),
+			Some(format!("Fresh"))
+		);
+		assert!(field_value(&object, "Fresh", "count") == int_value(0));
+		assert!(field_value(&object, "Fresh", "ratio") == double_value(0.0));
+		assert!(matches!(
+			field_value(&object, "Fresh", "flag"),
+			JvmValue::Primitive(JvmPrimitiveType::Boolean, 0, 0, _)
+		));
+		assert!(matches!(
+			field_value(&object, "Fresh", "next"),
+			JvmValue::Reference(_, JvmReferenceTargetType::Null, _)
+		));
+		assert!(field_value(&object, "Parent", "inherited") == long_value(0));
+	}
+
+	#[test]
+	fn new_refuses_interfaces_and_abstract_classes() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Abstract", Some("java/lang/Object"));
+		builder.access_flags(ClassAccessFlags::Public as u16 | ClassAccessFlags::Abstract as u16);
+		let abstract_class = builder.class("Abstract");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		assert!(matches!(
+			thread.execute_opcode(&[0xbb, 0, abstract_class as u8], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/InstantiationError"
+		));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn putfield_and_getfield_reach_inherited_fields() {
+		let mut thread = new_thread();
+		let mut parent = ClassFileBuilder::new("Parent", Some("java/lang/Object"));
+		parent.field(FieldAccessFlags::Protected as u16, "value", "I");
+		load_class(&thread, &mut parent);
+		let mut builder = ClassFileBuilder::new("Child", Some("Parent"));
+		let value = builder.field_ref("Child", "value", "I");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+		let child = new_object(&mut thread, "Child");
+
+		frame.operand_stack.push(child.clone());
+		frame.operand_stack.push(int_value(5));
+		assert!(matches!(
+			thread.execute_opcode(&[0xb5, 0, value as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(frame.operand_stack.is_empty());
+		assert!(field_value(&child, "Parent", "value") == int_value(5));
+
+		frame.operand_stack.push(child);
+		assert!(matches!(
+			thread.execute_opcode(&[0xb4, 0, value as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(frame.pop_int(), Ok(5));
+	}
+
+	#[test]
+	fn field_access_through_null_throws() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Holder", Some("java/lang/Object"));
+		let value = builder.field_ref("Holder", "value", "I");
+		builder.field(FieldAccessFlags::Private as u16, "value", "I");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		frame.operand_stack.push(create_null_value());
+		assert!(matches!(
+			thread.execute_opcode(&[0xb4, 0, value as u8], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+		));
+		frame.operand_stack.clear();
+		frame.operand_stack.push(create_null_value());
+		frame.operand_stack.push(int_value(1));
+		assert!(matches!(
+			thread.execute_opcode(&[0xb5, 0, value as u8], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+		));
+	}
+
+	#[test]
+	fn static_fields_initialize_their_class_once() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Counter", Some("java/lang/Object"));
+		let value = builder.field_ref("Counter", "value", "I");
+		let initializations = builder.field_ref("Counter", "initializations", "I");
+		let [initializations_high, initializations_low] = initializations.to_be_bytes();
+		let static_field = FieldAccessFlags::Public as u16 | FieldAccessFlags::Static as u16;
+		builder
+			.field(static_field, "value", "I")
+			.field(static_field, "initializations", "I")
+			/*
+			 * getstatic initializations; iconst_1; iadd; putstatic
+			 * initializations; return
+			 */
+			.method(
+				MethodAccessFlags::Static as u16,
+				"<clinit>",
+				"()V",
+				2,
+				0,
+				&[
+					0xb2,
+					initializations_high,
+					initializations_low,
+					0x04,
+					0x60,
+					0xb3,
+					initializations_high,
+					initializations_low,
+					0xb1,
+				],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		let mut frame = Frame::new();
+		frame.class = Some(Rc::clone(&class));
+		frame.operand_stack.push(int_value(9));
+		assert!(matches!(
+			thread.execute_opcode(&[0xb3, 0, value as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(frame.operand_stack.is_empty());
+
+		let mut frame = Frame::new();
+		frame.class = Some(class);
+		assert!(matches!(
+			thread.execute_opcode(&[0xb2, 0, value as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(frame.pop_int(), Ok(9));
+		assert!(matches!(
+			thread.execute_opcode(&[0xb2, 0, initializations as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(frame.pop_int(), Ok(1));
+	}
+
+	fn array_of(value: &JvmValue) -> Arc<Mutex<JvmArray>> {
+		match value {
+			JvmValue::Reference(_, JvmReferenceTargetType::Array(array), _) => Arc::clone(array),
+			_ => panic!("not a reference to an array"),
+		}
+	}
+
+	#[test]
+	fn newarray_fills_arrays_with_zeroes() {
+		let (result, mut frame) = execute_with_operands(&[0xbc, 10], vec![int_value(5)]);
+		assert!(matches!(result, OpcodeResult::Incr(2)));
+		let array = frame.operand_stack.pop().unwrap();
+		assert!(matches!(
+			&array,
+			JvmValue::Reference(JvmReferenceType::Array(element_type, 5), _, _)
+				if **element_type == JvmType::Primitive(JvmPrimitiveType::Integer)
+		));
+		let array = array_of(&array);
+		let array = array.lock().unwrap();
+		assert_eq!(array.len(), 5);
+		for index in 0..5 {
+			assert!(array.get(index) == Some(int_value(0)));
+		}
+
+		/*
+		 * T_LONG and T_BOOLEAN.
+		 */
+		let (_, mut frame) = execute_with_operands(&[0xbc, 11], vec![int_value(2)]);
+		let array = array_of(&frame.operand_stack.pop().unwrap());
+		assert!(array.lock().unwrap().get(1) == Some(long_value(0)));
+		let (_, mut frame) = execute_with_operands(&[0xbc, 4], vec![int_value(0)]);
+		assert_eq!(
+			array_of(&frame.operand_stack.pop().unwrap())
+				.lock()
+				.unwrap()
+				.len(),
+			0
+		);
+	}
+
+	#[test]
+	fn newarray_rejects_negative_sizes() {
+		let (result, frame) = execute_with_operands(&[0xbc, 10], vec![int_value(-1)]);
+		assert!(matches!(
+			result,
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NegativeArraySizeException"
+		));
+		assert!(frame.operand_stack.is_empty());
+
+		/*
+		 * 3 is not an array type.
+		 */
+		let (result, _) = execute_with_operands(&[0xbc, 3], vec![int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn anewarray_fills_arrays_with_typed_nulls() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let mut builder = ClassFileBuilder::new("Arrays", Some("java/lang/Object"));
+		let string = builder.class("java/lang/String");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		frame.operand_stack.push(int_value(3));
+		assert!(matches!(
+			thread.execute_opcode(&[0xbd, 0, string as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		let array = frame.operand_stack.pop().unwrap();
+		assert!(matches!(
+			&array,
+			JvmValue::Reference(JvmReferenceType::Array(element_type, 3), _, _)
+				if matches!(&**element_type, JvmType::Reference(JvmReferenceType::Class(name)) if name == "java/lang/String")
+		));
+		let array = array_of(&array);
+		let array = array.lock().unwrap();
+		for index in 0..3 {
+			assert!(matches!(
+				array.get(index),
+				Some(JvmValue::Reference(
+					JvmReferenceType::Class(ref class_name),
+					JvmReferenceTargetType::Null,
+					_
+				)) if class_name == "java/lang/String"
+			));
+		}
+		drop(array);
+
+		frame.operand_stack.push(int_value(-3));
+		assert!(matches!(
+			thread.execute_opcode(&[0xbd, 0, string as u8], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NegativeArraySizeException"
+		));
+	}
+
+	/*
+	 * A new array of _length_ elements of the primitive type
+	 * _array_type_ (a newarray atype).
+	 */
+	fn new_primitive_array(array_type: u8, length: i32) -> JvmValue {
+		let (_, mut frame) = execute_with_operands(&[0xbc, array_type], vec![int_value(length)]);
+		frame.operand_stack.pop().unwrap()
+	}
+
+	#[test]
+	fn iastore_and_iaload_round_trip() {
+		let array = new_primitive_array(10, 3);
+		let (result, frame) =
+			execute_with_operands(&[0x4f], vec![array.clone(), int_value(2), int_value(-9)]);
+		assert!(matches!(result, OpcodeResult::Incr(1)));
+		assert!(frame.operand_stack.is_empty());
+		assert_eq!(int_result(&[0x2e], vec![array.clone(), int_value(2)]), -9);
+		assert_eq!(int_result(&[0x2e], vec![array, int_value(0)]), 0);
+	}
+
+	#[test]
+	fn int_array_access_checks_the_array_and_index() {
+		let array = new_primitive_array(10, 3);
+		for index in [3, -1].iter() {
+			let (result, _) =
+				execute_with_operands(&[0x2e], vec![array.clone(), int_value(*index)]);
+			assert!(matches!(
+				result,
+				OpcodeResult::Exception(ref name, _) if name == "java/lang/ArrayIndexOutOfBoundsException"
+			));
+		}
+		let (result, _) = execute_with_operands(
+			&[0x4f],
+			vec![create_null_value(), int_value(0), int_value(1)],
+		);
+		assert!(matches!(
+			result,
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+		));
+		let (result, _) = execute_with_operands(&[0x2e], vec![create_null_value(), int_value(0)]);
+		assert!(matches!(
+			result,
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+		));
+	}
+
+	/*
+	 * Store _value_ at index 1 of a new array of _array_type_ with
+	 * _store_ and load it back with _load_.
+	 */
+	fn array_round_trip(array_type: u8, store: u8, load: u8, value: JvmValue) -> Frame {
+		let array = new_primitive_array(array_type, 2);
+		let (result, _) = execute_with_operands(&[store], vec![array.clone(), int_value(1), value]);
+		assert!(matches!(result, OpcodeResult::Incr(1)));
+		let (result, frame) = execute_with_operands(&[load], vec![array, int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Incr(1)));
+		frame
+	}
+
+	#[test]
+	fn typed_arrays_round_trip() {
+		assert_eq!(
+			array_round_trip(11, 0x50, 0x2f, long_value(-1 << 33)).pop_long(),
+			Ok(-1 << 33)
+		);
+		assert_eq!(
+			array_round_trip(6, 0x51, 0x30, float_value(0.5)).pop_float(),
+			Ok(0.5)
+		);
+		assert_eq!(
+			array_round_trip(7, 0x52, 0x31, double_value(-2.5)).pop_double(),
+			Ok(-2.5)
+		);
+
+		/*
+		 * Bytes and shorts are truncated on the way in and sign
+		 * extended on the way out; chars are zero extended.
+		 */
+		assert_eq!(
+			array_round_trip(8, 0x54, 0x33, int_value(0x1FF)).pop_int(),
+			Ok(-1)
+		);
+		assert_eq!(
+			array_round_trip(4, 0x54, 0x33, int_value(1)).pop_int(),
+			Ok(1)
+		);
+		assert_eq!(
+			array_round_trip(5, 0x55, 0x34, int_value(-1)).pop_int(),
+			Ok(65535)
+		);
+		assert_eq!(
+			array_round_trip(9, 0x56, 0x35, int_value(0x18000)).pop_int(),
+			Ok(-32768)
+		);
+
+		/*
+		 * The element has to match the array.
+		 */
+		let array = new_primitive_array(6, 1);
+		let (result, _) = execute_with_operands(&[0x50], vec![array, int_value(0), long_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn aastore_checks_the_element_type() {
+		let mut thread = new_thread();
+		load_base_and_derived(&thread);
+		let mut other = ClassFileBuilder::new("Other", Some("java/lang/Object"));
+		load_class(&thread, &mut other);
+		let mut builder = ClassFileBuilder::new("Arrays", Some("java/lang/Object"));
+		let base_index = builder.class("Base");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		frame.operand_stack.push(int_value(2));
+		assert!(matches!(
+			thread.execute_opcode(&[0xbd, 0, base_index as u8], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		let array = frame.operand_stack.pop().unwrap();
+		let derived = new_object(&mut thread, "Derived");
+		let other = new_object(&mut thread, "Other");
+
+		for element in vec![derived.clone(), create_null_value()] {
+			frame.operand_stack.push(array.clone());
+			frame.operand_stack.push(int_value(0));
+			frame.operand_stack.push(element);
+			assert!(matches!(
+				thread.execute_opcode(&[0x53], &mut frame),
+				OpcodeResult::Incr(1)
+			));
+		}
+		frame.operand_stack.push(array.clone());
+		frame.operand_stack.push(int_value(1));
+		frame.operand_stack.push(other);
+		assert!(matches!(
+			thread.execute_opcode(&[0x53], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/ArrayStoreException"
+		));
+
+		/*
+		 * aaload gives back what aastore stored.
+		 */
+		frame.operand_stack.clear();
+		frame.operand_stack.push(array.clone());
+		frame.operand_stack.push(int_value(1));
+		assert!(matches!(
+			thread.execute_opcode(&[0x32], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert!(matches!(
+			frame.operand_stack.pop(),
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Null, _))
+		));
+	}
+
+	#[test]
+	fn arraylength_gives_the_length() {
+		assert_eq!(int_result(&[0xbe], vec![new_primitive_array(10, 7)]), 7);
+		assert_eq!(int_result(&[0xbe], vec![new_primitive_array(11, 0)]), 0);
+
+		let (result, _) = execute_with_operands(&[0xbe], vec![create_null_value()]);
+		assert!(matches!(
+			result,
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+		));
+		let (result, _) = execute_with_operands(&[0xbe], vec![int_value(7)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn iinc_adds_a_signed_constant() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(10), int_value(i32::max_value())]);
+
+		assert!(matches!(
+			thread.execute_opcode(&[0x84, 0, 5], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(frame.locals[0] == int_value(15));
+		assert!(matches!(
+			thread.execute_opcode(&[0x84, 0, 0xff], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(frame.locals[0] == int_value(14));
+
+		/*
+		 * It wraps around.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x84, 1, 1], &mut frame),
+			OpcodeResult::Incr(3)
+		));
+		assert!(frame.locals[1] == int_value(i32::min_value()));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn wide_iinc_has_two_byte_operands() {
+		let mut thread = new_thread();
+		let mut locals = vec![int_value(0); 300];
+		locals[299] = int_value(1000);
+		let mut frame = frame_with_locals(locals);
+
+		/*
+		 * wide iinc 299, -1000 and then 299, 300.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x84, 0x01, 0x2b, 0xfc, 0x18], &mut frame),
+			OpcodeResult::Incr(6)
+		));
+		assert!(frame.locals[299] == int_value(0));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc4, 0x84, 0x01, 0x2b, 0x01, 0x2c], &mut frame),
+			OpcodeResult::Incr(6)
+		));
+		assert!(frame.locals[299] == int_value(300));
+
+		/*
+		 * iinc only increments ints.
+		 */
+		frame.locals[0] = float_value(1.0);
+		assert!(matches!(
+			thread.execute_opcode(&[0x84, 0, 1], &mut frame),
+			OpcodeResult::Error(_)
+		));
+	}
+
+	/*
+	 * Code that starts with iload_0 and then _switch_, so that the
+	 * switch is at the odd offset 1 and needs two bytes of padding,
+	 * followed by _targets_.
+	 */
+	fn switch_code(opcode: u8, operands: &[i32], targets: &[u8]) -> Vec<u8> {
+		let mut code = vec![0x1a, opcode, 0, 0];
+		for operand in operands {
+			code.extend_from_slice(&operand.to_be_bytes());
+		}
+		code.extend_from_slice(targets);
+		code
+	}
+
+	fn load_switch_method(thread: &JvmThread, code: &[u8]) -> Rc<Class> {
+		let mut builder = ClassFileBuilder::new("Switch", Some("java/lang/Object"));
+		builder.method(PUBLIC_STATIC, "pick", "(I)I", 1, 1, code);
+		load_class(thread, &mut builder)
+	}
+
+	fn pick(thread: &mut JvmThread, class: &Rc<Class>, key: i32) -> i32 {
+		returned_int(run_method(
+			thread,
+			class,
+			"pick",
+			"(I)I",
+			vec![int_value(key)],
+		))
+	}
+
+	#[test]
+	fn tableswitch_at_an_odd_offset() {
+		let mut thread = new_thread();
+
+		/*
+		 * The table is default, low 1, high 3 and three offsets; the
+		 * targets (at 28, 31, 34 and 37) return 10, 20, 30 and 0.
+		 */
+		let code = switch_code(
+			0xaa,
+			&[36, 1, 3, 27, 30, 33],
+			&[0x10, 10, 0xac, 0x10, 20, 0xac, 0x10, 30, 0xac, 0x03, 0xac],
+		);
+		assert_eq!(code.len(), 39);
+		let class = load_switch_method(&thread, &code);
+
+		assert_eq!(pick(&mut thread, &class, 1), 10);
+		assert_eq!(pick(&mut thread, &class, 2), 20);
+		assert_eq!(pick(&mut thread, &class, 3), 30);
+		assert_eq!(pick(&mut thread, &class, 0), 0);
+		assert_eq!(pick(&mut thread, &class, 4), 0);
+		assert_eq!(pick(&mut thread, &class, i32::min_value()), 0);
+	}
+
+	#[test]
+	fn tableswitch_rejects_operands_beyond_the_code() {
+		let mut thread = new_thread();
+
+		/*
+		 * A default and nothing else, a low greater than its high, and
+		 * ranges of offsets much larger than the code.
+		 */
+		let truncated = switch_code(0xaa, &[0], &[]);
+		let backwards = switch_code(0xaa, &[0, 2, 1, 0, 0], &[]);
+		let too_large = switch_code(0xaa, &[0, 0, i32::max_value(), 0], &[]);
+		let widest = switch_code(0xaa, &[0, i32::min_value(), i32::max_value()], &[]);
+		let padding_only = vec![0x1a, 0xaa, 0];
+		for code in [truncated, backwards, too_large, widest, padding_only].iter() {
+			let mut frame = Frame::new();
+			frame.pc = 1;
+			frame.operand_stack.push(int_value(0));
+			assert!(matches!(
+				thread.execute_opcode(&code[1..], &mut frame),
+				OpcodeResult::Error(_)
+			));
+		}
+	}
+
+	#[test]
+	fn lookupswitch_at_an_odd_offset() {
+		let mut thread = new_thread();
+
+		/*
+		 * default, two pairs (-5 and 100); the targets (at 28, 31
+		 * and 34) return 10, 20 and 0.
+		 */
+		let code = switch_code(
+			0xab,
+			&[33, 2, -5, 27, 100, 30],
+			&[0x10, 10, 0xac, 0x10, 20, 0xac, 0x03, 0xac],
+		);
+		let class = load_switch_method(&thread, &code);
+
+		assert_eq!(pick(&mut thread, &class, -5), 10);
+		assert_eq!(pick(&mut thread, &class, 100), 20);
+		assert_eq!(pick(&mut thread, &class, 0), 0);
+		assert_eq!(pick(&mut thread, &class, 99), 0);
+	}
+
+	#[test]
+	fn lookupswitch_without_pairs_takes_the_default() {
+		let mut thread = new_thread();
+
+		/*
+		 * The default target, at 12, returns 7.
+		 */
+		let code = switch_code(0xab, &[11, 0], &[0x10, 7, 0xac]);
+		let class = load_switch_method(&thread, &code);
+
+		assert_eq!(pick(&mut thread, &class, 0), 7);
+		assert_eq!(pick(&mut thread, &class, -1), 7);
+	}
+
+	#[test]
+	fn lookupswitch_rejects_pairs_beyond_the_code() {
+		let mut thread = new_thread();
+
+		/*
+		 * A default without npairs, a negative npairs, and npairs that
+		 * claim more pairs than there are.
+		 */
+		let truncated = switch_code(0xab, &[0], &[]);
+		let negative = switch_code(0xab, &[0, -1], &[]);
+		let too_many = switch_code(0xab, &[0, 2, 0, 0], &[]);
+		let far_too_many = switch_code(0xab, &[0, i32::max_value()], &[]);
+		for code in [truncated, negative, too_many, far_too_many].iter() {
+			let mut frame = Frame::new();
+			frame.pc = 1;
+			frame.operand_stack.push(int_value(0));
+			assert!(matches!(
+				thread.execute_opcode(&code[1..], &mut frame),
+				OpcodeResult::Error(_)
+			));
+		}
+	}
+
+	/*
+	 * Problem, a RuntimeException, and Thrower: fail() throws a new
+	 * Problem, middle() calls fail(), catchHere() throws and
+	 * catches a Problem itself and returns 5, and outer() catches
+	 * the RuntimeException that middle() lets through and returns 9.
+	 */
+	fn load_thrower(thread: &JvmThread) -> Rc<Class> {
+		let mut problem = ClassFileBuilder::new("Problem", Some("java/lang/RuntimeException"));
+		load_class(thread, &mut problem);
+
+		let mut builder = ClassFileBuilder::new("Thrower", Some("java/lang/Object"));
+		let problem = builder.class("Problem");
+		let runtime_exception = builder.class("java/lang/RuntimeException");
+		let fail = builder.method_ref("Thrower", "fail", "()V");
+		let middle = builder.method_ref("Thrower", "middle", "()I");
+		let [problem_high, problem_low] = problem.to_be_bytes();
+		let [fail_high, fail_low] = fail.to_be_bytes();
+		let [middle_high, middle_low] = middle.to_be_bytes();
+		builder
+			/*
+			 * new Problem; athrow
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"fail",
+				"()V",
+				1,
+				0,
+				&[0xbb, problem_high, problem_low, 0xbf],
+			)
+			/*
+			 * invokestatic fail; iconst_0; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"middle",
+				"()I",
+				1,
+				0,
+				&[0xb8, fail_high, fail_low, 0x03, 0xac],
+			)
+			/*
+			 * new Problem; athrow; and the handler: pop; bipush 5;
+			 * ireturn
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"catchHere",
+				"()I",
+				1,
+				0,
+				&[0xbb, problem_high, problem_low, 0xbf, 0x57, 0x10, 5, 0xac],
+				&[(0, 4, 4, problem)],
+				&[],
+			)
+			/*
+			 * invokestatic middle; ireturn; and the handler: pop;
+			 * bipush 9; ireturn
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"outer",
+				"()I",
+				1,
+				0,
+				&[0xb8, middle_high, middle_low, 0xac, 0x57, 0x10, 9, 0xac],
+				&[(0, 4, 4, runtime_exception)],
+				&[],
+			)
+			/*
+			 * aconst_null; athrow
+			 */
+			.method(PUBLIC_STATIC, "throwNull", "()V", 1, 0, &[0x01, 0xbf]);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn athrow_is_caught_by_a_handler() {
+		let mut thread = new_thread();
+		let class = load_thrower(&thread);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "catchHere", "()I", vec![])),
+			5
+		);
+		assert!(thread.frames.is_empty());
+	}
+
+	#[test]
+	fn athrow_unwinds_to_a_handler() {
+		let mut thread = new_thread();
+		let class = load_thrower(&thread);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "outer", "()I", vec![])),
+			9
+		);
+		assert!(thread.frames.is_empty());
+		assert_eq!(
+			thrown_class_name(run_method(&mut thread, &class, "middle", "()I", vec![])),
+			"Problem"
+		);
+		assert!(thread.frames.is_empty());
+	}
+
+	#[test]
+	fn athrow_of_null_throws_a_null_pointer_exception() {
+		let mut thread = new_thread();
+		let class = load_thrower(&thread);
+
+		assert_eq!(
+			thrown_class_name(run_method(&mut thread, &class, "throwNull", "()V", vec![])),
+			"java/lang/NullPointerException"
+		);
+	}
+
+	/*
+	 * What a thread writes to its standard error.
+	 */
+	#[derive(Clone, Default)]
+	struct Captured(Rc<std::cell::RefCell<Vec<u8>>>);
+
+	impl Write for Captured {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.borrow_mut().write(buf)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	impl Captured {
+		fn text(&self) -> String {
+			String::from_utf8(self.0.borrow().clone()).unwrap()
+		}
+	}
+
+	#[test]
+	fn uncaught_exceptions_print_a_stack_trace() {
+		let mut thread = new_thread();
+		let err = Captured::default();
+		thread.set_err(Box::new(err.clone()));
+		let mut builder = ClassFileBuilder::new("app/Trace", Some("java/lang/Object"));
+		let inner = builder.method_ref("app/Trace", "inner", "()V");
+		let [inner_high, inner_low] = inner.to_be_bytes();
+		builder
+			/*
+			 * nop; invokestatic inner; return
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"main",
+				"([Ljava/lang/String;)V",
+				0,
+				1,
+				&[0x00, 0xb8, inner_high, inner_low, 0xb1],
+				&[],
+				&[(0, 5), (1, 6), (4, 7)],
+			)
+			/*
+			 * aconst_null; arraylength; pop; return
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"inner",
+				"()V",
+				1,
+				0,
+				&[0x01, 0xbe, 0x57, 0xb1],
+				&[],
+				&[(0, 10), (1, 11)],
+			);
+		load_class(&thread, &mut builder);
+
+		assert!(matches!(
+			thread.run(&format!("app/Trace"), &format!("main")),
+			Err(JvmError::UncaughtException(ref name)) if name == "java/lang/NullPointerException"
+		));
+		assert_eq!(
+			err.text(),
+			"Exception in thread \"main\" java.lang.NullPointerException\n\
+			 \tat app.Trace.inner(Trace.java:11)\n\
+			 \tat app.Trace.main(Trace.java:6)\n"
+		);
+	}
+
+	#[test]
+	fn fatal_errors_stop_only_the_method() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Broken", Some("java/lang/Object"));
+		builder
+			.method(PUBLIC_STATIC, "broken", "()I", 1, 0, &[0x04, 0xcb, 0xac])
+			.method(PUBLIC_STATIC, "fine", "()I", 1, 0, &[0x04, 0xac]);
+		let class = load_class(&thread, &mut builder);
+
+		assert!(matches!(
+			run_method(&mut thread, &class, "broken", "()I", vec![]),
+			OpcodeResult::Error(JvmError::Fatal(FatalErrorType::UnimplementedOpcode(
+				0xcb, 1
+			)))
+		));
+		assert!(thread.frames.is_empty());
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "fine", "()I", vec![])),
+			1
+		);
+	}
+
+	#[test]
+	fn monitorenter_and_monitorexit_balance() {
+		let mut thread = new_thread();
+		let object = object_value();
+		let mut frame = Frame::new();
+		let current = std::thread::current().id();
+
+		for _ in 0..2 {
+			frame.operand_stack.push(object.clone());
+			assert!(matches!(
+				thread.execute_opcode(&[0xc2], &mut frame),
+				OpcodeResult::Incr(1)
+			));
+		}
+		{
+			let object = object_of(&object);
+			let object = object.lock().unwrap();
+			assert!(object.monitor().is_owned_by(current));
+			assert_eq!(object.monitor().count(), 2);
+		}
+		for _ in 0..2 {
+			frame.operand_stack.push(object.clone());
+			assert!(matches!(
+				thread.execute_opcode(&[0xc3], &mut frame),
+				OpcodeResult::Incr(1)
+			));
+		}
+		assert!(!object_of(&object)
+			.lock()
+			.unwrap()
+			.monitor()
+			.is_owned_by(current));
+		assert!(frame.operand_stack.is_empty());
+	}
+
+	#[test]
+	fn monitorexit_needs_an_owned_monitor() {
+		let mut thread = new_thread();
+		let mut frame = Frame::new();
+
+		frame.operand_stack.push(object_value());
+		assert!(matches!(
+			thread.execute_opcode(&[0xc3], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/IllegalMonitorStateException"
+		));
+		for opcode in [0xc2, 0xc3].iter() {
+			frame.operand_stack.push(create_null_value());
+			assert!(matches!(
+				thread.execute_opcode(&[*opcode], &mut frame),
+				OpcodeResult::Exception(ref name, _) if name == "java/lang/NullPointerException"
+			));
+		}
+	}
+
+	#[test]
+	fn synchronized_methods_hold_the_receivers_monitor() {
+		let mut natives = NativeRegistry::new();
+		natives.register("Lock", "held", "(LLock;)I", |_, arguments| {
+			let held = object_of(&arguments[0])
+				.lock()
+				.unwrap()
+				.monitor()
+				.is_owned_by(std::thread::current().id());
+			Ok(Some(Rc::new(int_value(held as i32))))
+		});
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			natives,
+			BootstrapRegistry::new(),
+		);
+
+		let mut builder = ClassFileBuilder::new("Lock", Some("java/lang/Object"));
+		let held = builder.method_ref("Lock", "held", "(LLock;)I");
+		let [held_high, held_low] = held.to_be_bytes();
+		let synchronized =
+			MethodAccessFlags::Public as u16 | MethodAccessFlags::Synchronized as u16;
+		builder
+			.method_without_code(
+				PUBLIC_STATIC | MethodAccessFlags::Native as u16,
+				"held",
+				"(LLock;)I",
+			)
+			/*
+			 * aload_0; invokestatic held; ireturn
+			 */
+			.method(
+				synchronized,
+				"body",
+				"()I",
+				1,
+				1,
+				&[0x2a, 0xb8, held_high, held_low, 0xac],
+			)
+			.method(
+				MethodAccessFlags::Public as u16,
+				"unsynchronized",
+				"()I",
+				1,
+				1,
+				&[0x2a, 0xb8, held_high, held_low, 0xac],
+			)
+			/*
+			 * aconst_null; athrow
+			 */
+			.method(synchronized, "fail", "()V", 1, 1, &[0x01, 0xbf]);
+		let class = load_class(&thread, &mut builder);
+		let lock = new_object(&mut thread, "Lock");
+		let current = std::thread::current().id();
+
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"body",
+				"()I",
+				vec![lock.clone()]
+			)),
+			1
+		);
+		assert!(!object_of(&lock)
+			.lock()
+			.unwrap()
+			.monitor()
+			.is_owned_by(current));
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"unsynchronized",
+				"()I",
+				vec![lock.clone()]
+			)),
+			0
+		);
+
+		/*
+		 * It is released when an exception unwinds the method too.
+		 */
+		assert_eq!(
+			thrown_class_name(run_method(
+				&mut thread,
+				&class,
+				"fail",
+				"()V",
+				vec![lock.clone()]
+			)),
+			"java/lang/NullPointerException"
+		);
+		assert!(!object_of(&lock)
+			.lock()
+			.unwrap()
+			.monitor()
+			.is_owned_by(current));
+	}
+
+	#[test]
+	fn multianewarray_allocates_nested_arrays() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Arrays", Some("java/lang/Object"));
+		let matrix = builder.class("[[I");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		frame.operand_stack.push(int_value(2));
+		frame.operand_stack.push(int_value(3));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc5, 0, matrix as u8, 2], &mut frame),
+			OpcodeResult::Incr(4)
+		));
+		let outer = array_of(&frame.operand_stack.pop().unwrap());
+		let outer = outer.lock().unwrap();
+		assert_eq!(outer.len(), 2);
+		for index in 0..2 {
+			let inner = array_of(&outer.get(index).unwrap());
+			let inner = inner.lock().unwrap();
+			assert_eq!(inner.len(), 3);
+			for inner_index in 0..3 {
+				assert!(inner.get(inner_index) == Some(int_value(0)));
+			}
+		}
+
+		/*
+		 * With one dimension given, the inner arrays stay null.
+		 */
+		frame.operand_stack.push(int_value(2));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc5, 0, matrix as u8, 1], &mut frame),
+			OpcodeResult::Incr(4)
+		));
+		let outer = array_of(&frame.operand_stack.pop().unwrap());
+		assert!(matches!(
+			outer.lock().unwrap().get(1),
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Null, _))
+		));
+	}
+
+	#[test]
+	fn multianewarray_rejects_negative_counts() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Arrays", Some("java/lang/Object"));
+		let matrix = builder.class("[[I");
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+
+		frame.operand_stack.push(int_value(2));
+		frame.operand_stack.push(int_value(-3));
+		assert!(matches!(
+			thread.execute_opcode(&[0xc5, 0, matrix as u8, 2], &mut frame),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/NegativeArraySizeException"
+		));
+	}
+
+	/*
+	 * Base, Derived, Other and String, and a frame whose class has
+	 * the constants that the type checks need: the classes Base,
+	 * Runnable, String, Object[] and String[].
+	 */
+	fn type_check_frame(thread: &mut JvmThread) -> (Frame, [u8; 5]) {
+		load_string_class(thread);
+		load_base_and_derived(thread);
+		let mut other = ClassFileBuilder::new("Other", Some("java/lang/Object"));
+		other.interface("java/lang/Runnable");
+		load_class(thread, &mut other);
+		let mut runnable = ClassFileBuilder::new("java/lang/Runnable", Some("java/lang/Object"));
+		runnable.access_flags(
+			ClassAccessFlags::Public as u16
+				| ClassAccessFlags::Interface as u16
+				| ClassAccessFlags::Abstract as u16,
+		);
+		load_class(thread, &mut runnable);
+
+		let mut builder = ClassFileBuilder::new("Checks", Some("java/lang/Object"));
+		let indexes = [
+			builder.class("Base") as u8,
+			builder.class("java/lang/Runnable") as u8,
+			builder.class("java/lang/String") as u8,
+			builder.class("[Ljava/lang/Object;") as u8,
+			builder.class("[Ljava/lang/String;") as u8,
+		];
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(thread, &mut builder));
+		(frame, indexes)
+	}
+
+	/*
+	 * A new String[1].
+	 */
+	fn string_array(thread: &mut JvmThread, frame: &mut Frame, string: u8) -> JvmValue {
+		frame.operand_stack.push(int_value(1));
+		assert!(matches!(
+			thread.execute_opcode(&[0xbd, 0, string], frame),
+			OpcodeResult::Incr(3)
+		));
+		frame.operand_stack.pop().unwrap()
+	}
+
+	/*
+	 * What checkcast to the class at _index_ does to _value_.
+	 */
+	fn checkcast(
+		thread: &mut JvmThread,
+		frame: &mut Frame,
+		value: JvmValue,
+		index: u8,
+	) -> OpcodeResult {
+		frame.operand_stack.clear();
+		frame.operand_stack.push(value);
+		thread.execute_opcode(&[0xc0, 0, index], frame)
+	}
+
+	#[test]
+	fn checkcast_follows_the_hierarchy() {
+		let mut thread = new_thread();
+		let (mut frame, [base, _, string, object_array, _]) = type_check_frame(&mut thread);
+		let derived = new_object(&mut thread, "Derived");
+		let other = new_object(&mut thread, "Other");
+
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, derived.clone(), base),
+			OpcodeResult::Incr(3)
+		));
+		assert!(same_object(frame.operand_stack.last().unwrap(), &derived));
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, other.clone(), base),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/ClassCastException"
+		));
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, create_null_value(), base),
+			OpcodeResult::Incr(3)
+		));
+		assert!(matches!(
+			frame.operand_stack.last(),
+			Some(JvmValue::Reference(_, JvmReferenceTargetType::Null, _))
+		));
+
+		/*
+		 * A String[] is an Object[]; an int[] and a String are not.
+		 */
+		let strings = string_array(&mut thread, &mut frame, string);
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, strings, object_array),
+			OpcodeResult::Incr(3)
+		));
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, new_primitive_array(10, 1), object_array),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/ClassCastException"
+		));
+		assert!(matches!(
+			checkcast(&mut thread, &mut frame, other, object_array),
+			OpcodeResult::Exception(ref name, _) if name == "java/lang/ClassCastException"
+		));
+	}
+
+	#[test]
+	fn instanceof_answers_without_throwing() {
+		let mut thread = new_thread();
+		let (mut frame, [base, runnable, string, object_array, string_array_index]) =
+			type_check_frame(&mut thread);
+		let derived = new_object(&mut thread, "Derived");
+		let other = new_object(&mut thread, "Other");
+		let strings = string_array(&mut thread, &mut frame, string);
+		let mut instanceof = |value: JvmValue, index: u8| {
+			frame.operand_stack.clear();
+			frame.operand_stack.push(value);
+			assert!(matches!(
+				thread.execute_opcode(&[0xc1, 0, index], &mut frame),
+				OpcodeResult::Incr(3)
+			));
+			frame.pop_int().unwrap()
+		};
+
+		assert_eq!(instanceof(derived.clone(), base), 1);
+		assert_eq!(instanceof(other.clone(), base), 0);
+		assert_eq!(instanceof(other, runnable), 1);
+		assert_eq!(instanceof(derived, runnable), 0);
+		assert_eq!(instanceof(create_null_value(), base), 0);
+		assert_eq!(instanceof(strings.clone(), object_array), 1);
+		assert_eq!(instanceof(strings, string_array_index), 1);
+		assert_eq!(instanceof(new_primitive_array(10, 1), object_array), 0);
+	}
+
+	#[test]
+	fn registered_natives_run_instead_of_bytecode() {
+		let arguments_seen = Rc::new(std::cell::RefCell::new(Vec::<(i32, i64)>::new()));
+		let mut natives = NativeRegistry::new();
+		let seen = Rc::clone(&arguments_seen);
+		natives.register("Host", "combine", "(IJ)J", move |_, arguments| {
+			let int = match &*arguments[0] {
+				JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _) => *value as i32,
+				_ => panic!("the first argument is not an int"),
+			};
+			let long = match &*arguments[1] {
+				JvmValue::Primitive(JvmPrimitiveType::LongInteger, value, _, _) => *value as i64,
+				_ => panic!("the second argument is not a long"),
+			};
+			seen.borrow_mut().push((int, long));
+			Ok(Some(Rc::new(long_value(int as i64 * 10 + long))))
+		});
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			natives,
+			BootstrapRegistry::new(),
+		);
+
+		let mut builder = ClassFileBuilder::new("Caller", Some("java/lang/Object"));
+		let combine = builder.method_ref("Host", "combine", "(IJ)J");
+		let [combine_high, combine_low] = combine.to_be_bytes();
+		/*
+		 * iconst_4; lconst_1; invokestatic Host.combine; lconst_1;
+		 * ladd; lreturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"call",
+			"()J",
+			5,
+			0,
+			&[
+				0x07,
+				0x0a,
+				0xb8,
+				combine_high,
+				combine_low,
+				0x0a,
+				0x61,
+				0xad,
+			],
+		);
+		let class = load_class(&thread, &mut builder);
+
+		match run_method(&mut thread, &class, "call", "()J", vec![]) {
+			OpcodeResult::Return(value) => assert!(value == long_value(42)),
+			_ => panic!("call did not return"),
+		}
+		assert_eq!(*arguments_seen.borrow(), vec![(4, 1)]);
+	}
+
+	#[test]
+	fn math_natives_through_invokestatic() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Maths", Some("java/lang/Object"));
+		let two = builder.double(2.0);
+		let sqrt = builder.method_ref("java/lang/Math", "sqrt", "(D)D");
+		let max = builder.method_ref("java/lang/Math", "max", "(II)I");
+		let [sqrt_high, sqrt_low] = sqrt.to_be_bytes();
+		let [max_high, max_low] = max.to_be_bytes();
+		builder
+			/*
+			 * ldc2_w 2.0; invokestatic Math.sqrt; dreturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"sqrt",
+				"()D",
+				2,
+				0,
+				&[0x14, 0, two as u8, 0xb8, sqrt_high, sqrt_low, 0xaf],
+			)
+			/*
+			 * bipush -3; bipush 9; invokestatic Math.max; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"max",
+				"()I",
+				2,
+				0,
+				&[0x10, 0xfd, 0x10, 9, 0xb8, max_high, max_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		match run_method(&mut thread, &class, "sqrt", "()D", vec![]) {
+			OpcodeResult::Return(value) => assert!(value == double_value(2f64.sqrt())),
+			_ => panic!("sqrt did not return"),
+		}
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "max", "()I", vec![])),
+			9
+		);
+	}
+
+	/*
+	 * A new int[] holding _values_.
+	 */
+	fn int_array(values: &[i32]) -> JvmValue {
+		let array = new_primitive_array(10, values.len() as i32);
+		for (index, value) in values.iter().enumerate() {
+			array_of(&array)
+				.lock()
+				.unwrap()
+				.set_at(index, int_value(*value));
+		}
+		array
+	}
+
+	fn int_array_values(array: &JvmValue) -> Vec<i32> {
+		let array = array_of(array);
+		let array = array.lock().unwrap();
+		(0..array.len())
+			.map(|index| match array.get(index) {
+				Some(JvmValue::Primitive(_, _, value, _)) => value as i32,
+				_ => panic!("not an int"),
+			})
+			.collect()
+	}
+
+	/*
+	 * System.arraycopy(source, source_position, destination,
+	 * destination_position, length) through invokestatic.
+	 */
+	fn arraycopy(
+		source: &JvmValue,
+		source_position: i32,
+		destination: &JvmValue,
+		destination_position: i32,
+		length: i32,
+	) -> OpcodeResult {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Copier", Some("java/lang/Object"));
+		let arraycopy = builder.method_ref(
+			"java/lang/System",
+			"arraycopy",
+			"(Ljava/lang/Object;ILjava/lang/Object;II)V",
+		);
+		let mut frame = Frame::new();
+		frame.class = Some(load_class(&thread, &mut builder));
+		for argument in vec![
+			source.clone(),
+			int_value(source_position),
+			destination.clone(),
+			int_value(destination_position),
+			int_value(length),
+		] {
+			frame.operand_stack.push(argument);
+		}
+		thread.execute_opcode(&[0xb8, 0, arraycopy as u8], &mut frame)
+	}
+
+	#[test]
+	fn arraycopy_copies_ranges() {
+		let source = int_array(&[1, 2, 3, 4]);
+		let destination = int_array(&[0, 0, 0, 0, 0]);
+		assert!(matches!(
+			arraycopy(&source, 1, &destination, 2, 3),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(int_array_values(&destination), vec![0, 0, 2, 3, 4]);
+
+		/*
+		 * Copying within one array works as if through a temporary.
+		 */
+		let shifted = int_array(&[1, 2, 3, 4, 5]);
+		assert!(matches!(
+			arraycopy(&shifted, 0, &shifted, 1, 4),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(int_array_values(&shifted), vec![1, 1, 2, 3, 4]);
+		assert!(matches!(
+			arraycopy(&shifted, 1, &shifted, 0, 4),
+			OpcodeResult::Incr(3)
+		));
+		assert_eq!(int_array_values(&shifted), vec![1, 2, 3, 4, 4]);
+	}
+
+	#[test]
+	fn arraycopy_checks_its_arguments() {
+		let source = int_array(&[1, 2, 3]);
+		let destination = int_array(&[0, 0, 0]);
+		let exception = |result: OpcodeResult| match result {
+			OpcodeResult::Exception(name, _) => name,
+			_ => panic!("arraycopy did not throw"),
+		};
+
+		assert_eq!(
+			exception(arraycopy(&source, 1, &destination, 0, 3)),
+			"java/lang/ArrayIndexOutOfBoundsException"
+		);
+		assert_eq!(
+			exception(arraycopy(&source, 0, &destination, 1, 3)),
+			"java/lang/ArrayIndexOutOfBoundsException"
+		);
+		assert_eq!(
+			exception(arraycopy(&source, 0, &destination, 0, -1)),
+			"java/lang/ArrayIndexOutOfBoundsException"
+		);
+		assert_eq!(int_array_values(&destination), vec![0, 0, 0]);
+		assert_eq!(
+			exception(arraycopy(&create_null_value(), 0, &destination, 0, 1)),
+			"java/lang/NullPointerException"
+		);
+		assert_eq!(
+			exception(arraycopy(&source, 0, &new_primitive_array(11, 3), 0, 1)),
+			"java/lang/ArrayStoreException"
+		);
+		assert_eq!(
+			exception(arraycopy(&source, 0, &object_value(), 0, 1)),
+			"java/lang/ArrayStoreException"
+		);
+	}
+
+	/*
+	 * A new array of _length_ nulls whose components are _component_type_.
+	 */
+	fn reference_array(component_type: JvmType, length: usize) -> JvmValue {
+		let array = JvmArray::new_with_value(length, create_null_value());
+		JvmValue::Reference(
+			JvmReferenceType::Array(Rc::new(component_type), length as u32),
+			JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),
+			0,
+		)
+	}
+
+	#[test]
+	fn arraycopy_copies_arrays_that_hold_their_destination() {
+		let object_type = JvmType::Reference(JvmReferenceType::Class(format!("java/lang/Object")));
+		let element =
+			|array: &JvmValue, index: usize| array_of(array).lock().unwrap().get(index).unwrap();
+
+		/*
+		 * An Object[] that holds itself, copied into itself.
+		 */
+		let objects = reference_array(object_type.clone(), 3);
+		let thing = object_value();
+		array_of(&objects)
+			.lock()
+			.unwrap()
+			.set_at(0, objects.clone());
+		array_of(&objects).lock().unwrap().set_at(1, thing.clone());
+		assert!(matches!(
+			arraycopy(&objects, 0, &objects, 1, 2),
+			OpcodeResult::Incr(3)
+		));
+		assert!(element(&objects, 0).identity() == objects.identity());
+		assert!(element(&objects, 1).identity() == objects.identity());
+		assert!(element(&objects, 2).identity() == thing.identity());
+
+		/*
+		 * An Object[][] destination, and a source whose elements are
+		 * the destination itself and then an Object that does not fit:
+		 * the destination is copied and then the copy stops.
+		 */
+		let destination = reference_array(
+			JvmType::Reference(JvmReferenceType::Array(Rc::new(object_type.clone()), 0)),
+			3,
+		);
+		let source = reference_array(object_type, 3);
+		array_of(&source)
+			.lock()
+			.unwrap()
+			.set_at(0, destination.clone());
+		array_of(&source).lock().unwrap().set_at(1, thing.clone());
+		array_of(&source)
+			.lock()
+			.unwrap()
+			.set_at(2, destination.clone());
+		match arraycopy(&source, 0, &destination, 0, 3) {
+			OpcodeResult::Exception(name, _) => assert_eq!(name, "java/lang/ArrayStoreException"),
+			_ => panic!("arraycopy did not throw"),
+		}
+		assert!(element(&destination, 0).identity() == destination.identity());
+		assert!(element(&destination, 1).is_null());
+		assert!(element(&destination, 2).is_null());
+	}
+
+	#[test]
+	fn clinit_runs_exactly_once() {
+		let mut thread = new_thread();
+		let static_field = FieldAccessFlags::Public as u16 | FieldAccessFlags::Static as u16;
+
+		let mut init = ClassFileBuilder::new("Init", Some("java/lang/Object"));
+		let [count_high, count_low] = init.field_ref("Init", "count", "I").to_be_bytes();
+		let [touch_high, touch_low] = init.method_ref("Init", "touch", "()V").to_be_bytes();
+		init.field(static_field, "count", "I")
+			.field(static_field, "other", "I")
+			/*
+			 * getstatic count; iconst_1; iadd; putstatic count;
+			 * invokestatic touch (which needs Init, which is being
+			 * initialized by this very thread); return
+			 */
+			.method(
+				MethodAccessFlags::Static as u16,
+				"<clinit>",
+				"()V",
+				2,
+				0,
+				&[
+					0xb2, count_high, count_low, 0x04, 0x60, 0xb3, count_high, count_low, 0xb8,
+					touch_high, touch_low, 0xb1,
+				],
+			)
+			/*
+			 * getstatic count; pop; return
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"touch",
+				"()V",
+				1,
+				0,
+				&[0xb2, count_high, count_low, 0x57, 0xb1],
+			)
+			/*
+			 * getstatic count; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"get",
+				"()I",
+				1,
+				0,
+				&[0xb2, count_high, count_low, 0xac],
+			);
+		load_class(&thread, &mut init);
+
+		let mut caller = ClassFileBuilder::new("Caller", Some("java/lang/Object"));
+		let [init_high, init_low] = caller.class("Init").to_be_bytes();
+		let [count_high, count_low] = caller.field_ref("Init", "count", "I").to_be_bytes();
+		let [other_high, other_low] = caller.field_ref("Init", "other", "I").to_be_bytes();
+		let [touch_high, touch_low] = caller.method_ref("Init", "touch", "()V").to_be_bytes();
+		let [get_high, get_low] = caller.method_ref("Init", "get", "()I").to_be_bytes();
+		/*
+		 * new Init; pop; getstatic count; pop; iconst_2; putstatic
+		 * other; invokestatic touch; invokestatic get; ireturn
+		 */
+		caller.method(
+			PUBLIC_STATIC,
+			"run",
+			"()I",
+			1,
+			0,
+			&[
+				0xbb, init_high, init_low, 0x57, 0xb2, count_high, count_low, 0x57, 0x05, 0xb3,
+				other_high, other_low, 0xb8, touch_high, touch_low, 0xb8, get_high, get_low, 0xac,
+			],
+		);
+		let caller = load_class(&thread, &mut caller);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &caller, "run", "()I", vec![])),
+			1
+		);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &caller, "run", "()I", vec![])),
+			1
+		);
+		let init = thread.load_class(&"Init".to_string()).unwrap();
+		assert_eq!(
+			returned_int(run_method(&mut thread, &init, "get", "()I", vec![])),
+			1
+		);
+	}
+
+	#[test]
+	fn lambdas_run_through_invokedynamic() {
+		let mut thread = new_thread();
+		let class = load_class(&thread, &mut lambda_class("Lambdas"));
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"add",
+				"(II)I",
+				vec![int_value(2), int_value(3)]
+			)),
+			5
+		);
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"add",
+				"(II)I",
+				vec![int_value(-7), int_value(40)]
+			)),
+			33
+		);
+	}
+
+	#[test]
+	fn call_sites_are_bootstrapped_once_by_registered_handlers() {
+		let bootstraps = Rc::new(std::cell::Cell::new(0));
+		let mut registry = BootstrapRegistry::new();
+		let counted = Rc::clone(&bootstraps);
+		registry.register("Custom", "bootstrap", move |_, call| {
+			assert_eq!(call.name, "answer");
+			assert_eq!(call.descriptor, "()I");
+			counted.set(counted.get() + 1);
+			let call_site: CallSite = Rc::new(|_, _| Ok(Some(Rc::new(int_value(42)))));
+			Ok(call_site)
+		});
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			registry,
+		);
+
+		let mut builder = ClassFileBuilder::new("Indy", Some("java/lang/Object"));
+		let bootstrap = builder.method_ref(
+			"Custom",
+			"bootstrap",
+			"(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;",
+		);
+		let bootstrap = builder.method_handle(6, bootstrap);
+		let bootstrap = builder.bootstrap_method(bootstrap, &[]);
+		let [answer_high, answer_low] = builder
+			.invoke_dynamic(bootstrap, "answer", "()I")
+			.to_be_bytes();
+		/*
+		 * invokedynamic answer; ireturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"answer",
+			"()I",
+			1,
+			0,
+			&[0xba, answer_high, answer_low, 0, 0, 0xac],
+		);
+		let class = load_class(&thread, &mut builder);
+
+		for _ in 0..3 {
+			assert_eq!(
+				returned_int(run_method(&mut thread, &class, "answer", "()I", vec![])),
+				42
+			);
+		}
+		assert_eq!(bootstraps.get(), 1);
+	}
+
+	#[test]
+	fn trace_level_traces_every_instruction() {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Trace,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		);
+		let err = Captured::default();
+		thread.set_err(Box::new(err.clone()));
+		let mut builder = ClassFileBuilder::new("Traced", Some("java/lang/Object"));
+		/*
+		 * iconst_2; bipush 40; iadd; ireturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"sum",
+			"()I",
+			2,
+			0,
+			&[0x05, 0x10, 40, 0x60, 0xac],
+		);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "sum", "()I", vec![])),
+			42
+		);
+		assert_eq!(
+			err.text(),
+			"Traced.sum 0: iconst_2 (stack depth: 0)\n\
+			 Traced.sum 1: bipush (stack depth: 1)\n\
+			 Traced.sum 3: iadd (stack depth: 2)\n\
+			 Traced.sum 4: ireturn (stack depth: 1)\n"
+		);
+
+		/*
+		 * Nothing is traced above the trace level.
+		 */
+		let mut thread = new_thread();
+		let err = Captured::default();
+		thread.set_err(Box::new(err.clone()));
+		let class = load_class(&thread, &mut builder);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "sum", "()I", vec![])),
+			42
+		);
+		assert!(err.text().is_empty());
+	}
+
+	#[test]
+	fn verification_is_optional() {
+		let mut builder = ClassFileBuilder::new("Unbalanced", Some("java/lang/Object"));
+		/*
+		 * iconst_1; iadd; ireturn
+		 */
+		builder.method(PUBLIC_STATIC, "f", "()I", 2, 0, &[0x04, 0x60, 0xac]);
+
+		let mut thread = new_thread();
+		thread.set_verify(true);
+		let class = load_class(&thread, &mut builder);
+		assert!(matches!(
+			run_method(&mut thread, &class, "f", "()I", vec![]),
+			OpcodeResult::Error(JvmError::VerificationFailed(ref reason))
+				if reason == "Unbalanced.f: iadd at pc 1 pops 2 slots from a stack of 1"
+		));
+
+		/*
+		 * Without verification, the method runs (and fails) as is.
+		 */
+		let mut thread = new_thread();
+		let class = load_class(&thread, &mut builder);
+		assert!(!matches!(
+			run_method(&mut thread, &class, "f", "()I", vec![]),
+			OpcodeResult::Error(JvmError::VerificationFailed(_))
+		));
+	}
+
+	#[test]
+	fn nop_does_nothing_and_unimplemented_opcodes_say_which() {
+		assert!(stack_after(0x00, vec![int_value(3)]) == vec![int_value(3)]);
+
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Unimplemented", Some("java/lang/Object"));
+		/*
+		 * nop; nop; breakpoint; return
+		 */
+		builder.method(PUBLIC_STATIC, "f", "()V", 0, 0, &[0x00, 0x00, 0xca, 0xb1]);
+		let class = load_class(&thread, &mut builder);
+		match run_method(&mut thread, &class, "f", "()V", vec![]) {
+			OpcodeResult::Error(error) => {
+				assert!(matches!(
+					error,
+					JvmError::Fatal(FatalErrorType::UnimplementedOpcode(0xca, 2))
+				));
+				assert_eq!(
+					format!("{}", error),
+					"Opcode 0xca (breakpoint) at pc 2 is not implemented."
+				);
+			}
+			_ => panic!("breakpoint did not fail"),
+		}
+	}
+
+	#[test]
+	fn aconst_null_pushes_null() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Nulls", Some("java/lang/Object"));
+		builder
+			/*
+			 * aconst_null; ifnull 7; iconst_0; ireturn; nop; iconst_1;
+			 * ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"isNull",
+				"()I",
+				1,
+				0,
+				&[0x01, 0xc6, 0, 6, 0x03, 0xac, 0x00, 0x04, 0xac],
+			)
+			/*
+			 * aconst_null; astore_0; aload_0; areturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"nothing",
+				"()Ljava/lang/Object;",
+				1,
+				1,
+				&[0x01, 0x4b, 0x2a, 0xb0],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "isNull", "()I", vec![])),
+			1
+		);
+		assert!(matches!(
+			run_method(
+				&mut thread,
+				&class,
+				"nothing",
+				"()Ljava/lang/Object;",
+				vec![]
+			),
+			OpcodeResult::Return(JvmValue::Reference(_, JvmReferenceTargetType::Null, _))
+		));
+	}
+
+	#[test]
+	fn strings_keep_their_characters_and_know_their_length() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let hello = thread.create_string(format!("hello")).unwrap();
+		let value = field_value(&hello, "java/lang/String", "value");
+		assert_eq!(array_of(&value).lock().unwrap().len(), 5);
+		assert_eq!(
+			string_object_value(&object_of(&hello).lock().unwrap()),
+			Some(format!("hello"))
+		);
+
+		let mut builder = ClassFileBuilder::new("Lengths", Some("java/lang/Object"));
+		let [length_high, length_low] = builder
+			.method_ref("java/lang/String", "length", "()I")
+			.to_be_bytes();
+		/*
+		 * aload_0; invokevirtual length; ireturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"length",
+			"(Ljava/lang/String;)I",
+			1,
+			1,
+			&[0x2a, 0xb6, length_high, length_low, 0xac],
+		);
+		let class = load_class(&thread, &mut builder);
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"length",
+				"(Ljava/lang/String;)I",
+				vec![hello]
+			)),
+			5
+		);
+		let empty = thread.create_string(String::new()).unwrap();
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&class,
+				"length",
+				"(Ljava/lang/String;)I",
+				vec![empty]
+			)),
+			0
+		);
+	}
+
+	/*
+	 * Strings.charAt(s, i) is s.charAt(i) and Strings.equals(s, o) is
+	 * s.equals(o).
+	 */
+	fn load_strings(thread: &JvmThread) -> Rc<Class> {
+		load_string_class(thread);
+		let mut builder = ClassFileBuilder::new("Strings", Some("java/lang/Object"));
+		let [char_at_high, char_at_low] = builder
+			.method_ref("java/lang/String", "charAt", "(I)C")
+			.to_be_bytes();
+		let [equals_high, equals_low] = builder
+			.method_ref("java/lang/String", "equals", "(Ljava/lang/Object;)Z")
+			.to_be_bytes();
+		builder
+			/*
+			 * aload_0; iload_1; invokevirtual charAt; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"charAt",
+				"(Ljava/lang/String;I)C",
+				2,
+				2,
+				&[0x2a, 0x1b, 0xb6, char_at_high, char_at_low, 0xac],
+			)
+			/*
+			 * aload_0; aload_1; invokevirtual equals; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"equals",
+				"(Ljava/lang/String;Ljava/lang/Object;)Z",
+				2,
+				2,
+				&[0x2a, 0x2b, 0xb6, equals_high, equals_low, 0xac],
+			);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn string_char_at_is_bounds_checked() {
+		let mut thread = new_thread();
+		let class = load_strings(&thread);
+		let hello = thread.create_string(format!("hello")).unwrap();
+		let char_at = |thread: &mut JvmThread, index: i32| {
+			run_method(
+				thread,
+				&class,
+				"charAt",
+				"(Ljava/lang/String;I)C",
+				vec![hello.clone(), int_value(index)],
+			)
+		};
+
+		assert_eq!(returned_int(char_at(&mut thread, 0)), 'h' as i32);
+		assert_eq!(returned_int(char_at(&mut thread, 4)), 'o' as i32);
+		assert_eq!(
+			thrown_class_name(char_at(&mut thread, 5)),
+			"java/lang/StringIndexOutOfBoundsException"
+		);
+		assert_eq!(
+			thrown_class_name(char_at(&mut thread, -1)),
+			"java/lang/StringIndexOutOfBoundsException"
+		);
+	}
+
+	#[test]
+	fn string_equals_compares_characters() {
+		let mut thread = new_thread();
+		let class = load_strings(&thread);
+		let hello = thread.create_string(format!("hello")).unwrap();
+		let equals = |thread: &mut JvmThread, other: JvmValue| {
+			returned_int(run_method(
+				thread,
+				&class,
+				"equals",
+				"(Ljava/lang/String;Ljava/lang/Object;)Z",
+				vec![hello.clone(), other],
+			))
+		};
+
+		let same = thread.create_string(format!("hello")).unwrap();
+		assert_eq!(equals(&mut thread, same), 1);
+		let different = thread.create_string(format!("help!")).unwrap();
+		assert_eq!(equals(&mut thread, different), 0);
+		let shorter = thread.create_string(format!("hell")).unwrap();
+		assert_eq!(equals(&mut thread, shorter), 0);
+		let object = new_object(&mut thread, "java/lang/Object");
+		assert_eq!(equals(&mut thread, object), 0);
+		assert_eq!(equals(&mut thread, create_null_value()), 0);
+	}
+
+	/*
+	 * Objects.hash(o) is o.hashCode() and Objects.same(o, p) is
+	 * o.equals(p); Thing inherits both from java/lang/Object.
+	 */
+	fn load_objects(thread: &JvmThread) -> Rc<Class> {
+		load_class(
+			thread,
+			&mut ClassFileBuilder::new("Thing", Some("java/lang/Object")),
+		);
+		let mut builder = ClassFileBuilder::new("Objects", Some("java/lang/Object"));
+		let [hash_high, hash_low] = builder
+			.method_ref("java/lang/Object", "hashCode", "()I")
+			.to_be_bytes();
+		let [equals_high, equals_low] = builder
+			.method_ref("java/lang/Object", "equals", "(Ljava/lang/Object;)Z")
+			.to_be_bytes();
+		builder
+			/*
+			 * aload_0; invokevirtual hashCode; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"hash",
+				"(Ljava/lang/Object;)I",
+				1,
+				1,
+				&[0x2a, 0xb6, hash_high, hash_low, 0xac],
+			)
+			/*
+			 * aload_0; aload_1; invokevirtual equals; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"same",
+				"(Ljava/lang/Object;Ljava/lang/Object;)Z",
+				2,
+				2,
+				&[0x2a, 0x2b, 0xb6, equals_high, equals_low, 0xac],
+			);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn object_identity_hashes_and_equality() {
+		let mut thread = new_thread();
+		let class = load_objects(&thread);
+		let thing = new_object(&mut thread, "Thing");
+		let other = new_object(&mut thread, "Thing");
+		let hash = |thread: &mut JvmThread, object: &JvmValue| {
+			returned_int(run_method(
+				thread,
+				&class,
+				"hash",
+				"(Ljava/lang/Object;)I",
+				vec![object.clone()],
+			))
+		};
+		let same = |thread: &mut JvmThread, object: &JvmValue, other: &JvmValue| {
+			returned_int(run_method(
+				thread,
+				&class,
+				"same",
+				"(Ljava/lang/Object;Ljava/lang/Object;)Z",
+				vec![object.clone(), other.clone()],
+			))
+		};
+
+		assert_eq!(hash(&mut thread, &thing), hash(&mut thread, &thing));
+		assert_ne!(hash(&mut thread, &thing), hash(&mut thread, &other));
+		assert_eq!(same(&mut thread, &thing, &thing), 1);
+		assert_eq!(same(&mut thread, &thing, &thing.clone()), 1);
+		assert_eq!(same(&mut thread, &thing, &other), 0);
+		assert_eq!(same(&mut thread, &thing, &create_null_value()), 0);
+	}
+
+	#[test]
+	fn get_class_names_the_runtime_class() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		load_base_and_derived(&thread);
+		let mut builder = ClassFileBuilder::new("Reflection", Some("java/lang/Object"));
+		let [get_class_high, get_class_low] = builder
+			.method_ref("java/lang/Object", "getClass", "()Ljava/lang/Class;")
+			.to_be_bytes();
+		let [get_name_high, get_name_low] = builder
+			.method_ref("java/lang/Class", "getName", "()Ljava/lang/String;")
+			.to_be_bytes();
+		/*
+		 * aload_0; invokevirtual getClass; invokevirtual getName;
+		 * areturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"className",
+			"(Ljava/lang/Object;)Ljava/lang/String;",
+			1,
+			1,
+			&[
+				0x2a,
+				0xb6,
+				get_class_high,
+				get_class_low,
+				0xb6,
+				get_name_high,
+				get_name_low,
+				0xb0,
+			],
+		);
+		let class = load_class(&thread, &mut builder);
+		let mut class_name = |thread: &mut JvmThread, object: JvmValue| match run_method(
+			thread,
+			&class,
+			"className",
+			"(Ljava/lang/Object;)Ljava/lang/String;",
+			vec![object],
+		) {
+			OpcodeResult::Return(name) => string_object_value(&object_of(&name).lock().unwrap()),
+			_ => None,
+		};
+
+		let string = thread.create_string(format!("hello")).unwrap();
+		assert_eq!(
+			class_name(&mut thread, string),
+			Some(format!("java.lang.String"))
+		);
+		let derived = new_object(&mut thread, "Derived");
+		assert_eq!(class_name(&mut thread, derived), Some(format!("Derived")));
+		assert_eq!(
+			class_name(&mut thread, int_array(&[1, 2])),
+			Some(format!("[I"))
+		);
+	}
+
+	/*
+	 * Boxes.box(i) is Integer.valueOf(i) and Boxes.unbox(i) is
+	 * i.intValue().
+	 */
+	fn load_boxes(thread: &JvmThread) -> Rc<Class> {
+		let mut integer = ClassFileBuilder::new("java/lang/Integer", Some("java/lang/Object"));
+		integer.field(
+			FieldAccessFlags::Private as u16 | FieldAccessFlags::Final as u16,
+			"value",
+			"I",
+		);
+		load_class(thread, &mut integer);
+		let mut builder = ClassFileBuilder::new("Boxes", Some("java/lang/Object"));
+		let [value_of_high, value_of_low] = builder
+			.method_ref("java/lang/Integer", "valueOf", "(I)Ljava/lang/Integer;")
+			.to_be_bytes();
+		let [int_value_high, int_value_low] = builder
+			.method_ref("java/lang/Integer", "intValue", "()I")
+			.to_be_bytes();
+		builder
+			/*
+			 * iload_0; invokestatic valueOf; areturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"box",
+				"(I)Ljava/lang/Integer;",
+				1,
+				1,
+				&[0x1a, 0xb8, value_of_high, value_of_low, 0xb0],
+			)
+			/*
+			 * aload_0; invokevirtual intValue; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"unbox",
+				"(Ljava/lang/Integer;)I",
+				1,
+				1,
+				&[0x2a, 0xb6, int_value_high, int_value_low, 0xac],
+			);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn small_integers_are_boxed_once() {
+		let mut thread = new_thread();
+		let class = load_boxes(&thread);
+		let boxed = |thread: &mut JvmThread, value: i32| match run_method(
+			thread,
+			&class,
+			"box",
+			"(I)Ljava/lang/Integer;",
+			vec![int_value(value)],
+		) {
+			OpcodeResult::Return(integer) => integer,
+			_ => panic!("valueOf did not return an Integer"),
+		};
+		let unboxed = |thread: &mut JvmThread, integer: &JvmValue| {
+			returned_int(run_method(
+				thread,
+				&class,
+				"unbox",
+				"(Ljava/lang/Integer;)I",
+				vec![integer.clone()],
+			))
+		};
+
+		let hundred = boxed(&mut thread, 100);
+		assert!(same_object(&hundred, &boxed(&mut thread, 100)));
+		let low = boxed(&mut thread, -128);
+		assert!(same_object(&low, &boxed(&mut thread, -128)));
+		let two_hundred = boxed(&mut thread, 200);
+		assert!(!same_object(&two_hundred, &boxed(&mut thread, 200)));
+		assert!(!same_object(
+			&boxed(&mut thread, -129),
+			&boxed(&mut thread, -129)
+		));
+
+		assert_eq!(unboxed(&mut thread, &hundred), 100);
+		assert_eq!(unboxed(&mut thread, &low), -128);
+		assert_eq!(unboxed(&mut thread, &two_hundred), 200);
+		assert!(matches!(
+			field_value(&two_hundred, "java/lang/Integer", "value"),
+			JvmValue::Primitive(JvmPrimitiveType::Integer, _, 200, _)
+		));
+	}
+
+	/*
+	 * The abstract class Animal has the abstract method sound(), which
+	 * Dog implements and Lazy (also abstract) does not.
+	 * Animals.call(a) is a.sound().
+	 */
+	fn load_animals(thread: &JvmThread) -> Rc<Class> {
+		let abstract_class = ClassAccessFlags::Public as u16 | ClassAccessFlags::Abstract as u16;
+		let mut animal = ClassFileBuilder::new("Animal", Some("java/lang/Object"));
+		animal.access_flags(abstract_class).method_without_code(
+			MethodAccessFlags::Public as u16 | MethodAccessFlags::Abstract as u16,
+			"sound",
+			"()I",
+		);
+		load_class(thread, &mut animal);
+		let mut dog = ClassFileBuilder::new("Dog", Some("Animal"));
+		dog.method(
+			MethodAccessFlags::Public as u16,
+			"sound",
+			"()I",
+			1,
+			1,
+			&[0x04, 0xac],
+		);
+		load_class(thread, &mut dog);
+		let mut lazy = ClassFileBuilder::new("Lazy", Some("Animal"));
+		lazy.access_flags(abstract_class);
+		load_class(thread, &mut lazy);
+
+		let mut builder = ClassFileBuilder::new("Animals", Some("java/lang/Object"));
+		let [sound_high, sound_low] = builder.method_ref("Animal", "sound", "()I").to_be_bytes();
+		/*
+		 * aload_0; invokevirtual sound; ireturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"call",
+			"(LAnimal;)I",
+			1,
+			1,
+			&[0x2a, 0xb6, sound_high, sound_low, 0xac],
+		);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn abstract_methods_dispatch_to_their_implementations() {
+		let mut thread = new_thread();
+		let animals = load_animals(&thread);
+		let animal = thread.load_class(&"Animal".to_string()).unwrap();
+		let dog = thread.load_class(&"Dog".to_string()).unwrap();
+		assert!(animal.is_abstract() && !animal.is_interface());
+		assert!(!dog.is_abstract());
+		let sound = |class: &Rc<Class>| {
+			class
+				.get_method_rc_by_name_and_type(&"sound".to_string(), &"()I".to_string())
+				.unwrap()
+		};
+		assert!(sound(&animal).is_abstract());
+		assert!(!sound(&dog).is_abstract());
+
+		let dog = new_object(&mut thread, "Dog");
+		assert_eq!(
+			returned_int(run_method(
+				&mut thread,
+				&animals,
+				"call",
+				"(LAnimal;)I",
+				vec![dog]
+			)),
+			1
+		);
+
+		/*
+		 * Only an object made behind new's back can be a Lazy.
+		 */
+		let lazy = new_object(&mut thread, "Lazy");
+		assert_eq!(
+			thrown_class_name(run_method(
+				&mut thread,
+				&animals,
+				"call",
+				"(LAnimal;)I",
+				vec![lazy]
+			)),
+			"java/lang/AbstractMethodError"
+		);
+	}
+
+	#[test]
+	fn new_refuses_abstract_classes_in_code() {
+		let mut thread = new_thread();
+		load_animals(&thread);
+		let mut shape = ClassFileBuilder::new("Shape", Some("java/lang/Object"));
+		shape.access_flags(
+			ClassAccessFlags::Public as u16
+				| ClassAccessFlags::Interface as u16
+				| ClassAccessFlags::Abstract as u16,
+		);
+		assert!(load_class(&thread, &mut shape).is_interface());
+
+		let mut builder = ClassFileBuilder::new("Maker", Some("java/lang/Object"));
+		let [animal_high, animal_low] = builder.class("Animal").to_be_bytes();
+		let [shape_high, shape_low] = builder.class("Shape").to_be_bytes();
+		/*
+		 * new Animal; areturn and new Shape; areturn
+		 */
+		builder
+			.method(
+				PUBLIC_STATIC,
+				"animal",
+				"()Ljava/lang/Object;",
+				1,
+				0,
+				&[0xbb, animal_high, animal_low, 0xb0],
+			)
+			.method(
+				PUBLIC_STATIC,
+				"shape",
+				"()Ljava/lang/Object;",
+				1,
+				0,
+				&[0xbb, shape_high, shape_low, 0xb0],
+			);
+		let maker = load_class(&thread, &mut builder);
+
+		for name in &["animal", "shape"] {
+			assert_eq!(
+				thrown_class_name(run_method(
+					&mut thread,
+					&maker,
+					name,
+					"()Ljava/lang/Object;",
+					vec![]
+				)),
+				"java/lang/InstantiationError"
+			);
+		}
+	}
+
+	#[test]
+	fn classes_inherit_the_built_in_object_methods() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let object = thread.load_class(&"java/lang/Object".to_string()).unwrap();
+		assert_eq!(
+			object
+				.get_method_signatures()
+				.iter()
+				.map(|(name, descriptor, _)| format!("{}{}", name, descriptor))
+				.collect::<Vec<String>>(),
+			vec![
+				format!("<init>()V"),
+				format!("hashCode()I"),
+				format!("equals(Ljava/lang/Object;)Z"),
+				format!("getClass()Ljava/lang/Class;"),
+				format!("toString()Ljava/lang/String;"),
+			]
+		);
+
+		load_class(
+			&thread,
+			&mut ClassFileBuilder::new("app/Plain", Some("java/lang/Object")),
+		);
+		let mut builder = ClassFileBuilder::new("Printer", Some("java/lang/Object"));
+		let [to_string_high, to_string_low] = builder
+			.method_ref("app/Plain", "toString", "()Ljava/lang/String;")
+			.to_be_bytes();
+		/*
+		 * aload_0; invokevirtual toString; areturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"show",
+			"(Lapp/Plain;)Ljava/lang/String;",
+			1,
+			1,
+			&[0x2a, 0xb6, to_string_high, to_string_low, 0xb0],
+		);
+		let printer = load_class(&thread, &mut builder);
+		let plain = new_object(&mut thread, "app/Plain");
+		let shown = match run_method(
+			&mut thread,
+			&printer,
+			"show",
+			"(Lapp/Plain;)Ljava/lang/String;",
+			vec![plain],
+		) {
+			OpcodeResult::Return(shown) => string_object_value(&object_of(&shown).lock().unwrap()),
+			_ => None,
+		}
+		.unwrap();
+		assert!(shown.starts_with("app.Plain@"));
+		assert!(u32::from_str_radix(&shown["app.Plain@".len()..], 16).is_ok());
+	}
+
+	#[test]
+	fn main_gets_the_arguments_as_a_string_array() {
+		let environment = Environment::new(&[], &["one", "two", "three"], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		);
+		load_string_class(&thread);
+		let static_field = FieldAccessFlags::Public as u16 | FieldAccessFlags::Static as u16;
+		let mut builder = ClassFileBuilder::new("Main", Some("java/lang/Object"));
+		let [count_high, count_low] = builder.field_ref("Main", "count", "I").to_be_bytes();
+		let [saved_high, saved_low] = builder
+			.field_ref("Main", "saved", "[Ljava/lang/String;")
+			.to_be_bytes();
+		builder
+			.field(static_field, "count", "I")
+			.field(static_field, "saved", "[Ljava/lang/String;")
+			/*
+			 * aload_0; arraylength; putstatic count; aload_0; putstatic
+			 * saved; return
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"main",
+				"([Ljava/lang/String;)V",
+				1,
+				1,
+				&[
+					0x2a, 0xbe, 0xb3, count_high, count_low, 0x2a, 0xb3, saved_high, saved_low,
+					0xb1,
+				],
+			)
+			/*
+			 * getstatic count; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"count",
+				"()I",
+				1,
+				0,
+				&[0xb2, count_high, count_low, 0xac],
+			)
+			/*
+			 * getstatic saved; iload_0; aaload; areturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"saved",
+				"(I)Ljava/lang/String;",
+				2,
+				1,
+				&[0xb2, saved_high, saved_low, 0x1a, 0x32, 0xb0],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert!(thread.run(&format!("Main"), &format!("main")).is_ok());
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "count", "()I", vec![])),
+			3
+		);
+		for (index, expected) in ["one", "two", "three"].iter().enumerate() {
+			match run_method(
+				&mut thread,
+				&class,
+				"saved",
+				"(I)Ljava/lang/String;",
+				vec![int_value(index as i32)],
+			) {
+				OpcodeResult::Return(argument) => assert_eq!(
+					string_object_value(&object_of(&argument).lock().unwrap()),
+					Some(expected.to_string())
+				),
+				_ => panic!("main did not save its arguments"),
+			}
+		}
+	}
+
+	#[test]
+	fn jsr_and_ret_return_after_the_jsr() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Finally", Some("java/lang/Object"));
+		builder
+			/*
+			 * iconst_0; istore_0; jsr 10; iload_0; ireturn; nop; nop;
+			 * nop; astore_1; iinc 0, 5; ret 1
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"jsr",
+				"()I",
+				1,
+				2,
+				&[
+					0x03, 0x3b, 0xa8, 0, 8, 0x1a, 0xac, 0x00, 0x00, 0x00, 0x4c, 0x84, 0, 5, 0xa9, 1,
+				],
+			)
+			/*
+			 * iconst_0; istore_0; jsr_w 11; iload_0; ireturn; nop; nop;
+			 * astore_1; iinc 0, 7; ret 1
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"jsrW",
+				"()I",
+				1,
+				2,
+				&[
+					0x03, 0x3b, 0xc9, 0, 0, 0, 9, 0x1a, 0xac, 0x00, 0x00, 0x4c, 0x84, 0, 7, 0xa9, 1,
+				],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "jsr", "()I", vec![])),
+			5
+		);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "jsrW", "()I", vec![])),
+			7
+		);
+
+		/*
+		 * jsr pushes the pc of the instruction after it and branches.
+		 */
+		let mut frame = Frame::new();
+		frame.pc = 2;
+		assert!(matches!(
+			thread.execute_opcode(&[0xa8, 0, 8], &mut frame),
+			OpcodeResult::Incr(8)
+		));
+		assert_eq!(frame.operand_stack.pop().unwrap().return_address(), Some(5));
+	}
+
+	#[test]
+	fn return_addresses_are_not_ints() {
+		let mut thread = new_thread();
+		let mut frame = frame_with_locals(vec![int_value(0), int_value(0)]);
+		frame.operand_stack.push(create_return_address_value(17));
+		assert!(matches!(
+			thread.execute_opcode(&[0x4c], &mut frame),
+			OpcodeResult::Incr(1)
+		));
+		assert_eq!(frame.locals[1].return_address(), Some(17));
+		assert_eq!(int_value(17).return_address(), None);
+
+		/*
+		 * Only ret can use it once it is in a local.
+		 */
+		assert!(matches!(
+			thread.execute_opcode(&[0x2b], &mut frame),
+			OpcodeResult::Error(_)
+		));
+		assert!(frame.operand_stack.is_empty());
+		frame.pc = 10;
+		assert!(matches!(
+			thread.execute_opcode(&[0xa9, 1], &mut frame),
+			OpcodeResult::Incr(7)
+		));
+		assert!(matches!(
+			thread.execute_opcode(&[0xa9, 0], &mut frame),
+			OpcodeResult::Error(JvmError::Fatal(FatalErrorType::WrongType(_, _)))
+		));
+
+		/*
+		 * And it is no good for arithmetic.
+		 */
+		let (result, _) =
+			execute_with_operands(&[0x60], vec![create_return_address_value(3), int_value(1)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+		let (result, _) =
+			execute_with_operands(&[0x60], vec![int_value(1), create_return_address_value(3)]);
+		assert!(matches!(result, OpcodeResult::Error(_)));
+	}
+
+	#[test]
+	fn the_frame_stack_grows_and_unwinds() {
+		let depths = Rc::new(std::cell::RefCell::new(Vec::new()));
+		let mut natives = NativeRegistry::new();
+		let seen = Rc::clone(&depths);
+		natives.register("Nested", "depth", "()I", move |thread, _| {
+			seen.borrow_mut()
+				.push((thread.frame_depth(), thread.frames.len()));
+			Ok(Some(Rc::new(int_value(0))))
+		});
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			natives,
+			BootstrapRegistry::new(),
+		);
+
+		let mut builder = ClassFileBuilder::new("Nested", Some("java/lang/Object"));
+		let [inner_high, inner_low] = builder.method_ref("Nested", "inner", "()I").to_be_bytes();
+		let [depth_high, depth_low] = builder.method_ref("Nested", "depth", "()I").to_be_bytes();
+		builder
+			.method_without_code(
+				PUBLIC_STATIC | MethodAccessFlags::Native as u16,
+				"depth",
+				"()I",
+			)
+			/*
+			 * invokestatic depth; pop; invokestatic inner; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"outer",
+				"()I",
+				1,
+				0,
+				&[
+					0xb8, depth_high, depth_low, 0x57, 0xb8, inner_high, inner_low, 0xac,
+				],
+			)
+			/*
+			 * invokestatic depth; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"inner",
+				"()I",
+				1,
+				0,
+				&[0xb8, depth_high, depth_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(thread.frame_depth(), 0);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "outer", "()I", vec![])),
+			0
+		);
+		/*
+		 * The frame of the method whose instruction is executing is
+		 * off the frame stack, so inner sees outer's frame under it.
+		 */
+		assert_eq!(*depths.borrow(), vec![(1, 0), (2, 1)]);
+		assert_eq!(thread.frame_depth(), 0);
+		assert!(thread.frames.is_empty());
+	}
+
+	/*
+	 * Recursion.down() calls itself forever; Recursion.safe() calls
+	 * it and returns 1 when it catches the StackOverflowError.
+	 */
+	fn load_recursion(thread: &JvmThread) -> Rc<Class> {
+		let mut builder = ClassFileBuilder::new("Recursion", Some("java/lang/Object"));
+		let [down_high, down_low] = builder.method_ref("Recursion", "down", "()V").to_be_bytes();
+		let overflow = builder.class("java/lang/StackOverflowError");
+		builder
+			/*
+			 * invokestatic down; return
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"down",
+				"()V",
+				0,
+				0,
+				&[0xb8, down_high, down_low, 0xb1],
+			)
+			/*
+			 * invokestatic down; iconst_0; ireturn; pop; iconst_1;
+			 * ireturn (the handler of pcs 0 to 3)
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"safe",
+				"()I",
+				1,
+				0,
+				&[0xb8, down_high, down_low, 0x03, 0xac, 0x57, 0x04, 0xac],
+				&[(0, 3, 5, overflow)],
+				&[],
+			);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn deep_recursion_throws_a_catchable_stack_overflow_error() {
+		let mut thread = new_thread();
+		let class = load_recursion(&thread);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "safe", "()I", vec![])),
+			1
+		);
+		assert_eq!(thread.frame_depth(), 0);
+		assert!(thread.frames.is_empty());
+
+		thread.set_max_frame_depth(16);
+		assert_eq!(
+			thrown_class_name(run_method(&mut thread, &class, "down", "()V", vec![])),
+			"java/lang/StackOverflowError"
+		);
+		assert_eq!(thread.frame_depth(), 0);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "safe", "()I", vec![])),
+			1
+		);
+	}
+
+	/*
+	 * The string that _result_ returns; None when it returns null.
+	 */
+	fn returned_string(result: OpcodeResult) -> Option<String> {
+		match result {
+			OpcodeResult::Return(JvmValue::Reference(_, JvmReferenceTargetType::Null, _)) => None,
+			OpcodeResult::Return(string) => {
+				Some(string_object_value(&object_of(&string).lock().unwrap()).unwrap())
+			}
+			_ => panic!("the method did not return a String"),
+		}
+	}
+
+	/*
+	 * Catcher.divide(a, b) returns the message of the
+	 * ArithmeticException that a / b throws (or "no exception"); Catcher.thrown(m) throws an ArithmeticException
+	 * with message m, catches it and returns its message.
+	 */
+	fn load_catcher(thread: &JvmThread) -> Rc<Class> {
+		load_string_class(thread);
+		let mut builder = ClassFileBuilder::new("Catcher", Some("java/lang/Object"));
+		let arithmetic = builder.class("java/lang/ArithmeticException");
+		let [arithmetic_high, arithmetic_low] = arithmetic.to_be_bytes();
+		let [init_high, init_low] = builder
+			.method_ref(
+				"java/lang/ArithmeticException",
+				"<init>",
+				"(Ljava/lang/String;)V",
+			)
+			.to_be_bytes();
+		let [message_high, message_low] = builder
+			.method_ref("java/lang/Throwable", "getMessage", "()Ljava/lang/String;")
+			.to_be_bytes();
+		let no_exception = builder.string("no exception");
+		builder
+			/*
+			 * iload_0; iload_1; idiv; pop; ldc "no exception"; areturn;
+			 * invokevirtual getMessage; areturn (the handler of pcs 0
+			 * to 6)
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"divide",
+				"(II)Ljava/lang/String;",
+				2,
+				2,
+				&[
+					0x1a,
+					0x1b,
+					0x6c,
+					0x57,
+					0x12,
+					no_exception as u8,
+					0xb0,
+					0xb6,
+					message_high,
+					message_low,
+					0xb0,
+				],
+				&[(0, 7, 7, arithmetic)],
+				&[],
+			)
+			/*
+			 * new ArithmeticException; dup; aload_0; invokespecial
+			 * <init>; athrow; invokevirtual getMessage; areturn (the
+			 * handler of pcs 0 to 8)
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"thrown",
+				"(Ljava/lang/String;)Ljava/lang/String;",
+				3,
+				1,
+				&[
+					0xbb,
+					arithmetic_high,
+					arithmetic_low,
+					0x59,
+					0x2a,
+					0xb7,
+					init_high,
+					init_low,
+					0xbf,
+					0xb6,
+					message_high,
+					message_low,
+					0xb0,
+				],
+				&[(0, 9, 9, arithmetic)],
+				&[],
+			);
+		load_class(thread, &mut builder)
+	}
+
+	#[test]
+	fn built_in_exceptions_can_be_thrown_and_caught() {
+		let mut thread = new_thread();
+		let class = load_catcher(&thread);
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"divide",
+				"(II)Ljava/lang/String;",
+				vec![int_value(1), int_value(0)]
+			)),
+			None
+		);
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"divide",
+				"(II)Ljava/lang/String;",
+				vec![int_value(1), int_value(1)]
+			)),
+			Some(format!("no exception"))
+		);
+		let message = thread.create_string(format!("/ by zero")).unwrap();
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"thrown",
+				"(Ljava/lang/String;)Ljava/lang/String;",
+				vec![message]
+			)),
+			Some(format!("/ by zero"))
+		);
+		assert!(thread.pending_exception.is_none());
+	}
+
+	#[test]
+	fn exceptions_keep_their_messages() {
+		let mut thread = new_thread();
+		let class = load_catcher(&thread);
+		let mut builder = ClassFileBuilder::new("Messages", Some("java/lang/Object"));
+		let [message_high, message_low] = builder
+			.method_ref("java/lang/Throwable", "getMessage", "()Ljava/lang/String;")
+			.to_be_bytes();
+		/*
+		 * aload_0; invokevirtual getMessage; areturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"of",
+			"(Ljava/lang/Throwable;)Ljava/lang/String;",
+			1,
+			1,
+			&[0x2a, 0xb6, message_high, message_low, 0xb0],
+		);
+		let messages = load_class(&thread, &mut builder);
+
+		let with_message = thrown(throw_exception(
+			&mut thread,
+			"java/lang/IllegalMonitorStateException",
+			Some("not the owner"),
+		));
+		let detail_message = field_value(&with_message, "java/lang/Throwable", "detailMessage");
+		assert_eq!(
+			string_object_value(&object_of(&detail_message).lock().unwrap()),
+			Some(format!("not the owner"))
+		);
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&messages,
+				"of",
+				"(Ljava/lang/Throwable;)Ljava/lang/String;",
+				vec![with_message]
+			)),
+			Some(format!("not the owner"))
+		);
+
+		let without_message = thrown(throw_exception(
+			&mut thread,
+			"java/lang/NullPointerException",
+			None,
+		));
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&messages,
+				"of",
+				"(Ljava/lang/Throwable;)Ljava/lang/String;",
+				vec![without_message]
+			)),
+			None
+		);
+
+		let message = thread.create_string(format!("caught")).unwrap();
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"thrown",
+				"(Ljava/lang/String;)Ljava/lang/String;",
+				vec![message]
+			)),
+			Some(format!("caught"))
+		);
+	}
+
+	#[test]
+	fn finally_handlers_catch_everything() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Finally", Some("java/lang/Object"));
+		let static_field = FieldAccessFlags::Public as u16 | FieldAccessFlags::Static as u16;
+		let [ran_high, ran_low] = builder.field_ref("Finally", "ran", "I").to_be_bytes();
+		let [arithmetic_high, arithmetic_low] =
+			builder.class("java/lang/ArithmeticException").to_be_bytes();
+		let null_pointer = builder.class("java/lang/NullPointerException");
+		let [init_high, init_low] = builder
+			.method_ref("java/lang/ArithmeticException", "<init>", "()V")
+			.to_be_bytes();
+		builder
+			.field(static_field, "ran", "I")
+			/*
+			 * new ArithmeticException; dup; invokespecial <init>;
+			 * athrow; the handler of pcs 0 to 7 for
+			 * NullPointerException: iconst_0; ireturn; the finally
+			 * handler of pcs 0 to 7: astore_0; getstatic ran; iconst_1;
+			 * iadd; putstatic ran; aload_0; athrow
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"fail",
+				"()I",
+				2,
+				1,
+				&[
+					0xbb,
+					arithmetic_high,
+					arithmetic_low,
+					0x59,
+					0xb7,
+					init_high,
+					init_low,
+					0xbf,
+					0x03,
+					0xac,
+					0x4b,
+					0xb2,
+					ran_high,
+					ran_low,
+					0x04,
+					0x60,
+					0xb3,
+					ran_high,
+					ran_low,
+					0x2a,
+					0xbf,
+				],
+				&[(0, 8, 8, null_pointer), (0, 8, 10, 0)],
+				&[],
+			)
+			/*
+			 * getstatic ran; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"ran",
+				"()I",
+				1,
+				0,
+				&[0xb2, ran_high, ran_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			thrown_class_name(run_method(&mut thread, &class, "fail", "()I", vec![])),
+			"java/lang/ArithmeticException"
+		);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "ran", "()I", vec![])),
+			1
+		);
+		assert_eq!(
+			thrown_class_name(run_method(&mut thread, &class, "fail", "()I", vec![])),
+			"java/lang/ArithmeticException"
+		);
+		assert_eq!(
+			returned_int(run_method(&mut thread, &class, "ran", "()I", vec![])),
+			2
+		);
+	}
+
+	#[test]
+	fn if_acmp_compares_identities() {
+		let mut thread = new_thread();
+		load_class(
+			&thread,
+			&mut ClassFileBuilder::new("Thing", Some("java/lang/Object")),
+		);
+		let thing = new_object(&mut thread, "Thing");
+		let other = new_object(&mut thread, "Thing");
+		let branches = |opcode: u8, first: &JvmValue, second: &JvmValue| {
+			let (result, frame) =
+				execute_with_operands(&[opcode, 0, 9], vec![first.clone(), second.clone()]);
+			assert!(frame.operand_stack.is_empty());
+			match result {
+				OpcodeResult::Incr(9) => true,
+				OpcodeResult::Incr(3) => false,
+				_ => panic!("if_acmp did not branch or fall through"),
+			}
+		};
+		let null = create_null_value();
+
+		assert!(branches(0xa5, &thing, &thing.clone()));
+		assert!(!branches(0xa5, &thing, &other));
+		assert!(branches(0xa5, &null, &create_null_value()));
+		assert!(!branches(0xa5, &null, &thing));
+		assert!(!branches(0xa5, &thing, &null));
+
+		assert!(!branches(0xa6, &thing, &thing.clone()));
+		assert!(branches(0xa6, &thing, &other));
+		assert!(!branches(0xa6, &null, &create_null_value()));
+		assert!(branches(0xa6, &null, &thing));
+
+		let array = int_array(&[1]);
+		assert!(branches(0xa5, &array, &array.clone()));
+		assert!(!branches(0xa5, &array, &int_array(&[1])));
+
+		/*
+		 * The same object seen through a reference of another type.
+		 */
+		let as_object = match &thing {
+			JvmValue::Reference(_, target, access) => JvmValue::Reference(
+				JvmReferenceType::Class(format!("java/lang/Object")),
+				target.clone(),
+				*access,
+			),
+			_ => panic!("Thing is not a reference"),
+		};
+		assert!(branches(0xa5, &thing, &as_object));
+		assert!(!branches(0xa6, &as_object, &thing));
+
+		let mirror = JvmValue::Reference(
+			JvmReferenceType::Class(format!("java/lang/Class")),
+			JvmReferenceTargetType::Class(thread.load_class(&format!("Thing")).unwrap()),
+			0,
+		);
+		assert!(branches(0xa5, &mirror, &mirror.clone()));
+		assert!(!branches(0xa5, &mirror, &thing));
+	}
+
+	#[test]
+	fn getfield_on_null_throws_a_catchable_null_pointer_exception() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let mut builder = ClassFileBuilder::new("Nulls", Some("java/lang/Object"));
+		let [value_high, value_low] = builder.field_ref("Nulls", "value", "I").to_be_bytes();
+		let null_pointer = builder.class("java/lang/NullPointerException");
+		let [message_high, message_low] = builder
+			.method_ref("java/lang/Throwable", "getMessage", "()Ljava/lang/String;")
+			.to_be_bytes();
+		builder
+			.field(FieldAccessFlags::Public as u16, "value", "I")
+			/*
+			 * aconst_null; getfield value; pop; aconst_null; areturn;
+			 * invokevirtual getMessage; areturn (the handler of pcs 0
+			 * to 4)
+			 */
+			.method_with_tables(
+				PUBLIC_STATIC,
+				"read",
+				"()Ljava/lang/String;",
+				1,
+				0,
+				&[
+					0x01,
+					0xb4,
+					value_high,
+					value_low,
+					0x57,
+					0x01,
+					0xb0,
+					0xb6,
+					message_high,
+					message_low,
+					0xb0,
+				],
+				&[(0, 4, 7, null_pointer)],
+				&[],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"read",
+				"()Ljava/lang/String;",
+				vec![]
+			)),
+			Some(format!("Cannot read field \"value\""))
+		);
+	}
+
+	#[test]
+	fn string_concatenation_goes_through_string_builder() {
+		let mut thread = new_thread();
+		load_string_class(&thread);
+		let native = MethodAccessFlags::Public as u16 | MethodAccessFlags::Native as u16;
+		let mut string_builder =
+			ClassFileBuilder::new("java/lang/StringBuilder", Some("java/lang/Object"));
+		string_builder
+			.method_without_code(native, "<init>", "()V")
+			.method_without_code(
+				native,
+				"append",
+				"(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+			)
+			.method_without_code(native, "append", "(I)Ljava/lang/StringBuilder;")
+			.method_without_code(native, "toString", "()Ljava/lang/String;");
+		load_class(&thread, &mut string_builder);
+
+		let mut builder = ClassFileBuilder::new("Concat", Some("java/lang/Object"));
+		let [class_high, class_low] = builder.class("java/lang/StringBuilder").to_be_bytes();
+		let [init_high, init_low] = builder
+			.method_ref("java/lang/StringBuilder", "<init>", "()V")
+			.to_be_bytes();
+		let [text_high, text_low] = builder
+			.method_ref(
+				"java/lang/StringBuilder",
+				"append",
+				"(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+			)
+			.to_be_bytes();
+		let [int_high, int_low] = builder
+			.method_ref(
+				"java/lang/StringBuilder",
+				"append",
+				"(I)Ljava/lang/StringBuilder;",
+			)
+			.to_be_bytes();
+		let [build_high, build_low] = builder
+			.method_ref(
+				"java/lang/StringBuilder",
+				"toString",
+				"()Ljava/lang/String;",
+			)
+			.to_be_bytes();
+		let prefix = builder.string("x=") as u8;
+		/*
+		 * "x=" + 5, the way javac compiles it: new StringBuilder; dup;
+		 * invokespecial <init>; ldc "x="; invokevirtual append(String);
+		 * iconst_5; invokevirtual append(int); invokevirtual toString;
+		 * areturn
+		 */
+		builder.method(
+			PUBLIC_STATIC,
+			"concat",
+			"()Ljava/lang/String;",
+			3,
+			0,
+			&[
+				0xbb, class_high, class_low, 0x59, 0xb7, init_high, init_low, 0x12, prefix, 0xb6,
+				text_high, text_low, 0x08, 0xb6, int_high, int_low, 0xb6, build_high, build_low,
+				0xb0,
+			],
+		);
+		let class = load_class(&thread, &mut builder);
+
+		assert_eq!(
+			returned_string(run_method(
+				&mut thread,
+				&class,
+				"concat",
+				"()Ljava/lang/String;",
+				vec![]
+			)),
+			Some(format!("x=5"))
+		);
+	}
+
+	#[test]
+	fn natives_without_a_registered_native_are_not_implemented() {
+		let mut thread = new_thread();
+		let mut builder = ClassFileBuilder::new("Unregistered", Some("java/lang/Object"));
+		let [public_high, public_low] = builder
+			.method_ref("Unregistered", "publicNative", "()I")
+			.to_be_bytes();
+		let [private_high, private_low] = builder
+			.method_ref("Unregistered", "privateNative", "()I")
+			.to_be_bytes();
+		let native = MethodAccessFlags::Native as u16;
+		builder
+			.method_without_code(
+				MethodAccessFlags::Public as u16 | native,
+				"publicNative",
+				"()I",
+			)
+			.method_without_code(
+				MethodAccessFlags::Private as u16 | native,
+				"privateNative",
+				"()I",
+			)
+			/*
+			 * aload_0; invokevirtual publicNative; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"callPublic",
+				"(LUnregistered;)I",
+				1,
+				1,
+				&[0x2a, 0xb6, public_high, public_low, 0xac],
+			)
+			/*
+			 * aload_0; invokevirtual privateNative; ireturn
+			 */
+			.method(
+				PUBLIC_STATIC,
+				"callPrivate",
+				"(LUnregistered;)I",
+				1,
+				1,
+				&[0x2a, 0xb6, private_high, private_low, 0xac],
+			);
+		let class = load_class(&thread, &mut builder);
+
+		for method in ["callPublic", "callPrivate"].iter() {
+			let object = new_object(&mut thread, "Unregistered");
+			assert!(matches!(
+				run_method(
+					&mut thread,
+					&class,
+					method,
+					"(LUnregistered;)I",
+					vec![object]
+				),
+				OpcodeResult::Error(JvmError::Fatal(FatalErrorType::NotImplemented(_)))
+			));
+		}
+	}
+}
@@ -19,14 +19,20 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
+use jvm::attribute::codeattributes::CodeAttribute;
+use jvm::attribute::linenumbertable::LineNumberTable;
 use jvm::attribute::Attributes;
+use jvm::class::ClassFileError;
+use jvm::class::ClassFileReader;
 use jvm::constant::Constant;
-use jvm::constant::Utf8Reserved;
 use jvm::constantpool::ConstantPool;
-use jvm::error::FatalError;
-use jvm::error::FatalErrorType;
+use jvm::exceptions::ExceptionTableEntry;
+use jvm::opcodes::instruction_length;
+use jvm::opcodes::opcode_info;
 use jvm::typevalues::JvmPrimitiveType;
 use jvm::typevalues::JvmType;
+use jvm::typevalues::MethodDescriptor;
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::repeat;
 use std::rc::Rc;
@@ -49,135 +55,269 @@ pub enum MethodAccessFlags {
 
 #[derive(Default, Clone)]
 pub struct Method {
-	byte_len: usize,
 	pub access_flags: u16,
 	pub name_index: u16,
 	pub class_index: u16,
 	pub descriptor_index: u16,
 	pub attributes_count: u16,
 	pub max_locals: usize,
+	pub max_stack: usize,
 	pub parameter_count: usize,
+	/*
+	 * How many locals the parameters take up: longs and doubles take
+	 * two.
+	 */
+	pub parameter_slots: usize,
+	pub parameter_types: Vec<JvmType>,
 	pub return_type: JvmType,
 	pub attributes: Attributes,
+	code: Option<CodeAttribute>,
 }
 
 impl Method {
-	pub fn get_code(&self, cp: &ConstantPool) -> Option<&[u8]> {
-		for i in 0..self.attributes.len() {
-			let attribute = self.attributes.get_ref(i);
-			if let Constant::Utf8(_, reserved, _, _) =
-				cp.get_constant_ref(attribute.attribute_name_index as usize)
-			{
-				if let Utf8Reserved::Code = reserved {
-					return Some(&attribute.info[8..]);
+	pub fn is_static(&self) -> bool {
+		(self.access_flags & (MethodAccessFlags::Static as u16)) != 0
+	}
+
+	pub fn is_synchronized(&self) -> bool {
+		(self.access_flags & (MethodAccessFlags::Synchronized as u16)) != 0
+	}
+
+	pub fn is_abstract(&self) -> bool {
+		(self.access_flags & (MethodAccessFlags::Abstract as u16)) != 0
+	}
+
+	pub fn is_native(&self) -> bool {
+		(self.access_flags & (MethodAccessFlags::Native as u16)) != 0
+	}
+
+	pub fn is_private(&self) -> bool {
+		(self.access_flags & (MethodAccessFlags::Private as u16)) != 0
+	}
+
+	pub fn get_code(&self) -> Option<&[u8]> {
+		self.code.as_ref().map(|code| code.code())
+	}
+
+	pub fn get_exception_table(&self) -> Option<&Vec<ExceptionTableEntry>> {
+		self.code.as_ref().map(|code| code.exception_table())
+	}
+
+	pub fn get_line_number_table(&self, cp: &ConstantPool) -> Option<LineNumberTable> {
+		self.code
+			.as_ref()
+			.and_then(|code| code.line_number_table(cp))
+	}
+
+	/*
+	 * List the method's code like javap -c does: one line per
+	 * instruction with its pc, its mnemonic and its operands. _cp_ is
+	 * the constant pool of the method's class.
+	 */
+	pub fn disassemble(&self, cp: &ConstantPool) -> String {
+		let code = match self.get_code() {
+			Some(code) => code,
+			None => return String::new(),
+		};
+		let mut listing = String::new();
+		let mut pc = 0usize;
+		while pc < code.len() {
+			let length = match instruction_length(code, pc) {
+				Some(length) => length,
+				None => {
+					listing += &format!("{:>5}: <truncated>\n", pc);
+					break;
 				}
+			};
+			let (mnemonic, _) = opcode_info(code[pc]);
+			let operands = disassemble_operands(cp, &code[pc..pc + length], pc);
+			if operands.is_empty() {
+				listing += &format!("{:>5}: {}\n", pc, mnemonic);
+			} else {
+				listing += &format!("{:>5}: {:<14}{}\n", pc, mnemonic, operands);
 			}
+			pc += length;
 		}
-		None
+		listing
 	}
+}
+
+/*
+ * The operands of _instruction_ (which is at _pc_), as disassemble
+ * shows them: branch targets are pcs and constants are described.
+ */
+fn disassemble_operands(cp: &ConstantPool, instruction: &[u8], pc: usize) -> String {
+	let u16_at =
+		|offset: usize| (instruction[offset] as u16) << 8 | (instruction[offset + 1] as u16);
+	let i32_at = |offset: usize| {
+		((instruction[offset] as u32) << 24
+			| (instruction[offset + 1] as u32) << 16
+			| (instruction[offset + 2] as u32) << 8
+			| (instruction[offset + 3] as u32)) as i32
+	};
+	let target = |offset: i32| (pc as i64 + offset as i64).to_string();
+	let constant = |index: u16| format!("#{:<18}// {}", index, cp.describe(index as usize));
 
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
+	match instruction[0] {
+		/*
+		 * bipush and sipush.
+		 */
+		0x10 => format!("{}", instruction[1] as i8),
+		0x11 => format!("{}", u16_at(1) as i16),
+		/*
+		 * ldc takes a one-byte index.
+		 */
+		0x12 => constant(instruction[1] as u16),
+		/*
+		 * Instructions whose operand is a local variable.
+		 */
+		0x15..=0x19 | 0x36..=0x3a | 0xa9 => format!("{}", instruction[1]),
+		0x84 => format!("{}, {}", instruction[1], instruction[2] as i8),
+		/*
+		 * Branches.
+		 */
+		0x99..=0xa8 | 0xc6 | 0xc7 => target(u16_at(1) as i16 as i32),
+		0xc8 | 0xc9 => target(i32_at(1)),
+		/*
+		 * tableswitch and lookupswitch.
+		 */
+		0xaa | 0xab => {
+			let padding = 3 - (pc % 4);
+			let operands = 1 + padding;
+			let default = i32_at(operands);
+			let mut cases = String::new();
+			if instruction[0] == 0xaa {
+				let low = i32_at(operands + 4);
+				let high = i32_at(operands + 8);
+				for (i, value) in (low..=high).enumerate() {
+					cases += &format!("{:>12}: {}\n", value, target(i32_at(operands + 12 + 4 * i)));
+				}
+			} else {
+				let npairs = i32_at(operands + 4) as usize;
+				for i in 0..npairs {
+					cases += &format!(
+						"{:>12}: {}\n",
+						i32_at(operands + 8 + 8 * i),
+						target(i32_at(operands + 12 + 8 * i))
+					);
+				}
+			}
+			format!(
+				"{{\n{}{:>12}: {}\n      }}",
+				cases,
+				"default",
+				target(default)
+			)
+		}
+		/*
+		 * invokeinterface also has a count, and invokedynamic two
+		 * zero bytes.
+		 */
+		0xb9 => format!(
+			"#{:<18}// {}",
+			format!("{},  {}", u16_at(1), instruction[3]),
+			cp.describe(u16_at(1) as usize)
+		),
+		/*
+		 * newarray names the element type.
+		 */
+		0xbc => match instruction[1] {
+			4 => format!("boolean"),
+			5 => format!("char"),
+			6 => format!("float"),
+			7 => format!("double"),
+			8 => format!("byte"),
+			9 => format!("short"),
+			10 => format!("int"),
+			11 => format!("long"),
+			element_type => format!("{}", element_type),
+		},
+		0xc4 => {
+			let (mnemonic, _) = opcode_info(instruction[1]);
+			if instruction[1] == 0x84 {
+				format!("{} {}, {}", mnemonic, u16_at(2), u16_at(4) as i16)
+			} else {
+				format!("{} {}", mnemonic, u16_at(2))
+			}
+		}
+		0xc5 => format!(
+			"#{:<18}// {}",
+			format!("{},  {}", u16_at(1), instruction[3]),
+			cp.describe(u16_at(1) as usize)
+		),
+		/*
+		 * Everything else with operands refers to a constant.
+		 */
+		_ if instruction.len() >= 3 => constant(u16_at(1)),
+		_ => String::new(),
 	}
 }
 
-impl<'l> From<(&'l Vec<u8>, &'l ConstantPool)> for Method {
-	fn from(f: (&'l Vec<u8>, &'l ConstantPool)) -> Self {
-		let (bytes, cp) = f;
-		let mut offset = 0;
+impl<'l, 'a> TryFrom<(&'l mut ClassFileReader<'a>, &'l ConstantPool)> for Method {
+	type Error = ClassFileError;
+
+	fn try_from(
+		f: (&'l mut ClassFileReader<'a>, &'l ConstantPool),
+	) -> Result<Self, ClassFileError> {
+		let (reader, cp) = f;
 		let access_flags: u16;
 		let name_index: u16;
 		let descriptor_index: u16;
 		let attributes: Attributes;
+		let code: Option<CodeAttribute>;
 		let max_locals: usize;
+		let max_stack: usize;
 		let parameter_count: usize;
 		let return_type: JvmType;
 
-		access_flags = (bytes[offset] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
-		offset += 2;
-		name_index = (bytes[offset] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
-		offset += 2;
-		descriptor_index = (bytes[offset] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
-		offset += 2;
+		access_flags = reader.read_u16()?;
+		name_index = reader.read_u16()?;
+		descriptor_index = reader.read_u16()?;
 
-		attributes = Attributes::from(&bytes[offset..].to_vec());
-		offset += attributes.byte_len();
+		attributes = Attributes::try_from(reader)?;
+
+		code = attributes.get_code(cp);
 
 		/*
 		 * Get the number of max locals.
 		 */
-		max_locals = {
-			let mut max_locals: usize = 0;
-			for i in 0..attributes.len() {
-				let attribute = attributes.get_ref(i);
-				if let Constant::Utf8(_, reserved, _, _) =
-					cp.get_constant_ref(attribute.attribute_name_index as usize)
-				{
-					if let Utf8Reserved::Code = reserved {
-						max_locals =
-							u16::from_le_bytes([attribute.info[3], attribute.info[2]]) as usize;
-					}
-				}
-			}
-			max_locals
-		};
+		max_locals = code.as_ref().map_or(0, |code| code.max_locals() as usize);
+		max_stack = code.as_ref().map_or(0, |code| code.max_stack() as usize);
 
 		/*
-		 * Get the parameter count.
+		 * Get the parameter and return types.
 		 */
-		parameter_count =
+		let descriptor =
 			if let Constant::Utf8(_, _, _, s) = cp.get_constant_ref(descriptor_index as usize) {
-				let mut parameter_count: usize = 0;
-				let signature = s.as_bytes();
-				if signature[0] == '(' as u8 {
-					let mut i = 1;
-					while i < signature.len() && signature[i] != ')' as u8 {
-						if signature[i] == 'L' as u8 {
-							/*
-							 * Lsome/class/name;
-							 * means a reference to a class of that name.
-							 */
-							while signature[i] != ';' as u8 {
-								i = i + 1;
-							}
-						} else if signature[i] == '[' as u8 {
-							i = i + 1;
-						}
-						i = i + 1;
-						parameter_count += 1;
-					}
-				}
-				parameter_count
+				MethodDescriptor::parse(s.as_bytes())
 			} else {
-				0
+				None
 			};
-
-		/*
-		 * Get the return type.
-		 */
-		return_type = {
-			let mut return_type: JvmType = JvmType::Primitive(JvmPrimitiveType::Invalid);
-			if let Constant::Utf8(_, _, _, s) = cp.get_constant_ref(descriptor_index as usize) {
-				let signature = s.as_bytes();
-				if let Some(index) = s.find(')') {
-					return_type = JvmType::from(&signature[index + 1..])
-				}
-			}
-			return_type
+		let descriptor = match descriptor {
+			Some(descriptor) => descriptor,
+			None => MethodDescriptor {
+				parameter_types: vec![],
+				return_type: JvmType::Primitive(JvmPrimitiveType::Invalid),
+			},
 		};
+		parameter_count = descriptor.parameter_types.len();
+		return_type = descriptor.return_type.clone();
 
-		Method {
-			byte_len: offset,
+		Ok(Method {
 			access_flags,
 			name_index,
 			class_index: 0,
 			descriptor_index,
 			attributes_count: attributes.attributes_count(),
 			max_locals: max_locals,
+			max_stack: max_stack,
 			parameter_count: parameter_count,
+			parameter_slots: descriptor.parameter_slots(),
+			parameter_types: descriptor.parameter_types,
 			return_type: return_type,
 			attributes,
-		}
+			code,
+		})
 	}
 }
 
@@ -194,7 +334,6 @@ impl fmt::Display for Method {
 
 #[derive(Clone, Default)]
 pub struct Methods {
-	byte_len: usize,
 	methods: Vec<Rc<Method>>,
 }
 
@@ -211,8 +350,21 @@ impl Methods {
 		self.methods.len() as u16
 	}
 
-	pub fn byte_len(&self) -> usize {
-		self.byte_len
+	/*
+	 * Every overload of _method_name_ (for debugging: invocations
+	 * have to find methods by name *and* descriptor).
+	 */
+	pub fn get_by_name(&self, method_name: &String, cp: &ConstantPool) -> Vec<Rc<Method>> {
+		self.methods
+			.iter()
+			.filter(
+				|method| match cp.get_constant_ref(method.name_index as usize) {
+					Constant::Utf8(_, _, _, value) => *value == *method_name,
+					_ => false,
+				},
+			)
+			.map(|method| Rc::clone(method))
+			.collect()
 	}
 
 	pub fn get_by_name_and_type(
@@ -240,26 +392,23 @@ impl Methods {
 	}
 }
 
-impl<'l> From<(&'l Vec<u8>, &'l ConstantPool)> for Methods {
-	fn from(f: (&'l Vec<u8>, &'l ConstantPool)) -> Self {
-		let (bytes, cp) = f;
-		let mut offset = 0;
-		let mut methods: Vec<Rc<Method>> = Vec::new();
-		let methods_count = (bytes[offset] as u16) << 8 | (bytes[offset + 1] as u16) << 0;
+impl<'l, 'a> TryFrom<(&'l mut ClassFileReader<'a>, &'l ConstantPool)> for Methods {
+	type Error = ClassFileError;
 
-		offset += 2;
-		for method_index in 0..methods_count as usize {
+	fn try_from(
+		f: (&'l mut ClassFileReader<'a>, &'l ConstantPool),
+	) -> Result<Self, ClassFileError> {
+		let (reader, cp) = f;
+		let methods_count = reader.read_u16()?;
+		let mut methods: Vec<Rc<Method>> = Vec::with_capacity(methods_count as usize);
+		for _ in 0..methods_count {
 			/*
 			 * Add a new reference-counted method to the list of
 			 * methods.
 			 */
-			methods.push(Rc::new(Method::from((&bytes[offset..].to_vec(), cp))));
-			offset += methods[method_index].byte_len();
-		}
-		Methods {
-			byte_len: offset,
-			methods: methods,
+			methods.push(Rc::new(Method::try_from((&mut *reader, cp))?));
 		}
+		Ok(Methods { methods: methods })
 	}
 }
 
@@ -302,3 +451,113 @@ impl Iterator for MethodIterator<'_> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+
+	#[test]
+	fn overloads_are_told_apart_by_descriptor() {
+		let public_static = MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16;
+		let mut builder = ClassFileBuilder::new("Overloads", Some("java/lang/Object"));
+		/*
+		 * iconst_1; ireturn and iconst_2; ireturn
+		 */
+		let class = builder
+			.method(public_static, "pick", "(I)I", 1, 1, &[0x04, 0xac])
+			.method(public_static, "pick", "(J)I", 1, 2, &[0x05, 0xac])
+			.build();
+		let methods = class.get_methods_ref();
+		let cp = class.get_constant_pool_ref();
+		let pick = "pick".to_string();
+
+		assert_eq!(methods.get_by_name(&pick, cp).len(), 2);
+		let int_pick = methods
+			.get_by_name_and_type(&pick, &"(I)I".to_string(), cp)
+			.unwrap();
+		assert_eq!(int_pick.get_code(), Some(&[0x04u8, 0xac][..]));
+		let long_pick = methods
+			.get_by_name_and_type(&pick, &"(J)I".to_string(), cp)
+			.unwrap();
+		assert_eq!(long_pick.get_code(), Some(&[0x05u8, 0xac][..]));
+		assert!(methods
+			.get_by_name_and_type(&pick, &"(D)I".to_string(), cp)
+			.is_none());
+		assert!(class
+			.get_method_rc_by_name_and_type(&pick, &"(J)I".to_string())
+			.is_some());
+	}
+
+	#[test]
+	fn disassembles_like_javap() {
+		let mut builder = ClassFileBuilder::new("Listed", Some("java/lang/Object"));
+		let [out_high, out_low] = builder
+			.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.to_be_bytes();
+		let hello = builder.string("hello");
+		let [println_high, println_low] = builder
+			.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+			.to_be_bytes();
+		let mut code = vec![
+			0xb2,
+			out_high,
+			out_low,
+			0x12,
+			hello as u8,
+			0xb6,
+			println_high,
+			println_low,
+			0x1a,
+			/*
+			 * tableswitch at pc 9, padded to pc 12.
+			 */
+			0xaa,
+			0,
+			0,
+		];
+		for value in &[25i32, 0, 1, 23, 24] {
+			code.extend_from_slice(&value.to_be_bytes());
+		}
+		/*
+		 * nop; nop; bipush -1; goto 0; return
+		 */
+		code.extend_from_slice(&[0x00, 0x00, 0x10, 0xff, 0xa7, 0xff, 0xdc, 0xb1]);
+		let class = builder
+			.method(
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16,
+				"show",
+				"(I)V",
+				2,
+				1,
+				&code,
+			)
+			.build();
+		let method = class
+			.get_method_rc_by_name_and_type(&"show".to_string(), &"(I)V".to_string())
+			.unwrap();
+		let listing = [
+			"    0: getstatic     #10                // Field java/lang/System.out:Ljava/io/PrintStream;",
+			"    3: ldc           #12                // String hello",
+			"    5: invokevirtual #18                // Method java/io/PrintStream.println:(Ljava/lang/String;)V",
+			"    8: iload_0",
+			"    9: tableswitch   {",
+			"           0: 32",
+			"           1: 33",
+			"     default: 34",
+			"      }",
+			"   32: nop",
+			"   33: nop",
+			"   34: bipush        -1",
+			"   36: goto          0",
+			"   39: return",
+		];
+		assert_eq!(
+			method.disassemble(class.get_constant_pool_ref()),
+			listing
+				.iter()
+				.map(|line| format!("{}\n", line))
+				.collect::<String>()
+		);
+	}
+}
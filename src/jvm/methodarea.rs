@@ -26,12 +26,12 @@ use jvm::classpath::ClassPath;
 use jvm::debug::Debug;
 use jvm::debug::DebugLevel;
 use jvm::environment::Environment;
-use jvm::error::FatalError;
-use jvm::error::FatalErrorType;
+use jvm::exceptions::synthetic_exception_class;
+use jvm::jar::Jar;
 use jvm::method::Method;
 use jvm::method::MethodAccessFlags;
+use jvm::monitor::Monitor;
 use jvm::typevalues::JvmValue;
-use rjar::Jar;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
@@ -51,6 +51,10 @@ pub struct LoadedClass {
 	pub lc: Mutex<ClassInitializationStatus>,
 	pub lc_waitq: Condvar,
 
+	/// The monitor of the class' Class object. Static synchronized
+	/// methods hold it while they run.
+	pub monitor: Mutex<Monitor>,
+
 	/// The base reference to class.
 	pub class: Rc<Class>,
 }
@@ -61,6 +65,7 @@ impl LoadedClass {
 			class: Rc::new(class),
 			lc: Mutex::new(ClassInitializationStatus::VerifiedPreparedNotInitialized),
 			lc_waitq: Condvar::new(),
+			monitor: Mutex::new(Monitor::new()),
 		}
 	}
 }
@@ -78,6 +83,13 @@ impl MethodArea {
 			environment: environment,
 			classes: HashMap::new(),
 		};
+		/*
+		 * java/lang/Object is built in, whether or not there is a class
+		 * file for it.
+		 */
+		if let Some(object_class) = Class::synthetic_object() {
+			result.add_loaded_class(object_class);
+		}
 		result
 	}
 
@@ -99,39 +111,69 @@ impl MethodArea {
 	}
 
 	///
+	/// Load the class named `class_name` unless it is already loaded.
+	/// A class file is only ever parsed the first time that its class
+	/// is needed; after that, this returns the very same class.
 	/// Must call this with the methodarea locked.
 	///
-	pub fn maybe_load_class(&mut self, class_name: &String) {
-		if let None = self.classes.get(class_name) {
-			match self.environment.class_location_for_class(class_name) {
-				Some(ClassLocation::ClassFile(location)) => {
-					self.load_class_from_file(&location);
-				}
-				Some(ClassLocation::JarFile(jarfile, location)) => {
-					if let Ok(mut jar) = Jar::open(&jarfile) {
-						if let Ok(bytes) = jar.file_contents_by_name(&location) {
-							self.load_class_from_bytes(bytes);
-						}
+	pub fn maybe_load_class(&mut self, class_name: &String) -> Option<Rc<Class>> {
+		if let Some(class) = self.get_class_rc(class_name) {
+			return Some(class);
+		}
+		match self.environment.class_location_for_class(class_name) {
+			Some(ClassLocation::ClassFile(location)) => self.load_class_from_file(&location),
+			Some(ClassLocation::JarFile(jarfile, location)) => {
+				if let Ok(jar) = Jar::open(&jarfile) {
+					if let Ok(bytes) = jar.file_contents_by_name(&location) {
+						return self.load_class_from_bytes(bytes);
 					}
 				}
-				None => {
-					println!("error: no path to {}", class_name);
+				None
+			}
+			None => {
+				/*
+				 * The exceptions that the JVM throws are built in, for
+				 * when the classpath has no class files for them.
+				 */
+				if let Some(exception_class) = synthetic_exception_class(class_name) {
+					return self.add_loaded_class(exception_class);
+				}
+				/*
+				 * So are the classes of arrays, which have no class
+				 * files at all.
+				 */
+				if let Some(array_class) = Class::synthetic_array(class_name) {
+					return self.add_loaded_class(array_class);
 				}
+				println!("error: no path to {}", class_name);
+				None
 			}
 		}
 	}
 
+	/*
+	 * The command-line arguments to the main method.
+	 */
+	pub fn arguments(&self) -> &Vec<String> {
+		&self.environment.arguments
+	}
+
 	pub fn get_loaded_class(&mut self, class_name: &String) -> Option<Arc<LoadedClass>> {
 		if let Some(loaded_class) = self.classes.get(class_name) {
 			Some(Arc::clone(loaded_class))
 		} else {
-			FatalError::new(FatalErrorType::ClassNotFound(class_name.to_string())).call();
 			None
 		}
 	}
 
+	/*
+	 * invoking_class is the class whose code invokes the method; a
+	 * package-private method it can invoke is declared in its runtime
+	 * package.
+	 */
 	pub fn select_method(
 		&mut self,
+		invoking_class: &Rc<Class>,
 		invoked_class: &Rc<Class>,
 		method_name: &String,
 		method_type: &String,
@@ -148,7 +190,8 @@ impl MethodArea {
 				if (target_method.access_flags & (MethodAccessFlags::Private as u16) == 0)
 					&& ((target_method.access_flags & (MethodAccessFlags::Public as u16) != 0)
 						|| (target_method.access_flags & (MethodAccessFlags::Protected as u16)
-							!= 0) || false/* TODO: mA is marked neither ACC_PUBLIC nor ACC_PROTECTED nor ACC_PRIVATE, and either (a) the declaration of mA appears in the same run-time package as the declaration of mC, or (b) if mA is declared in a class A and mC is declared in a class C, then there exists a method mB declared in a class B such that C is a subclass of B and B is a subclass of A and mC can override mB and mB can override mA. */)
+							!= 0) || target_class.get_package_name()
+						== invoking_class.get_package_name()/* TODO: mA is marked neither ACC_PUBLIC nor ACC_PROTECTED nor ACC_PRIVATE, and either (a) the declaration of mA appears in the same run-time package as the declaration of mC, or (b) if mA is declared in a class A and mC is declared in a class C, then there exists a method mB declared in a class B such that C is a subclass of B and B is a subclass of A and mC can override mB and mB can override mA. Only (a) is handled. */)
 				{
 					Debug(
 						format!(
@@ -172,6 +215,7 @@ impl MethodArea {
 				None => {
 					let mut traversed_to_superclass = false;
 					if let Some(superclass_name) = target_class.resolve_superclass() {
+						self.maybe_load_class(&superclass_name);
 						if let Some(superclass) = self.get_class_rc(&superclass_name) {
 							target_class = superclass;
 							traversed_to_superclass = true;
@@ -192,14 +236,13 @@ impl MethodArea {
 		} {}
 
 		/*
-		 * If we didn't find anything there, then let's look in
+		 * TODO: If we didn't find anything there, then let's look in
 		 * the superinterfaces:
 		 * Otherwise, the maximally-specific superinterface methods of C are determined (§5.4.3.3). If exactly one matches mR's name and descriptor and is not abstract, then it is the selected method.
+		 *
+		 * Until then, no result means that selection failed and it is up
+		 * to the caller to throw an AbstractMethodError.
 		 */
-		match result {
-			None => assert!(false, "TODO: Look in the superinterfaces."),
-			_ => {}
-		}
 		result
 	}
 
@@ -213,7 +256,7 @@ impl MethodArea {
 		field_name: &String,
 		field_type: &String,
 	) -> Option<String> {
-		let mut target_class = field_class;
+		let mut target_class = Rc::clone(field_class);
 		while {
 			if target_class
 				.get_fields_ref()
@@ -224,23 +267,58 @@ impl MethodArea {
 				) {
 				return target_class.get_class_name();
 			} else {
-				assert!(false, "Must look in the super class for a field.");
-				target_class = target_class;
-				true // go again
+				/*
+				 * TODO: Look in the superinterfaces before the superclass.
+				 */
+				let mut traversed_to_superclass = false;
+				if let Some(superclass_name) = target_class.resolve_superclass() {
+					self.maybe_load_class(&superclass_name);
+					if let Some(superclass) = self.get_class_rc(&superclass_name) {
+						target_class = superclass;
+						traversed_to_superclass = true;
+					}
+				}
+				traversed_to_superclass // go again
 			}
 		} {}
 		None
 	}
 
+	///
+	/// Must call this with the methodarea locked.
+	///
+	/// Whether the class (or interface) named `class_name` is the
+	/// type named `type_name`: the same class, one of its superclasses
+	/// or one of the interfaces that it (or a superclass) implements.
+	pub fn is_subtype_of(&mut self, class_name: &String, type_name: &String) -> bool {
+		if class_name == type_name || type_name == "java/lang/Object" {
+			return true;
+		}
+		self.maybe_load_class(class_name);
+		if let Some(class) = self.get_class_rc(class_name) {
+			if let Some(superclass_name) = class.resolve_superclass() {
+				if self.is_subtype_of(&superclass_name, type_name) {
+					return true;
+				}
+			}
+			for interface_name in class.get_interface_names() {
+				if self.is_subtype_of(&interface_name, type_name) {
+					return true;
+				}
+			}
+		}
+		false
+	}
+
 	pub fn resolve_method(
 		&mut self,
 		invoking_class: &Rc<Class>,
 		invoked_class: &Rc<Class>,
 		method_name: &String,
 		method_type: &String,
-	) -> Option<Rc<Method>> {
-		let mut target_class = invoked_class;
-		let mut result: Option<Rc<Method>> = None;
+	) -> Option<(Rc<Class>, Rc<Method>)> {
+		let mut target_class = Rc::clone(invoked_class);
+		let mut result: Option<(Rc<Class>, Rc<Method>)> = None;
 
 		/*
 		 * TODO: Check whether class is an interface. This is an
@@ -277,7 +355,7 @@ impl MethodArea {
 					DebugLevel::Info,
 				);
 
-				result = Some(target_method);
+				result = Some((target_class.clone(), target_method));
 				false /* this will break the loop */
 			} else {
 				/*
@@ -285,9 +363,15 @@ impl MethodArea {
 				 * lookup is recursively invoked on the direct superclass
 				 * of [class].
 				 */
-				assert!(false, "Must look in the super class for a method");
-				target_class = target_class;
-				true
+				let mut traversed_to_superclass = false;
+				if let Some(superclass_name) = target_class.resolve_superclass() {
+					self.maybe_load_class(&superclass_name);
+					if let Some(superclass) = self.get_class_rc(&superclass_name) {
+						target_class = superclass;
+						traversed_to_superclass = true;
+					}
+				}
+				traversed_to_superclass
 			}
 		} {}
 
@@ -306,46 +390,66 @@ impl MethodArea {
 
 		result
 	}
+
+	/*
+	 * Add _class_ to the classes that are loaded. If a class with its
+	 * name is already loaded, that one stays (along with its
+	 * initialization status) and _class_ is dropped.
+	 */
+	fn add_loaded_class(&mut self, class: Class) -> Option<Rc<Class>> {
+		let class_name = class.get_class_name()?;
+		let loaded_class = self
+			.classes
+			.entry(class_name)
+			.or_insert_with(|| Arc::new(LoadedClass::new(class)));
+		Some(Rc::clone(&loaded_class.class))
+	}
+
 	pub fn load_class_from_bytes(&mut self, class_bytes: Vec<u8>) -> Option<Rc<Class>> {
-		if let Some(class) = Class::load_from_bytes(class_bytes) {
-			if let Some(class_name) = class.get_class_name() {
-				if let Some(_) = self
-					.classes
-					.insert(class_name.to_string(), Arc::new(LoadedClass::new(class)))
-				{
-					/*
-					 * This is a fatal error -- loading the same class twice!
-					 */
-				}
-				/*
-				 * loaded_class is an Arc
-				 */
-				let loaded_class = self.classes.get(&class_name).unwrap();
-				return Some(Rc::clone(&loaded_class.class));
-			}
-		}
-		None
+		let class = Class::load_from_bytes(class_bytes)?;
+		self.add_loaded_class(class)
 	}
 
 	pub fn load_class_from_file(&mut self, class_filename: &String) -> Option<Rc<Class>> {
-		if let Some(class) = Class::load_from_file(class_filename) {
-			if let Some(class_name) = class.get_class_name() {
-				println!("load_class_from_file: {}", class_name);
-				if let Some(_) = self
-					.classes
-					.insert(class_name.to_string(), Arc::new(LoadedClass::new(class)))
-				{
-					/*
-					 * This is a fatal error -- loading the same class twice!
-					 */
-				}
-				/*
-				 * loaded_class is an Arc
-				 */
-				let loaded_class = self.classes.get(&class_name).unwrap();
-				return Some(Rc::clone(&loaded_class.class));
-			}
+		let class = Class::load_from_file(class_filename)?;
+		if let Some(class_name) = class.get_class_name() {
+			println!("load_class_from_file: {}", class_name);
 		}
-		None
+		self.add_loaded_class(class)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+
+	#[test]
+	fn classes_are_only_loaded_once() {
+		let directory =
+			std::env::temp_dir().join(format!("rust-jvm-methodarea-cache-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+		fs::write(
+			directory.join("Cached.class"),
+			ClassFileBuilder::new("Cached", Some("java/lang/Object")).bytes(),
+		)
+		.unwrap();
+
+		let mut methodarea = MethodArea::new(
+			DebugLevel::Error,
+			Environment::new(&[directory.to_str().unwrap()], &[], DebugLevel::Error),
+		);
+		let name = "Cached".to_string();
+		assert!(methodarea.get_class_rc(&name).is_none());
+		let first = methodarea.maybe_load_class(&name).unwrap();
+
+		/*
+		 * Once it is loaded, the class file is not needed again.
+		 */
+		fs::remove_dir_all(&directory).unwrap();
+		let second = methodarea.maybe_load_class(&name).unwrap();
+		assert!(Rc::ptr_eq(&first, &second));
+		assert!(Rc::ptr_eq(&first, &methodarea.get_class_rc(&name).unwrap()));
 	}
 }
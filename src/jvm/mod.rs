@@ -22,39 +22,263 @@
 use std::fmt;
 mod array;
 mod attribute;
-mod class;
-mod classpath;
+pub mod class;
+pub mod classpath;
 mod comparison;
 mod constant;
 mod constantpool;
 pub mod debug;
 mod environment;
-mod error;
+pub mod error;
 mod exceptions;
 mod field;
 mod frame;
-mod jvmthread;
+mod heap;
+pub mod invokedynamic;
+mod jar;
+pub mod jvmthread;
 mod method;
 mod methodarea;
+mod monitor;
+pub mod native;
 mod object;
 mod opcodes;
-mod typevalues;
+pub mod typevalues;
+mod verifier;
 
+use jvm::classpath::ClassPath;
 use jvm::debug::Debug;
 use jvm::debug::DebugLevel;
+use jvm::error::JvmError;
+use jvm::heap::Heap;
+use jvm::invokedynamic::BootstrapCall;
+use jvm::invokedynamic::BootstrapRegistry;
+use jvm::invokedynamic::CallSite;
 use jvm::methodarea::MethodArea;
+use jvm::native::NativeRegistry;
+use jvm::native::NativeResult;
+use jvm::typevalues::JvmValue;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+/*
+ * An output stream that every thread the VM runs writes to.
+ */
+#[derive(Clone)]
+struct SharedSink(Rc<RefCell<Box<dyn Write>>>);
+
+impl Write for SharedSink {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.borrow_mut().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.borrow_mut().flush()
+	}
+}
+
 pub struct Jvm {
 	debug_level: DebugLevel,
+	natives: NativeRegistry,
+	bootstraps: BootstrapRegistry,
+	verify: bool,
+	max_frame_depth: usize,
+	/*
+	 * How many bytes of objects and arrays a run may allocate, if
+	 * there is a limit.
+	 */
+	heap_limit: Option<usize>,
+	/*
+	 * Where the program's standard output and standard error go,
+	 * when not to the process'.
+	 */
+	stdout: Option<SharedSink>,
+	stderr: Option<SharedSink>,
+	/*
+	 * Where to find classes when run is given no classpath.
+	 */
+	classpath: Option<ClassPath>,
+}
+
+/*
+ * Configures a Jvm:
+ *
+ * let jvm = JvmBuilder::new()
+ * 	.debug(true)
+ * 	.classpath(ClassPath::new(&["classes"], DebugLevel::Info))
+ * 	.max_stack_depth(4096)
+ * 	.heap_limit(64 * 1024 * 1024)
+ * 	.build();
+ */
+pub struct JvmBuilder {
+	jvm: Jvm,
+}
+
+impl JvmBuilder {
+	pub fn new() -> Self {
+		JvmBuilder {
+			jvm: Jvm {
+				debug_level: DebugLevel::Error,
+				natives: NativeRegistry::new(),
+				bootstraps: BootstrapRegistry::new(),
+				verify: false,
+				max_frame_depth: jvmthread::DEFAULT_MAX_FRAME_DEPTH,
+				heap_limit: None,
+				stdout: None,
+				stderr: None,
+				classpath: None,
+			},
+		}
+	}
+
+	/*
+	 * Whether to print debugging output (like the -d option).
+	 */
+	pub fn debug(self, debug: bool) -> Self {
+		self.debug_level(if debug {
+			DebugLevel::Info
+		} else {
+			DebugLevel::Error
+		})
+	}
+
+	pub fn debug_level(mut self, debug_level: DebugLevel) -> Self {
+		self.jvm.debug_level = debug_level;
+		self
+	}
+
+	pub fn verify(mut self, verify: bool) -> Self {
+		self.jvm.set_verify(verify);
+		self
+	}
+
+	pub fn classpath(mut self, classpath: ClassPath) -> Self {
+		self.jvm.set_classpath(classpath);
+		self
+	}
+
+	pub fn max_stack_depth(mut self, max_stack_depth: usize) -> Self {
+		self.jvm.set_max_frame_depth(max_stack_depth);
+		self
+	}
+
+	pub fn heap_limit(mut self, heap_limit: usize) -> Self {
+		self.jvm.set_heap_limit(heap_limit);
+		self
+	}
+
+	pub fn stdout(mut self, sink: Box<dyn Write>) -> Self {
+		self.jvm.set_stdout(sink);
+		self
+	}
+
+	pub fn stderr(mut self, sink: Box<dyn Write>) -> Self {
+		self.jvm.set_stderr(sink);
+		self
+	}
+
+	pub fn build(self) -> Jvm {
+		self.jvm
+	}
 }
 
 impl Jvm {
 	pub fn new(debug_level: DebugLevel) -> Option<Jvm> {
-		Some(Jvm {
-			debug_level: debug_level,
-		})
+		Some(JvmBuilder::new().debug_level(debug_level).build())
+	}
+
+	/*
+	 * Make _native_ the implementation of
+	 * _class_name_._method_name_ with type _descriptor_ in
+	 * every thread that this VM runs.
+	 */
+	pub fn register_native<F>(
+		&mut self,
+		class_name: &str,
+		method_name: &str,
+		descriptor: &str,
+		native: F,
+	) where
+		F: Fn(&mut jvmthread::JvmThread, &[Rc<JvmValue>]) -> NativeResult + 'static,
+	{
+		self.natives
+			.register(class_name, method_name, descriptor, native);
+	}
+
+	/*
+	 * Whether the operand stack use of each method is verified before
+	 * it first runs.
+	 */
+	pub fn set_verify(&mut self, verify: bool) {
+		self.verify = verify;
+	}
+
+	/*
+	 * How deep the call stack of a thread may get before invoking
+	 * another method throws StackOverflowError. The frames are on the
+	 * heap, so the limit does not depend on the size of the stack of
+	 * the thread that runs the VM.
+	 */
+	pub fn set_max_frame_depth(&mut self, max_frame_depth: usize) {
+		self.max_frame_depth = max_frame_depth;
+	}
+
+	/*
+	 * How many bytes (roughly) the objects and arrays of a run may
+	 * take up before allocating another one throws OutOfMemoryError.
+	 */
+	pub fn set_heap_limit(&mut self, heap_limit: usize) {
+		self.heap_limit = Some(heap_limit);
+	}
+
+	/*
+	 * Find classes in _classpath_. A classpath given to run (or
+	 * try_run) is searched first, and this one after it.
+	 */
+	pub fn set_classpath(&mut self, classpath: ClassPath) {
+		self.classpath = Some(classpath);
+	}
+
+	/*
+	 * Send what the program prints to standard output to _sink_
+	 * instead of the process' standard output.
+	 */
+	pub fn set_stdout(&mut self, sink: Box<dyn Write>) {
+		self.stdout = Some(SharedSink(Rc::new(RefCell::new(sink))));
+	}
+
+	/*
+	 * Send what the program prints to standard error (the report of
+	 * an uncaught exception, say) to _sink_ instead of the process'
+	 * standard error.
+	 */
+	pub fn set_stderr(&mut self, sink: Box<dyn Write>) {
+		self.stderr = Some(SharedSink(Rc::new(RefCell::new(sink))));
+	}
+
+	/*
+	 * Where the program's standard error goes.
+	 */
+	fn stderr(&self) -> Box<dyn Write> {
+		match &self.stderr {
+			Some(stderr) => Box::new(stderr.clone()),
+			None => Box::new(io::stderr()),
+		}
+	}
+
+	/*
+	 * Make _handler_ the implementation of the bootstrap method
+	 * _class_name_._method_name_ in every thread that this VM runs.
+	 */
+	pub fn register_bootstrap_method<F>(&mut self, class_name: &str, method_name: &str, handler: F)
+	where
+		F: Fn(&mut jvmthread::JvmThread, &BootstrapCall) -> Result<CallSite, JvmError> + 'static,
+	{
+		self.bootstraps.register(class_name, method_name, handler);
 	}
 
 	pub fn run(
@@ -64,22 +288,70 @@ impl Jvm {
 		classpath: &[&str],
 		args: &[&str],
 	) -> bool {
+		match self.try_run(start_class, start_function, classpath, args) {
+			Ok(()) => true,
+			Err(error) => {
+				let _ = writeln!(
+					self.stderr(),
+					"Failure running {}.{}: {}",
+					start_class,
+					start_function,
+					error
+				);
+				false
+			}
+		}
+	}
+
+	pub fn try_run(
+		&self,
+		start_class: &String,
+		start_function: &String,
+		classpath: &[&str],
+		args: &[&str],
+	) -> Result<(), JvmError> {
 		/*
 		 * Create a VM and start running!
 		 */
-		let env = environment::Environment::new(classpath, args, self.debug_level.clone());
+		let env = match &self.classpath {
+			Some(configured_classpath) if classpath.is_empty() => {
+				environment::Environment::with_classpath(configured_classpath.clone(), args)
+			}
+			Some(configured_classpath) => {
+				let mut entries: Vec<&str> = classpath.to_vec();
+				entries.extend(
+					configured_classpath
+						.entries()
+						.iter()
+						.map(|entry| entry.as_str()),
+				);
+				environment::Environment::new(&entries, args, self.debug_level.clone())
+			}
+			None => environment::Environment::new(classpath, args, self.debug_level.clone()),
+		};
 		let methodarea = Arc::new(Mutex::new(MethodArea::new(self.debug_level.clone(), env)));
-		let mut thread = jvmthread::JvmThread::new(self.debug_level.clone(), methodarea);
-		if thread.run(start_class, start_function) {
-			Debug(
-				format!("Success running {}.{}", start_class, start_function),
-				&self.debug_level,
-				DebugLevel::Info,
-			);
-			return true;
+		let mut thread = jvmthread::JvmThread::new(
+			self.debug_level.clone(),
+			methodarea,
+			self.natives.clone(),
+			self.bootstraps.clone(),
+		);
+		thread.set_verify(self.verify);
+		thread.set_max_frame_depth(self.max_frame_depth);
+		thread.set_heap(Arc::new(Heap::new(self.heap_limit)));
+		if let Some(stdout) = &self.stdout {
+			thread.set_out(Box::new(stdout.clone()));
 		}
-		eprintln!("Failure running {}.{}", start_class, start_function);
-		false
+		thread.set_err(self.stderr());
+		let result = thread.run(start_class, start_function);
+		thread.flush();
+		result?;
+		Debug(
+			format!("Success running {}.{}", start_class, start_function),
+			&self.debug_level,
+			DebugLevel::Info,
+		);
+		Ok(())
 	}
 }
 
@@ -88,3 +360,246 @@ impl fmt::Display for Jvm {
 		write!(f, "debug_level: {}\n", &self.debug_level)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::field::FieldAccessFlags;
+	use jvm::method::MethodAccessFlags;
+	use std::fs;
+	use std::path::PathBuf;
+
+	/*
+	 * A fresh directory, named for _test_, holding _class_name_.class
+	 * with _bytes_.
+	 */
+	fn class_directory(test: &str, class_name: &str, bytes: &[u8]) -> PathBuf {
+		let directory =
+			std::env::temp_dir().join(format!("rust-jvm-{}-{}", test, std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+		fs::write(directory.join(format!("{}.class", class_name)), bytes).unwrap();
+		directory
+	}
+
+	fn quiet_jvm() -> Jvm {
+		let mut jvm = JvmBuilder::new().build();
+		jvm.set_stderr(Box::new(io::sink()));
+		jvm
+	}
+
+	#[test]
+	fn try_run_reports_a_missing_class() {
+		let directory = class_directory(
+			"missing-class",
+			"Empty",
+			&ClassFileBuilder::new("Empty", Some("java/lang/Object")).bytes(),
+		);
+		let classpath = directory.to_str().unwrap();
+		let jvm = quiet_jvm();
+
+		assert!(matches!(
+			jvm.try_run(&format!("Missing"), &format!("main"), &[classpath], &[]),
+			Err(JvmError::ClassNotFound(ref class_name)) if class_name == "Missing"
+		));
+		assert!(!jvm.run(&format!("Missing"), &format!("main"), &[classpath], &[]));
+		let _ = fs::remove_dir_all(&directory);
+	}
+
+	#[test]
+	fn try_run_reports_a_missing_method() {
+		let directory = class_directory(
+			"missing-method",
+			"Empty",
+			&ClassFileBuilder::new("Empty", Some("java/lang/Object")).bytes(),
+		);
+		let classpath = directory.to_str().unwrap();
+		let jvm = quiet_jvm();
+
+		assert!(matches!(
+			jvm.try_run(&format!("Empty"), &format!("main"), &[classpath], &[]),
+			Err(JvmError::MethodNotFound(ref method_name, ref class_name))
+				if method_name == "main" && class_name == "Empty"
+		));
+		assert!(!jvm.run(&format!("Empty"), &format!("main"), &[classpath], &[]));
+		let _ = fs::remove_dir_all(&directory);
+	}
+
+	/*
+	 * A sink that keeps what is written to it where the test can
+	 * still see it after handing the sink to the Jvm.
+	 */
+	#[derive(Clone)]
+	struct Captured(Rc<RefCell<Vec<u8>>>);
+
+	impl Write for Captured {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.borrow_mut().write(buf)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	/*
+	 * Add _class_name_.class with _bytes_ to _directory_, in the
+	 * subdirectories that its package names.
+	 */
+	fn add_class(directory: &PathBuf, class_name: &str, bytes: &[u8]) {
+		let path = directory.join(format!("{}.class", class_name));
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, bytes).unwrap();
+	}
+
+	#[test]
+	fn stdout_goes_to_the_installed_sink() {
+		let public_static = MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16;
+		let mut builder = ClassFileBuilder::new("Hello", Some("java/lang/Object"));
+		let [out_high, out_low] = builder
+			.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.to_be_bytes();
+		let hello = builder.string("hello") as u8;
+		let [string_high, string_low] = builder
+			.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+			.to_be_bytes();
+		let [int_high, int_low] = builder
+			.method_ref("java/io/PrintStream", "println", "(I)V")
+			.to_be_bytes();
+		/*
+		 * getstatic System.out; ldc "hello"; invokevirtual
+		 * println(String); getstatic System.out; bipush 42;
+		 * invokevirtual println(int); return
+		 */
+		builder.method(
+			public_static,
+			"main",
+			"([Ljava/lang/String;)V",
+			2,
+			1,
+			&[
+				0xb2,
+				out_high,
+				out_low,
+				0x12,
+				hello,
+				0xb6,
+				string_high,
+				string_low,
+				0xb2,
+				out_high,
+				out_low,
+				0x10,
+				42,
+				0xb6,
+				int_high,
+				int_low,
+				0xb1,
+			],
+		);
+		let directory = class_directory("stdout", "Hello", &builder.bytes());
+		let mut system = ClassFileBuilder::new("java/lang/System", Some("java/lang/Object"));
+		let [stream_high, stream_low] = system.class("java/io/PrintStream").to_be_bytes();
+		let [init_high, init_low] = system
+			.method_ref("java/io/PrintStream", "<init>", "()V")
+			.to_be_bytes();
+		let [out_high, out_low] = system
+			.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.to_be_bytes();
+		system
+			.field(
+				FieldAccessFlags::Public as u16 | FieldAccessFlags::Static as u16,
+				"out",
+				"Ljava/io/PrintStream;",
+			)
+			/*
+			 * new PrintStream; dup; invokespecial <init>; putstatic
+			 * out; return
+			 */
+			.method(
+				MethodAccessFlags::Static as u16,
+				"<clinit>",
+				"()V",
+				2,
+				0,
+				&[
+					0xbb,
+					stream_high,
+					stream_low,
+					0x59,
+					0xb7,
+					init_high,
+					init_low,
+					0xb3,
+					out_high,
+					out_low,
+					0xb1,
+				],
+			);
+		add_class(&directory, "java/lang/System", &system.bytes());
+		let mut stream = ClassFileBuilder::new("java/io/PrintStream", Some("java/lang/Object"));
+		let [object_init_high, object_init_low] = stream
+			.method_ref("java/lang/Object", "<init>", "()V")
+			.to_be_bytes();
+		/*
+		 * aload_0; invokespecial Object.<init>; return
+		 */
+		stream.method(
+			MethodAccessFlags::Public as u16,
+			"<init>",
+			"()V",
+			1,
+			1,
+			&[0x2a, 0xb7, object_init_high, object_init_low, 0xb1],
+		);
+		add_class(&directory, "java/io/PrintStream", &stream.bytes());
+		add_class(
+			&directory,
+			"java/lang/String",
+			&ClassFileBuilder::new("java/lang/String", Some("java/lang/Object"))
+				.field(FieldAccessFlags::Private as u16, "value", "[C")
+				.bytes(),
+		);
+		let classpath = directory.to_str().unwrap();
+		let captured = Captured(Rc::new(RefCell::new(Vec::new())));
+		let mut jvm = quiet_jvm();
+		jvm.set_stdout(Box::new(captured.clone()));
+
+		assert!(jvm
+			.try_run(&format!("Hello"), &format!("main"), &[classpath], &[])
+			.is_ok());
+		assert_eq!(&captured.0.borrow()[..], &b"hello\n42\n"[..]);
+		let _ = fs::remove_dir_all(&directory);
+	}
+
+	#[test]
+	fn builder_classpath_is_searched_when_run_is_given_none() {
+		let mut builder = ClassFileBuilder::new("Configured", Some("java/lang/Object"));
+		builder.method(
+			MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16,
+			"main",
+			"([Ljava/lang/String;)V",
+			0,
+			1,
+			&[0xb1],
+		);
+		let directory = class_directory("builder", "Configured", &builder.bytes());
+		let jvm = JvmBuilder::new()
+			.classpath(ClassPath::new(
+				&[directory.to_str().unwrap()],
+				DebugLevel::Error,
+			))
+			.stderr(Box::new(io::sink()))
+			.build();
+
+		assert!(jvm
+			.try_run(&format!("Configured"), &format!("main"), &[], &[])
+			.is_ok());
+		assert!(matches!(
+			quiet_jvm().try_run(&format!("Configured"), &format!("main"), &[], &[]),
+			Err(JvmError::ClassNotFound(_))
+		));
+		let _ = fs::remove_dir_all(&directory);
+	}
+}
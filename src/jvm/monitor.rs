@@ -0,0 +1,111 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::thread::ThreadId;
+
+/*
+ * The monitor that every object has (jvms 2.11.10). It is recursive:
+ * the thread that owns it may enter it again, and it is not released
+ * until that thread has exited it as many times as it entered.
+ */
+#[derive(Default, Clone)]
+pub struct Monitor {
+	owner: Option<ThreadId>,
+	count: usize,
+}
+
+impl Monitor {
+	pub fn new() -> Self {
+		Monitor {
+			owner: None,
+			count: 0,
+		}
+	}
+
+	/*
+	 * Returns false when another thread owns the monitor.
+	 */
+	pub fn enter(&mut self, thread: ThreadId) -> bool {
+		match self.owner {
+			Some(owner) if owner != thread => false,
+			_ => {
+				self.owner = Some(thread);
+				self.count += 1;
+				true
+			}
+		}
+	}
+
+	/*
+	 * Returns false when _thread_ does not own the monitor.
+	 */
+	pub fn exit(&mut self, thread: ThreadId) -> bool {
+		match self.owner {
+			Some(owner) if owner == thread => {
+				self.count -= 1;
+				if self.count == 0 {
+					self.owner = None;
+				}
+				true
+			}
+			_ => false,
+		}
+	}
+
+	pub fn is_owned_by(&self, thread: ThreadId) -> bool {
+		self.owner == Some(thread)
+	}
+
+	pub fn count(&self) -> usize {
+		self.count
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+
+	#[test]
+	fn monitors_are_recursive() {
+		let owner = thread::current().id();
+		let other = thread::spawn(|| thread::current().id()).join().unwrap();
+		let mut monitor = Monitor::new();
+
+		assert!(monitor.enter(owner));
+		assert!(monitor.enter(owner));
+		assert_eq!(monitor.count(), 2);
+		assert!(!monitor.enter(other));
+		assert!(!monitor.exit(other));
+
+		assert!(monitor.exit(owner));
+		assert!(monitor.is_owned_by(owner));
+		assert!(monitor.exit(owner));
+		assert!(!monitor.is_owned_by(owner));
+		assert_eq!(monitor.count(), 0);
+
+		/*
+		 * An unbalanced exit is refused, and then anyone may enter.
+		 */
+		assert!(!monitor.exit(owner));
+		assert!(monitor.enter(other));
+	}
+}
@@ -0,0 +1,1171 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use jvm::array::JvmArray;
+use jvm::error::FatalError;
+use jvm::error::FatalErrorType;
+use jvm::error::JvmError;
+use jvm::jvmthread::JvmThread;
+use jvm::object::string_object_value;
+use jvm::typevalues::create_null_value;
+use jvm::typevalues::JvmPrimitiveType;
+use jvm::typevalues::JvmReferenceTargetType;
+use jvm::typevalues::JvmReferenceType;
+use jvm::typevalues::JvmType;
+use jvm::typevalues::JvmValue;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/*
+ * What a native returns: the method's return value (None for a void
+ * method) or the error that stopped it.
+ */
+pub type NativeResult = Result<Option<Rc<JvmValue>>, JvmError>;
+
+/*
+ * A method implemented in Rust. It gets the method's arguments (for an
+ * instance method, the receiver is the first).
+ */
+pub type Native = Rc<dyn Fn(&mut JvmThread, &[Rc<JvmValue>]) -> NativeResult>;
+
+/*
+ * The natives, keyed by the class, name and descriptor of the method
+ * that they implement.
+ */
+#[derive(Clone, Default)]
+pub struct NativeRegistry {
+	natives: HashMap<(String, String, String), Native>,
+}
+
+impl NativeRegistry {
+	/*
+	 * A registry with the natives that every JVM has.
+	 */
+	pub fn new() -> Self {
+		let mut registry = NativeRegistry {
+			natives: HashMap::new(),
+		};
+		registry.register("java/io/PrintStream", "println", "(I)V", println_int);
+		registry.register(
+			"java/io/PrintStream",
+			"println",
+			"(Ljava/lang/String;)V",
+			println_string,
+		);
+		registry.register(
+			"java/lang/System",
+			"arraycopy",
+			"(Ljava/lang/Object;ILjava/lang/Object;II)V",
+			system_arraycopy,
+		);
+		registry.register("java/lang/String", "length", "()I", string_length);
+		registry.register("java/lang/String", "charAt", "(I)C", string_char_at);
+		registry.register(
+			"java/lang/String",
+			"equals",
+			"(Ljava/lang/Object;)Z",
+			string_equals,
+		);
+		registry.register("java/lang/Object", "hashCode", "()I", object_hash_code);
+		registry.register(
+			"java/lang/Object",
+			"equals",
+			"(Ljava/lang/Object;)Z",
+			object_equals,
+		);
+		registry.register(
+			"java/lang/Object",
+			"toString",
+			"()Ljava/lang/String;",
+			object_to_string,
+		);
+		registry.register(
+			"java/lang/Object",
+			"getClass",
+			"()Ljava/lang/Class;",
+			object_get_class,
+		);
+		registry.register(
+			"java/lang/Class",
+			"getName",
+			"()Ljava/lang/String;",
+			class_get_name,
+		);
+		registry.register(
+			"java/lang/Throwable",
+			"getMessage",
+			"()Ljava/lang/String;",
+			throwable_get_message,
+		);
+		registry.register(
+			"java/lang/Integer",
+			"valueOf",
+			"(I)Ljava/lang/Integer;",
+			integer_value_of,
+		);
+		registry.register("java/lang/Integer", "intValue", "()I", integer_int_value);
+		registry.register(
+			"java/lang/StringBuilder",
+			"<init>",
+			"()V",
+			string_builder_init,
+		);
+		registry.register(
+			"java/lang/StringBuilder",
+			"append",
+			"(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+			string_builder_append_string,
+		);
+		registry.register(
+			"java/lang/StringBuilder",
+			"append",
+			"(I)Ljava/lang/StringBuilder;",
+			string_builder_append_int,
+		);
+		registry.register(
+			"java/lang/StringBuilder",
+			"toString",
+			"()Ljava/lang/String;",
+			string_builder_to_string,
+		);
+		registry.register("java/lang/Math", "sqrt", "(D)D", math_sqrt);
+		registry.register("java/lang/Math", "abs", "(I)I", math_abs_int);
+		registry.register("java/lang/Math", "abs", "(D)D", math_abs_double);
+		registry.register("java/lang/Math", "max", "(II)I", math_max_int);
+		registry.register("java/lang/Math", "min", "(II)I", math_min_int);
+		registry
+	}
+
+	/*
+	 * A native registered for a method replaces whatever was there
+	 * before.
+	 */
+	pub fn register<F>(&mut self, class_name: &str, method_name: &str, descriptor: &str, native: F)
+	where
+		F: Fn(&mut JvmThread, &[Rc<JvmValue>]) -> NativeResult + 'static,
+	{
+		self.natives.insert(
+			(
+				class_name.to_string(),
+				method_name.to_string(),
+				descriptor.to_string(),
+			),
+			Rc::new(native),
+		);
+	}
+
+	pub fn get(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<Native> {
+		self.natives
+			.get(&(
+				class_name.to_string(),
+				method_name.to_string(),
+				descriptor.to_string(),
+			))
+			.map(|native| Rc::clone(native))
+	}
+}
+
+/*
+ * Like PrintStream itself, the println natives ignore errors writing
+ * to the thread's output.
+ */
+fn println_int(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	match arguments.get(1).map(|argument| &**argument) {
+		Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _)) => {
+			let _ = writeln!(thread.out(), "{}", *value as i32);
+		}
+		_ => {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				format!("println"),
+				format!("int"),
+			))
+			.into_error())
+		}
+	}
+	Ok(None)
+}
+
+fn println_string(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	match arguments.get(1).map(|argument| &**argument) {
+		Some(string) if string.is_null() => {
+			let _ = writeln!(thread.out(), "null");
+		}
+		Some(JvmValue::Reference(_, JvmReferenceTargetType::Object(string), _)) => {
+			match string
+				.lock()
+				.ok()
+				.and_then(|string| string_object_value(&string))
+			{
+				Some(value) => {
+					let _ = writeln!(thread.out(), "{}", value);
+				}
+				None => {
+					return Err(FatalError::new(FatalErrorType::FieldNotFound(
+						format!("value"),
+						format!("java/lang/String"),
+					))
+					.into_error())
+				}
+			}
+		}
+		_ => {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				format!("println"),
+				format!("reference to a String"),
+			))
+			.into_error())
+		}
+	}
+	Ok(None)
+}
+
+/*
+ * The int that is the _index_th argument of _method_.
+ */
+fn int_argument(method: &str, arguments: &[Rc<JvmValue>], index: usize) -> Result<i32, JvmError> {
+	match arguments.get(index).map(|argument| &**argument) {
+		Some(JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _)) => Ok(*value as i32),
+		_ => Err(FatalError::new(FatalErrorType::WrongType(
+			method.to_string(),
+			format!("int"),
+		))
+		.into_error()),
+	}
+}
+
+/*
+ * The double that is the _index_th argument of _method_.
+ */
+fn double_argument(
+	method: &str,
+	arguments: &[Rc<JvmValue>],
+	index: usize,
+) -> Result<f64, JvmError> {
+	match arguments.get(index).map(|argument| &**argument) {
+		Some(JvmValue::Primitive(JvmPrimitiveType::Double, value, _, _)) => {
+			Ok(f64::from_bits(*value))
+		}
+		_ => Err(FatalError::new(FatalErrorType::WrongType(
+			method.to_string(),
+			format!("double"),
+		))
+		.into_error()),
+	}
+}
+
+fn int_value(value: i32) -> NativeResult {
+	Ok(Some(Rc::new(JvmValue::Primitive(
+		JvmPrimitiveType::Integer,
+		0,
+		value as u32,
+		0,
+	))))
+}
+
+fn double_value(value: f64) -> NativeResult {
+	Ok(Some(Rc::new(JvmValue::Primitive(
+		JvmPrimitiveType::Double,
+		value.to_bits(),
+		0,
+		0,
+	))))
+}
+
+/*
+ * The array that holds the characters of the String that _argument_
+ * refers to.
+ */
+fn string_characters(method: &str, argument: &JvmValue) -> Result<Arc<Mutex<JvmArray>>, JvmError> {
+	if let JvmValue::Reference(_, JvmReferenceTargetType::Object(string), _) = argument {
+		if let Ok(string) = string.lock() {
+			if let Some(value) = string.get_field(&format!("java/lang/String"), &format!("value")) {
+				if let JvmValue::Reference(_, JvmReferenceTargetType::Array(array), _) = &*value {
+					return Ok(Arc::clone(array));
+				}
+			}
+		}
+	}
+	Err(FatalError::new(FatalErrorType::WrongType(
+		method.to_string(),
+		format!("reference to a String"),
+	))
+	.into_error())
+}
+
+/*
+ * The length of a String is the number of UTF-16 code units in it.
+ */
+fn string_length(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let characters = string_characters("length", &arguments[0])?;
+	let length = match characters.lock() {
+		Ok(characters) => characters.len(),
+		_ => {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("Array."),
+				format!("length"),
+			))
+			.into_error());
+		}
+	};
+	int_value(length as i32)
+}
+
+/*
+ * A char is an int on the operand stack.
+ */
+fn string_char_at(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let characters = string_characters("charAt", &arguments[0])?;
+	let index = int_argument("charAt", arguments, 1)?;
+	let character = match characters.lock() {
+		Ok(characters) => {
+			if index < 0 || index as usize >= characters.len() {
+				None
+			} else {
+				characters.get(index as usize)
+			}
+		}
+		_ => {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("Array."),
+				format!("charAt"),
+			))
+			.into_error());
+		}
+	};
+	match character {
+		Some(JvmValue::Primitive(_, _, character, _)) => int_value(character as i32),
+		_ => {
+			thread.set_pending_exception(
+				"java/lang/StringIndexOutOfBoundsException",
+				Some(format!("String index out of range: {}", index)),
+			);
+			Ok(None)
+		}
+	}
+}
+
+/*
+ * A String equals another String with the same characters and
+ * nothing else. A boolean is an int on the operand stack.
+ */
+fn string_equals(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let is_string = match &*arguments[1] {
+		JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) => match object.lock() {
+			Ok(object) => object.get_class().get_class_name() == Some(format!("java/lang/String")),
+			_ => false,
+		},
+		_ => false,
+	};
+	if !is_string {
+		return int_value(0);
+	}
+
+	let characters = string_characters("equals", &arguments[0])?;
+	let other_characters = string_characters("equals", &arguments[1])?;
+	/*
+	 * Strings can share their characters (and the array cannot be
+	 * locked twice).
+	 */
+	if Arc::ptr_eq(&characters, &other_characters) {
+		return int_value(1);
+	}
+	let equal = match (characters.lock(), other_characters.lock()) {
+		(Ok(characters), Ok(other_characters)) => {
+			characters.len() == other_characters.len()
+				&& (0..characters.len()).all(|index| {
+					match (characters.get(index), other_characters.get(index)) {
+						(
+							Some(JvmValue::Primitive(_, _, character, _)),
+							Some(JvmValue::Primitive(_, _, other_character, _)),
+						) => character == other_character,
+						_ => false,
+					}
+				})
+		}
+		_ => {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("Array."),
+				format!("equals"),
+			))
+			.into_error());
+		}
+	};
+	int_value(if equal { 1 } else { 0 })
+}
+
+/*
+ * Run _update_ on the characters of the StringBuilder that _argument_
+ * refers to. They are kept on the builder object itself.
+ */
+fn with_string_builder<T>(
+	method: &str,
+	argument: &JvmValue,
+	update: impl FnOnce(&mut String) -> T,
+) -> Result<T, JvmError> {
+	match argument {
+		JvmValue::Reference(_, JvmReferenceTargetType::Object(builder), _) => builder
+			.lock()
+			.map(|mut builder| update(builder.string_builder_mut()))
+			.map_err(|_| {
+				FatalError::new(FatalErrorType::CouldNotLock(
+					format!("StringBuilder"),
+					method.to_string(),
+				))
+				.into_error()
+			}),
+		_ => Err(FatalError::new(FatalErrorType::WrongType(
+			method.to_string(),
+			format!("reference to a StringBuilder"),
+		))
+		.into_error()),
+	}
+}
+
+fn string_builder_init(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	with_string_builder("<init>", &arguments[0], |builder| builder.clear())?;
+	Ok(None)
+}
+
+/*
+ * Appending a null String appends "null". Like every append, it
+ * returns the builder.
+ */
+fn string_builder_append_string(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let string = if arguments[1].is_null() {
+		format!("null")
+	} else {
+		match &*arguments[1] {
+			JvmValue::Reference(_, JvmReferenceTargetType::Object(string), _) => {
+				match string
+					.lock()
+					.ok()
+					.and_then(|string| string_object_value(&string))
+				{
+					Some(string) => string,
+					None => {
+						return Err(FatalError::new(FatalErrorType::FieldNotFound(
+							format!("value"),
+							format!("java/lang/String"),
+						))
+						.into_error());
+					}
+				}
+			}
+			_ => {
+				return Err(FatalError::new(FatalErrorType::WrongType(
+					format!("append"),
+					format!("reference to a String"),
+				))
+				.into_error());
+			}
+		}
+	};
+	with_string_builder("append", &arguments[0], |builder| builder.push_str(&string))?;
+	Ok(Some(Rc::clone(&arguments[0])))
+}
+
+fn string_builder_append_int(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let value = int_argument("append", arguments, 1)?;
+	with_string_builder("append", &arguments[0], |builder| {
+		builder.push_str(&value.to_string())
+	})?;
+	Ok(Some(Rc::clone(&arguments[0])))
+}
+
+fn string_builder_to_string(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let value = with_string_builder("toString", &arguments[0], |builder| builder.clone())?;
+	thread
+		.create_string(value)
+		.map(|string| Some(Rc::new(string)))
+}
+
+/*
+ * The identity hash of an object is (the low bits of) its identity.
+ */
+fn object_hash_code(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	match arguments[0].identity() {
+		Some(identity) => int_value(identity as i32),
+		None => Err(FatalError::new(FatalErrorType::WrongType(
+			format!("hashCode"),
+			format!("reference to an object"),
+		))
+		.into_error()),
+	}
+}
+
+fn object_equals(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let equal = match (arguments[0].identity(), arguments[1].identity()) {
+		(Some(this), Some(other)) => this == other,
+		_ => false,
+	};
+	int_value(equal as i32)
+}
+
+/*
+ * The binary name of the object's class and its identity hash in hex,
+ * just like java.lang.Object@1b6d3586.
+ */
+fn object_to_string(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let class_name = match thread.runtime_type(&arguments[0]) {
+		Ok(JvmType::Reference(JvmReferenceType::Class(class_name))) => class_name,
+		_ => {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				format!("toString"),
+				format!("reference to an object"),
+			))
+			.into_error());
+		}
+	};
+	let hash_code = arguments[0].identity().unwrap_or(0) as u32;
+	thread
+		.create_string(format!("{}@{:x}", class_name.replace("/", "."), hash_code))
+		.map(|string| Some(Rc::new(string)))
+}
+
+/*
+ * A java/lang/Class instance is a reference to the Class itself: the
+ * one in the method area, so that every getClass of objects (or
+ * arrays) of the same class gets the same Class.
+ */
+fn object_get_class(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let class = match &*arguments[0] {
+		JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) => match object.lock() {
+			Ok(object) => object.get_class(),
+			_ => {
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
+					format!("Object."),
+					format!("getClass"),
+				))
+				.into_error())
+			}
+		},
+		JvmValue::Reference(
+			array_type @ JvmReferenceType::Array(_, _),
+			JvmReferenceTargetType::Array(_),
+			_,
+		) => thread.load_class(&JvmType::Reference(array_type.clone()).descriptor())?,
+		_ => {
+			return Err(FatalError::new(FatalErrorType::WrongType(
+				format!("getClass"),
+				format!("reference to an object or array"),
+			))
+			.into_error())
+		}
+	};
+	Ok(Some(Rc::new(JvmValue::Reference(
+		JvmReferenceType::Class(format!("java/lang/Class")),
+		JvmReferenceTargetType::Class(class),
+		0,
+	))))
+}
+
+/*
+ * The detail message that the exception's constructor stored, or
+ * null when it was constructed without one.
+ */
+fn throwable_get_message(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	if let JvmValue::Reference(_, JvmReferenceTargetType::Object(object), _) = &*arguments[0] {
+		if let Some(message) = object.lock().ok().and_then(|object| {
+			object.get_field(&format!("java/lang/Throwable"), &format!("detailMessage"))
+		}) {
+			return Ok(Some(message));
+		}
+	}
+	Ok(Some(Rc::new(create_null_value())))
+}
+
+/*
+ * The binary name of the class (java.lang.String), not its internal
+ * name (java/lang/String).
+ */
+fn class_get_name(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	match &*arguments[0] {
+		JvmValue::Reference(_, JvmReferenceTargetType::Class(class), _) => {
+			let name = match class.get_class_name() {
+				Some(name) => name.replace("/", "."),
+				None => return Err(FatalError::new(FatalErrorType::ClassNoName).into_error()),
+			};
+			thread.create_string(name).map(|name| Some(Rc::new(name)))
+		}
+		_ => Err(FatalError::new(FatalErrorType::WrongType(
+			format!("getName"),
+			format!("reference to a Class"),
+		))
+		.into_error()),
+	}
+}
+
+/*
+ * Boxing -128 to 127 always gives the same Integer (jls 5.1.7).
+ */
+fn integer_value_of(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let value = int_argument("valueOf", arguments, 0)?;
+	let cached = value >= -128 && value <= 127;
+	if cached {
+		if let Some(integer) = thread.integer_cache_mut().get(&value) {
+			return Ok(Some(Rc::new(integer.clone())));
+		}
+	}
+
+	let integer_class_name = format!("java/lang/Integer");
+	let mut integer = thread.create_object(&integer_class_name)?;
+	let int = Rc::new(JvmValue::Primitive(
+		JvmPrimitiveType::Integer,
+		0,
+		value as u32,
+		0,
+	));
+	if !integer.set_field(&integer_class_name, &format!("value"), int) {
+		return Err(FatalError::new(FatalErrorType::FieldNotFound(
+			format!("value"),
+			integer_class_name,
+		))
+		.into_error());
+	}
+	let integer = JvmValue::Reference(
+		JvmReferenceType::Class(integer_class_name),
+		JvmReferenceTargetType::Object(Arc::new(Mutex::new(integer))),
+		0,
+	);
+	if cached {
+		thread.integer_cache_mut().insert(value, integer.clone());
+	}
+	Ok(Some(Rc::new(integer)))
+}
+
+fn integer_int_value(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	if let JvmValue::Reference(_, JvmReferenceTargetType::Object(integer), _) = &*arguments[0] {
+		if let Ok(integer) = integer.lock() {
+			if let Some(value) = integer.get_field(&format!("java/lang/Integer"), &format!("value"))
+			{
+				return Ok(Some(value));
+			}
+		}
+	}
+	Err(FatalError::new(FatalErrorType::WrongType(
+		format!("intValue"),
+		format!("reference to an Integer"),
+	))
+	.into_error())
+}
+
+/*
+ * Just like Java's, Rust's sqrt of a negative number is NaN.
+ */
+fn math_sqrt(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	double_value(double_argument("sqrt", arguments, 0)?.sqrt())
+}
+
+/*
+ * The absolute value of Integer.MIN_VALUE is Integer.MIN_VALUE.
+ */
+fn math_abs_int(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	int_value(int_argument("abs", arguments, 0)?.wrapping_abs())
+}
+
+fn math_abs_double(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	double_value(double_argument("abs", arguments, 0)?.abs())
+}
+
+fn math_max_int(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	int_value(std::cmp::max(
+		int_argument("max", arguments, 0)?,
+		int_argument("max", arguments, 1)?,
+	))
+}
+
+fn math_min_int(_: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	int_value(std::cmp::min(
+		int_argument("min", arguments, 0)?,
+		int_argument("min", arguments, 1)?,
+	))
+}
+
+/*
+ * The array that _argument_ refers to and the type of its components.
+ * None when _argument_ does not refer to an array.
+ */
+fn array_argument(argument: &JvmValue) -> Option<(Arc<Mutex<JvmArray>>, Rc<JvmType>)> {
+	match argument {
+		JvmValue::Reference(
+			JvmReferenceType::Array(component_type, _),
+			JvmReferenceTargetType::Array(array),
+			_,
+		) => Some((Arc::clone(array), Rc::clone(component_type))),
+		_ => None,
+	}
+}
+
+/*
+ * Copy _length_ elements of _source_ from _source_position_ into
+ * _destination_ from _destination_position_. The copy happens as if
+ * through a temporary array so that it works when _source_ and
+ * _destination_ are the same array and the ranges overlap.
+ */
+fn system_arraycopy(thread: &mut JvmThread, arguments: &[Rc<JvmValue>]) -> NativeResult {
+	let source_position = int_argument("arraycopy", arguments, 1)?;
+	let destination_position = int_argument("arraycopy", arguments, 3)?;
+	let length = int_argument("arraycopy", arguments, 4)?;
+
+	if arguments[0].is_null() || arguments[2].is_null() {
+		thread.set_pending_exception("java/lang/NullPointerException", None);
+		return Ok(None);
+	}
+	let (source, source_component_type, destination, destination_component_type) =
+		match (array_argument(&arguments[0]), array_argument(&arguments[2])) {
+			(
+				Some((source, source_component_type)),
+				Some((destination, destination_component_type)),
+			) => (
+				source,
+				source_component_type,
+				destination,
+				destination_component_type,
+			),
+			_ => {
+				thread.set_pending_exception(
+					"java/lang/ArrayStoreException",
+					Some(format!("arraycopy: source or destination is not an array")),
+				);
+				return Ok(None);
+			}
+		};
+
+	/*
+	 * Arrays of primitives only copy to arrays of the very same
+	 * primitive. When every element of the source array is assignable
+	 * to the destination's components, there is no need to check them
+	 * one by one.
+	 */
+	let check_elements = match (&*source_component_type, &*destination_component_type) {
+		(JvmType::Primitive(source_type), JvmType::Primitive(destination_type)) => {
+			if source_type != destination_type {
+				thread.set_pending_exception(
+					"java/lang/ArrayStoreException",
+					Some(format!("arraycopy: type mismatch")),
+				);
+				return Ok(None);
+			}
+			false
+		}
+		(JvmType::Reference(_), JvmType::Reference(_)) => {
+			!thread.is_assignable(&source_component_type, &destination_component_type)?
+		}
+		_ => {
+			thread.set_pending_exception(
+				"java/lang/ArrayStoreException",
+				Some(format!("arraycopy: type mismatch")),
+			);
+			return Ok(None);
+		}
+	};
+
+	/*
+	 * The elements are copied out of the source before anything else
+	 * is locked. That is the temporary array that a copy within the
+	 * same array goes through, and it leaves no array locked while the
+	 * elements' types are worked out.
+	 */
+	let values = {
+		let source = match source.lock() {
+			Ok(source) => source,
+			_ => {
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
+					format!("Array."),
+					format!("arraycopy"),
+				))
+				.into_error());
+			}
+		};
+		if source_position < 0
+			|| length < 0
+			|| source_position as usize + length as usize > source.len()
+		{
+			thread.set_pending_exception(
+				"java/lang/ArrayIndexOutOfBoundsException",
+				Some(format!(
+					"arraycopy: source range {}+{} out of bounds for length {}",
+					source_position,
+					length,
+					source.len()
+				)),
+			);
+			return Ok(None);
+		}
+		(source_position as usize..source_position as usize + length as usize)
+			.map(|index| source.get(index))
+			.collect::<Vec<Option<JvmValue>>>()
+	};
+
+	let destination_length = match destination.lock() {
+		Ok(destination) => destination.len(),
+		_ => {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("Array."),
+				format!("arraycopy"),
+			))
+			.into_error());
+		}
+	};
+	if destination_position < 0
+		|| destination_position as usize + length as usize > destination_length
+	{
+		thread.set_pending_exception(
+			"java/lang/ArrayIndexOutOfBoundsException",
+			Some(format!(
+				"arraycopy: destination range {}+{} out of bounds for length {}",
+				destination_position, length, destination_length
+			)),
+		);
+		return Ok(None);
+	}
+
+	/*
+	 * The elements before the first one that does not fit stay copied.
+	 */
+	let mut fitting = values.len();
+	if check_elements {
+		for (offset, value) in values.iter().enumerate() {
+			if let Some(value) = value {
+				if !value.is_null() {
+					let value_type = thread.runtime_type(value)?;
+					if !thread.is_assignable(&value_type, &destination_component_type)? {
+						fitting = offset;
+						break;
+					}
+				}
+			}
+		}
+	}
+
+	match destination.lock() {
+		Ok(mut destination) => {
+			for (offset, value) in values.into_iter().take(fitting).enumerate() {
+				if let Some(value) = value {
+					destination.set_at(destination_position as usize + offset, value);
+				}
+			}
+		}
+		_ => {
+			return Err(FatalError::new(FatalErrorType::CouldNotLock(
+				format!("Array."),
+				format!("arraycopy"),
+			))
+			.into_error());
+		}
+	}
+	if fitting < length as usize {
+		thread.set_pending_exception(
+			"java/lang/ArrayStoreException",
+			Some(format!("arraycopy: element type mismatch")),
+		);
+	}
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::class::Class;
+	use jvm::debug::DebugLevel;
+	use jvm::environment::Environment;
+	use jvm::exceptions::synthetic_exception_class;
+	use jvm::field::FieldAccessFlags;
+	use jvm::invokedynamic::BootstrapRegistry;
+	use jvm::methodarea::MethodArea;
+	use jvm::object::JvmObject;
+	use std::cell::RefCell;
+	use std::io;
+	use std::io::Write;
+
+	fn new_thread() -> JvmThread {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment))),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		)
+	}
+
+	/*
+	 * The natives do not look at the class of the builder, so any
+	 * class will do.
+	 */
+	fn new_builder() -> (Arc<Mutex<JvmObject>>, Rc<JvmValue>) {
+		let class = synthetic_exception_class("java/lang/Throwable").unwrap();
+		let object = Arc::new(Mutex::new(JvmObject::new(
+			Rc::new(class),
+			DebugLevel::Error,
+		)));
+		let builder = Rc::new(JvmValue::Reference(
+			JvmReferenceType::Class(format!("java/lang/StringBuilder")),
+			JvmReferenceTargetType::Object(Arc::clone(&object)),
+			0,
+		));
+		(object, builder)
+	}
+
+	fn int_value(value: i32) -> Rc<JvmValue> {
+		Rc::new(JvmValue::Primitive(
+			JvmPrimitiveType::Integer,
+			0,
+			value as u32,
+			0,
+		))
+	}
+
+	#[test]
+	fn string_builders_keep_their_characters() {
+		let mut thread = new_thread();
+		let (first, first_builder) = new_builder();
+		let (second, second_builder) = new_builder();
+
+		assert!(string_builder_init(&mut thread, &[Rc::clone(&first_builder)]).is_ok());
+		assert!(string_builder_init(&mut thread, &[Rc::clone(&second_builder)]).is_ok());
+		assert!(string_builder_append_int(
+			&mut thread,
+			&[Rc::clone(&first_builder), int_value(12)]
+		)
+		.is_ok());
+		assert!(string_builder_append_int(
+			&mut thread,
+			&[Rc::clone(&second_builder), int_value(-3)]
+		)
+		.is_ok());
+		assert!(string_builder_append_string(
+			&mut thread,
+			&[Rc::clone(&first_builder), Rc::new(create_null_value())]
+		)
+		.is_ok());
+
+		assert_eq!(first.lock().unwrap().string_builder_mut(), "12null");
+		assert_eq!(second.lock().unwrap().string_builder_mut(), "-3");
+	}
+
+	#[test]
+	fn string_builder_natives_need_an_object() {
+		let mut thread = new_thread();
+		assert!(string_builder_init(&mut thread, &[Rc::new(create_null_value())]).is_err());
+		assert!(string_builder_append_int(&mut thread, &[int_value(1), int_value(2)]).is_err());
+	}
+
+	/*
+	 * What a thread prints.
+	 */
+	#[derive(Clone, Default)]
+	struct Captured(Rc<RefCell<Vec<u8>>>);
+
+	impl Write for Captured {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.borrow_mut().write(buf)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	/*
+	 * A thread that can make Strings and prints to _out_.
+	 */
+	fn printing_thread(out: &Captured) -> JvmThread {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut methodarea = MethodArea::new(DebugLevel::Error, environment);
+		let mut string = ClassFileBuilder::new("java/lang/String", Some("java/lang/Object"));
+		string.field(FieldAccessFlags::Private as u16, "value", "[C");
+		methodarea.load_class_from_bytes(string.bytes()).unwrap();
+		let mut thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::new(Mutex::new(methodarea)),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		);
+		thread.set_out(Box::new(out.clone()));
+		thread
+	}
+
+	#[test]
+	fn println_prints_ints_and_strings() {
+		let out = Captured::default();
+		let mut thread = printing_thread(&out);
+		let string = Rc::new(thread.create_string(format!("héllo")).unwrap());
+		let stream = Rc::new(create_null_value());
+
+		assert!(matches!(
+			println_int(&mut thread, &[Rc::clone(&stream), int_value(-12)]),
+			Ok(None)
+		));
+		assert!(matches!(
+			println_string(&mut thread, &[Rc::clone(&stream), string]),
+			Ok(None)
+		));
+		assert!(matches!(
+			println_string(
+				&mut thread,
+				&[Rc::clone(&stream), Rc::new(create_null_value())]
+			),
+			Ok(None)
+		));
+		thread.flush();
+		assert_eq!(
+			String::from_utf8(out.0.borrow().clone()).unwrap(),
+			"-12\nhéllo\nnull\n"
+		);
+
+		assert!(println_string(&mut thread, &[Rc::clone(&stream), int_value(1)]).is_err());
+
+		/*
+		 * Only ints print as ints.
+		 */
+		let float = Rc::new(JvmValue::Primitive(
+			JvmPrimitiveType::Float,
+			0,
+			1.5f32.to_bits(),
+			0,
+		));
+		let long = Rc::new(JvmValue::Primitive(JvmPrimitiveType::LongInteger, 1, 2, 0));
+		assert!(println_int(&mut thread, &[Rc::clone(&stream), float]).is_err());
+		assert!(println_int(&mut thread, &[Rc::clone(&stream), long]).is_err());
+		assert!(println_int(&mut thread, &[stream, Rc::new(create_null_value())]).is_err());
+	}
+
+	#[test]
+	fn natives_are_keyed_by_class_name_and_descriptor() {
+		let mut registry = NativeRegistry::new();
+		registry.register("Host", "answer", "()I", |_, _| Ok(Some(int_value(42))));
+
+		assert!(registry.get("Host", "answer", "()I").is_some());
+		assert!(registry.get("Host", "answer", "()J").is_none());
+		assert!(registry.get("Host", "question", "()I").is_none());
+		assert!(registry.get("Guest", "answer", "()I").is_none());
+
+		/*
+		 * Registering a native again replaces it.
+		 */
+		registry.register("Host", "answer", "()I", |_, _| Ok(Some(int_value(7))));
+		let native = registry.get("Host", "answer", "()I").unwrap();
+		let mut thread = new_thread();
+		assert!(matches!(
+			native(&mut thread, &[]),
+			Ok(Some(ref value)) if **value == *int_value(7)
+		));
+	}
+
+	fn double_argument(value: f64) -> Rc<JvmValue> {
+		Rc::new(JvmValue::Primitive(
+			JvmPrimitiveType::Double,
+			value.to_bits(),
+			0,
+			0,
+		))
+	}
+
+	fn returned_double(result: NativeResult) -> f64 {
+		match result {
+			Ok(Some(value)) => match *value {
+				JvmValue::Primitive(JvmPrimitiveType::Double, bits, _, _) => f64::from_bits(bits),
+				_ => panic!("not a double"),
+			},
+			_ => panic!("nothing was returned"),
+		}
+	}
+
+	fn returned_int(result: NativeResult) -> i32 {
+		match result {
+			Ok(Some(value)) => match *value {
+				JvmValue::Primitive(JvmPrimitiveType::Integer, _, value, _) => value as i32,
+				_ => panic!("not an int"),
+			},
+			_ => panic!("nothing was returned"),
+		}
+	}
+
+	#[test]
+	fn math_natives_match_java() {
+		let mut thread = new_thread();
+
+		assert_eq!(
+			returned_double(math_sqrt(&mut thread, &[double_argument(2.0)])),
+			2f64.sqrt()
+		);
+		assert!(returned_double(math_sqrt(&mut thread, &[double_argument(-1.0)])).is_nan());
+		assert_eq!(
+			returned_double(math_abs_double(&mut thread, &[double_argument(-0.5)])),
+			0.5
+		);
+		assert_eq!(returned_int(math_abs_int(&mut thread, &[int_value(-7)])), 7);
+		assert_eq!(
+			returned_int(math_abs_int(&mut thread, &[int_value(i32::min_value())])),
+			i32::min_value()
+		);
+		assert_eq!(
+			returned_int(math_max_int(&mut thread, &[int_value(-3), int_value(2)])),
+			2
+		);
+		assert_eq!(
+			returned_int(math_min_int(&mut thread, &[int_value(-3), int_value(2)])),
+			-3
+		);
+		assert!(math_max_int(&mut thread, &[int_value(1)]).is_err());
+	}
+
+	fn class_of(thread: &mut JvmThread, value: &Rc<JvmValue>) -> Rc<Class> {
+		match object_get_class(thread, &[Rc::clone(value)]) {
+			Ok(Some(class)) => match &*class {
+				JvmValue::Reference(_, JvmReferenceTargetType::Class(class), _) => Rc::clone(class),
+				_ => panic!("getClass did not return a Class"),
+			},
+			_ => panic!("getClass failed"),
+		}
+	}
+
+	fn int_array() -> Rc<JvmValue> {
+		Rc::new(JvmValue::Reference(
+			JvmReferenceType::Array(Rc::new(JvmType::Primitive(JvmPrimitiveType::Integer)), 1),
+			JvmReferenceTargetType::Array(Arc::new(Mutex::new(JvmArray::new(1)))),
+			0,
+		))
+	}
+
+	#[test]
+	fn get_class_shares_the_loaded_class() {
+		let mut thread = new_thread();
+		let (object, builder) = new_builder();
+		assert!(Rc::ptr_eq(
+			&class_of(&mut thread, &builder),
+			&object.lock().unwrap().get_class()
+		));
+
+		let array_class = class_of(&mut thread, &int_array());
+		assert_eq!(array_class.get_class_name(), Some(format!("[I")));
+		assert_eq!(
+			array_class.superclass_name(),
+			Some(format!("java/lang/Object"))
+		);
+		assert!(Rc::ptr_eq(
+			&array_class,
+			&class_of(&mut thread, &int_array())
+		));
+	}
+}
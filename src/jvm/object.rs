@@ -19,16 +19,20 @@
  * You should have received a copy of the GNU General Public License
  * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
  */
-use super::array::JvmArray;
+use jvm::array::JvmArray;
 use jvm::class::Class;
-use jvm::constant::Constant;
 use jvm::constantpool::ConstantPool;
 use jvm::debug::{Debug, DebugLevel};
 use jvm::error::FatalError;
 use jvm::error::FatalErrorType;
+use jvm::error::JvmError;
+use jvm::field::FieldAccessFlags;
+use jvm::heap::allocate_identity;
+use jvm::heap::Allocation;
+use jvm::invokedynamic::Lambda;
 use jvm::jvmthread::JvmThread;
 use jvm::methodarea::MethodArea;
-use jvm::typevalues::create_null_value;
+use jvm::monitor::Monitor;
 use jvm::typevalues::JvmPrimitiveType;
 use jvm::typevalues::JvmReferenceTargetType;
 use jvm::typevalues::JvmReferenceType;
@@ -36,39 +40,124 @@ use jvm::typevalues::JvmType;
 use jvm::typevalues::JvmValue;
 use std::collections::HashMap;
 use std::fmt;
+use std::mem;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 pub struct JvmObject {
-	spr: Option<Rc<JvmObject>>,
+	/*
+	 * What tells this object apart from every other (see
+	 * heap::allocate_identity).
+	 */
+	identity: u64,
+	spr: Option<Box<JvmObject>>,
 	class: Rc<Class>,
 	fields: HashMap<String, Rc<JvmValue>>,
+	monitor: Monitor,
 	debug_level: DebugLevel,
+	/*
+	 * What the object implements when LambdaMetafactory made it.
+	 */
+	lambda: Option<Rc<Lambda>>,
+	/*
+	 * The characters of the object when it is a StringBuilder (see
+	 * native::string_builder_init).
+	 */
+	string_builder: Option<String>,
+	/*
+	 * The part of the heap that the object takes up, when it counts
+	 * against the heap limit.
+	 */
+	allocation: Option<Allocation>,
 }
 
 /*
- * This assumes that the String class has already been loaded and initialized.
+ * A new object of class _class_name_ (loaded and initialized first,
+ * when need be) with all of its fields at their default values.
  */
-pub fn create_static_string_object(
-	value: String,
+pub fn create_object(
+	class_name: &String,
 	thread: &mut JvmThread,
 	methodarea_mutex: Arc<Mutex<MethodArea>>,
-) -> Option<JvmObject> {
-	if let Ok(methodarea) = methodarea_mutex.lock() {
-		let string_class_name = format!("java/lang/String");
-		if let Some(string_class) = methodarea.get_class_rc(&string_class_name) {
-			let mut string_object = JvmObject::new(Rc::clone(&string_class), thread.debug_level());
-			string_object.instantiate(thread, Arc::clone(&methodarea_mutex));
-			return Some(string_object);
-		} else {
-			FatalError::new(FatalErrorType::ClassNotFound(string_class_name)).call();
-		}
+) -> Result<JvmObject, JvmError> {
+	let class = if let Ok(mut methodarea) = methodarea_mutex.lock() {
+		methodarea.maybe_load_class(class_name);
+		methodarea.get_class_rc(class_name)
 	} else {
-		FatalError::new(FatalErrorType::CouldNotLock(
+		return Err(FatalError::new(FatalErrorType::CouldNotLock(
 			format!("Method Area"),
-			format!("create_static_string_object"),
+			format!("create_object"),
 		))
-		.call();
+		.into_error());
+	};
+	let class = match class {
+		Some(class) => class,
+		None => return Err(JvmError::ClassNotFound(class_name.clone())),
+	};
+
+	/*
+	 * Instantiating may load and initialize other classes, so the
+	 * method area cannot stay locked.
+	 */
+	thread.maybe_initialize_class(&class)?;
+	let mut object = JvmObject::new(Rc::clone(&class), thread.debug_level());
+	object.instantiate(thread, Arc::clone(&methodarea_mutex))?;
+	Ok(object)
+}
+
+/*
+ * Make a String object for _value_. Its characters are kept, as
+ * UTF-16 code units, in the String's value field.
+ */
+pub fn create_static_string_object(
+	value: String,
+	thread: &mut JvmThread,
+	methodarea_mutex: Arc<Mutex<MethodArea>>,
+) -> Result<JvmObject, JvmError> {
+	let string_class_name = format!("java/lang/String");
+	let mut string_object = create_object(&string_class_name, thread, methodarea_mutex)?;
+
+	let characters: Vec<u16> = value.encode_utf16().collect();
+	let mut array = JvmArray::new(characters.len());
+	for (index, character) in characters.iter().enumerate() {
+		array.set_at(
+			index,
+			JvmValue::Primitive(JvmPrimitiveType::Char, 0, *character as u32, 0),
+		);
+	}
+	let array = JvmValue::Reference(
+		JvmReferenceType::Array(
+			Rc::new(JvmType::Primitive(JvmPrimitiveType::Char)),
+			characters.len() as u32,
+		),
+		JvmReferenceTargetType::Array(Arc::new(Mutex::new(array))),
+		0,
+	);
+	if !string_object.set_field(&string_class_name, &format!("value"), Rc::new(array)) {
+		return Err(FatalError::new(FatalErrorType::FieldNotFound(
+			format!("value"),
+			string_class_name,
+		))
+		.into_error());
+	}
+	Ok(string_object)
+}
+
+/*
+ * The characters of a String object, from its value field.
+ */
+pub fn string_object_value(string_object: &JvmObject) -> Option<String> {
+	let value = string_object.get_field(&format!("java/lang/String"), &format!("value"))?;
+	if let JvmValue::Reference(_, JvmReferenceTargetType::Array(array), _) = &*value {
+		if let Ok(mut array) = array.lock() {
+			let mut characters: Vec<u16> = vec![];
+			for index in 0..array.len() {
+				if let Some(JvmValue::Primitive(_, _, character, _)) = array.get_at(index) {
+					characters.push(*character as u16);
+				}
+			}
+			return Some(String::from_utf16_lossy(&characters));
+		}
 	}
 	None
 }
@@ -76,24 +165,104 @@ pub fn create_static_string_object(
 impl JvmObject {
 	pub fn new(class: Rc<Class>, debug_level: DebugLevel) -> Self {
 		JvmObject {
+			identity: allocate_identity(),
 			spr: None,
 			class: class,
 			fields: HashMap::<String, Rc<JvmValue>>::new(),
+			monitor: Monitor::new(),
 			debug_level,
+			lambda: None,
+			string_builder: None,
+			allocation: None,
 		}
 	}
 
+	pub fn identity(&self) -> u64 {
+		self.identity
+	}
+
+	/*
+	 * Roughly how many bytes the object takes up, along with the
+	 * fields of its superclasses.
+	 */
+	pub fn size(&self) -> usize {
+		let superclass_size = match &self.spr {
+			Some(spr) => spr.size(),
+			None => 0,
+		};
+		mem::size_of::<JvmObject>()
+			+ self.fields.len() * mem::size_of::<JvmValue>()
+			+ superclass_size
+	}
+
+	pub fn set_allocation(&mut self, allocation: Allocation) {
+		self.allocation = Some(allocation);
+	}
+
+	pub fn lambda(&self) -> Option<Rc<Lambda>> {
+		self.lambda.clone()
+	}
+
+	pub fn set_lambda(&mut self, lambda: Rc<Lambda>) {
+		self.lambda = Some(lambda);
+	}
+
+	pub fn string_builder_mut(&mut self) -> &mut String {
+		self.string_builder.get_or_insert_with(String::new)
+	}
+
 	pub fn get_class(&self) -> Rc<Class> {
 		Rc::clone(&self.class)
 	}
 
-	pub fn set_field(&mut self, field_name: &String, value: Rc<JvmValue>) {
-		self.fields.insert(field_name.clone(), value);
+	pub fn monitor(&self) -> &Monitor {
+		&self.monitor
+	}
+
+	pub fn monitor_mut(&mut self) -> &mut Monitor {
+		&mut self.monitor
 	}
 
-	pub fn get_field(&mut self, field_name: &String) -> Option<Rc<JvmValue>> {
-		if let Some(field_value) = self.fields.get(field_name) {
-			Some(Rc::clone(field_value))
+	/*
+	 * Fields are stored with the (super)object of the class that
+	 * declares them, so a field in a subclass does not clobber a
+	 * field with the same name in a superclass. field_class_name is
+	 * the name of the declaring class, as determined by field
+	 * resolution. Only fields created by instantiate can be set.
+	 */
+	pub fn set_field(
+		&mut self,
+		field_class_name: &String,
+		field_name: &String,
+		value: Rc<JvmValue>,
+	) -> bool {
+		if self.class.get_class_name().unwrap() == *field_class_name {
+			if self.fields.contains_key(field_name) {
+				self.fields.insert(field_name.clone(), value);
+				true
+			} else {
+				false
+			}
+		} else if let Some(spr) = &mut self.spr {
+			spr.set_field(field_class_name, field_name, value)
+		} else {
+			false
+		}
+	}
+
+	pub fn get_field(
+		&self,
+		field_class_name: &String,
+		field_name: &String,
+	) -> Option<Rc<JvmValue>> {
+		if self.class.get_class_name().unwrap() == *field_class_name {
+			if let Some(field_value) = self.fields.get(field_name) {
+				Some(Rc::clone(field_value))
+			} else {
+				None
+			}
+		} else if let Some(spr) = &self.spr {
+			spr.get_field(field_class_name, field_name)
 		} else {
 			None
 		}
@@ -110,6 +279,8 @@ impl JvmObject {
 	pub fn is_type_of(&self, r#type: &String) -> bool {
 		if self.class.get_class_name().unwrap() == *r#type {
 			true
+		} else if let Some(lambda) = &self.lambda {
+			lambda.interface_name == *r#type
 		} else if let Some(spr) = &self.spr {
 			spr.is_type_of(r#type)
 		} else {
@@ -121,7 +292,7 @@ impl JvmObject {
 		&mut self,
 		initializing_thread: &mut JvmThread,
 		methodarea: Arc<Mutex<MethodArea>>,
-	) -> bool {
+	) -> Result<(), JvmError> {
 		let fields = self.class.get_fields_ref();
 		let constantpool = self.class.get_constant_pool_ref();
 
@@ -129,25 +300,20 @@ impl JvmObject {
 
 		for i in 0..fields.fields_count() {
 			let field = fields.get(i as usize);
+
+			/*
+			 * Static fields belong to the class, not to its instances.
+			 */
+			if field.access_flags & (FieldAccessFlags::Static as u16) != 0 {
+				continue;
+			}
 			/*
 			 * Get the field type.
 			 */
-			let r#type: JvmType =
-				match constantpool.get_constant_ref(field.descriptor_index as usize) {
-					Constant::Utf8(_, _, _, d) => {
-						let descriptor = d.as_bytes();
-						JvmType::from(descriptor)
-					}
-					_ => {
-						FatalError::new(FatalErrorType::InvalidConstantReference(
-							self.class.get_class_name().unwrap(),
-							"Utf8".to_string(),
-							field.descriptor_index,
-						))
-						.call();
-						JvmType::Primitive(JvmPrimitiveType::Void)
-					}
-				};
+			let r#type: JvmType = match constantpool.get_utf8(field.descriptor_index as usize) {
+				Ok(descriptor) => JvmType::from(descriptor.as_bytes()),
+				Err(error) => return Err(error.into_error()),
+			};
 
 			/*
 			 * Get the field access modifiers.
@@ -155,40 +321,23 @@ impl JvmObject {
 			let access_flags = field.access_flags;
 
 			/*
-			 * Get the default field value.
+			 * Get the default field value. The default for every
+			 * reference type is a null reference of the field's
+			 * declared type.
 			 */
 			let value = match r#type {
 				JvmType::Primitive(primitive) => JvmValue::Primitive(primitive, 0, 0, access_flags),
-				JvmType::Reference(reference) => match reference {
-					JvmReferenceType::Array(r#type, access) => JvmValue::Reference(
-						JvmReferenceType::Array(Rc::clone(&r#type), access),
-						JvmReferenceTargetType::Array(Arc::new(Mutex::new(JvmArray::new(0)))),
-						0,
-					),
-					_ => {
-						FatalError::new(FatalErrorType::NotImplemented(format!(
-							"Getting a reference type field other than an array."
-						)))
-						.call();
-						create_null_value()
-					}
-				},
+				JvmType::Reference(reference) => {
+					JvmValue::Reference(reference, JvmReferenceTargetType::Null, 0)
+				}
 			};
 
 			/*
 			 * Get the field name.
 			 */
-			let name = match constantpool.get_constant_ref(field.name_index as usize) {
-				Constant::Utf8(_, _, _, name) => name.clone(),
-				_ => {
-					FatalError::new(FatalErrorType::InvalidConstantReference(
-						self.class.get_class_name().unwrap(),
-						"Utf8".to_string(),
-						field.name_index,
-					))
-					.call();
-					"".to_string()
-				}
+			let name = match constantpool.get_utf8(field.name_index as usize) {
+				Ok(name) => name.to_string(),
+				Err(error) => return Err(error.into_error()),
 			};
 
 			/*
@@ -198,7 +347,8 @@ impl JvmObject {
 		}
 
 		/*
-		 * TODO: Handle superclass instantiation!
+		 * Every class but java/lang/Object has a superclass, and the
+		 * chain of (super)objects goes all the way up to it.
 		 */
 		if let Some(superclass_name) = self.class.resolve_superclass() {
 			// We have a superclass and we know it's name.
@@ -208,47 +358,37 @@ impl JvmObject {
 				DebugLevel::Info,
 			);
 
-			/*
-			 * TODO: Let this go all the way to object!
-			 */
-
-			if superclass_name == format!("java/lang/Object") {
-				Debug(
-					format!("Not making the base class java/lang/Object"),
-					&self.debug_level,
-					DebugLevel::Info,
-				);
-				return true;
-			}
-
-			let mut instantiated_class: Option<Rc<Class>> = None;
+			let instantiated_class: Option<Rc<Class>>;
 			if let Ok(mut methodarea) = methodarea.lock() {
 				(*methodarea).maybe_load_class(&superclass_name);
 				instantiated_class = (*methodarea).get_class_rc(&superclass_name);
 			} else {
-				FatalError::new(FatalErrorType::CouldNotLock(
+				return Err(FatalError::new(FatalErrorType::CouldNotLock(
 					"Method Area.".to_string(),
 					"instantiate".to_string(),
 				))
-				.call();
+				.into_error());
 			}
 			if let Some(instantiated_class) = instantiated_class {
-				initializing_thread.maybe_initialize_class(&instantiated_class);
+				initializing_thread.maybe_initialize_class(&instantiated_class)?;
 
 				let mut object = JvmObject::new(instantiated_class, self.debug_level.clone());
 
-				object.instantiate(initializing_thread, Arc::clone(&methodarea));
-				self.spr = Some(Rc::new(object));
+				object.instantiate(initializing_thread, Arc::clone(&methodarea))?;
+				self.spr = Some(Box::new(object));
 				Debug(
 					format!("Made a new superclass of {}.", superclass_name),
 					&self.debug_level,
 					DebugLevel::Info,
 				);
 			} else {
-				FatalError::new(FatalErrorType::ClassNotLoaded(superclass_name.to_string())).call();
+				return Err(FatalError::new(FatalErrorType::ClassNotLoaded(
+					superclass_name.to_string(),
+				))
+				.into_error());
 			}
 		}
-		true
+		Ok(())
 	}
 }
 
@@ -257,3 +397,101 @@ impl fmt::Display for JvmObject {
 		write!(f, "Object of type {}", self.class.get_class_name().unwrap())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::environment::Environment;
+	use jvm::invokedynamic::BootstrapRegistry;
+	use jvm::native::NativeRegistry;
+
+	/*
+	 * A thread, and the method area that it loads classes into.
+	 */
+	fn new_thread() -> (JvmThread, Arc<Mutex<MethodArea>>) {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let methodarea = Arc::new(Mutex::new(MethodArea::new(DebugLevel::Error, environment)));
+		let thread = JvmThread::new(
+			DebugLevel::Error,
+			Arc::clone(&methodarea),
+			NativeRegistry::new(),
+			BootstrapRegistry::new(),
+		);
+		(thread, methodarea)
+	}
+
+	fn load_class(methodarea: &Arc<Mutex<MethodArea>>, builder: &mut ClassFileBuilder) {
+		methodarea
+			.lock()
+			.unwrap()
+			.load_class_from_bytes(builder.bytes())
+			.unwrap();
+	}
+
+	#[test]
+	fn instantiate_builds_the_whole_superclass_chain() {
+		let (mut thread, methodarea) = new_thread();
+		load_class(
+			&methodarea,
+			&mut ClassFileBuilder::new("Grandparent", Some("java/lang/Object")),
+		);
+		load_class(
+			&methodarea,
+			&mut ClassFileBuilder::new("Parent", Some("Grandparent")),
+		);
+		load_class(
+			&methodarea,
+			&mut ClassFileBuilder::new("Child", Some("Parent")),
+		);
+
+		let child = create_object(&format!("Child"), &mut thread, methodarea).unwrap();
+		assert_eq!(
+			child.hierarchy(),
+			"Child, Parent, Grandparent, java/lang/Object"
+		);
+		assert!(child.is_type_of(&format!("Grandparent")));
+		assert!(child.is_type_of(&format!("java/lang/Object")));
+		assert!(!child.is_type_of(&format!("Other")));
+	}
+
+	#[test]
+	fn instantiate_needs_every_superclass() {
+		let (mut thread, methodarea) = new_thread();
+		let orphan = Rc::new(ClassFileBuilder::new("Orphan", Some("does/not/Exist")).build());
+		let mut object = JvmObject::new(orphan, DebugLevel::Error);
+
+		assert!(matches!(
+			object.instantiate(&mut thread, methodarea),
+			Err(JvmError::Fatal(FatalErrorType::ClassNotLoaded(ref class_name)))
+				if class_name == "does/not/Exist"
+		));
+	}
+
+	#[test]
+	fn reference_fields_start_out_null() {
+		let (mut thread, methodarea) = new_thread();
+		let mut builder = ClassFileBuilder::new("Holder", Some("java/lang/Object"));
+		builder
+			.field(
+				FieldAccessFlags::Private as u16,
+				"name",
+				"Ljava/lang/String;",
+			)
+			.field(FieldAccessFlags::Private as u16, "counts", "[I");
+		load_class(&methodarea, &mut builder);
+
+		let holder = create_object(&format!("Holder"), &mut thread, methodarea).unwrap();
+		let class_name = format!("Holder");
+		assert!(matches!(
+			&*holder.get_field(&class_name, &format!("name")).unwrap(),
+			JvmValue::Reference(JvmReferenceType::Class(ref type_name), JvmReferenceTargetType::Null, _)
+				if type_name == "java/lang/String"
+		));
+		assert!(matches!(
+			&*holder.get_field(&class_name, &format!("counts")).unwrap(),
+			JvmValue::Reference(JvmReferenceType::Array(ref component, _), JvmReferenceTargetType::Null, _)
+				if matches!(**component, JvmType::Primitive(JvmPrimitiveType::Integer))
+		));
+	}
+}
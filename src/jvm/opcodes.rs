@@ -23,6 +23,7 @@
 
 enum_from_primitive! {
 	pub enum OperandCode {
+		Nop = 0x0,
 		Aconst_null = 0x1,
 		Iconst_m1 = 0x2,
 		Iconst_0 = 0x3,
@@ -31,55 +32,147 @@ enum_from_primitive! {
 		Iconst_3 = 0x6,
 		Iconst_4 = 0x7,
 		Iconst_5 = 0x8,
+		Lconst_0 = 0x9,
+		Lconst_1 = 0xA,
 		Fconst_0 = 0xB,
 		Fconst_1 = 0xC,
+		Fconst_2 = 0xD,
+		Dconst_0 = 0xE,
+		Dconst_1 = 0xF,
 		Bipush = 0x10,
+		Sipush = 0x11,
 		Ldc = 0x12,
+		Ldc_w = 0x13,
+		Ldc2_w = 0x14,
+		Iload = 0x15,
+		Lload = 0x16,
+		Fload = 0x17,
+		Dload = 0x18,
+		Aload = 0x19,
 		Iload_0 = 0x1a,
 		Iload_1 = 0x1b,
 		Iload_2 = 0x1c,
 		Iload_3 = 0x1d,
+		Lload_0 = 0x1e,
+		Lload_1 = 0x1f,
+		Lload_2 = 0x20,
+		Lload_3 = 0x21,
 		Fload_0 = 0x22,
 		Fload_1 = 0x23,
 		Fload_2 = 0x24,
 		Fload_3 = 0x25,
+		Dload_0 = 0x26,
+		Dload_1 = 0x27,
+		Dload_2 = 0x28,
+		Dload_3 = 0x29,
 		Aload_0 = 0x2a,
 		Aload_1 = 0x2b,
 		Aload_2 = 0x2c,
 		Aload_3 = 0x2d,
+		IaLoad = 0x2e,
+		LaLoad = 0x2f,
+		FaLoad = 0x30,
+		DaLoad = 0x31,
 		AaLoad = 0x32,
+		BaLoad = 0x33,
 		CaLoad = 0x34,
+		SaLoad = 0x35,
+		Istore = 0x36,
+		Lstore = 0x37,
 		Fstore = 0x38,
+		Dstore = 0x39,
+		Astore = 0x3a,
 		Istore_0 = 0x3b,
 		Istore_1 = 0x3c,
 		Istore_2 = 0x3d,
 		Istore_3 = 0x3e,
+		Lstore_0 = 0x3f,
+		Lstore_1 = 0x40,
+		Lstore_2 = 0x41,
+		Lstore_3 = 0x42,
 		Fstore_0 = 0x43,
 		Fstore_1 = 0x44,
 		Fstore_2 = 0x45,
 		Fstore_3 = 0x46,
+		Dstore_0 = 0x47,
+		Dstore_1 = 0x48,
+		Dstore_2 = 0x49,
+		Dstore_3 = 0x4a,
 		Astore_0 = 0x4b,
 		Astore_1 = 0x4c,
 		Astore_2 = 0x4d,
 		Astore_3 = 0x4e,
+		IaStore = 0x4f,
+		LaStore = 0x50,
+		FaStore = 0x51,
+		DaStore = 0x52,
 		AaStore = 0x53,
+		BaStore = 0x54,
 		CaStore = 0x55,
+		SaStore = 0x56,
 		Pop = 0x57,
+		Pop2 = 0x58,
 		Dup = 0x59,
+		Dup_x1 = 0x5a,
+		Dup_x2 = 0x5b,
+		Dup2 = 0x5c,
+		Dup2_x1 = 0x5d,
+		Dup2_x2 = 0x5e,
+		Swap = 0x5f,
 		Iadd = 0x60,
+		Ladd = 0x61,
 		Fadd = 0x62,
+		Dadd = 0x63,
+		Isub = 0x64,
+		Lsub = 0x65,
 		Fsub = 0x66,
+		Dsub = 0x67,
 		Imul = 0x68,
+		Lmul = 0x69,
 		Fmul = 0x6a,
+		Dmul = 0x6b,
+		Idiv = 0x6c,
+		Ldiv = 0x6d,
 		Fdiv = 0x6e,
+		Ddiv = 0x6f,
+		Irem = 0x70,
+		Lrem = 0x71,
+		Frem = 0x72,
+		Drem = 0x73,
+		Iinc = 0x84,
+		I2l = 0x85,
+		I2f = 0x86,
+		I2d = 0x87,
+		L2i = 0x88,
+		L2f = 0x89,
+		L2d = 0x8a,
+		F2i = 0x8b,
+		F2l = 0x8c,
+		F2d = 0x8d,
+		D2i = 0x8e,
+		D2l = 0x8f,
+		D2f = 0x90,
+		I2b = 0x91,
+		I2c = 0x92,
+		I2s = 0x93,
 		If_icmpeq = 0x9f,
 		If_icmpne = 0xa0,
 		If_icmplt = 0xa1,
 		If_icmpge = 0xa2,
 		If_icmpgt = 0xa3,
 		If_icmple = 0xa4,
+		If_acmpeq = 0xa5,
+		If_acmpne = 0xa6,
 		Goto = 0xa7,
+		Jsr = 0xa8,
+		Ret = 0xa9,
+		Tableswitch = 0xaa,
+		Lookupswitch = 0xab,
 		Ireturn = 0xac,
+		Lreturn = 0xad,
+		Freturn = 0xae,
+		Dreturn = 0xaf,
+		Areturn = 0xb0,
 		r#Return = 0xb1,
 		GetStatic = 0xb2,
 		PutStatic = 0xb3,
@@ -88,12 +181,28 @@ enum_from_primitive! {
 		Invokevirtual = 0xb6,
 		Invokespecial = 0xb7,
 		Invokestatic = 0xb8,
+		Invokeinterface = 0xb9,
+		Invokedynamic = 0xba,
 		New = 0xbb,
 		NewArray = 0xbc,
 		ANewArray = 0xbd,
 		ArrayLength = 0xbe,
-		Fcmplt = 0x95,
-		Fcmpgt = 0x96,
+		Athrow = 0xbf,
+		Checkcast = 0xc0,
+		Instanceof = 0xc1,
+		Monitorenter = 0xc2,
+		Monitorexit = 0xc3,
+		Wide = 0xc4,
+		Multianewarray = 0xc5,
+		Ifnull = 0xc6,
+		Ifnonnull = 0xc7,
+		Goto_w = 0xc8,
+		Jsr_w = 0xc9,
+		Lcmp = 0x94,
+		Fcmpl = 0x95,
+		Fcmpg = 0x96,
+		Dcmpl = 0x97,
+		Dcmpg = 0x98,
 		Ifeq = 0x99,
 		Ifne = 0x9a,
 		Iflt = 0x9b,
@@ -102,3 +211,330 @@ enum_from_primitive! {
 		Ifle = 0x9e,
 	}
 }
+
+/*
+ * The mnemonic of the instruction with opcode _opcode_ and how many
+ * operand bytes follow it. How many operand bytes tableswitch,
+ * lookupswitch (which are padded) and wide have depends on where
+ * they are and on their operands; their count here is 0. Opcodes
+ * that are not instructions are "unknown".
+ */
+pub fn opcode_info(opcode: u8) -> (&'static str, usize) {
+	match opcode {
+		0x00 => ("nop", 0),
+		0x01 => ("aconst_null", 0),
+		0x02 => ("iconst_m1", 0),
+		0x03 => ("iconst_0", 0),
+		0x04 => ("iconst_1", 0),
+		0x05 => ("iconst_2", 0),
+		0x06 => ("iconst_3", 0),
+		0x07 => ("iconst_4", 0),
+		0x08 => ("iconst_5", 0),
+		0x09 => ("lconst_0", 0),
+		0x0a => ("lconst_1", 0),
+		0x0b => ("fconst_0", 0),
+		0x0c => ("fconst_1", 0),
+		0x0d => ("fconst_2", 0),
+		0x0e => ("dconst_0", 0),
+		0x0f => ("dconst_1", 0),
+		0x10 => ("bipush", 1),
+		0x11 => ("sipush", 2),
+		0x12 => ("ldc", 1),
+		0x13 => ("ldc_w", 2),
+		0x14 => ("ldc2_w", 2),
+		0x15 => ("iload", 1),
+		0x16 => ("lload", 1),
+		0x17 => ("fload", 1),
+		0x18 => ("dload", 1),
+		0x19 => ("aload", 1),
+		0x1a => ("iload_0", 0),
+		0x1b => ("iload_1", 0),
+		0x1c => ("iload_2", 0),
+		0x1d => ("iload_3", 0),
+		0x1e => ("lload_0", 0),
+		0x1f => ("lload_1", 0),
+		0x20 => ("lload_2", 0),
+		0x21 => ("lload_3", 0),
+		0x22 => ("fload_0", 0),
+		0x23 => ("fload_1", 0),
+		0x24 => ("fload_2", 0),
+		0x25 => ("fload_3", 0),
+		0x26 => ("dload_0", 0),
+		0x27 => ("dload_1", 0),
+		0x28 => ("dload_2", 0),
+		0x29 => ("dload_3", 0),
+		0x2a => ("aload_0", 0),
+		0x2b => ("aload_1", 0),
+		0x2c => ("aload_2", 0),
+		0x2d => ("aload_3", 0),
+		0x2e => ("iaload", 0),
+		0x2f => ("laload", 0),
+		0x30 => ("faload", 0),
+		0x31 => ("daload", 0),
+		0x32 => ("aaload", 0),
+		0x33 => ("baload", 0),
+		0x34 => ("caload", 0),
+		0x35 => ("saload", 0),
+		0x36 => ("istore", 1),
+		0x37 => ("lstore", 1),
+		0x38 => ("fstore", 1),
+		0x39 => ("dstore", 1),
+		0x3a => ("astore", 1),
+		0x3b => ("istore_0", 0),
+		0x3c => ("istore_1", 0),
+		0x3d => ("istore_2", 0),
+		0x3e => ("istore_3", 0),
+		0x3f => ("lstore_0", 0),
+		0x40 => ("lstore_1", 0),
+		0x41 => ("lstore_2", 0),
+		0x42 => ("lstore_3", 0),
+		0x43 => ("fstore_0", 0),
+		0x44 => ("fstore_1", 0),
+		0x45 => ("fstore_2", 0),
+		0x46 => ("fstore_3", 0),
+		0x47 => ("dstore_0", 0),
+		0x48 => ("dstore_1", 0),
+		0x49 => ("dstore_2", 0),
+		0x4a => ("dstore_3", 0),
+		0x4b => ("astore_0", 0),
+		0x4c => ("astore_1", 0),
+		0x4d => ("astore_2", 0),
+		0x4e => ("astore_3", 0),
+		0x4f => ("iastore", 0),
+		0x50 => ("lastore", 0),
+		0x51 => ("fastore", 0),
+		0x52 => ("dastore", 0),
+		0x53 => ("aastore", 0),
+		0x54 => ("bastore", 0),
+		0x55 => ("castore", 0),
+		0x56 => ("sastore", 0),
+		0x57 => ("pop", 0),
+		0x58 => ("pop2", 0),
+		0x59 => ("dup", 0),
+		0x5a => ("dup_x1", 0),
+		0x5b => ("dup_x2", 0),
+		0x5c => ("dup2", 0),
+		0x5d => ("dup2_x1", 0),
+		0x5e => ("dup2_x2", 0),
+		0x5f => ("swap", 0),
+		0x60 => ("iadd", 0),
+		0x61 => ("ladd", 0),
+		0x62 => ("fadd", 0),
+		0x63 => ("dadd", 0),
+		0x64 => ("isub", 0),
+		0x65 => ("lsub", 0),
+		0x66 => ("fsub", 0),
+		0x67 => ("dsub", 0),
+		0x68 => ("imul", 0),
+		0x69 => ("lmul", 0),
+		0x6a => ("fmul", 0),
+		0x6b => ("dmul", 0),
+		0x6c => ("idiv", 0),
+		0x6d => ("ldiv", 0),
+		0x6e => ("fdiv", 0),
+		0x6f => ("ddiv", 0),
+		0x70 => ("irem", 0),
+		0x71 => ("lrem", 0),
+		0x72 => ("frem", 0),
+		0x73 => ("drem", 0),
+		0x74 => ("ineg", 0),
+		0x75 => ("lneg", 0),
+		0x76 => ("fneg", 0),
+		0x77 => ("dneg", 0),
+		0x78 => ("ishl", 0),
+		0x79 => ("lshl", 0),
+		0x7a => ("ishr", 0),
+		0x7b => ("lshr", 0),
+		0x7c => ("iushr", 0),
+		0x7d => ("lushr", 0),
+		0x7e => ("iand", 0),
+		0x7f => ("land", 0),
+		0x80 => ("ior", 0),
+		0x81 => ("lor", 0),
+		0x82 => ("ixor", 0),
+		0x83 => ("lxor", 0),
+		0x84 => ("iinc", 2),
+		0x85 => ("i2l", 0),
+		0x86 => ("i2f", 0),
+		0x87 => ("i2d", 0),
+		0x88 => ("l2i", 0),
+		0x89 => ("l2f", 0),
+		0x8a => ("l2d", 0),
+		0x8b => ("f2i", 0),
+		0x8c => ("f2l", 0),
+		0x8d => ("f2d", 0),
+		0x8e => ("d2i", 0),
+		0x8f => ("d2l", 0),
+		0x90 => ("d2f", 0),
+		0x91 => ("i2b", 0),
+		0x92 => ("i2c", 0),
+		0x93 => ("i2s", 0),
+		0x94 => ("lcmp", 0),
+		0x95 => ("fcmpl", 0),
+		0x96 => ("fcmpg", 0),
+		0x97 => ("dcmpl", 0),
+		0x98 => ("dcmpg", 0),
+		0x99 => ("ifeq", 2),
+		0x9a => ("ifne", 2),
+		0x9b => ("iflt", 2),
+		0x9c => ("ifge", 2),
+		0x9d => ("ifgt", 2),
+		0x9e => ("ifle", 2),
+		0x9f => ("if_icmpeq", 2),
+		0xa0 => ("if_icmpne", 2),
+		0xa1 => ("if_icmplt", 2),
+		0xa2 => ("if_icmpge", 2),
+		0xa3 => ("if_icmpgt", 2),
+		0xa4 => ("if_icmple", 2),
+		0xa5 => ("if_acmpeq", 2),
+		0xa6 => ("if_acmpne", 2),
+		0xa7 => ("goto", 2),
+		0xa8 => ("jsr", 2),
+		0xa9 => ("ret", 1),
+		0xaa => ("tableswitch", 0),
+		0xab => ("lookupswitch", 0),
+		0xac => ("ireturn", 0),
+		0xad => ("lreturn", 0),
+		0xae => ("freturn", 0),
+		0xaf => ("dreturn", 0),
+		0xb0 => ("areturn", 0),
+		0xb1 => ("return", 0),
+		0xb2 => ("getstatic", 2),
+		0xb3 => ("putstatic", 2),
+		0xb4 => ("getfield", 2),
+		0xb5 => ("putfield", 2),
+		0xb6 => ("invokevirtual", 2),
+		0xb7 => ("invokespecial", 2),
+		0xb8 => ("invokestatic", 2),
+		0xb9 => ("invokeinterface", 4),
+		0xba => ("invokedynamic", 4),
+		0xbb => ("new", 2),
+		0xbc => ("newarray", 1),
+		0xbd => ("anewarray", 2),
+		0xbe => ("arraylength", 0),
+		0xbf => ("athrow", 0),
+		0xc0 => ("checkcast", 2),
+		0xc1 => ("instanceof", 2),
+		0xc2 => ("monitorenter", 0),
+		0xc3 => ("monitorexit", 0),
+		0xc4 => ("wide", 0),
+		0xc5 => ("multianewarray", 3),
+		0xc6 => ("ifnull", 2),
+		0xc7 => ("ifnonnull", 2),
+		0xc8 => ("goto_w", 4),
+		0xc9 => ("jsr_w", 4),
+		0xca => ("breakpoint", 0),
+		0xfe => ("impdep1", 0),
+		0xff => ("impdep2", 0),
+		_ => ("unknown", 0),
+	}
+}
+
+/*
+ * How many bytes the instruction at _pc_ in _code_ takes up, opcode
+ * and operands, or None if it does not fit in _code_.
+ */
+pub fn instruction_length(code: &[u8], pc: usize) -> Option<usize> {
+	let read_u32 = |offset: usize| -> Option<u32> {
+		let bytes = code.get(offset..offset + 4)?;
+		Some(
+			(bytes[0] as u32) << 24
+				| (bytes[1] as u32) << 16
+				| (bytes[2] as u32) << 8
+				| (bytes[3] as u32),
+		)
+	};
+	let opcode = *code.get(pc)?;
+	/*
+	 * The operands of the switches start at the next multiple of
+	 * four (from the start of the code).
+	 */
+	let padding = 3 - (pc % 4);
+	let length = match opcode {
+		0xaa => {
+			let default_offset = pc + 1 + padding;
+			let low = read_u32(default_offset + 4)? as i32;
+			let high = read_u32(default_offset + 8)? as i32;
+			if high < low {
+				return None;
+			}
+			1 + padding + 12 + 4 * ((high as i64 - low as i64 + 1) as usize)
+		}
+		0xab => {
+			let default_offset = pc + 1 + padding;
+			let npairs = read_u32(default_offset + 4)? as i32;
+			if npairs < 0 {
+				return None;
+			}
+			1 + padding + 8 + 8 * (npairs as usize)
+		}
+		/*
+		 * wide iinc has a two-byte index and a two-byte constant; the
+		 * other instructions that wide modifies have just the index.
+		 */
+		0xc4 => match *code.get(pc + 1)? {
+			0x84 => 6,
+			_ => 4,
+		},
+		_ => 1 + opcode_info(opcode).1,
+	};
+	if pc + length > code.len() {
+		return None;
+	}
+	Some(length)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decodes_mnemonics_and_operand_counts() {
+		assert_eq!(opcode_info(0x00), ("nop", 0));
+		assert_eq!(opcode_info(0x10), ("bipush", 1));
+		assert_eq!(opcode_info(0x11), ("sipush", 2));
+		assert_eq!(opcode_info(0x12), ("ldc", 1));
+		assert_eq!(opcode_info(0x60), ("iadd", 0));
+		assert_eq!(opcode_info(0x84), ("iinc", 2));
+		assert_eq!(opcode_info(0xa7), ("goto", 2));
+		assert_eq!(opcode_info(0xaa), ("tableswitch", 0));
+		assert_eq!(opcode_info(0xb9), ("invokeinterface", 4));
+		assert_eq!(opcode_info(0xc8), ("goto_w", 4));
+		assert_eq!(opcode_info(0xcb), ("unknown", 0));
+	}
+
+	#[test]
+	fn measures_variable_length_instructions() {
+		assert_eq!(instruction_length(&[0x60], 0), Some(1));
+		assert_eq!(instruction_length(&[0x11, 0, 1], 0), Some(3));
+		assert_eq!(instruction_length(&[0x11, 0], 0), None);
+		assert_eq!(instruction_length(&[], 0), None);
+
+		/*
+		 * wide iload 256 and wide iinc 256 by 1
+		 */
+		assert_eq!(instruction_length(&[0xc4, 0x15, 1, 0], 0), Some(4));
+		assert_eq!(instruction_length(&[0xc4, 0x84, 1, 0, 0, 1], 0), Some(6));
+
+		/*
+		 * A tableswitch at pc 1 with two bytes of padding, default,
+		 * low 0, high 1 and two offsets.
+		 */
+		let mut tableswitch = vec![0x00, 0xaa, 0, 0];
+		for value in &[0u32, 0, 1, 20, 24] {
+			tableswitch.extend_from_slice(&value.to_be_bytes());
+		}
+		assert_eq!(instruction_length(&tableswitch, 1), Some(23));
+		assert_eq!(instruction_length(&tableswitch[..23], 1), None);
+
+		/*
+		 * A lookupswitch at pc 0 with three bytes of padding, default
+		 * and one pair.
+		 */
+		let mut lookupswitch = vec![0xab, 0, 0, 0];
+		for value in &[0u32, 1, 7, 20] {
+			lookupswitch.extend_from_slice(&value.to_be_bytes());
+		}
+		assert_eq!(instruction_length(&lookupswitch, 0), Some(20));
+	}
+}
@@ -21,8 +21,7 @@
  */
 use jvm::array::JvmArray;
 use jvm::class::Class;
-use jvm::error::FatalError;
-use jvm::error::FatalErrorType;
+use jvm::methodarea::MethodArea;
 use jvm::object::JvmObject;
 use std::fmt;
 use std::rc::Rc;
@@ -39,6 +38,11 @@ pub enum JvmPrimitiveType {
 	LongInteger,
 	Short,
 	Boolean,
+	/*
+	 * The address of the instruction after a jsr (or jsr_w); ret
+	 * jumps back to it.
+	 */
+	ReturnAddress,
 	Invalid,
 }
 
@@ -54,6 +58,7 @@ impl fmt::Display for JvmPrimitiveType {
 			JvmPrimitiveType::LongInteger => write!(f, "LongInteger"),
 			JvmPrimitiveType::Short => write!(f, "Short"),
 			JvmPrimitiveType::Boolean => write!(f, "Boolean"),
+			JvmPrimitiveType::ReturnAddress => write!(f, "ReturnAddress"),
 			JvmPrimitiveType::Invalid => write!(f, "Invalid"),
 		}
 	}
@@ -64,7 +69,7 @@ pub enum JvmReferenceTargetType {
 	Null,
 	Array(Arc<Mutex<JvmArray>>),
 	Object(Arc<Mutex<JvmObject>>),
-	Class(Class),
+	Class(Rc<Class>),
 }
 
 #[derive(Clone)]
@@ -81,16 +86,158 @@ pub enum JvmValue {
 	Reference(JvmReferenceType, JvmReferenceTargetType, u16),
 }
 
+impl JvmValue {
+	/*
+	 * Longs and doubles are category 2 values: they occupy two
+	 * slots on the operand stack and in the local variable array.
+	 * Everything else occupies one.
+	 */
+	pub fn size(&self) -> usize {
+		match self {
+			JvmValue::Primitive(JvmPrimitiveType::LongInteger, _, _, _)
+			| JvmValue::Primitive(JvmPrimitiveType::Double, _, _, _) => 2,
+			_ => 1,
+		}
+	}
+
+	/*
+	 * A reference is null when it has no target, regardless of
+	 * the type of the reference.
+	 */
+	pub fn is_null(&self) -> bool {
+		if let JvmValue::Reference(_, JvmReferenceTargetType::Null, _) = self {
+			true
+		} else {
+			false
+		}
+	}
+
+	/*
+	 * The pc that a returnAddress holds; None for any other value.
+	 */
+	pub fn return_address(&self) -> Option<usize> {
+		if let JvmValue::Primitive(JvmPrimitiveType::ReturnAddress, _, pc, _) = self {
+			Some(*pc as usize)
+		} else {
+			None
+		}
+	}
+
+	/*
+	 * The identity of the object or array that the value refers to
+	 * (see JvmReferenceTargetType::identity). None for anything else.
+	 */
+	pub fn identity(&self) -> Option<u64> {
+		match self {
+			JvmValue::Reference(_, target, _) => target.identity(),
+			_ => None,
+		}
+	}
+
+	/*
+	 * Booleans, bytes, chars and shorts are ints on the operand
+	 * stack (their computational type is int). Every other value
+	 * is its own computational type.
+	 */
+	pub fn to_computational_type(&self) -> JvmValue {
+		match self {
+			JvmValue::Primitive(JvmPrimitiveType::Boolean, _, value, access)
+			| JvmValue::Primitive(JvmPrimitiveType::Byte, _, value, access)
+			| JvmValue::Primitive(JvmPrimitiveType::Char, _, value, access)
+			| JvmValue::Primitive(JvmPrimitiveType::Short, _, value, access) => {
+				JvmValue::Primitive(JvmPrimitiveType::Integer, 0, *value, *access)
+			}
+			_ => self.clone(),
+		}
+	}
+}
+
+/*
+ * The null reference that aconst_null pushes. It has no type of its
+ * own (jvms 2.4), so it can be stored into a local of any reference
+ * type or returned by areturn from any method that returns one.
+ */
 pub fn create_null_value() -> JvmValue {
 	JvmValue::Reference(JvmReferenceType::Null, JvmReferenceTargetType::Null, 0)
 }
 
+/*
+ * The returnAddress that jsr pushes. It is not an int: only astore
+ * (to keep it in a local) and ret (to jump to it) take one.
+ */
+pub fn create_return_address_value(pc: usize) -> JvmValue {
+	JvmValue::Primitive(JvmPrimitiveType::ReturnAddress, 0, pc as u32, 0)
+}
+
 #[derive(Clone)]
 pub enum JvmType {
 	Primitive(JvmPrimitiveType),
 	Reference(JvmReferenceType),
 }
 
+impl JvmType {
+	/*
+	 * Whether a value of this type can be used where a value of type
+	 * _to_ is expected: primitive types have to be the same; reference
+	 * types follow the class hierarchy and array components.
+	 *
+	 * Must call this with the methodarea locked.
+	 */
+	pub fn is_assignable_to(&self, to: &JvmType, methodarea: &mut MethodArea) -> bool {
+		match (self, to) {
+			(JvmType::Primitive(from), JvmType::Primitive(to)) => from == to,
+			(
+				JvmType::Reference(JvmReferenceType::Class(from)),
+				JvmType::Reference(JvmReferenceType::Class(to)),
+			) => methodarea.is_subtype_of(from, to),
+			(
+				JvmType::Reference(JvmReferenceType::Array(from, _)),
+				JvmType::Reference(JvmReferenceType::Array(to, _)),
+			) => match (&**from, &**to) {
+				(JvmType::Primitive(from), JvmType::Primitive(to)) => from == to,
+				(JvmType::Reference(_), JvmType::Reference(_)) => {
+					from.is_assignable_to(to, methodarea)
+				}
+				_ => false,
+			},
+			(
+				JvmType::Reference(JvmReferenceType::Array(_, _)),
+				JvmType::Reference(JvmReferenceType::Class(to)),
+			) => {
+				to == "java/lang/Object"
+					|| to == "java/lang/Cloneable"
+					|| to == "java/io/Serializable"
+			}
+			_ => false,
+		}
+	}
+
+	/*
+	 * The field descriptor (jvms 4.3.2) of the type: I, [J or
+	 * Ljava/lang/String;, say. The name of an array class is the
+	 * descriptor of its type.
+	 */
+	pub fn descriptor(&self) -> String {
+		match self {
+			JvmType::Primitive(JvmPrimitiveType::Byte) => format!("B"),
+			JvmType::Primitive(JvmPrimitiveType::Char) => format!("C"),
+			JvmType::Primitive(JvmPrimitiveType::Double) => format!("D"),
+			JvmType::Primitive(JvmPrimitiveType::Float) => format!("F"),
+			JvmType::Primitive(JvmPrimitiveType::Integer) => format!("I"),
+			JvmType::Primitive(JvmPrimitiveType::LongInteger) => format!("J"),
+			JvmType::Primitive(JvmPrimitiveType::Short) => format!("S"),
+			JvmType::Primitive(JvmPrimitiveType::Boolean) => format!("Z"),
+			JvmType::Primitive(_) => format!("V"),
+			JvmType::Reference(JvmReferenceType::Array(component, _)) => {
+				format!("[{}", component.descriptor())
+			}
+			JvmType::Reference(JvmReferenceType::Class(name))
+			| JvmType::Reference(JvmReferenceType::Interface(name)) => format!("L{};", name),
+			JvmType::Reference(JvmReferenceType::Null) => format!("Ljava/lang/Object;"),
+		}
+	}
+}
+
 impl Default for JvmType {
 	fn default() -> Self {
 		JvmType::Primitive(JvmPrimitiveType::Invalid)
@@ -198,6 +345,28 @@ impl PartialEq for JvmValue {
 	}
 }
 
+impl JvmReferenceTargetType {
+	/*
+	 * What tells the object or array apart from every other (see
+	 * heap::allocate_identity). None for null (and for classes). The
+	 * identity never changes, so it can be read even from an object
+	 * whose lock is poisoned.
+	 */
+	pub fn identity(&self) -> Option<u64> {
+		match self {
+			JvmReferenceTargetType::Object(object) => match object.lock() {
+				Ok(object) => Some(object.identity()),
+				Err(poisoned) => Some(poisoned.into_inner().identity()),
+			},
+			JvmReferenceTargetType::Array(array) => match array.lock() {
+				Ok(array) => Some(array.identity()),
+				Err(poisoned) => Some(poisoned.into_inner().identity()),
+			},
+			_ => None,
+		}
+	}
+}
+
 impl PartialEq for JvmReferenceTargetType {
 	fn eq(&self, other: &Self) -> bool {
 		match self {
@@ -227,63 +396,367 @@ impl PartialEq for JvmReferenceTargetType {
 	}
 }
 
-impl From<&[u8]> for JvmType {
-	fn from(from: &[u8]) -> Self {
-		let mut result = JvmType::Primitive(JvmPrimitiveType::Invalid);
-		match from[0] as char {
-			'V' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Void);
-			}
-			'B' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Byte);
-			}
-			'C' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Char);
-			}
-			'F' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Float);
-			}
-			'D' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Double);
-			}
-			'I' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Integer);
-			}
-			'J' => {
-				result = JvmType::Primitive(JvmPrimitiveType::LongInteger);
-			}
+/*
+ * What a field descriptor (jvms 4.3.2) describes: the type of its
+ * elements (or of the field itself, when it is not an array), how many
+ * array dimensions are around them and, when the elements are objects,
+ * the name of their class.
+ */
+#[derive(Clone)]
+pub struct FieldDescriptor {
+	pub base_type: JvmType,
+	pub dimensions: usize,
+	pub class_name: Option<String>,
+}
+
+impl FieldDescriptor {
+	/*
+	 * Parse the descriptor at the start of _descriptor_. The result
+	 * is the descriptor and how many bytes of _descriptor_ it took up
+	 * or None when it is malformed. V is not a field type, but it is
+	 * accepted (without dimensions) so that return types can be
+	 * parsed the same way.
+	 */
+	pub fn parse(descriptor: &[u8]) -> Option<(FieldDescriptor, usize)> {
+		let mut index = 0;
+		while index < descriptor.len() && descriptor[index] == '[' as u8 {
+			index += 1;
+		}
+		let dimensions = index;
+		/*
+		 * An array type has at most 255 dimensions.
+		 */
+		if dimensions > 255 || index >= descriptor.len() {
+			return None;
+		}
+
+		let mut class_name: Option<String> = None;
+		let base_type = match descriptor[index] as char {
+			'B' => JvmType::Primitive(JvmPrimitiveType::Byte),
+			'C' => JvmType::Primitive(JvmPrimitiveType::Char),
+			'D' => JvmType::Primitive(JvmPrimitiveType::Double),
+			'F' => JvmType::Primitive(JvmPrimitiveType::Float),
+			'I' => JvmType::Primitive(JvmPrimitiveType::Integer),
+			'J' => JvmType::Primitive(JvmPrimitiveType::LongInteger),
+			'S' => JvmType::Primitive(JvmPrimitiveType::Short),
+			'Z' => JvmType::Primitive(JvmPrimitiveType::Boolean),
+			'V' if dimensions == 0 => JvmType::Primitive(JvmPrimitiveType::Void),
 			'L' => {
 				/*
-				 * Walk through the end of the string
-				 * and make that our class name.
+				 * Lsome/class/name; names a class.
 				 */
-				let mut index = 1;
-				while from[index] != ';' as u8 {
-					index = index + 1;
-				}
-				if let Ok(classname) = std::str::from_utf8(&from[1..index]) {
-					result = JvmType::Reference(JvmReferenceType::Class(classname.to_string()))
-				} else {
-					FatalError::new(FatalErrorType::InvalidFieldType('L')).call();
+				let start = index + 1;
+				let end = match descriptor[start..].iter().position(|c| *c == ';' as u8) {
+					Some(length) if length > 0 => start + length,
+					_ => return None,
+				};
+				match std::str::from_utf8(&descriptor[start..end]) {
+					Ok(name) => class_name = Some(name.to_string()),
+					_ => return None,
 				}
+				index = end;
+				JvmType::Reference(JvmReferenceType::Class(class_name.clone().unwrap()))
 			}
-			'S' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Short);
-			}
-			'Z' => {
-				result = JvmType::Primitive(JvmPrimitiveType::Boolean);
-			}
-			'[' => {
-				result = JvmType::Reference(JvmReferenceType::Array(
-					Rc::<JvmType>::new(JvmType::from(&from[1..])),
-					0,
-				));
-			}
+			_ => return None,
+		};
 
-			_ => {
-				FatalError::new(FatalErrorType::InvalidFieldType(from[0] as char)).call();
+		Some((
+			FieldDescriptor {
+				base_type,
+				dimensions,
+				class_name,
+			},
+			index + 1,
+		))
+	}
+
+	/*
+	 * The type that the descriptor describes, with one array type
+	 * around the base type for each dimension.
+	 */
+	pub fn to_type(&self) -> JvmType {
+		let mut r#type = self.base_type.clone();
+		for _ in 0..self.dimensions {
+			r#type = JvmType::Reference(JvmReferenceType::Array(Rc::new(r#type), 0));
+		}
+		r#type
+	}
+}
+
+/*
+ * What a method descriptor (jvms 4.3.3) describes: the types of the
+ * parameters, in order, and the return type.
+ */
+#[derive(Clone)]
+pub struct MethodDescriptor {
+	pub parameter_types: Vec<JvmType>,
+	pub return_type: JvmType,
+}
+
+impl MethodDescriptor {
+	/*
+	 * Returns None when _descriptor_ is malformed.
+	 */
+	pub fn parse(descriptor: &[u8]) -> Option<MethodDescriptor> {
+		if descriptor.first() != Some(&('(' as u8)) {
+			return None;
+		}
+		let mut index = 1;
+		let mut parameter_types: Vec<JvmType> = vec![];
+		while index < descriptor.len() && descriptor[index] != ')' as u8 {
+			let (parameter, length) = FieldDescriptor::parse(&descriptor[index..])?;
+			if let JvmType::Primitive(JvmPrimitiveType::Void) = parameter.base_type {
+				return None;
 			}
+			parameter_types.push(parameter.to_type());
+			index += length;
+		}
+		if index >= descriptor.len() {
+			return None;
+		}
+		index += 1;
+
+		let (return_descriptor, length) = FieldDescriptor::parse(&descriptor[index..])?;
+		if index + length != descriptor.len() {
+			return None;
+		}
+
+		Some(MethodDescriptor {
+			parameter_types,
+			return_type: return_descriptor.to_type(),
+		})
+	}
+
+	/*
+	 * How many locals (or operand stack slots) the parameters take
+	 * up: a long or a double takes two.
+	 */
+	pub fn parameter_slots(&self) -> usize {
+		self.parameter_types
+			.iter()
+			.map(|parameter_type| match parameter_type {
+				JvmType::Primitive(JvmPrimitiveType::LongInteger)
+				| JvmType::Primitive(JvmPrimitiveType::Double) => 2,
+				_ => 1,
+			})
+			.sum()
+	}
+}
+
+impl From<&[u8]> for JvmType {
+	fn from(from: &[u8]) -> Self {
+		match FieldDescriptor::parse(from) {
+			Some((descriptor, _)) => descriptor.to_type(),
+			None => JvmType::Primitive(JvmPrimitiveType::Invalid),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::debug::DebugLevel;
+	use jvm::environment::Environment;
+	use std::panic;
+
+	fn array_reference(array: &Arc<Mutex<JvmArray>>) -> JvmReferenceTargetType {
+		JvmReferenceTargetType::Array(Arc::clone(array))
+	}
+
+	fn object_reference(object: &Arc<Mutex<JvmObject>>) -> JvmReferenceTargetType {
+		JvmReferenceTargetType::Object(Arc::clone(object))
+	}
+
+	fn new_object() -> Arc<Mutex<JvmObject>> {
+		let class = Rc::new(Class::synthetic_object().unwrap());
+		Arc::new(Mutex::new(JvmObject::new(class, DebugLevel::Error)))
+	}
+
+	fn poison<T>(mutex: &Arc<Mutex<T>>) {
+		let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+			let _guard = mutex.lock().unwrap();
+			panic!("poisoning the lock");
+		}));
+		assert!(mutex.is_poisoned());
+	}
+
+	#[test]
+	fn arrays_have_distinct_identities() {
+		let first = Arc::new(Mutex::new(JvmArray::new(1)));
+		let second = Arc::new(Mutex::new(JvmArray::new(1)));
+		assert!(array_reference(&first).identity().is_some());
+		assert_ne!(
+			array_reference(&first).identity(),
+			array_reference(&second).identity()
+		);
+		assert_eq!(
+			array_reference(&first).identity(),
+			array_reference(&first).identity()
+		);
+	}
+
+	#[test]
+	fn references_are_equal_only_to_the_same_target() {
+		let first = Arc::new(Mutex::new(JvmArray::new(1)));
+		let second = Arc::new(Mutex::new(JvmArray::new(1)));
+		assert!(array_reference(&first) == array_reference(&first));
+		assert!(array_reference(&first) != array_reference(&second));
+
+		let first = new_object();
+		let second = new_object();
+		assert!(object_reference(&first) == object_reference(&first));
+		assert!(object_reference(&first) != object_reference(&second));
+	}
+
+	#[test]
+	fn poisoned_references_keep_their_identity() {
+		let first = new_object();
+		let second = new_object();
+		poison(&first);
+		poison(&second);
+		assert!(object_reference(&first) != object_reference(&second));
+		assert!(object_reference(&first).identity().is_some());
+		assert_ne!(
+			object_reference(&first).identity(),
+			object_reference(&second).identity()
+		);
+
+		let array = Arc::new(Mutex::new(JvmArray::new(1)));
+		poison(&array);
+		assert!(array_reference(&array).identity().is_some());
+	}
+
+	fn class_type(class_name: &str) -> JvmType {
+		JvmType::Reference(JvmReferenceType::Class(class_name.to_string()))
+	}
+
+	fn array_type(component: JvmType) -> JvmType {
+		JvmType::Reference(JvmReferenceType::Array(Rc::new(component), 0))
+	}
+
+	#[test]
+	fn assignability_follows_hierarchies_and_array_components() {
+		let environment = Environment::new(&[], &[], DebugLevel::Error);
+		let mut methodarea = MethodArea::new(DebugLevel::Error, environment);
+		let mut base = ClassFileBuilder::new("Base", Some("java/lang/Object"));
+		methodarea.load_class_from_bytes(base.bytes()).unwrap();
+		let mut derived = ClassFileBuilder::new("Derived", Some("Base"));
+		methodarea.load_class_from_bytes(derived.bytes()).unwrap();
+
+		let int = JvmType::Primitive(JvmPrimitiveType::Integer);
+		let long = JvmType::Primitive(JvmPrimitiveType::LongInteger);
+		assert!(int.is_assignable_to(&int, &mut methodarea));
+		assert!(!int.is_assignable_to(&long, &mut methodarea));
+
+		assert!(class_type("Derived").is_assignable_to(&class_type("Base"), &mut methodarea));
+		assert!(class_type("Derived")
+			.is_assignable_to(&class_type("java/lang/Object"), &mut methodarea));
+		assert!(!class_type("Base").is_assignable_to(&class_type("Derived"), &mut methodarea));
+
+		assert!(array_type(class_type("Derived"))
+			.is_assignable_to(&array_type(class_type("Base")), &mut methodarea));
+		assert!(!array_type(class_type("Base"))
+			.is_assignable_to(&array_type(class_type("Derived")), &mut methodarea));
+		assert!(!array_type(int.clone())
+			.is_assignable_to(&array_type(class_type("java/lang/Object")), &mut methodarea));
+		assert!(array_type(int.clone())
+			.is_assignable_to(&class_type("java/lang/Object"), &mut methodarea));
+		assert!(!array_type(int).is_assignable_to(&class_type("Base"), &mut methodarea));
+	}
+
+	/*
+	 * The base type, dimensions, class name and length of _descriptor_.
+	 */
+	fn field_descriptor(descriptor: &str) -> (JvmType, usize, Option<String>, usize) {
+		let (parsed, length) = FieldDescriptor::parse(descriptor.as_bytes()).unwrap();
+		(
+			parsed.base_type,
+			parsed.dimensions,
+			parsed.class_name,
+			length,
+		)
+	}
+
+	#[test]
+	fn parses_field_descriptors() {
+		assert!(matches!(
+			field_descriptor("I"),
+			(JvmType::Primitive(JvmPrimitiveType::Integer), 0, None, 1)
+		));
+		assert!(matches!(
+			field_descriptor("[I"),
+			(JvmType::Primitive(JvmPrimitiveType::Integer), 1, None, 2)
+		));
+		assert!(matches!(
+			field_descriptor("Ljava/lang/Object;"),
+			(JvmType::Reference(JvmReferenceType::Class(_)), 0, Some(ref name), 18)
+				if name == "java/lang/Object"
+		));
+		assert!(matches!(
+			field_descriptor("[[D"),
+			(JvmType::Primitive(JvmPrimitiveType::Double), 2, None, 3)
+		));
+		assert!(matches!(
+			field_descriptor("[Ljava/lang/String;I"),
+			(JvmType::Reference(JvmReferenceType::Class(_)), 1, Some(ref name), 19)
+				if name == "java/lang/String"
+		));
+
+		for malformed in ["", "[", "Q", "L;", "Ljava/lang/Object", "[V"].iter() {
+			assert!(FieldDescriptor::parse(malformed.as_bytes()).is_none());
+		}
+	}
+
+	#[test]
+	fn field_descriptors_nest_their_arrays() {
+		match JvmType::from(&b"[[Ljava/lang/String;"[..]) {
+			JvmType::Reference(JvmReferenceType::Array(outer, _)) => match &*outer {
+				JvmType::Reference(JvmReferenceType::Array(inner, _)) => assert!(matches!(
+					&**inner,
+					JvmType::Reference(JvmReferenceType::Class(name)) if name == "java/lang/String"
+				)),
+				_ => panic!("[[Ljava/lang/String; has one dimension"),
+			},
+			_ => panic!("[[Ljava/lang/String; is not an array"),
+		}
+		assert!(matches!(
+			JvmType::from(&b"X"[..]),
+			JvmType::Primitive(JvmPrimitiveType::Invalid)
+		));
+	}
+
+	#[test]
+	fn parses_method_descriptors() {
+		let no_arguments = MethodDescriptor::parse(b"()V").unwrap();
+		assert!(no_arguments.parameter_types.is_empty());
+		assert_eq!(no_arguments.parameter_slots(), 0);
+		assert!(matches!(
+			no_arguments.return_type,
+			JvmType::Primitive(JvmPrimitiveType::Void)
+		));
+
+		let mixed = MethodDescriptor::parse(b"(IJLjava/lang/String;)V").unwrap();
+		assert_eq!(mixed.parameter_types.len(), 3);
+		assert!(matches!(
+			mixed.parameter_types[1],
+			JvmType::Primitive(JvmPrimitiveType::LongInteger)
+		));
+		assert!(matches!(
+			&mixed.parameter_types[2],
+			JvmType::Reference(JvmReferenceType::Class(name)) if name == "java/lang/String"
+		));
+		assert_eq!(mixed.parameter_slots(), 4);
+
+		let returns_array = MethodDescriptor::parse(b"(D)[[I").unwrap();
+		assert_eq!(returns_array.parameter_slots(), 2);
+		assert!(matches!(
+			returns_array.return_type,
+			JvmType::Reference(JvmReferenceType::Array(_, _))
+		));
+
+		for malformed in ["", "V", "(V)V", "(I", "(I)", "()VV", "(Ljava/lang/String)V"].iter() {
+			assert!(MethodDescriptor::parse(malformed.as_bytes()).is_none());
 		}
-		result
 	}
 }
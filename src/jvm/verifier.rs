@@ -0,0 +1,401 @@
+/*
+ * FILE: XXXXX
+ * DESCRIPTION:
+ *
+ * Copyright (c) 2019, Will Hawkins
+ *
+ * This file is part of Rust-JVM.
+ *
+ * Rust-JVM is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Rust-JVM is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Rust-JVM.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use jvm::constant::Constant;
+use jvm::constantpool::ConstantPool;
+use jvm::method::Method;
+use jvm::opcodes::instruction_length;
+use jvm::opcodes::opcode_info;
+use jvm::typevalues::JvmPrimitiveType;
+use jvm::typevalues::JvmType;
+use jvm::typevalues::MethodDescriptor;
+
+/*
+ * A (much) simplified version of verification by type checking
+ * (jvms 4.10.1): only the depth of the operand stack is tracked. Every
+ * instruction has to be reached with the same depth along every path
+ * to it, must have the operands that it pops and must not push the
+ * stack deeper than max_stack. _cp_ is the constant pool of the
+ * method's class. The result says what is wrong, if something is.
+ */
+pub fn verify_stack_depths(method: &Method, cp: &ConstantPool) -> Result<(), String> {
+	let code = match method.get_code() {
+		Some(code) => code,
+		None => return Ok(()),
+	};
+	let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+	let mut pending: Vec<(usize, usize)> = vec![(0, 0)];
+
+	/*
+	 * The operand stack of a handler holds just the exception.
+	 */
+	if let Some(exception_table) = method.get_exception_table() {
+		for entry in exception_table {
+			pending.push((entry.handler_pc() as usize, 1));
+		}
+	}
+
+	while let Some((pc, depth)) = pending.pop() {
+		if pc >= code.len() {
+			return Err(format!("control flows to pc {}, outside of the code", pc));
+		}
+		match depths[pc] {
+			Some(existing) if existing == depth => continue,
+			Some(existing) => {
+				return Err(format!(
+					"pc {} is reached with stack depths {} and {}",
+					pc, existing, depth
+				))
+			}
+			None => depths[pc] = Some(depth),
+		}
+
+		let length = match instruction_length(code, pc) {
+			Some(length) => length,
+			None => return Err(format!("the instruction at pc {} is truncated", pc)),
+		};
+		let instruction = &code[pc..pc + length];
+		let (mnemonic, _) = opcode_info(instruction[0]);
+		let (pops, pushes) = match stack_effect(instruction, cp) {
+			Some(effect) => effect,
+			None => return Err(format!("{} at pc {} cannot be verified", mnemonic, pc)),
+		};
+		if depth < pops {
+			return Err(format!(
+				"{} at pc {} pops {} slots from a stack of {}",
+				mnemonic, pc, pops, depth
+			));
+		}
+		let depth = depth - pops + pushes;
+		if depth > method.max_stack {
+			return Err(format!(
+				"{} at pc {} overflows the operand stack (max_stack is {})",
+				mnemonic, pc, method.max_stack
+			));
+		}
+
+		let (targets, falls_through) = successors(instruction, pc);
+		for target in targets {
+			if target < 0 {
+				return Err(format!("{} at pc {} branches to {}", mnemonic, pc, target));
+			}
+			pending.push((target as usize, depth));
+		}
+		/*
+		 * A subroutine returns (with ret) to the instruction after
+		 * its jsr, with the return address consumed.
+		 */
+		match instruction[0] {
+			0xa8 | 0xc9 => pending.push((pc + length, depth - 1)),
+			_ if falls_through => pending.push((pc + length, depth)),
+			_ => (),
+		}
+	}
+	Ok(())
+}
+
+/*
+ * Where control can go from _instruction_ (at _pc_) other than to the
+ * next instruction, and whether it can go to the next instruction.
+ */
+fn successors(instruction: &[u8], pc: usize) -> (Vec<i64>, bool) {
+	let i16_at =
+		|offset: usize| ((instruction[offset] as u16) << 8 | instruction[offset + 1] as u16) as i16;
+	let i32_at = |offset: usize| {
+		((instruction[offset] as u32) << 24
+			| (instruction[offset + 1] as u32) << 16
+			| (instruction[offset + 2] as u32) << 8
+			| (instruction[offset + 3] as u32)) as i32
+	};
+	let target = |offset: i32| pc as i64 + offset as i64;
+
+	match instruction[0] {
+		/*
+		 * Conditional branches.
+		 */
+		0x99..=0xa6 | 0xc6 | 0xc7 => (vec![target(i16_at(1) as i32)], true),
+		/*
+		 * goto and jsr (see verify_stack_depths for where jsr goes
+		 * next).
+		 */
+		0xa7 | 0xa8 => (vec![target(i16_at(1) as i32)], false),
+		0xc8 | 0xc9 => (vec![target(i32_at(1))], false),
+		0xaa | 0xab => {
+			let operands = 1 + 3 - (pc % 4);
+			let mut targets = vec![target(i32_at(operands))];
+			if instruction[0] == 0xaa {
+				let low = i32_at(operands + 4) as i64;
+				let high = i32_at(operands + 8) as i64;
+				for i in 0..(high - low + 1) as usize {
+					targets.push(target(i32_at(operands + 12 + 4 * i)));
+				}
+			} else {
+				let npairs = i32_at(operands + 4) as usize;
+				for i in 0..npairs {
+					targets.push(target(i32_at(operands + 12 + 8 * i)));
+				}
+			}
+			(targets, false)
+		}
+		/*
+		 * ret, the returns and athrow.
+		 */
+		0xa9 | 0xac..=0xb1 | 0xbf => (vec![], false),
+		0xc4 if instruction[1] == 0xa9 => (vec![], false),
+		_ => (vec![], true),
+	}
+}
+
+/*
+ * How many slots _instruction_ pops off the operand stack and how many
+ * it pushes onto it. None when that cannot be determined (e.g., the
+ * instruction refers to a constant that it cannot).
+ */
+fn stack_effect(instruction: &[u8], cp: &ConstantPool) -> Option<(usize, usize)> {
+	let index = || ((instruction[1] as u16) << 8 | instruction[2] as u16) as usize;
+
+	Some(match instruction[0] {
+		0x00 => (0, 0),
+		/*
+		 * Constants.
+		 */
+		0x01..=0x08 | 0x0b..=0x0d | 0x10 | 0x11 => (0, 1),
+		0x09 | 0x0a | 0x0e | 0x0f => (0, 2),
+		0x12 | 0x13 => (0, 1),
+		0x14 => (0, 2),
+		/*
+		 * Loads.
+		 */
+		0x15 | 0x17 | 0x19 | 0x1a..=0x1d | 0x22..=0x25 | 0x2a..=0x2d => (0, 1),
+		0x16 | 0x18 | 0x1e..=0x21 | 0x26..=0x29 => (0, 2),
+		0x2e | 0x30 | 0x32..=0x35 => (2, 1),
+		0x2f | 0x31 => (2, 2),
+		/*
+		 * Stores.
+		 */
+		0x36 | 0x38 | 0x3a | 0x3b..=0x3e | 0x43..=0x46 | 0x4b..=0x4e => (1, 0),
+		0x37 | 0x39 | 0x3f..=0x42 | 0x47..=0x4a => (2, 0),
+		0x4f | 0x51 | 0x53..=0x56 => (3, 0),
+		0x50 | 0x52 => (4, 0),
+		/*
+		 * Stack manipulation, in slots.
+		 */
+		0x57 => (1, 0),
+		0x58 => (2, 0),
+		0x59 => (1, 2),
+		0x5a => (2, 3),
+		0x5b => (3, 4),
+		0x5c => (2, 4),
+		0x5d => (3, 5),
+		0x5e => (4, 6),
+		0x5f => (2, 2),
+		/*
+		 * Arithmetic: int, long, float and double for each operation.
+		 */
+		0x60..=0x73 => match (instruction[0] - 0x60) % 4 {
+			1 | 3 => (4, 2),
+			_ => (2, 1),
+		},
+		0x74 | 0x76 => (1, 1),
+		0x75 | 0x77 => (2, 2),
+		0x78 | 0x7a | 0x7c => (2, 1),
+		0x79 | 0x7b | 0x7d => (3, 2),
+		0x7e | 0x80 | 0x82 => (2, 1),
+		0x7f | 0x81 | 0x83 => (4, 2),
+		0x84 => (0, 0),
+		/*
+		 * Conversions.
+		 */
+		0x85 | 0x87 | 0x8c | 0x8d => (1, 2),
+		0x86 | 0x8b | 0x91..=0x93 => (1, 1),
+		0x88 | 0x89 | 0x8e | 0x90 => (2, 1),
+		0x8a | 0x8f => (2, 2),
+		/*
+		 * Comparisons and branches.
+		 */
+		0x94 | 0x97 | 0x98 => (4, 1),
+		0x95 | 0x96 => (2, 1),
+		0x99..=0x9e | 0xc6 | 0xc7 => (1, 0),
+		0x9f..=0xa6 => (2, 0),
+		0xa7 | 0xc8 => (0, 0),
+		0xa8 | 0xc9 => (0, 1),
+		0xa9 => (0, 0),
+		0xaa | 0xab => (1, 0),
+		/*
+		 * Returns.
+		 */
+		0xac | 0xae | 0xb0 => (1, 0),
+		0xad | 0xaf => (2, 0),
+		0xb1 => (0, 0),
+		/*
+		 * Fields.
+		 */
+		0xb2 => (0, field_slots(cp, index())?),
+		0xb3 => (field_slots(cp, index())?, 0),
+		0xb4 => (1, field_slots(cp, index())?),
+		0xb5 => (1 + field_slots(cp, index())?, 0),
+		/*
+		 * Invocations.
+		 */
+		0xb6 | 0xb7 | 0xb9 => {
+			let (parameters, returns) = method_slots(cp, index())?;
+			(1 + parameters, returns)
+		}
+		0xb8 | 0xba => method_slots(cp, index())?,
+		/*
+		 * Objects and arrays.
+		 */
+		0xbb => (0, 1),
+		0xbc | 0xbd | 0xbe | 0xc0 | 0xc1 => (1, 1),
+		0xbf => (1, 0),
+		0xc2 | 0xc3 => (1, 0),
+		0xc5 => (instruction[3] as usize, 1),
+		/*
+		 * wide changes the size of the index, not the effect.
+		 */
+		0xc4 => match instruction[1] {
+			0x84 | 0xa9 => (0, 0),
+			opcode => stack_effect(&[opcode, 0, 0], cp)?,
+		},
+		_ => return None,
+	})
+}
+
+/*
+ * How many slots a value of the field that the Fieldref at _index_
+ * refers to takes up.
+ */
+fn field_slots(cp: &ConstantPool, index: usize) -> Option<usize> {
+	let (_, _, descriptor) = cp.resolve_fieldref(index)?;
+	match descriptor.as_bytes().get(0) {
+		Some(b'J') | Some(b'D') => Some(2),
+		Some(_) => Some(1),
+		None => None,
+	}
+}
+
+/*
+ * How many slots the parameters of the method that the Methodref (or
+ * InvokeDynamic) at _index_ refers to take up, and how many its
+ * return value does.
+ */
+fn method_slots(cp: &ConstantPool, index: usize) -> Option<(usize, usize)> {
+	let descriptor = match cp.get_constant_ref(index) {
+		Constant::InvokeDynamic(_, _, name_and_type_index) => {
+			cp.resolve_name_and_type(*name_and_type_index as usize)?.1
+		}
+		_ => cp.resolve_methodref(index)?.1,
+	};
+	let descriptor = MethodDescriptor::parse(descriptor.as_bytes())?;
+	let returns = match descriptor.return_type {
+		JvmType::Primitive(JvmPrimitiveType::Void) => 0,
+		JvmType::Primitive(JvmPrimitiveType::LongInteger)
+		| JvmType::Primitive(JvmPrimitiveType::Double) => 2,
+		_ => 1,
+	};
+	Some((descriptor.parameter_slots(), returns))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jvm::class::tests::ClassFileBuilder;
+	use jvm::method::MethodAccessFlags;
+
+	/*
+	 * Verify a static method f(I)I with _code_ and _max_stack_.
+	 */
+	fn verify(code: &[u8], max_stack: u16) -> Result<(), String> {
+		let mut builder = ClassFileBuilder::new("Verified", Some("java/lang/Object"));
+		let class = builder
+			.method(
+				MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16,
+				"f",
+				"(I)I",
+				max_stack,
+				1,
+				code,
+			)
+			.build();
+		let method = class
+			.get_method_rc_by_name_and_type(&"f".to_string(), &"(I)I".to_string())
+			.unwrap();
+		verify_stack_depths(&method, class.get_constant_pool_ref())
+	}
+
+	#[test]
+	fn well_formed_methods_verify() {
+		/*
+		 * iload_0; ifeq 7; iconst_1; goto 8; iconst_0; ireturn
+		 */
+		assert_eq!(
+			verify(&[0x1a, 0x99, 0, 7, 0x04, 0xa7, 0, 4, 0x03, 0xac], 1),
+			Ok(())
+		);
+		/*
+		 * A loop: iload_0; ifle 10; iinc 0, -1; goto 0; iload_0;
+		 * ireturn
+		 */
+		assert_eq!(
+			verify(
+				&[0x1a, 0x9e, 0, 9, 0x84, 0, 0xff, 0xa7, 0xff, 0xf9, 0x1a, 0xac],
+				1
+			),
+			Ok(())
+		);
+	}
+
+	#[test]
+	fn inconsistent_methods_do_not_verify() {
+		/*
+		 * iload_0; ifeq 7 (with nothing on the stack); iconst_1;
+		 * iconst_1; goto 8; iconst_0 (which both paths reach: one with
+		 * two values, one with none); ireturn
+		 */
+		assert_eq!(
+			verify(&[0x1a, 0x99, 0, 8, 0x04, 0x04, 0xa7, 0, 3, 0x03, 0xac], 3),
+			Err(format!("pc 9 is reached with stack depths 2 and 0"))
+		);
+		/*
+		 * iadd with one value on the stack.
+		 */
+		assert_eq!(
+			verify(&[0x1a, 0x60, 0xac], 2),
+			Err(format!("iadd at pc 1 pops 2 slots from a stack of 1"))
+		);
+		/*
+		 * Two values on a stack of one.
+		 */
+		assert_eq!(
+			verify(&[0x1a, 0x1a, 0x60, 0xac], 1),
+			Err(format!(
+				"iload_0 at pc 1 overflows the operand stack (max_stack is 1)"
+			))
+		);
+		/*
+		 * Falling off the end of the code.
+		 */
+		assert_eq!(
+			verify(&[0x1a, 0x57], 1),
+			Err(format!("control flows to pc 2, outside of the code"))
+		);
+	}
+}
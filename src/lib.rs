@@ -1,4 +1,3 @@
 #[macro_use]
 extern crate enum_primitive;
-extern crate rjar;
 pub mod jvm;
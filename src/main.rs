@@ -23,8 +23,11 @@ extern crate clap;
 extern crate enum_primitive;
 extern crate jvm;
 use clap::{App, Arg};
+use std::process;
 
 use jvm::jvm::debug::DebugLevel;
+use jvm::jvm::jvmthread::{DEFAULT_MAX_FRAME_DEPTH, EXCEPTION_FRAME_RESERVE};
+use jvm::jvm::JvmBuilder;
 
 fn main() {
 	let mut debug = DebugLevel::Error;
@@ -46,12 +49,34 @@ fn main() {
 				.help("Enable debugging output.")
 				.short("d"),
 		)
+		.arg(
+			Arg::with_name("trace")
+				.help("Trace every instruction that is executed.")
+				.short("t"),
+		)
+		.arg(
+			Arg::with_name("verify")
+				.help("Verify methods before they run.")
+				.short("v"),
+		)
 		.arg(
 			Arg::with_name("classpath")
 				.help("Class path.")
 				.short("c")
 				.takes_value(true),
 		)
+		.arg(
+			Arg::with_name("max-depth")
+				.help("How deep the call stack may get.")
+				.long("max-depth")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("max-heap")
+				.help("How many bytes the program's objects and arrays may take up.")
+				.long("max-heap")
+				.takes_value(true),
+		)
 		.arg(
 			Arg::with_name("args")
 				.help("Java application arguments.")
@@ -61,6 +86,9 @@ fn main() {
 		)
 		.get_matches();
 
+	if cli_matches.is_present("trace") {
+		debug = DebugLevel::Trace;
+	}
 	if cli_matches.is_present("debug") {
 		debug = DebugLevel::Info;
 	}
@@ -69,14 +97,46 @@ fn main() {
 	let method = cli_matches.value_of("method").unwrap_or("main").to_string();
 
 	let classpath_arg = cli_matches.value_of("classpath").unwrap_or("");
-	let classpath: Vec<&str> = classpath_arg.split(":").collect();
+	let classpath: Vec<String> = classpath_arg.split(":").map(|s| s.to_string()).collect();
 
-	let args: Vec<&str> = cli_matches
+	let args: Vec<String> = cli_matches
 		.values_of("args")
 		.unwrap_or(clap::Values::default())
+		.map(|s| s.to_string())
 		.collect();
 
-	if let Some(jvm) = jvm::jvm::Jvm::new(debug) {
-		jvm.run(&class, &method, classpath.as_slice(), args.as_slice());
+	let verify = cli_matches.is_present("verify");
+	/*
+	 * The call stack lives on the heap, so any depth will do as long
+	 * as there is room for the frames that throwing a
+	 * StackOverflowError takes on top of it.
+	 */
+	let max_frame_depth = match cli_matches.value_of("max-depth") {
+		Some(depth) => match depth.parse::<usize>() {
+			Ok(depth) if depth > 0 && depth.checked_add(EXCEPTION_FRAME_RESERVE).is_some() => depth,
+			_ => {
+				eprintln!("Invalid maximum stack depth: {}", depth);
+				process::exit(1);
+			}
+		},
+		None => DEFAULT_MAX_FRAME_DEPTH,
+	};
+
+	let mut builder = JvmBuilder::new()
+		.debug_level(debug)
+		.verify(verify)
+		.max_stack_depth(max_frame_depth);
+	if let Some(heap_limit) = cli_matches.value_of("max-heap") {
+		match heap_limit.parse::<usize>() {
+			Ok(heap_limit) => builder = builder.heap_limit(heap_limit),
+			Err(_) => {
+				eprintln!("Invalid maximum heap size: {}", heap_limit);
+				process::exit(1);
+			}
+		}
 	}
+	let jvm = builder.build();
+	let classpath: Vec<&str> = classpath.iter().map(|s| s.as_str()).collect();
+	let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+	jvm.run(&class, &method, classpath.as_slice(), args.as_slice());
 }